@@ -0,0 +1,388 @@
+// Простой редактор строки для консоли без сторонних библиотек.
+// Терминал переводится в неканонический режим утилитой stty только на время чтения строки,
+// поэтому во время выполнения команд Ctrl-C работает как обычно.
+// Поддерживаются стрелки влево и вправо, Home и End (Ctrl-A, Ctrl-E), Backspace и Delete,
+// Ctrl-U для очистки строки, стрелки вверх и вниз для истории и Ctrl-R для поиска по истории.
+// История сохраняется в файл и загружается при следующем запуске.
+// Если ввод не является терминалом, строки читаются как обычно, без редактирования.
+
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+}
+
+// Результат чтения строки
+pub enum ReadLine {
+    Line(String),
+    // Ввод закончился или нажато Ctrl-D в пустой строке
+    Eof,
+}
+
+impl LineEditor {
+    pub fn with_history_file(path: Option<PathBuf>) -> Self {
+        let history = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|history| history.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Self {
+            history,
+            history_path: path,
+        }
+    }
+
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<ReadLine> {
+        let line = match RawMode::enable() {
+            Some(_raw_mode) => Editor::new(prompt, &self.history).run()?,
+            None => read_plain_line(prompt)?,
+        };
+
+        if let ReadLine::Line(line) = &line {
+            self.add_history(line)?;
+        }
+        Ok(line)
+    }
+
+    fn add_history(&mut self, line: &str) -> io::Result<()> {
+        let line = line.trim();
+        if line.is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+
+        self.history.push(line.to_string());
+        if let Some(path) = &self.history_path {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            writeln!(file, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+fn read_plain_line(prompt: &str) -> io::Result<ReadLine> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{prompt}")?;
+    stdout.flush()?;
+
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Ok(ReadLine::Eof);
+    }
+    Ok(ReadLine::Line(line))
+}
+
+// Неканонический режим терминала без эха, прежний режим восстанавливается при удалении
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Option<Self> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return None;
+        }
+
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "min", "1"])?;
+        Some(Self {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    ClearLine,
+    Search,
+    Interrupt,
+    EndOfInput,
+    Escape,
+    Unknown,
+}
+
+// Редактирование одной строки в неканоническом режиме
+struct Editor<'h> {
+    prompt: &'h str,
+    history: &'h [String],
+    buffer: Vec<char>,
+    cursor: usize,
+    // Позиция в истории при листании стрелками, history.len() означает новую строку
+    history_index: usize,
+    // Строка, которую пользователь набирал до того, как начал листать историю
+    draft: Vec<char>,
+    input: io::StdinLock<'static>,
+    output: io::Stdout,
+}
+
+impl<'h> Editor<'h> {
+    fn new(prompt: &'h str, history: &'h [String]) -> Self {
+        Self {
+            prompt,
+            history,
+            buffer: Vec::new(),
+            cursor: 0,
+            history_index: history.len(),
+            draft: Vec::new(),
+            input: io::stdin().lock(),
+            output: io::stdout(),
+        }
+    }
+
+    fn run(mut self) -> io::Result<ReadLine> {
+        self.redraw()?;
+
+        loop {
+            match self.read_key()? {
+                Key::Char(ch) => {
+                    self.buffer.insert(self.cursor, ch);
+                    self.cursor += 1;
+                }
+                Key::Enter => return self.submit(),
+                Key::Backspace if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.buffer.remove(self.cursor);
+                }
+                Key::Delete if self.cursor < self.buffer.len() => {
+                    self.buffer.remove(self.cursor);
+                }
+                Key::Left => self.cursor = self.cursor.saturating_sub(1),
+                Key::Right => self.cursor = (self.cursor + 1).min(self.buffer.len()),
+                Key::Home => self.cursor = 0,
+                Key::End => self.cursor = self.buffer.len(),
+                Key::Up => self.browse_history(-1),
+                Key::Down => self.browse_history(1),
+                Key::ClearLine => {
+                    self.buffer.clear();
+                    self.cursor = 0;
+                }
+                Key::Search if self.search()? => return self.submit(),
+                Key::Interrupt => {
+                    write!(self.output, "^C\r\n")?;
+                    self.buffer.clear();
+                    self.cursor = 0;
+                }
+                Key::EndOfInput if self.buffer.is_empty() => {
+                    write!(self.output, "\r\n")?;
+                    self.output.flush()?;
+                    return Ok(ReadLine::Eof);
+                }
+                _ => {}
+            }
+            self.redraw()?;
+        }
+    }
+
+    fn browse_history(&mut self, step: isize) {
+        let Some(index) = self.history_index.checked_add_signed(step) else {
+            return;
+        };
+        if index > self.history.len() {
+            return;
+        }
+
+        if self.history_index == self.history.len() {
+            self.draft = self.buffer.clone();
+        }
+        self.history_index = index;
+        self.buffer = match self.history.get(index) {
+            Some(line) => line.chars().collect(),
+            None => self.draft.clone(),
+        };
+        self.cursor = self.buffer.len();
+    }
+
+    // Поиск по истории в обратном порядке, как Ctrl-R в readline: повторное нажатие
+    // ищет более раннее совпадение, Enter сразу отправляет найденную строку,
+    // а любая другая клавиша оставляет ее для редактирования
+    fn search(&mut self) -> io::Result<bool> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+
+        loop {
+            let matched = found.map_or("", |index| self.history[index].as_str());
+            write!(
+                self.output,
+                "\r(reverse-i-search)`{query}': {matched}\x1b[K"
+            )?;
+            self.output.flush()?;
+
+            match self.read_key()? {
+                Key::Char(ch) => {
+                    query.push(ch);
+                    found = self.find(&query, found.map_or(self.history.len(), |index| index + 1));
+                }
+                Key::Backspace => {
+                    query.pop();
+                    found = self.find(&query, self.history.len());
+                }
+                Key::Search => {
+                    if let Some(index) = found {
+                        found = self.find(&query, index).or(found);
+                    }
+                }
+                Key::Interrupt | Key::Escape => return Ok(false),
+                key => {
+                    if let Some(index) = found {
+                        self.buffer = self.history[index].chars().collect();
+                        self.cursor = self.buffer.len();
+                    }
+                    return Ok(matches!(key, Key::Enter));
+                }
+            }
+        }
+    }
+
+    // Последняя строка истории до указанной позиции, содержащая запрос
+    fn find(&self, query: &str, before: usize) -> Option<usize> {
+        if query.is_empty() {
+            return None;
+        }
+        self.history[..before]
+            .iter()
+            .rposition(|line| line.contains(query))
+    }
+
+    fn submit(&mut self) -> io::Result<ReadLine> {
+        self.redraw()?;
+        write!(self.output, "\r\n")?;
+        self.output.flush()?;
+        let line: String = self.buffer.iter().collect();
+        Ok(ReadLine::Line(line + "\n"))
+    }
+
+    fn redraw(&mut self) -> io::Result<()> {
+        let line: String = self.buffer.iter().collect();
+        write!(self.output, "\r{}{line}\x1b[K", self.prompt)?;
+        let back = self.buffer.len() - self.cursor;
+        if back > 0 {
+            write!(self.output, "\x1b[{back}D")?;
+        }
+        self.output.flush()
+    }
+
+    fn read_key(&mut self) -> io::Result<Key> {
+        let Some(byte) = self.read_byte()? else {
+            return Ok(Key::EndOfInput);
+        };
+
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            0x7f | 0x08 => Key::Backspace,
+            0x01 => Key::Home,
+            0x05 => Key::End,
+            0x02 => Key::Left,
+            0x06 => Key::Right,
+            0x10 => Key::Up,
+            0x0e => Key::Down,
+            0x15 => Key::ClearLine,
+            0x12 => Key::Search,
+            0x03 => Key::Interrupt,
+            0x04 => Key::EndOfInput,
+            0x1b => self.read_escape()?,
+            byte if byte < 0x20 => Key::Unknown,
+            byte => self.read_char(byte)?,
+        };
+        Ok(key)
+    }
+
+    // Разбирает последовательности вида ESC [ A и ESC [ 3 ~
+    fn read_escape(&mut self) -> io::Result<Key> {
+        if !matches!(self.read_byte()?, Some(b'[' | b'O')) {
+            return Ok(Key::Escape);
+        }
+
+        let key = match self.read_byte()? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            Some(digit @ b'0'..=b'9') => {
+                let mut code = vec![digit];
+                while let Some(byte) = self.read_byte()? {
+                    if byte == b'~' {
+                        break;
+                    }
+                    code.push(byte);
+                }
+                match code.as_slice() {
+                    b"1" | b"7" => Key::Home,
+                    b"4" | b"8" => Key::End,
+                    b"3" => Key::Delete,
+                    _ => Key::Unknown,
+                }
+            }
+            _ => Key::Unknown,
+        };
+        Ok(key)
+    }
+
+    // Собирает символ UTF-8 из первого байта и байтов продолжения
+    fn read_char(&mut self, first: u8) -> io::Result<Key> {
+        let width = match first {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+
+        let mut bytes = vec![first];
+        for _ in 1..width {
+            match self.read_byte()? {
+                Some(byte) => bytes.push(byte),
+                None => break,
+            }
+        }
+
+        Ok(str::from_utf8(&bytes)
+            .ok()
+            .and_then(|text| text.chars().next())
+            .map_or(Key::Unknown, Key::Char))
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.input.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// Запускает интерактивную консоль для управления роботом с помощью интерпретатора.
+// Возможные команды:
+// - move <distance>: переместить робота на указанное расстояние, отрицательное значение - назад
+// - back <distance>: переместить робота назад на указанное расстояние
+// - goto <x> <y>: перенести робота в клетку с указанными координатами, не рисуя
+// - face <up|down|left|right>: развернуть робота в указанную сторону
+// - turn_left <angle>: повернуть робота налево на 90 градусов указанное количество раз
+// - turn_right <angle>: повернуть робота направо на 90 градусов указанное количество раз
+// - down_pen: опустить перо
+// - color <name>: сменить цвет пера, например color red
+// - width <n>: задать толщину пера
+// - up_pen: поднять перо
+// - repeat <n> [ ... ]: повторить команды в скобках указанное количество раз
+// - def <name> ... end: объявить процедуру, которую затем можно вызвать по имени
+// - let <name> = <expr>: объявить переменную, аргументы команд могут быть выражениями
+// - if <cond> [ ... ] else [ ... ]: выполнить одну из веток в зависимости от условия
+// - while <cond> [ ... ]: повторять команды, пока выполняется условие, например not blocked
+// - # комментарий до конца строки или /* блочный комментарий */
+// Аргументы можно передавать по имени: move distance=10, turn_left times=2
+// Ключевые слова не зависят от регистра и имеют псевдонимы: fd, bk, lt, rt, pd, pu
+// Команды консоли обрабатываются до интерпретатора и не являются частью языка:
+// - help: показать список команд и синтаксис
+// - state: показать положение, направление и состояние пера робота
+// - undo: отменить последнюю выполненную строку
+// - redo: повторить отмененную строку
+// - load <file>: выполнить скрипт из файла, отменяется целиком одной командой undo
+// - save <file>: сохранить выполненные и не отмененные строки в файл для повторного запуска
+// - suspend <file>: с функцией "serde" сохранить сеанс вместе с историей отмены
+// - resume <file>: продолжить сохраненный сеанс с того же места
+// - record <name>: начать запись макроса из следующих выполненных строк
+// - stop: закончить запись макроса
+// - play <name>: выполнить записанный макрос, отменяется целиком одной командой undo
+// Строку можно редактировать стрелками, история строк листается стрелками вверх и вниз,
+// Ctrl-R ищет по истории. История хранится в файле ROBOT_HISTORY или ~/.robot_history.
+// Ошибка в строке показывается вместе со строкой и отметкой под ошибочным фрагментом,
+// с feature "color" сообщение выделяется цветом.
+mod line_editor;
+
+use std::{collections::HashMap, env, error, fs, path::PathBuf};
+
+use homework12::{command::CompositeCommand, interpreter::Interpreter, session::Session};
+use line_editor::{LineEditor, ReadLine};
+
+fn main() {
+    init_logger();
+
+    let mut console = Console::default();
+    run_prompt(&mut console).unwrap();
+}
+
+fn init_logger() {
+    let log_level = env::var("RUST_LOG").unwrap_or_else(|_| "debug".into());
+    env_logger::Builder::new()
+        .filter(None, log_level.parse().unwrap())
+        .init();
+}
+
+fn run_prompt(console: &mut Console) -> Result<(), Box<dyn error::Error>> {
+    let mut editor = LineEditor::with_history_file(history_path());
+
+    loop {
+        let ReadLine::Line(line) = editor.read_line("> ")? else {
+            return Ok(());
+        };
+
+        let command = line.trim();
+        let (name, argument) = command.split_once(' ').unwrap_or((command, ""));
+
+        let result = match (name, argument.trim()) {
+            ("help", "") => {
+                print!("{HELP}");
+                Ok(())
+            }
+            ("state", "") => {
+                console.print_state();
+                Ok(())
+            }
+            ("undo", "") => console.undo(),
+            ("redo", "") => console.redo(),
+            ("load", path) => console.load(path),
+            ("save", path) => console.save(path),
+            #[cfg(feature = "serde")]
+            ("suspend", path) if !path.is_empty() => console.suspend(path),
+            #[cfg(feature = "serde")]
+            ("resume", path) if !path.is_empty() => console.resume(path),
+            ("record", name) if !name.is_empty() => console.record(name),
+            ("stop", "") => console.stop(),
+            ("play", name) if !name.is_empty() => console.play(name),
+            _ => console.execute(&line),
+        };
+        if let Err(err) = result {
+            eprintln!("{err}");
+        }
+    }
+}
+
+const HELP: &str = "\
+Robot commands:
+  move <distance>, fd         move forward, a negative distance moves back
+  back <distance>, bk         move back
+  goto <x> <y>                jump to a cell without drawing, goto 1 (-2)
+  face <direction>            face up, down, left, right, up_left, up_right,
+                              down_left or down_right
+  home                        return home with the pen up
+  turn_left <times>, lt       turn left by 90 degrees the given number of times
+  turn_right <times>, rt      turn right by 90 degrees the given number of times
+  turn_left_45 <times>        turn left by 45 degrees the given number of times
+  turn_right_45 <times>       turn right by 45 degrees the given number of times
+  down_pen, pd                start drawing
+  up_pen, pu                  stop drawing
+  color <name>                pen color: black, white, red, green, blue, yellow,
+                              cyan or magenta
+  width <n>                   pen width, at least 1
+  refuel <n>                  add fuel if the robot uses it
+  stamp <shape>               stamp dot, square <size> or circle <radius>
+  write \"<text>\"              write text in block letters
+Statements:
+  repeat <n> [ ... ]          repeat the block n times
+  def <name> ... end          declare a procedure, call it by name
+  let <name> = <expr>         declare a variable
+  if <cond> [ ... ] else [ ... ]
+  while <cond> [ ... ]        repeat while the condition holds, e.g. not blocked
+  include \"<file>\"            run commands from another script
+  robot <name>: <statement>   run the statement with a named robot of the world
+  robot <name>: [ ... ]       run the block with a named robot of the world
+Expressions:
+  numbers, variables, + - * / ( ), == != < <= > >=, not and or,
+  random <min> <max>, blocked
+  arguments can be named: move distance=10, turn_left times=2
+Comments:
+  # to the end of line, /* block */
+Keywords are case-insensitive.
+Console commands:
+  help                        show this help
+  state                       show the robot position, direction and pen
+  undo, redo                  revert or repeat the last executed line
+  load <file>                 run a script file
+  save <file>                 save the executed lines to a script file
+  suspend <file>              save the session with its undo history (serde feature)
+  resume <file>               continue a saved session
+  record <name>, stop         record the following lines as a macro
+  play <name>                 run a recorded macro
+";
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("ROBOT_HISTORY")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".robot_history")))
+}
+
+// Состояние консоли: сеанс с роботом и историей для отмены и повтора, и записанные макросы.
+// Строка, файл или макрос выполняется одной группой команд, поэтому и отменяется целиком.
+#[derive(Default)]
+struct Console {
+    session: Session,
+    macros: HashMap<String, CompositeCommand>,
+    recording: Option<Recording>,
+}
+
+// Записываемый макрос: в него попадут строки, выполненные после начала записи
+struct Recording {
+    name: String,
+    start: usize,
+}
+
+impl Console {
+    fn execute(&mut self, source: &str) -> Result<(), Box<dyn error::Error>> {
+        let commands = Interpreter::new(source)
+            .interpret()
+            .map_err(|err| err.annotate(source).trim_end().to_string())?;
+        // Строка с ошибкой выполнения не меняет состояние робота
+        let entry = CompositeCommand::new(commands.into_iter().collect());
+        self.session.execute(Box::new(entry))?;
+        Ok(())
+    }
+
+    fn load(&mut self, path: &str) -> Result<(), Box<dyn error::Error>> {
+        let source = fs::read_to_string(path)?;
+        self.execute(&source)
+    }
+
+    // Выполненные строки записываются скриптом, по строке скрипта на строку консоли
+    fn save(&self, path: &str) -> Result<(), Box<dyn error::Error>> {
+        let mut transcript = String::new();
+        for entry in self.session.history().commands() {
+            transcript.push_str(&entry.to_string());
+            transcript.push('\n');
+        }
+        fs::write(path, transcript)?;
+        Ok(())
+    }
+
+    // Сохраняет сеанс вместе с историей отмены, чтобы продолжить его после выхода
+    #[cfg(feature = "serde")]
+    fn suspend(&self, path: &str) -> Result<(), Box<dyn error::Error>> {
+        self.session.save(path)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn error::Error>> {
+        self.session = Session::load(path)?;
+        self.recording = None;
+        Ok(())
+    }
+
+    fn print_state(&self) {
+        println!("{}", self.session.robot());
+    }
+
+    fn undo(&mut self) -> Result<(), Box<dyn error::Error>> {
+        if !self.session.undo()? {
+            return Err("Nothing to undo".into());
+        }
+        // Отмена строки, выполненной до начала записи, не должна попасть в макрос
+        let executed = self.session.history().commands().len();
+        if let Some(recording) = &mut self.recording {
+            recording.start = recording.start.min(executed);
+        }
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), Box<dyn error::Error>> {
+        if !self.session.redo()? {
+            return Err("Nothing to redo".into());
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, name: &str) -> Result<(), Box<dyn error::Error>> {
+        if let Some(recording) = &self.recording {
+            return Err(format!("Already recording macro {}", recording.name).into());
+        }
+        self.recording = Some(Recording {
+            name: name.to_string(),
+            start: self.session.history().commands().len(),
+        });
+        Ok(())
+    }
+
+    // Объединяет строки, выполненные с начала записи, в один макрос
+    fn stop(&mut self) -> Result<(), Box<dyn error::Error>> {
+        let recording = self.recording.take().ok_or("Not recording")?;
+        let recorded = self.session.history().commands()[recording.start..]
+            .iter()
+            .map(|entry| entry.box_clone())
+            .collect();
+        self.macros
+            .insert(recording.name, CompositeCommand::new(recorded));
+        Ok(())
+    }
+
+    fn play(&mut self, name: &str) -> Result<(), Box<dyn error::Error>> {
+        let entry = self
+            .macros
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Undefined macro {name}"))?;
+        self.session.execute(Box::new(entry))?;
+        Ok(())
+    }
+}
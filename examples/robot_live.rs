@@ -0,0 +1,82 @@
+// Живой холст в терминале: после каждой выполненной команды экран очищается и холст
+// печатается заново, так сразу видно, что нарисовала команда.
+// Запуск: cargo run --example robot_live [--delay <ms>] [script],
+// без скрипта выполняется демонстрационная программа.
+// Пауза между кадрами по умолчанию 300 мс, ее можно задать и переменной ROBOT_LIVE_DELAY.
+// В отличие от robot_tui, рисунок собирает холст (Canvas), подписанный на события робота,
+// поэтому видны и диагонали, и штампы, а размер кадра растет вместе с рисунком.
+// Экран очищается escape-последовательностями ANSI без сторонних библиотек.
+
+use std::{
+    env, error, fs,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use homework12::{canvas::Canvas, interpreter::Interpreter, robot::Robot};
+
+const DEMO: &str = "\
+down_pen
+repeat 4 [ move 4 turn_right 1 ]
+turn_right_45 1 move 4 turn_left_45 1
+stamp circle 2
+up_pen goto 6 0 face right down_pen
+write \"HI\"
+";
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let mut delay = env::var("ROBOT_LIVE_DELAY")
+        .ok()
+        .and_then(|delay| delay.parse().ok())
+        .unwrap_or(300);
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--delay" => {
+                delay = args
+                    .next()
+                    .and_then(|delay| delay.parse().ok())
+                    .ok_or("--delay expects milliseconds")?;
+            }
+            _ => path = Some(arg),
+        }
+    }
+    let source = match path {
+        Some(path) => fs::read_to_string(path)?,
+        None => DEMO.to_string(),
+    };
+    let delay = Duration::from_millis(delay);
+
+    let mut commands = Interpreter::new(&source).interpret()?;
+    let total = commands.commands().len();
+
+    let mut robot = Robot::default();
+    let canvas = Arc::new(Mutex::new(Canvas::from_robot(&robot)));
+    robot.add_observer(canvas.clone());
+
+    let mut stdout = io::stdout();
+    draw(&mut stdout, &canvas, &format!("0/{total}"))?;
+
+    for (index, command) in (&mut commands).into_iter().enumerate() {
+        thread::sleep(delay);
+
+        let result = command.execute(&mut robot);
+        let status = match &result {
+            Ok(()) => format!("{}/{total} {command}", index + 1),
+            Err(err) => format!("{}/{total} {command}: {err}", index + 1),
+        };
+        draw(&mut stdout, &canvas, &status)?;
+        result?;
+    }
+    Ok(())
+}
+
+// Очищает экран и печатает холст со строкой состояния
+fn draw(output: &mut impl Write, canvas: &Mutex<Canvas>, status: &str) -> io::Result<()> {
+    let picture = canvas.lock().unwrap().render_ascii_colored();
+    writeln!(output, "\x1b[H\x1b[2J{picture}{status}")?;
+    output.flush()
+}
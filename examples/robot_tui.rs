@@ -0,0 +1,125 @@
+// Показывает выполнение скрипта в терминале: поле с нарисованными клетками, робота
+// со стрелкой по направлению движения и строку состояния обновляются после каждой команды.
+// Запуск: cargo run --example robot_tui [script], без скрипта выполняется демонстрационная программа.
+// Пауза между кадрами задается в миллисекундах переменной ROBOT_TUI_DELAY,
+// а масштаб поля (клеток в символе) - переменной ROBOT_TUI_ZOOM.
+// Рисунок собирает холст, подписанный на события робота, а поле - его видимая область:
+// когда робот уходит за край, область центрируется на нем заново.
+// Экран перерисовывается escape-последовательностями ANSI без сторонних библиотек.
+// Цикл "while" выполняется одной командой, поэтому внутри него виден только результат.
+
+use std::{
+    env, error, fs,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use homework12::{
+    canvas::{Canvas, Viewport},
+    interpreter::Interpreter,
+    robot::Robot,
+};
+
+const DEMO: &str = "\
+down_pen
+repeat 4 [ move 6 turn_right 1 ]
+up_pen move 2 turn_right 1 move 2 turn_left 1
+down_pen
+repeat 2 [ move 2 turn_right 1 ]
+";
+
+const WIDTH: u32 = 41;
+const HEIGHT: u32 = 21;
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let source = match env::args().nth(1) {
+        Some(path) => fs::read_to_string(path)?,
+        None => DEMO.to_string(),
+    };
+    let delay = env::var("ROBOT_TUI_DELAY")
+        .ok()
+        .and_then(|delay| delay.parse().ok())
+        .map_or(Duration::from_millis(200), Duration::from_millis);
+    let zoom = env::var("ROBOT_TUI_ZOOM")
+        .ok()
+        .and_then(|zoom| zoom.parse().ok())
+        .unwrap_or(1);
+
+    let commands = Interpreter::new(&source).interpret()?;
+    let total = commands.commands().len();
+    let mut view = View::new(Robot::default(), zoom);
+
+    let mut stdout = io::stdout();
+    // Скрыть курсор на время анимации
+    write!(stdout, "\x1b[?25l")?;
+    view.draw(&mut stdout, &format!("0/{total}"))?;
+
+    let mut result = Ok(());
+    for (index, mut command) in commands.commands().to_vec().into_iter().enumerate() {
+        thread::sleep(delay);
+
+        result = command.execute(&mut view.robot);
+        view.track();
+        let status = match &result {
+            Ok(()) => format!("{}/{total} {command:?}", index + 1),
+            Err(err) => format!("{}/{total} {command:?}: {err}", index + 1),
+        };
+        view.draw(&mut stdout, &status)?;
+        if result.is_err() {
+            break;
+        }
+    }
+
+    write!(stdout, "\x1b[?25h")?;
+    stdout.flush()?;
+    Ok(result?)
+}
+
+// Робот, его рисунок и видимая область поля
+struct View {
+    robot: Robot,
+    canvas: Arc<Mutex<Canvas>>,
+    viewport: Viewport,
+}
+
+impl View {
+    fn new(mut robot: Robot, zoom: u32) -> Self {
+        let canvas = Arc::new(Mutex::new(Canvas::from_robot(&robot)));
+        robot.add_observer(canvas.clone());
+        let viewport = Viewport {
+            zoom,
+            ..Viewport::new(robot.position(), WIDTH, HEIGHT)
+        };
+        Self {
+            robot,
+            canvas,
+            viewport,
+        }
+    }
+
+    // Робот ушел за край видимой области, центрируем ее заново
+    fn track(&mut self) {
+        if !self.viewport.contains(self.robot.position()) {
+            self.viewport.center = self.robot.position();
+        }
+    }
+
+    fn draw(&self, output: &mut impl Write, status: &str) -> io::Result<()> {
+        let picture = self.canvas.lock().unwrap().view(self.viewport);
+        let border = format!("+{}+\r\n", "-".repeat(WIDTH as usize));
+        let mut frame = String::from("\x1b[H\x1b[2J");
+
+        frame.push_str(&border);
+        for line in picture.lines() {
+            frame.push_str(&format!("|{line}|\r\n"));
+        }
+        frame.push_str(&border);
+
+        frame.push_str(&format!("{}\r\n{status}\x1b[K\r\n", self.robot));
+
+        output.write_all(frame.as_bytes())?;
+        output.flush()
+    }
+}
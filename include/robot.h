@@ -0,0 +1,82 @@
+/*
+ * C interface of the homework12 robot (feature "ffi").
+ * Build the shared library with
+ *   cargo rustc --release --lib --crate-type cdylib --features ffi
+ *
+ * Directions and colors are indices:
+ *   direction: 0 up, 1 down, 2 left, 3 right, 4 up-left, 5 up-right, 6 down-left, 7 down-right
+ *   color:     0 black, 1 white, 2 red, 3 green, 4 blue, 5 yellow, 6 cyan, 7 magenta
+ * Functions returning int return -1 on error; robot_last_error() describes the last
+ * error of the calling thread. Strings returned by the library are freed with
+ * robot_string_free().
+ */
+
+#ifndef HOMEWORK12_ROBOT_H
+#define HOMEWORK12_ROBOT_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Robot Robot;
+typedef struct CommandList CommandList;
+typedef struct Canvas Canvas;
+
+typedef struct {
+    int32_t x;
+    int32_t y;
+    int direction;
+    int color;
+    bool pen_down;
+} RobotStatus;
+
+/* Creates a robot at (x, y) with the pen up, returns NULL for an invalid direction. */
+Robot *robot_new(int32_t x, int32_t y, int direction);
+
+void robot_free(Robot *robot);
+
+/* Interprets and executes a UTF-8 script, returns 0 on success. */
+int robot_execute_script(Robot *robot, const char *script);
+
+int robot_get_state(const Robot *robot, RobotStatus *status);
+
+/* Color index of a drawn cell or -1 for an empty cell, builds the drawing on every call. */
+int robot_canvas_color_at(const Robot *robot, int32_t x, int32_t y);
+
+/* The drawing of the robot at the time of the call, free it with canvas_free(). */
+Canvas *robot_canvas_new(const Robot *robot);
+
+void canvas_free(Canvas *canvas);
+
+/* Color index of a drawn cell or -1 for an empty cell. */
+int canvas_color_at(const Canvas *canvas, int32_t x, int32_t y);
+
+/* The drawing as text, free it with robot_string_free(). */
+char *robot_canvas_ascii(const Robot *robot);
+
+/* Message of the last error or NULL, owned by the library. */
+const char *robot_last_error(void);
+
+void robot_string_free(char *text);
+
+/* Parses a script without executing it, returns NULL on error. */
+CommandList *robot_interpret(const char *script);
+
+void command_list_free(CommandList *commands);
+
+int command_list_len(const CommandList *commands);
+
+/* The commands as script text, one per line, free it with robot_string_free(). */
+char *command_list_to_string(const CommandList *commands);
+
+/* Executes a copy of the commands, so a list can be executed many times. */
+int command_list_execute(const CommandList *commands, Robot *robot);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,258 @@
+"""Python classes for the homework12 robot, for teaching in notebooks.
+
+This module stands in for PyO3 bindings: PyO3 is not a dependency of the crate, so
+instead of a compiled extension module the classes wrap the C interface of the crate
+(include/robot.h) with ctypes. The Rust core stays the single implementation.
+Build the shared library with the "ffi" feature first:
+
+    cargo rustc --release --lib --crate-type cdylib --features ffi
+
+The library is looked up in HOMEWORK12_LIB, then in target/release and target/debug.
+The examples below are doctests, run them against the built library with:
+
+    python3 -m doctest python/robot.py
+
+    >>> robot = Robot(direction="right")
+    >>> robot.execute("down_pen\\nmove 2")
+    >>> (robot.x, robot.y, robot.pen_down)
+    (2, 0, True)
+    >>> drawing = robot.drawing()
+    >>> [drawing.color_at(x, 0) for x in range(4)]
+    ['black', 'black', 'black', None]
+    >>> commands = Interpreter("repeat 2 [ turn_left 1 ]").interpret()
+    >>> len(commands), str(commands)
+    (2, 'turn_left 1\\nturn_left 1\\n')
+"""
+
+import ctypes
+import os
+import sys
+
+__all__ = ["Robot", "Canvas", "CommandList", "Interpreter", "RobotError", "DIRECTIONS", "COLORS"]
+
+# Indices of Direction::ALL and Color::ALL
+DIRECTIONS = ["up", "down", "left", "right", "up_left", "up_right", "down_left", "down_right"]
+COLORS = ["black", "white", "red", "green", "blue", "yellow", "cyan", "magenta"]
+
+
+class RobotError(Exception):
+    """A script or execution error reported by the library."""
+
+
+class _Status(ctypes.Structure):
+    _fields_ = [
+        ("x", ctypes.c_int32),
+        ("y", ctypes.c_int32),
+        ("direction", ctypes.c_int),
+        ("color", ctypes.c_int),
+        ("pen_down", ctypes.c_bool),
+    ]
+
+
+def _library_path():
+    if "HOMEWORK12_LIB" in os.environ:
+        return os.environ["HOMEWORK12_LIB"]
+    if sys.platform == "win32":
+        name = "homework12.dll"
+    elif sys.platform == "darwin":
+        name = "libhomework12.dylib"
+    else:
+        name = "libhomework12.so"
+    root = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "target")
+    for profile in ("release", "debug"):
+        path = os.path.join(root, profile, name)
+        if os.path.exists(path):
+            return path
+    raise RobotError(
+        f"{name} is not built, run: "
+        "cargo rustc --release --lib --crate-type cdylib --features ffi"
+    )
+
+
+def _load():
+    lib = ctypes.CDLL(_library_path())
+    handle = ctypes.c_void_p
+    lib.robot_new.argtypes = [ctypes.c_int32, ctypes.c_int32, ctypes.c_int]
+    lib.robot_new.restype = handle
+    lib.robot_free.argtypes = [handle]
+    lib.robot_free.restype = None
+    lib.robot_execute_script.argtypes = [handle, ctypes.c_char_p]
+    lib.robot_execute_script.restype = ctypes.c_int
+    lib.robot_get_state.argtypes = [handle, ctypes.POINTER(_Status)]
+    lib.robot_get_state.restype = ctypes.c_int
+    lib.robot_canvas_color_at.argtypes = [handle, ctypes.c_int32, ctypes.c_int32]
+    lib.robot_canvas_color_at.restype = ctypes.c_int
+    lib.robot_canvas_new.argtypes = [handle]
+    lib.robot_canvas_new.restype = handle
+    lib.canvas_free.argtypes = [handle]
+    lib.canvas_free.restype = None
+    lib.canvas_color_at.argtypes = [handle, ctypes.c_int32, ctypes.c_int32]
+    lib.canvas_color_at.restype = ctypes.c_int
+    lib.robot_canvas_ascii.argtypes = [handle]
+    lib.robot_canvas_ascii.restype = ctypes.c_void_p
+    lib.robot_last_error.argtypes = []
+    lib.robot_last_error.restype = ctypes.c_char_p
+    lib.robot_string_free.argtypes = [ctypes.c_void_p]
+    lib.robot_string_free.restype = None
+    lib.robot_interpret.argtypes = [ctypes.c_char_p]
+    lib.robot_interpret.restype = handle
+    lib.command_list_free.argtypes = [handle]
+    lib.command_list_free.restype = None
+    lib.command_list_len.argtypes = [handle]
+    lib.command_list_len.restype = ctypes.c_int
+    lib.command_list_to_string.argtypes = [handle]
+    lib.command_list_to_string.restype = ctypes.c_void_p
+    lib.command_list_execute.argtypes = [handle, handle]
+    lib.command_list_execute.restype = ctypes.c_int
+    return lib
+
+
+_lib = None
+
+
+def _library():
+    global _lib
+    if _lib is None:
+        _lib = _load()
+    return _lib
+
+
+def _error():
+    message = _library().robot_last_error()
+    return RobotError(message.decode() if message else "unknown error")
+
+
+def _check(code):
+    if code != 0:
+        raise _error()
+
+
+def _take_string(pointer):
+    if not pointer:
+        raise _error()
+    try:
+        return ctypes.string_at(pointer).decode()
+    finally:
+        _library().robot_string_free(pointer)
+
+
+class Robot:
+    """A robot on the grid, its drawing is kept by the library."""
+
+    def __init__(self, x=0, y=0, direction="up"):
+        if direction not in DIRECTIONS:
+            raise ValueError(f"invalid direction {direction!r}")
+        self._handle = _library().robot_new(x, y, DIRECTIONS.index(direction))
+        if not self._handle:
+            raise _error()
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _library().robot_free(self._handle)
+            self._handle = None
+
+    def execute(self, script):
+        """Interprets and executes a script."""
+        _check(_library().robot_execute_script(self._handle, script.encode()))
+
+    def _status(self):
+        status = _Status()
+        _check(_library().robot_get_state(self._handle, ctypes.byref(status)))
+        return status
+
+    @property
+    def x(self):
+        return self._status().x
+
+    @property
+    def y(self):
+        return self._status().y
+
+    @property
+    def direction(self):
+        return DIRECTIONS[self._status().direction]
+
+    @property
+    def color(self):
+        return COLORS[self._status().color]
+
+    @property
+    def pen_down(self):
+        return self._status().pen_down
+
+    def color_at(self, x, y):
+        """Color of a drawn cell or None for an empty cell."""
+        index = _library().robot_canvas_color_at(self._handle, x, y)
+        return None if index < 0 else COLORS[index]
+
+    def drawing(self):
+        """The drawing at this moment, for reading many cells."""
+        handle = _library().robot_canvas_new(self._handle)
+        if not handle:
+            raise _error()
+        return Canvas(handle)
+
+    def canvas(self):
+        """The drawing as text."""
+        return _take_string(_library().robot_canvas_ascii(self._handle))
+
+    def __repr__(self):
+        pen = "down" if self.pen_down else "up"
+        return f"Robot at ({self.x}, {self.y}) facing {self.direction}, pen {pen}"
+
+
+class Canvas:
+    """A drawing of a robot, it does not change when the robot moves on."""
+
+    def __init__(self, handle):
+        self._handle = handle
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _library().canvas_free(self._handle)
+            self._handle = None
+
+    def color_at(self, x, y):
+        """Color of a drawn cell or None for an empty cell."""
+        index = _library().canvas_color_at(self._handle, x, y)
+        return None if index < 0 else COLORS[index]
+
+
+class CommandList:
+    """Parsed commands, which can be executed by any robot many times."""
+
+    def __init__(self, handle):
+        self._handle = handle
+
+    def __del__(self):
+        if getattr(self, "_handle", None):
+            _library().command_list_free(self._handle)
+            self._handle = None
+
+    def __len__(self):
+        return _library().command_list_len(self._handle)
+
+    def __str__(self):
+        return _take_string(_library().command_list_to_string(self._handle))
+
+    def execute(self, robot):
+        _check(_library().command_list_execute(self._handle, robot._handle))
+
+
+class Interpreter:
+    """Parses scripts of the robot language without executing them."""
+
+    def __init__(self, source):
+        self.source = source
+
+    def interpret(self):
+        handle = _library().robot_interpret(self.source.encode())
+        if not handle:
+            raise _error()
+        return CommandList(handle)
+
+
+if __name__ == "__main__":
+    import doctest
+
+    doctest.testmod()
@@ -0,0 +1,370 @@
+// Консольная программа для запуска скриптов робота.
+// Подкоманды:
+// - run <script>: выполнить скрипт и напечатать итоговое состояние робота
+// - check <script>: проверить скрипт на копии робота, не выполняя его
+// - repl: читать команды построчно со стандартного ввода и выполнять их,
+//   после каждой строки печатаются состояние и статистика робота и рисунок, если он есть;
+//   строки ":undo" и ":redo" отменяют и повторяют строку, а с функцией "serde"
+//   ":save <file>" и ":load <file>" сохраняют сеанс вместе с историей и продолжают его
+// - debug <script>: выполнять скрипт по шагам, останавливаясь перед каждой командой
+// - serve <addr>: с функцией "server" принимать скрипты по TCP и выполнять их общим роботом
+// - websocket <addr>: то же для страниц в браузере, с событиями робота после каждой команды
+// Параметры задают начальное состояние робота и диалект языка:
+// --x <n>, --y <n>, --direction <up|down|left|right>, --pen-down, --logo
+
+use std::{
+    env, error, fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use homework12::{
+    command::{Command, CompositeCommand, StepAction},
+    error::Error,
+    interpreter::Interpreter,
+    robot::{Direction, Robot, RobotBuilder},
+    session::Session,
+};
+#[cfg(feature = "server")]
+use {
+    homework12::server::{Server, WebSocketServer},
+    std::net::TcpListener,
+};
+
+const USAGE: &str = "\
+Usage: robot [OPTIONS] <COMMAND>
+
+Commands:
+  run <script>      execute a script and print the final robot state
+  check <script>    simulate a script and report errors without executing it
+  repl              read and execute commands line by line
+  debug <script>    execute a script step by step
+  serve <addr>      accept scripts over TCP (with the server feature)
+  websocket <addr>  accept scripts over WebSocket (with the server feature)
+
+Options:
+  --x <n>           initial x coordinate [default: 0]
+  --y <n>           initial y coordinate [default: 0]
+  --direction <d>   initial direction: up, down, left or right [default: up]
+  --pen-down        start with the pen down
+  --logo            use the Logo dialect
+  -h, --help        print this help
+";
+
+enum Subcommand {
+    Run(String),
+    Check(String),
+    Repl,
+    Debug(String),
+    #[cfg(feature = "server")]
+    Serve(String),
+    #[cfg(feature = "server")]
+    WebSocket(String),
+}
+
+struct Options {
+    subcommand: Subcommand,
+    robot: Robot,
+    logo: bool,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Разбирает аргументы командной строки, возвращает None, если запрошена справка
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut builder = RobotBuilder::new();
+    let mut logo = false;
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("Missing value for {name}"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--x" => builder = builder.x(parse_coordinate("--x", &value("--x")?)?),
+            "--y" => builder = builder.y(parse_coordinate("--y", &value("--y")?)?),
+            "--direction" => builder = builder.direction(parse_direction(&value("--direction")?)?),
+            "--pen-down" => builder = builder.drawing(true),
+            "--logo" => logo = true,
+            arg if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("Unknown option {arg}"));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let subcommand = match (positional.next().as_deref(), positional.next()) {
+        (Some("run"), Some(script)) => Subcommand::Run(script),
+        (Some("check"), Some(script)) => Subcommand::Check(script),
+        (Some("repl"), None) => Subcommand::Repl,
+        (Some("debug"), Some(script)) => Subcommand::Debug(script),
+        #[cfg(feature = "server")]
+        (Some("serve"), Some(addr)) => Subcommand::Serve(addr),
+        #[cfg(feature = "server")]
+        (Some("websocket"), Some(addr)) => Subcommand::WebSocket(addr),
+        #[cfg(feature = "server")]
+        (Some(command @ ("serve" | "websocket")), None) => {
+            return Err(format!("Missing address for {command}"));
+        }
+        (Some(command @ ("run" | "check" | "debug")), None) => {
+            return Err(format!("Missing script for {command}"));
+        }
+        (Some(command), _) => return Err(format!("Unknown command {command}")),
+        (None, _) => return Err("Missing command".to_string()),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument {extra}"));
+    }
+
+    Ok(Some(Options {
+        subcommand,
+        robot: builder.build(),
+        logo,
+    }))
+}
+
+fn parse_coordinate(name: &str, value: &str) -> Result<i32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value {value} for {name}"))
+}
+
+fn parse_direction(value: &str) -> Result<Direction, String> {
+    match value {
+        "up" => Ok(Direction::Up),
+        "down" => Ok(Direction::Down),
+        "left" => Ok(Direction::Left),
+        "right" => Ok(Direction::Right),
+        _ => Err(format!("Invalid direction {value}")),
+    }
+}
+
+fn run(options: Options) -> Result<(), Box<dyn error::Error>> {
+    let mut robot = options.robot;
+
+    match options.subcommand {
+        Subcommand::Run(script) => {
+            let source = read_script(&script)?;
+            let mut interpreter = interpreter(&source, &script, options.logo);
+            interpreter.interpret()?.execute_all(&mut robot)?;
+            print_state(&robot);
+        }
+        Subcommand::Check(script) => {
+            let source = read_script(&script)?;
+            let report = interpreter(&source, &script, options.logo).check(&robot)?;
+            print!("{script}: {} commands, final ", report.commands);
+            print_state(&report.robot);
+            if let Some(violation) = report.violation {
+                let message = format!("command {}: {}", violation.index + 1, violation.error);
+                return Err(message.into());
+            }
+        }
+        Subcommand::Repl => run_repl(Session::new(robot), options.logo)?,
+        Subcommand::Debug(script) => {
+            let source = read_script(&script)?;
+            let mut commands = interpreter(&source, &script, options.logo).interpret()?;
+            let total = commands.commands().len();
+            let mut input = io::stdin().lock();
+            let executed = commands.execute_stepwise(&mut robot, |index, command, robot| {
+                ask_step(&mut input, index, total, command, robot)
+            })?;
+            if executed < total {
+                println!("Aborted after {executed} of {total} commands");
+            }
+            print_state(&robot);
+        }
+        #[cfg(feature = "server")]
+        Subcommand::Serve(addr) => {
+            let listener = TcpListener::bind(&addr)?;
+            println!("Listening on {}", listener.local_addr()?);
+            Server::new(robot).serve(listener)?;
+        }
+        #[cfg(feature = "server")]
+        Subcommand::WebSocket(addr) => {
+            let listener = TcpListener::bind(&addr)?;
+            println!("Listening on {}", listener.local_addr()?);
+            WebSocketServer::new(Server::new(robot)).serve(listener)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Скрипт "-" читается со стандартного ввода
+fn read_script(script: &str) -> io::Result<String> {
+    if script == "-" {
+        return io::read_to_string(io::stdin());
+    }
+    fs::read_to_string(script)
+}
+
+// Подключаемые файлы ищутся рядом со скриптом
+fn interpreter<'a>(source: &'a str, script: &str, logo: bool) -> Interpreter<'a> {
+    let interpreter = if logo {
+        Interpreter::logo(source)
+    } else {
+        Interpreter::new(source)
+    };
+    let script_dir = Path::new(script).parent().unwrap_or(Path::new(""));
+    interpreter.with_search_path([script_dir])
+}
+
+fn run_repl(mut session: Session, logo: bool) -> Result<(), Box<dyn error::Error>> {
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout();
+    let mut line = String::new();
+
+    loop {
+        write!(stdout, "> ")?;
+        stdout.flush()?;
+        line.clear();
+        if stdin.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let result = match line.trim().split_once(' ').unwrap_or((line.trim(), "")) {
+            (":undo", "") => repl_step(session.undo(), "Nothing to undo"),
+            (":redo", "") => repl_step(session.redo(), "Nothing to redo"),
+            #[cfg(feature = "serde")]
+            (":save", path) if !path.is_empty() => {
+                session.save(path.trim()).map_err(|err| err.to_string())
+            }
+            #[cfg(feature = "serde")]
+            (":load", path) if !path.is_empty() => Session::load(path.trim())
+                .map(|loaded| session = loaded)
+                .map_err(|err| err.to_string()),
+            _ => {
+                let mut interpreter = interpreter(&line, "", logo);
+                interpreter
+                    .interpret()
+                    .and_then(|commands| {
+                        let entry = CompositeCommand::new(commands.into_iter().collect());
+                        session.execute(Box::new(entry))
+                    })
+                    .map_err(|err| err.annotate(&line))
+            }
+        };
+        match result {
+            Ok(()) => {
+                let robot = session.robot();
+                print_state(robot);
+                println!("{}", robot.stats());
+                if !session.canvas().is_empty() {
+                    print!("{}", session.canvas().render_ascii());
+                }
+            }
+            Err(err) => eprintln!("{}", err.trim_end()),
+        }
+    }
+}
+
+// Результат отмены или повтора: false означает, что отменять или повторять нечего
+fn repl_step(result: Result<bool, Error>, empty: &str) -> Result<(), String> {
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(empty.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+// Показывает следующую команду и состояние робота и спрашивает, что делать дальше.
+// Пустая строка означает шаг, конец ввода прерывает выполнение.
+fn ask_step(
+    input: &mut impl BufRead,
+    index: usize,
+    total: usize,
+    command: &dyn Command,
+    robot: &Robot,
+) -> StepAction {
+    let mut line = String::new();
+
+    loop {
+        print!("[{}/{total}] {command:?}, ", index + 1);
+        print_state(robot);
+        print!("(s)tep, (c)ontinue, (a)bort> ");
+        if io::stdout().flush().is_err() {
+            return StepAction::Abort;
+        }
+
+        line.clear();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return StepAction::Abort,
+            Ok(_) => {}
+        }
+        match line.trim() {
+            "" | "s" | "step" => return StepAction::Step,
+            "c" | "continue" => return StepAction::Continue,
+            "a" | "abort" => return StepAction::Abort,
+            other => println!("Unknown action {other}"),
+        }
+    }
+}
+
+fn print_state(robot: &Robot) {
+    println!("{robot}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args(&[
+            "--x",
+            "-3",
+            "run",
+            "square.robot",
+            "--direction",
+            "left",
+            "--pen-down",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert!(matches!(options.subcommand, Subcommand::Run(script) if script == "square.robot"));
+        assert_eq!((options.robot.x(), options.robot.y()), (-3, 0));
+        assert_eq!(options.robot.direction(), Direction::Left);
+        assert!(options.robot.is_drawing());
+        assert!(!options.logo);
+
+        assert!(parse_args(args(&["repl", "--help"])).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["check"])).is_err());
+        assert!(parse_args(args(&["repl", "extra"])).is_err());
+        assert!(parse_args(args(&["run", "a.robot", "--direction", "north"])).is_err());
+        assert!(parse_args(args(&["run", "a.robot", "--y"])).is_err());
+        assert!(parse_args(args(&["--verbose", "repl"])).is_err());
+    }
+}
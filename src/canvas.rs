@@ -0,0 +1,431 @@
+// Холст хранит то, что нарисовал робот: отрезки пути и закрашенные ими клетки.
+// Холст можно собрать из пути робота (from_robot) или подписать на события робота:
+// холст в Mutex является наблюдателем и рисует каждый новый отрезок.
+// Рамка рисунка (bounding_box) и сдвиг рисунка к началу координат (cropped) помогают
+// выбрать размер изображения.
+// Клетка хранит цвет последнего прошедшего по ней пера, перо в режиме стирания
+// очищает клетки. Холст помнит положение и направление робота для отображения.
+// Цвета клеток образуют палитру (palette), а снимок с палитрой (IndexedCanvas) хранит
+// рисунок по байту на клетку.
+// Отрезок под любым углом закрашивает все пройденные клетки (алгоритм Брезенхэма),
+// линию можно нарисовать и без робота (draw_line).
+// Холст работает с роботом на сетке i32, как и команды.
+// Холст можно напечатать текстом (модуль ascii) и сохранить в SVG (модуль svg).
+// Большой рисунок печатается по частям через видимую область (Viewport) с масштабом.
+// Растровые изображения: PPM без зависимостей (модуль ppm), а с функцией "image" - PNG (модуль png).
+// Выполнение команд можно записать в анимацию GIF (модуль gif).
+// Для плоттеров рисунок раскладывается на штрихи (модуль plotter) и выводится в G-code
+// или в HPGL.
+// Рисунок состоит из слоев (Layer) со своей видимостью и порядком, робот рисует на слое "drawing".
+// Холсты можно сравнить по клеткам (diff) и проверить в тестах макросом assert_canvas_eq!.
+// Рисунок другого холста можно перенести поверх своего (merge).
+// Показатели рисунка (metrics) помогают сравнивать программы: закрашенные клетки,
+// пройденный с опущенным пером путь и повторно закрашенные клетки.
+// С функцией "serde" холст сериализуется разреженно: записываются только закрашенные клетки.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use super::robot::{
+    Bounds, Color, Direction, Pen, PenMode, Point, Robot, RobotEvent, RobotObserver, Segment,
+};
+
+mod ascii;
+mod diff;
+mod gcode;
+mod gif;
+mod hpgl;
+mod indexed;
+mod layer;
+mod metrics;
+mod plotter;
+#[cfg(feature = "image")]
+mod png;
+mod ppm;
+mod raster;
+#[cfg(feature = "serde")]
+mod serialize;
+mod svg;
+mod viewport;
+
+pub use diff::CanvasDiff;
+pub use gcode::GcodeConfig;
+pub use gif::GifEncoderOutput;
+pub use hpgl::HpglOptions;
+pub use indexed::IndexedCanvas;
+pub use layer::Layer;
+pub use metrics::CanvasMetrics;
+pub use svg::SvgOptions;
+pub use viewport::Viewport;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Canvas {
+    // Слои в порядке добавления, порядок рисования задает z слоя
+    layers: Vec<Layer>,
+    robot: Option<(Point, Direction)>,
+}
+
+impl Canvas {
+    // Слой, на котором рисует робот
+    pub const DRAWING: &str = "drawing";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Холст с отрезками пути по порядку
+    pub fn from_path(path: &[Segment]) -> Self {
+        let mut canvas = Self::new();
+        for segment in path {
+            canvas.draw(*segment);
+        }
+        canvas
+    }
+
+    // Холст с путем робота и его текущим положением
+    pub fn from_robot(robot: &Robot) -> Self {
+        let mut canvas = Self::from_path(robot.path());
+        canvas.set_robot(robot.position(), robot.direction());
+        canvas
+    }
+
+    // Рисует отрезок на слое робота
+    pub fn draw(&mut self, segment: Segment) {
+        self.layer(Self::DRAWING).draw(segment);
+    }
+
+    // Рисует на слое робота линию пером по умолчанию
+    pub fn draw_line(&mut self, from: Point, to: Point) {
+        let pen = Pen::new();
+        self.draw(Segment {
+            from,
+            to,
+            color: pen.color(),
+            width: pen.width(),
+            mode: pen.mode(),
+        });
+    }
+
+    // Слой с указанным именем, новый слой добавляется видимым поверх слоев с тем же z
+    pub fn layer(&mut self, name: &str) -> &mut Layer {
+        let index = match self.layers.iter().position(|layer| layer.name() == name) {
+            Some(index) => index,
+            None => {
+                self.layers.push(Layer::new(name));
+                self.layers.len() - 1
+            }
+        };
+        &mut self.layers[index]
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name() == name)
+    }
+
+    pub fn remove_layer(&mut self, name: &str) -> Option<Layer> {
+        let index = self.layers.iter().position(|layer| layer.name() == name)?;
+        Some(self.layers.remove(index))
+    }
+
+    // Все слои снизу вверх
+    pub fn layers(&self) -> impl Iterator<Item = &Layer> {
+        let mut layers: Vec<_> = self.layers.iter().collect();
+        layers.sort_by_key(|layer| layer.z());
+        layers.into_iter()
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &Layer> {
+        self.layers().filter(|layer| layer.is_visible())
+    }
+
+    // Отрезки видимых слоев снизу вверх
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.visible().flat_map(|layer| layer.segments())
+    }
+
+    // Видимые клетки в произвольном порядке с цветом верхнего слоя
+    pub fn cells(&self) -> impl Iterator<Item = (Point, Color)> + use<> {
+        self.composite().into_iter()
+    }
+
+    pub fn color_at(&self, x: i32, y: i32) -> Option<Color> {
+        let layers: Vec<_> = self.visible().collect();
+        layers.iter().rev().find_map(|layer| layer.color_at(x, y))
+    }
+
+    pub fn is_drawn(&self, x: i32, y: i32) -> bool {
+        self.color_at(x, y).is_some()
+    }
+
+    // Количество видимых закрашенных клеток
+    pub fn len(&self) -> usize {
+        self.composite().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.visible().all(Layer::is_empty)
+    }
+
+    // Наименьший прямоугольник с видимыми клетками или None для пустого холста
+    pub fn bounding_box(&self) -> Option<Bounds> {
+        extent(
+            self.visible()
+                .flat_map(|layer| layer.cells())
+                .map(|(point, _)| point),
+        )
+        .map(|(min, max)| Bounds::new(min.x, max.x, min.y, max.y))
+    }
+
+    // Копия холста, сдвинутая так, что рисунок начинается в клетке (0, 0).
+    // Стертые отрезки и робот могут оказаться за краем сетки, тогда они прижимаются к краю.
+    pub fn cropped(&self) -> Self {
+        let Some(bounds) = self.bounding_box() else {
+            return self.clone();
+        };
+        let shift = |point: Point| {
+            Point::new(
+                point.x.saturating_sub(bounds.min_x),
+                point.y.saturating_sub(bounds.min_y),
+            )
+        };
+
+        Self {
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| layer.shifted(shift))
+                .collect(),
+            robot: self
+                .robot
+                .map(|(position, direction)| (shift(position), direction)),
+        }
+    }
+
+    // Положение и направление робота, если холст их знает
+    pub fn robot(&self) -> Option<(Point, Direction)> {
+        self.robot
+    }
+
+    pub fn set_robot(&mut self, position: Point, direction: Direction) {
+        self.robot = Some((position, direction));
+    }
+
+    // Переносит рисунок другого холста: его клетки закрашиваются поверх клеток слоев
+    // с тем же именем, а отрезки добавляются в конец. Слои, которых здесь нет,
+    // копируются целиком вместе с видимостью и z. Положение робота не меняется.
+    pub fn merge(&mut self, other: &Canvas) {
+        for layer in &other.layers {
+            if self.get_layer(layer.name()).is_none() {
+                self.layers.push(layer.clone());
+                continue;
+            }
+            let target = self.layer(layer.name());
+            target.cells.extend(layer.cells());
+            target.segments.extend_from_slice(layer.segments());
+        }
+    }
+
+    // Очищает все слои, слои и положение робота остаются
+    pub fn clear(&mut self) {
+        for layer in &mut self.layers {
+            layer.clear();
+        }
+    }
+
+    // Видимые клетки всех слоев, верхний слой закрывает нижние
+    fn composite(&self) -> HashMap<Point, Color> {
+        self.visible().flat_map(|layer| layer.cells()).collect()
+    }
+
+    fn handle(&mut self, event: &RobotEvent) {
+        match *event {
+            RobotEvent::Drawn(segment) => self.draw(segment),
+            // Положение робота обновляется, только если холст его уже знает
+            RobotEvent::Moved { to, .. } => {
+                if let Some((position, _)) = &mut self.robot {
+                    *position = to;
+                }
+            }
+            RobotEvent::Turned { to, .. } => {
+                if let Some((_, direction)) = &mut self.robot {
+                    *direction = to;
+                }
+            }
+            RobotEvent::PenChanged(_) | RobotEvent::Restored => {}
+        }
+    }
+}
+
+// Холст в Mutex можно добавить роботу как наблюдателя и читать между командами
+impl RobotObserver for Mutex<Canvas> {
+    fn notify(&self, event: &RobotEvent) {
+        // После паники другого владельца холст продолжает принимать отрезки
+        let mut canvas = self.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        canvas.handle(event);
+    }
+}
+
+// Наименьшие и наибольшие координаты точек или None, если точек нет
+fn extent(points: impl Iterator<Item = Point>) -> Option<(Point, Point)> {
+    points.fold(None, |extent, point| {
+        let (min, max) = extent.unwrap_or((point, point));
+        Some((
+            Point::new(min.x.min(point.x), min.y.min(point.y)),
+            Point::new(max.x.max(point.x), max.y.max(point.y)),
+        ))
+    })
+}
+
+// Клетки отрезка по алгоритму Брезенхэма: для отрезка под любым углом отмечается каждая
+// пройденная клетка, а отрезки по восьми направлениям робота проходят клетки с одинаковым шагом
+fn cells(from: Point, to: Point) -> impl Iterator<Item = Point> {
+    let (x1, y1) = (i64::from(to.x), i64::from(to.y));
+    let (dx, dy) = (
+        (x1 - i64::from(from.x)).abs(),
+        -(y1 - i64::from(from.y)).abs(),
+    );
+    let (step_x, step_y) = (
+        (x1 - i64::from(from.x)).signum(),
+        (y1 - i64::from(from.y)).signum(),
+    );
+    let mut point = Some((i64::from(from.x), i64::from(from.y)));
+    let mut error = dx + dy;
+
+    std::iter::from_fn(move || {
+        let (x, y) = point?;
+        point = if (x, y) == (x1, y1) {
+            None
+        } else {
+            let (mut next_x, mut next_y) = (x, y);
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                next_x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                next_y += step_y;
+            }
+            Some((next_x, next_y))
+        };
+        // Точки лежат между концами отрезка, поэтому помещаются в i32
+        Some(Point::new(x as i32, y as i32))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_canvas_from_robot() {
+        let mut robot = Robot::default();
+        robot.down_pen();
+        robot.move_forward_by(2).unwrap();
+        robot.turn_right_45();
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+
+        let canvas = Canvas::from_robot(&robot);
+        assert_eq!(canvas.len(), 4);
+        assert_eq!(canvas.segments().count(), 2);
+        assert_eq!(canvas.color_at(0, 1), Some(Color::Black));
+        assert_eq!(canvas.color_at(0, 2), Some(Color::Red));
+        assert_eq!(canvas.color_at(1, 3), Some(Color::Red));
+        assert!(!canvas.is_drawn(1, 2));
+        assert_eq!(canvas.robot(), Some((Point::new(1, 3), Direction::UpRight)));
+    }
+
+    #[test]
+    fn test_canvas_bounding_box_and_cropped() {
+        let mut robot = Robot::new(-3, 5, Direction::Right, true);
+        robot.move_forward_by(2).unwrap();
+        robot.turn_right();
+        robot.move_forward().unwrap();
+
+        let canvas = Canvas::from_robot(&robot);
+        assert_eq!(canvas.bounding_box(), Some(Bounds::new(-3, -1, 4, 5)));
+        assert_eq!(Canvas::new().bounding_box(), None);
+
+        let cropped = canvas.cropped();
+        assert_eq!(cropped.bounding_box(), Some(Bounds::new(0, 2, 0, 1)));
+        assert!(cropped.is_drawn(0, 1) && cropped.is_drawn(2, 0));
+        assert_eq!(cropped.segments().nth(1).unwrap().to, Point::new(2, 0));
+        assert_eq!(cropped.robot(), Some((Point::new(2, 0), Direction::Down)));
+        assert_eq!(cropped.render_ascii(), canvas.render_ascii());
+    }
+
+    #[test]
+    fn test_canvas_merge() {
+        let mut canvas = Canvas::new();
+        canvas.draw_line(Point::new(0, 0), Point::new(2, 0));
+        canvas.set_robot(Point::new(2, 0), Direction::Right);
+
+        let segment = |from: Point, to: Point, color: Color| Segment {
+            from,
+            to,
+            color,
+            width: 1,
+            mode: PenMode::Draw,
+        };
+        let mut other = Canvas::new();
+        other.draw(segment(Point::new(2, 0), Point::new(3, 0), Color::Red));
+        let notes = other.layer("notes");
+        notes.set_z(1);
+        notes.draw(segment(Point::new(0, 0), Point::new(0, 0), Color::Blue));
+        other.set_robot(Point::new(9, 9), Direction::Up);
+
+        canvas.merge(&other);
+        assert_eq!(canvas.len(), 4);
+        assert_eq!(canvas.color_at(2, 0), Some(Color::Red));
+        assert_eq!(canvas.color_at(0, 0), Some(Color::Blue));
+        assert_eq!(
+            canvas.get_layer(Canvas::DRAWING).unwrap().segments().len(),
+            2
+        );
+        assert_eq!(canvas.get_layer("notes").unwrap().z(), 1);
+        assert_eq!(canvas.robot(), Some((Point::new(2, 0), Direction::Right)));
+    }
+
+    #[test]
+    fn test_canvas_draw_line() {
+        let line =
+            |from: (i32, i32), to: (i32, i32)| cells(from.into(), to.into()).collect::<Vec<_>>();
+        assert_eq!(
+            line((0, 0), (4, 2)),
+            [(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)].map(Point::from)
+        );
+        assert_eq!(
+            line((0, 0), (-1, -3)),
+            [(0, 0), (0, -1), (-1, -2), (-1, -3)].map(Point::from)
+        );
+        assert_eq!(line((2, 2), (2, 2)), [Point::new(2, 2)]);
+        assert_eq!(line((3, 0), (0, 3)).len(), 4);
+
+        let mut canvas = Canvas::new();
+        canvas.draw_line(Point::new(0, 0), Point::new(2, 5));
+        assert_eq!(canvas.len(), 6);
+        assert!(canvas.is_drawn(1, 2) && canvas.is_drawn(1, 3));
+        assert_eq!(canvas.color_at(2, 5), Some(Color::Black));
+    }
+
+    #[test]
+    fn test_canvas_observer() {
+        let mut robot = Robot::default();
+        let canvas = Arc::new(Mutex::new(Canvas::from_robot(&robot)));
+        robot.add_observer(canvas.clone());
+
+        robot.down_pen();
+        robot.move_forward_by(3).unwrap();
+        robot.set_pen_mode(PenMode::Erase);
+        robot.move_backward().unwrap();
+        robot.turn_left();
+
+        let canvas = canvas.lock().unwrap();
+        assert_eq!(*canvas, Canvas::from_robot(&robot));
+        assert!(canvas.is_drawn(0, 0));
+        assert!(!canvas.is_drawn(0, 2));
+        assert_eq!(canvas.robot(), Some((Point::new(0, 2), Direction::Left)));
+    }
+}
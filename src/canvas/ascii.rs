@@ -0,0 +1,153 @@
+// Текстовое изображение холста для терминала и тестов.
+// Видимая закрашенная клетка - "*", пустая - ".", робот - стрелка по направлению: "^", "v", "<", ">",
+// а по диагоналям "/" и "\". Границы выбираются по рисунку и роботу,
+// верхняя строка - наибольший y, как в карте мира.
+// Цветной вариант (render_ascii_colored) окрашивает клетки цветом пера escape-последовательностями
+// ANSI, белые клетки не видны на светлом терминале, как и на белом изображении.
+// Большой рисунок можно печатать по частям (view): выводится только видимая область
+// (Viewport) заданного размера, символ закрашен, если закрашена любая его клетка.
+
+use super::*;
+
+impl Canvas {
+    pub fn render_ascii(&self) -> String {
+        self.render(|_| "*".to_string())
+    }
+
+    pub fn render_ascii_colored(&self) -> String {
+        self.render(|color| format!("\x1b[{}m*\x1b[0m", ansi(color)))
+    }
+
+    // Печатает холст, закрашенная клетка выводится функцией cell по ее цвету
+    fn render(&self, cell: impl Fn(Color) -> String) -> String {
+        let cells = self.composite();
+        let robot = self.robot.map(|(position, _)| position);
+        let Some((min, max)) = extent(cells.keys().copied().chain(robot)) else {
+            return String::new();
+        };
+
+        let mut output = String::new();
+        for y in (min.y..=max.y).rev() {
+            for x in min.x..=max.x {
+                let point = Point::new(x, y);
+                match (self.robot, cells.get(&point)) {
+                    (Some((position, direction)), _) if position == point => {
+                        output.push(arrow(direction))
+                    }
+                    (_, Some(&color)) => output.push_str(&cell(color)),
+                    _ => output.push('.'),
+                }
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    // Текст видимой области: всегда height строк по width символов
+    pub fn view(&self, viewport: Viewport) -> String {
+        let (width, height) = (viewport.width as usize, viewport.height as usize);
+        let mut symbols = vec![vec!['.'; width]; height];
+        for (point, _) in self.cells() {
+            if let Some((column, row)) = viewport.locate(point) {
+                symbols[row][column] = '*';
+            }
+        }
+        if let Some((position, direction)) = self.robot
+            && let Some((column, row)) = viewport.locate(position)
+        {
+            symbols[row][column] = arrow(direction);
+        }
+
+        let mut output = String::with_capacity((width + 1) * height);
+        for row in symbols {
+            output.extend(row);
+            output.push('\n');
+        }
+        output
+    }
+}
+
+// Цвет текста ANSI
+fn ansi(color: Color) -> u8 {
+    match color {
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::White => 37,
+    }
+}
+
+fn arrow(direction: Direction) -> char {
+    match direction {
+        Direction::Up => '^',
+        Direction::Down => 'v',
+        Direction::Left => '<',
+        Direction::Right => '>',
+        Direction::UpRight | Direction::DownLeft => '/',
+        Direction::UpLeft | Direction::DownRight => '\\',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_ascii() {
+        let mut robot = Robot::new(-1, 0, Direction::Up, true);
+        robot.move_forward_by(2).unwrap();
+        robot.turn_right();
+        robot.move_forward_by(2).unwrap();
+        robot.up_pen();
+        robot.turn_right();
+        robot.move_forward().unwrap();
+
+        assert_eq!(Canvas::from_robot(&robot).render_ascii(), "***\n*.v\n*..\n");
+        assert_eq!(
+            Canvas::from_path(robot.path()).render_ascii(),
+            "***\n*..\n*..\n"
+        );
+        assert_eq!(Canvas::new().render_ascii(), "");
+    }
+
+    #[test]
+    fn test_render_ascii_colored() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+        robot.up_pen();
+        robot.move_forward().unwrap();
+
+        assert_eq!(
+            Canvas::from_robot(&robot).render_ascii_colored(),
+            "\x1b[31m*\x1b[0m\x1b[31m*\x1b[0m>\n"
+        );
+    }
+
+    #[test]
+    fn test_view() {
+        let mut canvas = Canvas::new();
+        canvas.draw_line(Point::new(-100, 0), Point::new(100, 0));
+        canvas.draw_line(Point::new(3, 3), Point::new(3, 3));
+        canvas.set_robot(Point::new(0, 1), Direction::Right);
+
+        let viewport = Viewport::new(Point::new(0, 0), 7, 3);
+        assert_eq!(canvas.view(viewport), "...>...\n*******\n.......\n");
+
+        // Вдвое мельче: клетка (3, 3) попадает в символ с клетками x 2..=3, y 2..=3,
+        // а робот закрывает символ линии, в который попал
+        let zoomed = Viewport {
+            zoom: 2,
+            ..Viewport::new(Point::new(0, 0), 5, 3)
+        };
+        assert_eq!(canvas.view(zoomed), "...*.\n**>**\n.....\n");
+
+        let empty = Viewport::new(Point::new(1000, 1000), 3, 1);
+        assert_eq!(canvas.view(empty), "...\n");
+        assert_eq!(canvas.view(Viewport::new(Point::new(0, 0), 0, 0)), "");
+    }
+}
@@ -0,0 +1,149 @@
+// Сравнение холстов для тестов с эталонными рисунками.
+// Сравниваются только видимые клетки и их цвета: отрезки, слои и положение робота
+// могут отличаться, если рисунок получился тем же.
+// Макрос assert_canvas_eq! проверяет, что рисунки совпадают, и печатает различия.
+
+use std::fmt;
+
+use super::*;
+
+// Различия двух холстов, клетки упорядочены по x, затем по y
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CanvasDiff {
+    removed: Vec<(Point, Color)>,
+    added: Vec<(Point, Color)>,
+    changed: Vec<(Point, Color, Color)>,
+}
+
+impl CanvasDiff {
+    // Клетки, которые есть только на первом холсте
+    pub fn removed(&self) -> &[(Point, Color)] {
+        &self.removed
+    }
+
+    // Клетки, которые есть только на втором холсте
+    pub fn added(&self) -> &[(Point, Color)] {
+        &self.added
+    }
+
+    // Клетки обоих холстов с разным цветом: сначала цвет первого, затем второго
+    pub fn changed(&self) -> &[(Point, Color, Color)] {
+        &self.changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for CanvasDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (point, color) in &self.removed {
+            writeln!(f, "- {point} {color}")?;
+        }
+        for (point, color) in &self.added {
+            writeln!(f, "+ {point} {color}")?;
+        }
+        for (point, from, to) in &self.changed {
+            writeln!(f, "~ {point} {from} -> {to}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Canvas {
+    // Клетки, которые отличаются на этом и другом холсте
+    pub fn diff(&self, other: &Canvas) -> CanvasDiff {
+        let (lhs, rhs) = (self.composite(), other.composite());
+        let mut diff = CanvasDiff::default();
+        for (&point, &color) in &lhs {
+            match rhs.get(&point) {
+                None => diff.removed.push((point, color)),
+                Some(&other) if other != color => diff.changed.push((point, color, other)),
+                Some(_) => {}
+            }
+        }
+        for (&point, &color) in &rhs {
+            if !lhs.contains_key(&point) {
+                diff.added.push((point, color));
+            }
+        }
+
+        diff.removed.sort_by_key(|(point, _)| (point.x, point.y));
+        diff.added.sort_by_key(|(point, _)| (point.x, point.y));
+        diff.changed.sort_by_key(|(point, ..)| (point.x, point.y));
+        diff
+    }
+}
+
+// Паникует, если на холстах закрашены разные клетки, и печатает различия.
+// После холстов можно передать сообщение с аргументами, как в assert_eq!.
+#[macro_export]
+macro_rules! assert_canvas_eq {
+    ($left:expr, $right:expr $(,)?) => {{
+        let diff = $crate::canvas::Canvas::diff(&$left, &$right);
+        if !diff.is_empty() {
+            panic!("canvases differ:\n{diff}");
+        }
+    }};
+    ($left:expr, $right:expr, $($arg:tt)+) => {{
+        let diff = $crate::canvas::Canvas::diff(&$left, &$right);
+        if !diff.is_empty() {
+            panic!("canvases differ: {}\n{diff}", format_args!($($arg)+));
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_diff() {
+        let mut lhs = Canvas::new();
+        lhs.draw_line(Point::new(0, 0), Point::new(2, 0));
+        let mut rhs = Canvas::new();
+        rhs.draw_line(Point::new(1, 0), Point::new(3, 0));
+        rhs.layer("marks").draw(Segment {
+            from: Point::new(2, 0),
+            to: Point::new(2, 0),
+            color: Color::Red,
+            width: 1,
+            mode: PenMode::Draw,
+        });
+
+        let diff = lhs.diff(&rhs);
+        assert_eq!(diff.removed(), [(Point::new(0, 0), Color::Black)]);
+        assert_eq!(diff.added(), [(Point::new(3, 0), Color::Black)]);
+        assert_eq!(
+            diff.changed(),
+            [(Point::new(2, 0), Color::Black, Color::Red)]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "- (0, 0) black\n+ (3, 0) black\n~ (2, 0) black -> red\n"
+        );
+        assert!(lhs.diff(&lhs).is_empty());
+    }
+
+    #[test]
+    fn test_assert_canvas_eq() {
+        // Один и тот же рисунок, нарисованный разными отрезками
+        let mut lhs = Canvas::new();
+        lhs.draw_line(Point::new(0, 0), Point::new(3, 0));
+        let mut rhs = Canvas::new();
+        rhs.draw_line(Point::new(3, 0), Point::new(2, 0));
+        rhs.draw_line(Point::new(0, 0), Point::new(1, 0));
+        rhs.set_robot(Point::new(5, 5), Direction::Left);
+        assert_canvas_eq!(lhs, rhs);
+        assert_canvas_eq!(lhs, rhs, "drawing {}", "line");
+    }
+
+    #[test]
+    #[should_panic(expected = "canvases differ: after clear\n- (0, 0) black")]
+    fn test_assert_canvas_eq_fails() {
+        let mut canvas = Canvas::new();
+        canvas.draw_line(Point::new(0, 0), Point::new(1, 0));
+        assert_canvas_eq!(canvas, Canvas::new(), "after clear");
+    }
+}
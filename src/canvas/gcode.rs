@@ -0,0 +1,160 @@
+// Экспорт рисунка в G-code для перьевого плоттера или станка с ЧПУ.
+// Координаты в миллиметрах (G21) и абсолютные (G90), клетка занимает scale миллиметров.
+// Перо поднимается и опускается осью Z: переезды между штрихами идут холостым ходом (G0)
+// с поднятым пером, а штрихи - рабочим ходом (G1) с заданной подачей.
+// В конце перо поднимается и возвращается в начало координат.
+
+use std::fmt::Write;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GcodeConfig {
+    feed_rate: f64,
+    pen_up_z: f64,
+    pen_down_z: f64,
+    scale: f64,
+}
+
+impl Default for GcodeConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GcodeConfig {
+    pub fn new() -> Self {
+        Self {
+            feed_rate: 1000.0,
+            pen_up_z: 5.0,
+            pen_down_z: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    // Подача при рисовании, мм/мин
+    pub fn with_feed_rate(mut self, feed_rate: f64) -> Self {
+        self.feed_rate = feed_rate;
+        self
+    }
+
+    pub fn with_pen_up_z(mut self, z: f64) -> Self {
+        self.pen_up_z = z;
+        self
+    }
+
+    pub fn with_pen_down_z(mut self, z: f64) -> Self {
+        self.pen_down_z = z;
+        self
+    }
+
+    // Миллиметров в одной клетке
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn feed_rate(&self) -> f64 {
+        self.feed_rate
+    }
+
+    pub fn pen_up_z(&self) -> f64 {
+        self.pen_up_z
+    }
+
+    pub fn pen_down_z(&self) -> f64 {
+        self.pen_down_z
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+}
+
+impl Canvas {
+    pub fn to_gcode(&self, config: GcodeConfig) -> String {
+        let position = |(x, y): (u64, u64)| {
+            format!(
+                "X{} Y{}",
+                number(x as f64 * config.scale),
+                number(y as f64 * config.scale)
+            )
+        };
+        let pen_up = format!("G0 Z{}", number(config.pen_up_z));
+        let pen_down = format!(
+            "G1 Z{} F{}",
+            number(config.pen_down_z),
+            number(config.feed_rate)
+        );
+
+        let mut gcode = String::from("G21\nG90\n");
+        let _ = writeln!(gcode, "{pen_up}");
+        for stroke in self.strokes() {
+            let _ = writeln!(gcode, "G0 {}", position(stroke.points[0]));
+            let _ = writeln!(gcode, "{pen_down}");
+            for &point in &stroke.points[1..] {
+                let _ = writeln!(
+                    gcode,
+                    "G1 {} F{}",
+                    position(point),
+                    number(config.feed_rate)
+                );
+            }
+            let _ = writeln!(gcode, "{pen_up}");
+        }
+        gcode.push_str("G0 X0 Y0\nM2\n");
+        gcode
+    }
+}
+
+// Число с точностью до тысячной без лишних нулей: 1.5 вместо 1.500
+fn number(value: f64) -> String {
+    let text = format!("{value:.3}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_gcode() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.move_forward_by(2).unwrap();
+        robot.up_pen();
+        robot.move_forward().unwrap();
+        robot.down_pen();
+        robot.turn_left();
+        robot.move_forward().unwrap();
+
+        let config = GcodeConfig::new()
+            .with_scale(2.5)
+            .with_feed_rate(600.0)
+            .with_pen_down_z(-0.5);
+        assert_eq!(
+            Canvas::from_robot(&robot).to_gcode(config),
+            concat!(
+                "G21\nG90\nG0 Z5\n",
+                "G0 X0 Y0\nG1 Z-0.5 F600\nG1 X5 Y0 F600\nG0 Z5\n",
+                "G0 X7.5 Y0\nG1 Z-0.5 F600\nG1 X7.5 Y2.5 F600\nG0 Z5\n",
+                "G0 X0 Y0\nM2\n",
+            )
+        );
+        assert_eq!(
+            Canvas::new().to_gcode(GcodeConfig::new()),
+            "G21\nG90\nG0 Z5\nG0 X0 Y0\nM2\n"
+        );
+    }
+
+    #[test]
+    fn test_number() {
+        assert_eq!(number(1.0), "1");
+        assert_eq!(number(0.1 + 0.2), "0.3");
+        assert_eq!(number(-0.0001), "0");
+        assert_eq!(number(12.3456), "12.346");
+    }
+}
@@ -0,0 +1,295 @@
+// Анимация выполнения команд в формате GIF.
+// Первый кадр показывает рисунок до выполнения, затем после каждой команды снимается кадр.
+// Все кадры одного размера: рамка охватывает рисунок всех кадров.
+// Цвета робота образуют палитру GIF, поэтому пиксели записываются номерами цветов.
+// Данные LZW записываются без сжатия: код очистки повторяется раньше, чем растет
+// размер кода, поэтому кодировщику не нужна таблица строк.
+
+use std::{fs, io, path::Path};
+
+use super::*;
+use crate::{
+    command::{Command, CommandList},
+    error::Error,
+};
+
+// Пикселей на клетку и пауза между кадрами в сотых долях секунды
+const SCALE: u32 = 8;
+const FRAME_DELAY: u16 = 20;
+// Палитра из восьми цветов, номер цвета записывается тремя битами
+const MIN_CODE_SIZE: u8 = 3;
+
+// Готовый файл GIF
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GifEncoderOutput {
+    bytes: Vec<u8>,
+    frames: usize,
+}
+
+impl GifEncoderOutput {
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, &self.bytes)
+    }
+}
+
+impl Canvas {
+    // Выполняет команды роботом и записывает анимацию рисования.
+    // При ошибке команды анимация не создается, робот остается после последней команды.
+    pub fn record_animation<C: Command + 'static>(
+        robot: &mut Robot,
+        commands: &mut CommandList<C>,
+    ) -> Result<GifEncoderOutput, Error> {
+        let mut canvas = Canvas::from_robot(robot);
+        let mut frames = vec![canvas.clone()];
+        for command in commands {
+            command.execute(robot)?;
+            // Новые отрезки дорисовываются на холст предыдущего кадра
+            for segment in robot
+                .path()
+                .get(canvas.layer(Canvas::DRAWING).segments().len()..)
+                .unwrap_or_default()
+            {
+                canvas.draw(*segment);
+            }
+            canvas.set_robot(robot.position(), robot.direction());
+            frames.push(canvas.clone());
+        }
+
+        let bounds = frames
+            .iter()
+            .filter_map(Canvas::bounding_box)
+            .reduce(|lhs, rhs| {
+                Bounds::new(
+                    lhs.min_x.min(rhs.min_x),
+                    lhs.max_x.max(rhs.max_x),
+                    lhs.min_y.min(rhs.min_y),
+                    lhs.max_y.max(rhs.max_y),
+                )
+            })
+            .unwrap_or(Bounds::new(0, 0, 0, 0));
+        let rasters: Vec<_> = frames
+            .iter()
+            .map(|frame| frame.rasterize_in(bounds, SCALE))
+            .collect::<Result<_, _>>()?;
+
+        let (width, height) = (rasters[0].width, rasters[0].height);
+        let (Ok(screen_width), Ok(screen_height)) = (u16::try_from(width), u16::try_from(height))
+        else {
+            return Err(Error::ImageTooLarge(width, height));
+        };
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&screen_width.to_le_bytes());
+        gif.extend_from_slice(&screen_height.to_le_bytes());
+        // Глобальная палитра из 2^(2 + 1) цветов, фон - белый
+        gif.extend_from_slice(&[0xa2, palette_index(Color::White.rgb()), 0]);
+        for color in Color::ALL {
+            let (red, green, blue) = color.rgb();
+            gif.extend_from_slice(&[red, green, blue]);
+        }
+        // Бесконечное повторение анимации
+        gif.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        for raster in &rasters {
+            gif.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+            gif.extend_from_slice(&FRAME_DELAY.to_le_bytes());
+            gif.extend_from_slice(&[0x00, 0x00]);
+
+            gif.extend_from_slice(&[0x2c, 0, 0, 0, 0]);
+            gif.extend_from_slice(&screen_width.to_le_bytes());
+            gif.extend_from_slice(&screen_height.to_le_bytes());
+            gif.push(0);
+
+            let indices: Vec<u8> = raster
+                .pixels
+                .chunks(3)
+                .map(|rgb| palette_index((rgb[0], rgb[1], rgb[2])))
+                .collect();
+            gif.push(MIN_CODE_SIZE);
+            for block in lzw(&indices).chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0);
+        }
+        gif.push(0x3b);
+
+        Ok(GifEncoderOutput {
+            bytes: gif,
+            frames: rasters.len(),
+        })
+    }
+}
+
+// Пиксели растра всегда имеют цвет из палитры
+fn palette_index(rgb: (u8, u8, u8)) -> u8 {
+    Color::ALL
+        .iter()
+        .position(|color| color.rgb() == rgb)
+        .expect("raster color is in the palette") as u8
+}
+
+// Коды LZW без сжатия: каждый пиксель - отдельный код, а таблица сбрасывается раньше,
+// чем в нее добавится код, требующий на бит больше
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let code_size = u32::from(MIN_CODE_SIZE) + 1;
+    let run = usize::from(clear) - 2;
+
+    let mut writer = BitWriter::default();
+    for chunk in indices.chunks(run) {
+        writer.write(clear, code_size);
+        for &index in chunk {
+            writer.write(u16::from(index), code_size);
+        }
+    }
+    writer.write(clear, code_size);
+    writer.write(end, code_size);
+    writer.finish()
+}
+
+// Запись кодов младшими битами вперед, как требует GIF
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= u32::from(code) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{DownPenCommand, MoveCommand, TurnRightCommand};
+
+    // Распаковщик LZW для проверки: повторяет таблицу строк декодера GIF
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let clear = 1usize << MIN_CODE_SIZE;
+        let (mut position, mut size) = (0, u32::from(MIN_CODE_SIZE) + 1);
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        let mut output = Vec::new();
+        loop {
+            let mut code = 0;
+            for bit in 0..size {
+                let byte = data[(position + bit as usize) / 8];
+                code |= usize::from((byte >> ((position + bit as usize) % 8)) & 1) << bit;
+            }
+            position += size as usize;
+            if code == clear {
+                table = (0..clear).map(|index| vec![index as u8]).collect();
+                table.extend([Vec::new(), Vec::new()]);
+                size = u32::from(MIN_CODE_SIZE) + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return output;
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("invalid code {code}"),
+            };
+            if let Some(previous) = previous {
+                table.push([previous, vec![entry[0]]].concat());
+                if table.len() == 1 << size {
+                    size += 1;
+                }
+            }
+            output.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let indices: Vec<u8> = (0..1000).map(|index| (index * 7 % 8) as u8).collect();
+        assert_eq!(unlzw(&lzw(&indices)), indices);
+        assert_eq!(unlzw(&lzw(&[])), []);
+    }
+
+    #[test]
+    fn test_record_animation() {
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(DownPenCommand) as Box<dyn Command>);
+        commands.add_command(Box::new(MoveCommand::new(2)));
+        commands.add_command(Box::new(TurnRightCommand::new(1)));
+        commands.add_command(Box::new(MoveCommand::new(1)));
+
+        let mut robot = Robot::default();
+        let gif = Canvas::record_animation(&mut robot, &mut commands).unwrap();
+        assert_eq!(gif.frames(), 5);
+        assert_eq!((robot.x(), robot.y()), (1, 2));
+
+        let bytes = gif.as_bytes();
+        assert_eq!(&bytes[..6], b"GIF89a");
+        // Рисунок последнего кадра занимает 2x3 клетки по 8 пикселей
+        assert_eq!(bytes[6..10], [16, 0, 24, 0]);
+        assert_eq!(bytes.last(), Some(&0x3b));
+
+        // Заголовок, палитра и расширение повтора, затем кадры
+        let mut position = 13 + 24 + 19;
+        let mut frames = Vec::new();
+        while bytes[position] == 0x21 {
+            assert_eq!(bytes[position + 8], 0x2c);
+            position += 8 + 10 + 1;
+            let mut data = Vec::new();
+            while bytes[position] != 0 {
+                let len = usize::from(bytes[position]);
+                data.extend_from_slice(&bytes[position + 1..position + 1 + len]);
+                position += 1 + len;
+            }
+            position += 1;
+            frames.push(unlzw(&data));
+        }
+        assert_eq!(frames.len(), 5);
+        let (white, black) = (
+            palette_index(Color::White.rgb()),
+            palette_index(Color::Black.rgb()),
+        );
+        assert!(frames[0].iter().all(|&index| index == white));
+        // В последнем кадре закрашены левый столбец и правая верхняя клетка
+        let last = &frames[4];
+        assert_eq!(last.len(), 16 * 24);
+        assert_eq!((last[0], last[8], last[16 * 8 + 8]), (black, black, white));
+        assert_eq!(last[16 * 23], black);
+
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(MoveCommand::new(1)));
+        robot.set_bounds(Bounds::new(0, 1, 0, 2));
+        let result = Canvas::record_animation(&mut robot, &mut commands);
+        assert!(matches!(result, Err(Error::OutOfBounds)));
+    }
+}
@@ -0,0 +1,112 @@
+// Экспорт рисунка в HPGL для классических плоттеров HP.
+// Программа начинается с IN (сброс) и PA (абсолютные координаты), затем для каждого
+// штриха перо поднимается (PU) над его началом и опускается (PD) по его точкам.
+// Координаты в единицах плоттера: по умолчанию 40 единиц (1 мм) на клетку.
+// Цвет пера выбирает перо плоттера (SP) по номеру цвета в Color::ALL, начиная с единицы,
+// или для всего рисунка используется первое перо. В конце перо убирается (SP0).
+
+use std::fmt::Write;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HpglOptions {
+    scale: u32,
+    pens: bool,
+}
+
+impl Default for HpglOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HpglOptions {
+    pub fn new() -> Self {
+        Self {
+            scale: 40,
+            pens: true,
+        }
+    }
+
+    // Единиц плоттера в одной клетке
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    // Выбирать ли перо по цвету отрезков
+    pub fn with_pens(mut self, pens: bool) -> Self {
+        self.pens = pens;
+        self
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn pens(&self) -> bool {
+        self.pens
+    }
+}
+
+impl Canvas {
+    pub fn to_hpgl(&self, options: HpglOptions) -> String {
+        let scale = u64::from(options.scale);
+        let mut hpgl = String::from("IN;PA;");
+        let mut pen = None;
+        for stroke in self.strokes() {
+            let stroke_pen = if options.pens {
+                Color::ALL
+                    .iter()
+                    .position(|&color| color == stroke.color)
+                    .unwrap_or(0)
+                    + 1
+            } else {
+                1
+            };
+            if pen != Some(stroke_pen) {
+                let _ = write!(hpgl, "SP{stroke_pen};");
+                pen = Some(stroke_pen);
+            }
+
+            let (x, y) = stroke.points[0];
+            let _ = write!(hpgl, "PU{},{};PD", x * scale, y * scale);
+            for (index, (x, y)) in stroke.points[1..].iter().enumerate() {
+                if index > 0 {
+                    hpgl.push(',');
+                }
+                let _ = write!(hpgl, "{},{}", x * scale, y * scale);
+            }
+            hpgl.push(';');
+        }
+        hpgl.push_str("PU;SP0;");
+        hpgl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_hpgl() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.move_forward_by(2).unwrap();
+        robot.turn_left();
+        robot.move_forward().unwrap();
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+        let canvas = Canvas::from_robot(&robot);
+
+        assert_eq!(
+            canvas.to_hpgl(HpglOptions::new()),
+            "IN;PA;SP1;PU0,0;PD80,0,80,40;SP3;PU80,40;PD80,80;PU;SP0;"
+        );
+        assert_eq!(
+            canvas.to_hpgl(HpglOptions::new().with_scale(1).with_pens(false)),
+            "IN;PA;SP1;PU0,0;PD2,0,2,1;PU2,1;PD2,2;PU;SP0;"
+        );
+        assert_eq!(Canvas::new().to_hpgl(HpglOptions::new()), "IN;PA;PU;SP0;");
+    }
+}
@@ -0,0 +1,142 @@
+// Компактный снимок рисунка с палитрой. Палитра - цвета, которые есть на рисунке,
+// в порядке Color::ALL, а каждая клетка рамки рисунка хранится одним байтом:
+// 0 - пустая клетка, n - n-й цвет палитры. Клетки идут по строкам сверху вниз,
+// как в растровых изображениях.
+// Снимок хранит только видимые клетки: слои, отрезки и робот в него не попадают,
+// а холст из снимка содержит клетки на слое робота.
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IndexedCanvas {
+    pub(super) palette: Vec<Color>,
+    // Левый нижний угол рамки
+    pub(super) origin: Point,
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) indices: Vec<u8>,
+}
+
+impl IndexedCanvas {
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    pub fn color_at(&self, x: i32, y: i32) -> Option<Color> {
+        let column = usize::try_from(i64::from(x) - i64::from(self.origin.x)).ok()?;
+        let row = usize::try_from(i64::from(y) - i64::from(self.origin.y)).ok()?;
+        if column >= self.width || row >= self.height {
+            return None;
+        }
+        let index = self.indices[(self.height - 1 - row) * self.width + column];
+        self.palette
+            .get(usize::from(index).checked_sub(1)?)
+            .copied()
+    }
+
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new();
+        let layer = canvas.layer(Canvas::DRAWING);
+        for (offset, &index) in self.indices.iter().enumerate() {
+            let Some(&color) = usize::from(index)
+                .checked_sub(1)
+                .and_then(|index| self.palette.get(index))
+            else {
+                continue;
+            };
+            let (row, column) = (offset / self.width, offset % self.width);
+            let point = Point::new(
+                (i64::from(self.origin.x) + column as i64) as i32,
+                (i64::from(self.origin.y) + (self.height - 1 - row) as i64) as i32,
+            );
+            layer.cells.insert(point, color);
+        }
+        canvas
+    }
+}
+
+impl Canvas {
+    // Цвета видимых клеток в порядке Color::ALL
+    pub fn palette(&self) -> Vec<Color> {
+        let cells = self.composite();
+        Color::ALL
+            .into_iter()
+            .filter(|color| cells.values().any(|cell| cell == color))
+            .collect()
+    }
+
+    pub fn to_indexed(&self) -> IndexedCanvas {
+        let Some(bounds) = self.bounding_box() else {
+            return IndexedCanvas::default();
+        };
+        let palette = self.palette();
+        let width = (i64::from(bounds.max_x) - i64::from(bounds.min_x) + 1) as usize;
+        let height = (i64::from(bounds.max_y) - i64::from(bounds.min_y) + 1) as usize;
+
+        let mut indices = vec![0; width * height];
+        for (point, color) in self.cells() {
+            let column = (i64::from(point.x) - i64::from(bounds.min_x)) as usize;
+            let row = (i64::from(bounds.max_y) - i64::from(point.y)) as usize;
+            let index = palette.iter().position(|known| *known == color);
+            // В палитре не больше восьми цветов, поэтому номер помещается в байт
+            indices[row * width + column] = index.map_or(0, |index| index as u8 + 1);
+        }
+
+        IndexedCanvas {
+            palette,
+            origin: Point::new(bounds.min_x, bounds.min_y),
+            width,
+            height,
+            indices,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_indexed() {
+        let mut robot = Robot::new(-1, 0, Direction::Right, true);
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+        robot.turn_left_45();
+        robot.set_color(Color::Blue);
+        robot.move_forward().unwrap();
+
+        let canvas = Canvas::from_robot(&robot);
+        assert_eq!(canvas.palette(), [Color::Red, Color::Blue]);
+        let indexed = canvas.to_indexed();
+        assert_eq!(indexed.origin(), Point::new(-1, 0));
+        assert_eq!((indexed.width(), indexed.height()), (3, 2));
+        // Синий конец диагонали закрасил клетку, где красная линия закончилась
+        assert_eq!(indexed.indices(), [0, 0, 2, 1, 2, 0]);
+        assert_eq!(indexed.color_at(1, 1), Some(Color::Blue));
+        assert_eq!(indexed.color_at(1, 0), None);
+        assert_eq!(indexed.color_at(5, 5), None);
+
+        let restored = indexed.to_canvas();
+        assert!(restored.diff(&canvas).is_empty());
+        assert_eq!(restored.robot(), None);
+
+        assert_eq!(Canvas::new().to_indexed(), IndexedCanvas::default());
+        assert!(IndexedCanvas::default().to_canvas().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+// Именованный слой холста. Робот рисует на слое "drawing", а на других слоях можно
+// держать сетку, подсказки или образец. У слоя своя видимость и порядок (z):
+// слой с большим z рисуется поверх, при равном z выше лежит слой, добавленный позже.
+// Стирание на слое очищает только клетки этого слоя.
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub(super) name: String,
+    pub(super) visible: bool,
+    pub(super) z: i32,
+    pub(super) cells: HashMap<Point, Color>,
+    pub(super) segments: Vec<Segment>,
+}
+
+impl Layer {
+    pub(super) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            visible: true,
+            z: 0,
+            cells: HashMap::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn z(&self) -> i32 {
+        self.z
+    }
+
+    pub fn set_z(&mut self, z: i32) {
+        self.z = z;
+    }
+
+    // Рисует или стирает клетки отрезка, включая оба конца
+    pub fn draw(&mut self, segment: Segment) {
+        for point in cells(segment.from, segment.to) {
+            match segment.mode {
+                PenMode::Draw => {
+                    self.cells.insert(point, segment.color);
+                }
+                PenMode::Erase => {
+                    self.cells.remove(&point);
+                }
+            }
+        }
+        self.segments.push(segment);
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    // Закрашенные клетки слоя в произвольном порядке
+    pub fn cells(&self) -> impl Iterator<Item = (Point, Color)> + '_ {
+        self.cells.iter().map(|(point, color)| (*point, *color))
+    }
+
+    pub fn color_at(&self, x: i32, y: i32) -> Option<Color> {
+        self.cells.get(&Point::new(x, y)).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.segments.clear();
+    }
+
+    // Копия слоя, сдвинутая функцией shift
+    pub(super) fn shifted(&self, shift: impl Fn(Point) -> Point) -> Self {
+        Self {
+            name: self.name.clone(),
+            visible: self.visible,
+            z: self.z,
+            cells: self
+                .cells
+                .iter()
+                .map(|(point, color)| (shift(*point), *color))
+                .collect(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| Segment {
+                    from: shift(segment.from),
+                    to: shift(segment.to),
+                    ..*segment
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(from: (i32, i32), to: (i32, i32), color: Color) -> Segment {
+        Segment {
+            from: from.into(),
+            to: to.into(),
+            color,
+            width: 1,
+            mode: PenMode::Draw,
+        }
+    }
+
+    #[test]
+    fn test_layers_z_order_and_visibility() {
+        let mut canvas = Canvas::new();
+        canvas.draw(segment((0, 0), (2, 0), Color::Black));
+        let grid = canvas.layer("grid");
+        grid.draw(segment((1, -1), (1, 1), Color::Cyan));
+
+        // Сетка добавлена позже и лежит поверх рисунка
+        assert_eq!(canvas.color_at(1, 0), Some(Color::Cyan));
+        canvas.layer("grid").set_z(-1);
+        assert_eq!(canvas.color_at(1, 0), Some(Color::Black));
+        assert_eq!(canvas.color_at(1, 1), Some(Color::Cyan));
+        assert_eq!(canvas.len(), 5);
+        assert_eq!(
+            canvas.layers().map(Layer::name).collect::<Vec<_>>(),
+            ["grid", Canvas::DRAWING]
+        );
+        assert_eq!(canvas.render_ascii(), ".*.\n***\n.*.\n");
+
+        canvas.layer("grid").set_visible(false);
+        assert_eq!(canvas.render_ascii(), "***\n");
+        assert_eq!(canvas.bounding_box(), Some(Bounds::new(0, 2, 0, 0)));
+        assert_eq!(canvas.segments().count(), 1);
+        assert!(
+            canvas
+                .get_layer("grid")
+                .is_some_and(|grid| !grid.is_visible())
+        );
+
+        assert!(canvas.remove_layer("grid").is_some());
+        assert!(canvas.get_layer("grid").is_none());
+    }
+}
@@ -0,0 +1,108 @@
+// Показатели рисунка для сравнения программ: сколько разных клеток закрашено,
+// сколько шагов пройдено с опущенным пером и сколько клеток закрашено больше одного раза.
+// Считаются отрезки видимых слоев. Отрезок, который начинается в конце предыдущего
+// отрезка того же слоя, продолжает линию, поэтому общая клетка не считается повторной.
+// Стирание тоже расходует шаги, но клетки не закрашивает.
+
+use std::fmt;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CanvasMetrics {
+    drawn: usize,
+    distance: u64,
+    overdrawn: usize,
+}
+
+impl CanvasMetrics {
+    // Число разных видимых закрашенных клеток
+    pub fn drawn(&self) -> usize {
+        self.drawn
+    }
+
+    // Число шагов по отрезкам, шаг по диагонали считается одним шагом, как у робота
+    pub fn distance(&self) -> u64 {
+        self.distance
+    }
+
+    // Число клеток, по которым перо прошло больше одного раза
+    pub fn overdrawn(&self) -> usize {
+        self.overdrawn
+    }
+}
+
+impl fmt::Display for CanvasMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cells drawn: {}, distance: {}, overdrawn: {}",
+            self.drawn, self.distance, self.overdrawn
+        )
+    }
+}
+
+impl Canvas {
+    pub fn metrics(&self) -> CanvasMetrics {
+        let mut passes: HashMap<Point, u32> = HashMap::new();
+        let mut distance = 0;
+        for layer in self.visible() {
+            let mut end = None;
+            for segment in layer.segments() {
+                distance += u64::from(segment.from.x.abs_diff(segment.to.x))
+                    .max(u64::from(segment.from.y.abs_diff(segment.to.y)));
+                if segment.mode == PenMode::Draw {
+                    let skip = usize::from(end == Some(segment.from));
+                    for point in cells(segment.from, segment.to).skip(skip) {
+                        *passes.entry(point).or_default() += 1;
+                    }
+                }
+                end = Some(segment.to);
+            }
+        }
+
+        CanvasMetrics {
+            drawn: self.len(),
+            distance,
+            overdrawn: passes.values().filter(|&&count| count > 1).count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_metrics() {
+        // Квадрат 3x3 обходится по контуру и возвращается в начало
+        let mut robot = Robot::default();
+        robot.down_pen();
+        for _ in 0..4 {
+            robot.move_forward_by(2).unwrap();
+            robot.turn_right();
+        }
+        let canvas = Canvas::from_robot(&robot);
+        let metrics = canvas.metrics();
+        assert_eq!(metrics.drawn(), 8);
+        assert_eq!(metrics.distance(), 8);
+        // Начальная клетка пройдена дважды: в начале и в конце обхода
+        assert_eq!(metrics.overdrawn(), 1);
+
+        // Повторный проход по стороне и диагональ
+        robot.move_forward_by(2).unwrap();
+        robot.turn_right_45();
+        robot.move_forward().unwrap();
+        let metrics = Canvas::from_robot(&robot).metrics();
+        assert_eq!(
+            (metrics.drawn(), metrics.distance(), metrics.overdrawn()),
+            (9, 11, 3)
+        );
+        assert_eq!(
+            metrics.to_string(),
+            "cells drawn: 9, distance: 11, overdrawn: 3"
+        );
+
+        assert_eq!(Canvas::new().metrics(), CanvasMetrics::default());
+    }
+}
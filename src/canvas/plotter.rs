@@ -0,0 +1,90 @@
+// Общая часть вывода для плоттеров: рисунок раскладывается на штрихи - ломаные,
+// которые перо проводит не поднимаясь. Отрезок, начинающийся в конце предыдущего,
+// того же цвета продолжает штрих, иначе перо поднимается и переезжает к началу нового штриха.
+// Плоттер не умеет стирать, поэтому стертые отрезки пропускаются.
+// Координаты отсчитываются от левого нижнего угла рисунка, чтобы все они были неотрицательными.
+
+use super::*;
+
+// Ломаная одного цвета, точки - смещения в клетках от угла рисунка
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Stroke {
+    pub(super) color: Color,
+    pub(super) points: Vec<(u64, u64)>,
+}
+
+impl Canvas {
+    // Штрихи видимых слоев снизу вверх
+    pub(super) fn strokes(&self) -> Vec<Stroke> {
+        let segments: Vec<_> = self
+            .segments()
+            .filter(|segment| segment.mode == PenMode::Draw)
+            .collect();
+        let Some((min, _)) = extent(
+            segments
+                .iter()
+                .flat_map(|segment| [segment.from, segment.to]),
+        ) else {
+            return Vec::new();
+        };
+        let offset = |point: Point| {
+            (
+                point.x.abs_diff(min.x).into(),
+                point.y.abs_diff(min.y).into(),
+            )
+        };
+
+        let mut strokes: Vec<Stroke> = Vec::new();
+        let mut end = None;
+        for segment in segments {
+            if end != Some((segment.from, segment.color)) {
+                strokes.push(Stroke {
+                    color: segment.color,
+                    points: vec![offset(segment.from)],
+                });
+            }
+            if let Some(stroke) = strokes.last_mut() {
+                stroke.points.push(offset(segment.to));
+            }
+            end = Some((segment.to, segment.color));
+        }
+        strokes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strokes() {
+        let mut robot = Robot::new(-2, 3, Direction::Right, true);
+        robot.move_forward_by(2).unwrap();
+        robot.turn_left();
+        robot.move_forward().unwrap();
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+        robot.up_pen();
+        robot.move_forward().unwrap();
+        robot.down_pen();
+        robot.set_pen_mode(PenMode::Erase);
+        robot.move_forward().unwrap();
+        robot.set_pen_mode(PenMode::Draw);
+        robot.move_forward().unwrap();
+
+        let strokes: Vec<_> = Canvas::from_robot(&robot)
+            .strokes()
+            .into_iter()
+            .map(|stroke| (stroke.color, stroke.points))
+            .collect();
+        assert_eq!(
+            strokes,
+            [
+                (Color::Black, vec![(0, 0), (2, 0), (2, 1)]),
+                (Color::Red, vec![(2, 1), (2, 2)]),
+                (Color::Red, vec![(2, 4), (2, 5)]),
+            ]
+        );
+        assert!(Canvas::new().strokes().is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+// Экспорт холста в PNG с функцией "image".
+// Изображение записывается без сжатия: данные лежат в несжатых блоках deflate,
+// поэтому кодировщику не нужны внешние библиотеки, а файл открывается любой программой.
+
+use std::{fs, path::Path};
+
+use super::{raster::Raster, *};
+use crate::error::Error;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// Наибольший размер несжатого блока deflate
+const MAX_BLOCK: usize = 0xffff;
+
+impl Canvas {
+    // Сохраняет рисунок в файл PNG, клетка занимает scale пикселей
+    pub fn to_png(&self, path: impl AsRef<Path>, scale: u32) -> Result<(), Error> {
+        fs::write(path, encode(&self.rasterize(scale)?)?)?;
+        Ok(())
+    }
+}
+
+// Размеры PNG записываются 32-битными числами
+fn encode(raster: &Raster) -> Result<Vec<u8>, Error> {
+    let (Ok(width), Ok(height)) = (u32::try_from(raster.width), u32::try_from(raster.height))
+    else {
+        return Err(Error::ImageTooLarge(raster.width, raster.height));
+    };
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 бит на канал, RGB, стандартные сжатие и фильтры, без чередования строк
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+
+    // Каждая строка начинается с номера фильтра, 0 - без фильтра
+    let mut data = Vec::with_capacity(raster.pixels.len() + raster.height);
+    for row in raster.pixels.chunks(raster.width * 3) {
+        data.push(0);
+        data.extend_from_slice(row);
+    }
+    chunk(&mut png, b"IDAT", &zlib(&data));
+    chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Поток zlib из несжатых блоков
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        stream.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_to_png() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.move_forward_by(2).unwrap();
+
+        let path = std::env::temp_dir().join(format!("canvas-{}.png", std::process::id()));
+        Canvas::from_robot(&robot).to_png(&path, 3).unwrap();
+        let png = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 0, 9, 0, 0, 0, 3]);
+        assert_eq!(
+            png[png.len() - 12..],
+            [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+        // Три строки по 9 пикселей с байтом фильтра в одном несжатом блоке
+        let data_len = 3 * (1 + 9 * 3);
+        assert_eq!(png.len(), 8 + 25 + 12 + 2 + 5 + data_len + 4 + 12);
+    }
+
+    #[test]
+    fn test_encode_too_large() {
+        let raster = Raster {
+            width: 1 << 32,
+            height: 0,
+            pixels: Vec::new(),
+        };
+        assert!(matches!(
+            encode(&raster),
+            Err(Error::ImageTooLarge(width, 0)) if width == 1 << 32
+        ));
+    }
+}
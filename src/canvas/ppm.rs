@@ -0,0 +1,39 @@
+// Экспорт холста в двоичный PPM (P6): заголовок с размерами и пиксели RGB подряд.
+// Формат не требует ни сжатия, ни библиотек, каждая клетка рисунка - один пиксель.
+
+use std::io::Write;
+
+use super::*;
+use crate::error::Error;
+
+impl Canvas {
+    pub fn to_ppm(&self, mut writer: impl Write) -> Result<(), Error> {
+        let raster = self.rasterize(1)?;
+        write!(writer, "P6\n{} {}\n255\n", raster.width, raster.height)?;
+        writer.write_all(&raster.pixels)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ppm() {
+        let mut robot = Robot::new(0, 0, Direction::Up, true);
+        robot.set_color(Color::Green);
+        robot.move_forward().unwrap();
+        robot.turn_right_45();
+        robot.move_forward().unwrap();
+
+        let mut ppm = Vec::new();
+        Canvas::from_robot(&robot).to_ppm(&mut ppm).unwrap();
+        let mut expected = b"P6\n2 3\n255\n".to_vec();
+        expected.extend_from_slice(&[255, 255, 255, 0, 255, 0]);
+        expected.extend_from_slice(&[0, 255, 0, 255, 255, 255]);
+        expected.extend_from_slice(&[0, 255, 0, 255, 255, 255]);
+        assert_eq!(ppm, expected);
+    }
+}
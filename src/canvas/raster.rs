@@ -0,0 +1,116 @@
+// Растровое изображение холста для экспорта в графические файлы.
+// Изображение занимает рамку рисунка (bounding_box), клетка становится квадратом
+// из scale пикселей, пустые клетки - белые, верхняя строка изображения - наибольший y рисунка.
+// Изображение, байты которого не помещаются в памяти, не создается: ошибка ImageTooLarge.
+
+use super::*;
+use crate::error::Error;
+
+const BACKGROUND: (u8, u8, u8) = (255, 255, 255);
+
+// Пиксели RGB по строкам сверху вниз
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Raster {
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) pixels: Vec<u8>,
+}
+
+impl Canvas {
+    // Пустой холст становится одной пустой клеткой, нулевой масштаб считается единицей
+    pub(super) fn rasterize(&self, scale: u32) -> Result<Raster, Error> {
+        self.rasterize_in(
+            self.bounding_box().unwrap_or(Bounds::new(0, 0, 0, 0)),
+            scale,
+        )
+    }
+
+    // Изображение заданной части холста, клетки вне рамки не рисуются
+    pub(super) fn rasterize_in(&self, bounds: Bounds, scale: u32) -> Result<Raster, Error> {
+        let scale = scale.max(1) as usize;
+        let columns = (i64::from(bounds.max_x) - i64::from(bounds.min_x) + 1) as usize;
+        let rows = (i64::from(bounds.max_y) - i64::from(bounds.min_y) + 1) as usize;
+        let (width, height) = (columns.saturating_mul(scale), rows.saturating_mul(scale));
+        let len = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .filter(|&len| isize::try_from(len).is_ok())
+            .ok_or(Error::ImageTooLarge(width, height))?;
+
+        let mut pixels = Vec::with_capacity(len);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&[BACKGROUND.0, BACKGROUND.1, BACKGROUND.2]);
+        }
+        for (point, color) in self.cells() {
+            if !bounds.contains(point.x, point.y) {
+                continue;
+            }
+            let column = (i64::from(point.x) - i64::from(bounds.min_x)) as usize;
+            let row = (i64::from(bounds.max_y) - i64::from(point.y)) as usize;
+            let (red, green, blue) = color.rgb();
+            for y in row * scale..(row + 1) * scale {
+                for x in column * scale..(column + 1) * scale {
+                    let offset = (y * width + x) * 3;
+                    pixels[offset..offset + 3].copy_from_slice(&[red, green, blue]);
+                }
+            }
+        }
+
+        Ok(Raster {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rasterize() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+        robot.turn_left_45();
+        robot.set_color(Color::Blue);
+        robot.move_forward().unwrap();
+
+        let raster = Canvas::from_robot(&robot).rasterize(2).unwrap();
+        assert_eq!((raster.width, raster.height), (6, 4));
+        let pixel = |x: usize, y: usize| {
+            let offset = (y * raster.width + x) * 3;
+            (
+                raster.pixels[offset],
+                raster.pixels[offset + 1],
+                raster.pixels[offset + 2],
+            )
+        };
+        assert_eq!(pixel(0, 3), (255, 0, 0));
+        assert_eq!(pixel(3, 2), (0, 0, 255));
+        assert_eq!(pixel(5, 0), (0, 0, 255));
+        assert_eq!(pixel(0, 0), BACKGROUND);
+
+        let empty = Canvas::new().rasterize(0).unwrap();
+        assert_eq!((empty.width, empty.height), (1, 1));
+    }
+
+    #[test]
+    fn test_rasterize_too_large() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.move_forward_by(2).unwrap();
+        let canvas = Canvas::from_robot(&robot);
+
+        let scale = u32::MAX as usize;
+        assert!(matches!(
+            canvas.rasterize(u32::MAX),
+            Err(Error::ImageTooLarge(width, height)) if width == 3 * scale && height == scale
+        ));
+        let bounds = Bounds::new(i32::MIN, i32::MAX, i32::MIN, i32::MAX);
+        assert!(matches!(
+            canvas.rasterize_in(bounds, 1),
+            Err(Error::ImageTooLarge(width, height)) if width == 1 << 32 && height == 1 << 32
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+// Сериализация холста для сохранения рисунков и передачи их, например, веб-странице.
+// Холст записывается отображением со слоями и положением робота, а слой - разреженно:
+// только закрашенные клетки списком [x, y, цвет], упорядоченным по x и y, и отрезки пути.
+// Клетки при чтении не пересчитываются по отрезкам, поэтому рисунок читается таким,
+// каким был записан.
+// Снимок с палитрой (IndexedCanvas) записывается еще компактнее: палитра названиями цветов
+// и по числу на клетку рамки.
+
+use std::fmt;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor},
+    ser::SerializeStruct,
+};
+
+use super::*;
+
+const CANVAS_FIELDS: &[&str] = &["layers", "robot"];
+const LAYER_FIELDS: &[&str] = &["name", "visible", "z", "cells", "segments"];
+const INDEXED_FIELDS: &[&str] = &["palette", "origin", "width", "height", "indices"];
+
+impl Serialize for Canvas {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut canvas = serializer.serialize_struct("Canvas", CANVAS_FIELDS.len())?;
+        canvas.serialize_field("layers", &self.layers)?;
+        canvas.serialize_field("robot", &self.robot)?;
+        canvas.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Canvas {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(CanvasVisitor)
+    }
+}
+
+struct CanvasVisitor;
+
+impl<'de> Visitor<'de> for CanvasVisitor {
+    type Value = Canvas;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a canvas")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Canvas, A::Error> {
+        let (mut layers, mut robot) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "layers" => set(&mut layers, "layers", &mut map)?,
+                "robot" => set(&mut robot, "robot", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, CANVAS_FIELDS)),
+            }
+        }
+
+        let layers: Vec<Layer> = required(layers, "layers")?;
+        for (index, layer) in layers.iter().enumerate() {
+            if layers[..index].iter().any(|other| other.name == layer.name) {
+                return Err(de::Error::custom(format!(
+                    "duplicate layer `{}`",
+                    layer.name
+                )));
+            }
+        }
+        Ok(Canvas {
+            layers,
+            robot: required(robot, "robot")?,
+        })
+    }
+}
+
+impl Serialize for Layer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut cells: Vec<_> = self
+            .cells
+            .iter()
+            .map(|(point, color)| (point.x, point.y, *color))
+            .collect();
+        cells.sort_by_key(|&(x, y, _)| (x, y));
+
+        let mut layer = serializer.serialize_struct("Layer", LAYER_FIELDS.len())?;
+        layer.serialize_field("name", &self.name)?;
+        layer.serialize_field("visible", &self.visible)?;
+        layer.serialize_field("z", &self.z)?;
+        layer.serialize_field("cells", &cells)?;
+        layer.serialize_field("segments", &self.segments)?;
+        layer.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Layer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(LayerVisitor)
+    }
+}
+
+struct LayerVisitor;
+
+impl<'de> Visitor<'de> for LayerVisitor {
+    type Value = Layer;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a canvas layer")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Layer, A::Error> {
+        let (mut name, mut visible, mut z, mut cells, mut segments) =
+            (None, None, None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" => set(&mut name, "name", &mut map)?,
+                "visible" => set(&mut visible, "visible", &mut map)?,
+                "z" => set(&mut z, "z", &mut map)?,
+                "cells" => set(&mut cells, "cells", &mut map)?,
+                "segments" => set(&mut segments, "segments", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, LAYER_FIELDS)),
+            }
+        }
+
+        let cells: Vec<(i32, i32, Color)> = required(cells, "cells")?;
+        Ok(Layer {
+            name: required(name, "name")?,
+            visible: required(visible, "visible")?,
+            z: required(z, "z")?,
+            cells: cells
+                .into_iter()
+                .map(|(x, y, color)| (Point::new(x, y), color))
+                .collect(),
+            segments: required(segments, "segments")?,
+        })
+    }
+}
+
+impl Serialize for IndexedCanvas {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut indexed = serializer.serialize_struct("IndexedCanvas", INDEXED_FIELDS.len())?;
+        indexed.serialize_field("palette", &self.palette)?;
+        indexed.serialize_field("origin", &self.origin)?;
+        indexed.serialize_field("width", &self.width)?;
+        indexed.serialize_field("height", &self.height)?;
+        indexed.serialize_field("indices", &self.indices)?;
+        indexed.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for IndexedCanvas {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(IndexedVisitor)
+    }
+}
+
+struct IndexedVisitor;
+
+impl<'de> Visitor<'de> for IndexedVisitor {
+    type Value = IndexedCanvas;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an indexed canvas")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<IndexedCanvas, A::Error> {
+        let (mut palette, mut origin, mut width, mut height, mut indices) =
+            (None, None, None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "palette" => set(&mut palette, "palette", &mut map)?,
+                "origin" => set(&mut origin, "origin", &mut map)?,
+                "width" => set(&mut width, "width", &mut map)?,
+                "height" => set(&mut height, "height", &mut map)?,
+                "indices" => set(&mut indices, "indices", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, INDEXED_FIELDS)),
+            }
+        }
+
+        let indexed = IndexedCanvas {
+            palette: required(palette, "palette")?,
+            origin: required(origin, "origin")?,
+            width: required(width, "width")?,
+            height: required(height, "height")?,
+            indices: required(indices, "indices")?,
+        };
+        // Размер и номера цветов проверяются сразу, чтобы снимок нельзя было прочитать
+        // с клетками за рамкой или цветами вне палитры
+        if Some(indexed.indices.len()) != indexed.width.checked_mul(indexed.height) {
+            return Err(de::Error::custom("indices do not match the size"));
+        }
+        if indexed
+            .indices
+            .iter()
+            .any(|&index| usize::from(index) > indexed.palette.len())
+        {
+            return Err(de::Error::custom("index outside the palette"));
+        }
+        Ok(indexed)
+    }
+}
+
+fn set<'de, T: Deserialize<'de>, A: MapAccess<'de>>(
+    slot: &mut Option<T>,
+    name: &'static str,
+    map: &mut A,
+) -> Result<(), A::Error> {
+    if slot.is_some() {
+        return Err(de::Error::duplicate_field(name));
+    }
+    *slot = Some(map.next_value()?);
+    Ok(())
+}
+
+fn required<T, E: de::Error>(value: Option<T>, name: &'static str) -> Result<T, E> {
+    value.ok_or_else(|| E::missing_field(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_canvas_round_trip() {
+        let mut robot = Robot::default();
+        robot.down_pen();
+        robot.move_forward_by(2).unwrap();
+        robot.set_color(Color::Red);
+        robot.turn_right();
+        robot.move_forward().unwrap();
+
+        let mut canvas = Canvas::from_robot(&robot);
+        let grid = canvas.layer("grid");
+        grid.set_visible(false);
+        grid.set_z(-1);
+        canvas.draw_line(Point::new(-1, 0), Point::new(-1, 0));
+
+        let json = json::to_string(&canvas).unwrap();
+        assert!(json.starts_with(concat!(
+            r#"{"layers":[{"name":"drawing","visible":true,"z":0,"cells":["#,
+            r#"[-1,0,"black"],[0,0,"black"],[0,1,"black"],[0,2,"red"],[1,2,"red"]],"#
+        )));
+        assert!(json.ends_with(r#""robot":[{"x":1,"y":2},"right"]}"#));
+
+        let restored: Canvas = json::from_str(&json).unwrap();
+        assert_eq!(restored, canvas);
+        assert_eq!(json::to_string(&restored).unwrap(), json);
+
+        let empty: Canvas = json::from_str(r#"{"layers":[],"robot":null}"#).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.robot(), None);
+    }
+
+    #[test]
+    fn test_indexed_round_trip() {
+        let mut canvas = Canvas::new();
+        canvas.draw_line(Point::new(0, 0), Point::new(1, 1));
+
+        let indexed = canvas.to_indexed();
+        let json = json::to_string(&indexed).unwrap();
+        assert_eq!(
+            json,
+            r#"{"palette":["black"],"origin":{"x":0,"y":0},"width":2,"height":2,"indices":[0,1,1,0]}"#
+        );
+        assert_eq!(json::from_str::<IndexedCanvas>(&json).unwrap(), indexed);
+
+        let short = json.replace("[0,1,1,0]", "[0,1,1]");
+        assert!(json::from_str::<IndexedCanvas>(&short).is_err());
+        let outside = json.replace("[0,1,1,0]", "[0,2,1,0]");
+        assert!(json::from_str::<IndexedCanvas>(&outside).is_err());
+    }
+
+    #[test]
+    fn test_canvas_invalid() {
+        assert!(json::from_str::<Canvas>(r#"{"layers":[]}"#).is_err());
+        let layer = r#"{"name":"a","visible":true,"z":0,"cells":[],"segments":[]}"#;
+        let duplicate = format!(r#"{{"layers":[{layer},{layer}],"robot":null}}"#);
+        assert!(json::from_str::<Canvas>(&duplicate).is_err());
+        let bad_cell = r#"{"name":"a","visible":true,"z":0,"cells":[[0,0,"pink"]],"segments":[]}"#;
+        assert!(json::from_str::<Layer>(bad_cell).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+// Векторное изображение холста в формате SVG.
+// Соседние отрезки одного стиля, идущие друг за другом, объединяются в один элемент path.
+// Клетка занимает scale пикселей, ось y направлена вверх, как у робота.
+// Отрезки видимых слоев выводятся снизу вверх. Стертые отрезки рисуются цветом фона,
+// поэтому порядок отрезков сохраняется.
+// Цвет и толщину линий можно задать для всего рисунка, по умолчанию берутся цвет
+// и толщина пера каждого отрезка, а толщина задается в пикселях.
+
+use std::fmt::Write;
+
+use super::*;
+
+const BACKGROUND: Color = Color::White;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SvgOptions {
+    scale: u32,
+    stroke: Option<Color>,
+    width: Option<u32>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SvgOptions {
+    pub fn new() -> Self {
+        Self {
+            scale: 10,
+            stroke: None,
+            width: None,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_stroke(mut self, stroke: Color) -> Self {
+        self.stroke = Some(stroke);
+        self
+    }
+
+    pub fn with_width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn stroke(&self) -> Option<Color> {
+        self.stroke
+    }
+
+    pub fn width(&self) -> Option<u32> {
+        self.width
+    }
+}
+
+impl Canvas {
+    pub fn to_svg(&self, options: SvgOptions) -> String {
+        let scale = i64::from(options.scale);
+        let (min, max) = extent(
+            self.segments()
+                .flat_map(|segment| [segment.from, segment.to]),
+        )
+        .unwrap_or_default();
+        // Вокруг рисунка остается поле в одну клетку, чтобы не обрезать толстые линии
+        let width = (i64::from(max.x) - i64::from(min.x) + 2) * scale;
+        let height = (i64::from(max.y) - i64::from(min.y) + 2) * scale;
+        let project = |point: Point| {
+            (
+                (i64::from(point.x) - i64::from(min.x) + 1) * scale,
+                (i64::from(max.y) - i64::from(point.y) + 1) * scale,
+            )
+        };
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"  <rect width="100%" height="100%" fill="{}"/>"#,
+            hex(BACKGROUND)
+        );
+
+        let mut previous: Option<&Segment> = None;
+        for segment in self.segments() {
+            let (x, y) = project(segment.to);
+            match previous {
+                Some(previous) if previous.to == segment.from && same_style(previous, segment) => {
+                    let _ = write!(svg, " L {x} {y}");
+                }
+                _ => {
+                    if previous.is_some() {
+                        svg.push_str(r#""/>"#);
+                        svg.push('\n');
+                    }
+                    let color = match segment.mode {
+                        PenMode::Draw => options.stroke.unwrap_or(segment.color),
+                        PenMode::Erase => BACKGROUND,
+                    };
+                    let (from_x, from_y) = project(segment.from);
+                    let _ = write!(
+                        svg,
+                        r#"  <path fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round" d="M {from_x} {from_y} L {x} {y}"#,
+                        hex(color),
+                        options.width.unwrap_or(segment.width),
+                    );
+                }
+            }
+            previous = Some(segment);
+        }
+        if previous.is_some() {
+            svg.push_str(r#""/>"#);
+            svg.push('\n');
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn same_style(lhs: &Segment, rhs: &Segment) -> bool {
+    (lhs.color, lhs.width, lhs.mode) == (rhs.color, rhs.width, rhs.mode)
+}
+
+fn hex(color: Color) -> String {
+    let (red, green, blue) = color.rgb();
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_svg() {
+        let mut robot = Robot::new(0, 0, Direction::Up, true);
+        robot.move_forward_by(2).unwrap();
+        robot.turn_right();
+        robot.move_forward().unwrap();
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+
+        let svg = Canvas::from_robot(&robot).to_svg(SvgOptions::new().with_scale(5));
+        assert_eq!(
+            svg,
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 20 20">"#,
+                "\n",
+                r##"  <rect width="100%" height="100%" fill="#ffffff"/>"##,
+                "\n",
+                r##"  <path fill="none" stroke="#000000" stroke-width="1" stroke-linecap="round" stroke-linejoin="round" d="M 5 15 L 5 5 L 10 5"/>"##,
+                "\n",
+                r##"  <path fill="none" stroke="#ff0000" stroke-width="1" stroke-linecap="round" stroke-linejoin="round" d="M 10 5 L 15 5"/>"##,
+                "\n",
+                "</svg>\n",
+            )
+        );
+
+        let svg = Canvas::from_path(robot.path())
+            .to_svg(SvgOptions::new().with_stroke(Color::Blue).with_width(3));
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert!(svg.contains(r##"stroke="#0000ff" stroke-width="3""##));
+        assert!(!svg.contains("#ff0000"));
+    }
+}
@@ -0,0 +1,75 @@
+// Видимая область холста для рисунков, которые не помещаются на экран.
+// Область задается клеткой в центре и размером в символах, а zoom - сколько клеток
+// по каждой оси попадает в один символ: при zoom 1 символ - это клетка, при zoom 2
+// символ показывает квадрат 2x2 клетки и закрашен, если закрашена любая из них.
+// Сдвигая центр и меняя zoom, большой рисунок можно просматривать по частям.
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub center: Point,
+    // Ширина и высота в символах
+    pub width: u32,
+    pub height: u32,
+    // Клеток в одном символе, ноль считается единицей
+    pub zoom: u32,
+}
+
+impl Viewport {
+    pub fn new(center: Point, width: u32, height: u32) -> Self {
+        Self {
+            center,
+            width,
+            height,
+            zoom: 1,
+        }
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        self.locate(point).is_some()
+    }
+
+    // Символ, в который попадает клетка: столбец слева и строка сверху
+    pub(super) fn locate(&self, point: Point) -> Option<(usize, usize)> {
+        let zoom = i64::from(self.zoom.max(1));
+        let (width, height) = (i64::from(self.width), i64::from(self.height));
+        let left = i64::from(self.center.x) - width / 2 * zoom;
+        let bottom = i64::from(self.center.y) - height / 2 * zoom;
+
+        let column = (i64::from(point.x) - left).div_euclid(zoom);
+        let row = (i64::from(point.y) - bottom).div_euclid(zoom);
+        if !(0..width).contains(&column) || !(0..height).contains(&row) {
+            return None;
+        }
+        Some((column as usize, (height - 1 - row) as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewport_locate() {
+        let viewport = Viewport::new(Point::new(0, 0), 5, 3);
+        assert_eq!(viewport.locate(Point::new(0, 0)), Some((2, 1)));
+        assert_eq!(viewport.locate(Point::new(-2, 1)), Some((0, 0)));
+        assert_eq!(viewport.locate(Point::new(2, -1)), Some((4, 2)));
+        assert_eq!(viewport.locate(Point::new(3, 0)), None);
+        assert!(!viewport.contains(Point::new(0, 2)));
+
+        let viewport = Viewport {
+            zoom: 2,
+            ..Viewport::new(Point::new(0, 0), 4, 2)
+        };
+        assert_eq!(viewport.locate(Point::new(0, 0)), Some((2, 0)));
+        assert_eq!(viewport.locate(Point::new(1, 1)), Some((2, 0)));
+        assert_eq!(viewport.locate(Point::new(-1, -1)), Some((1, 1)));
+        assert_eq!(viewport.locate(Point::new(-4, -2)), Some((0, 1)));
+        assert_eq!(viewport.locate(Point::new(-5, 0)), None);
+
+        let edge = Viewport::new(Point::new(i32::MAX, i32::MIN), 3, 3);
+        assert_eq!(edge.locate(Point::new(i32::MAX, i32::MIN)), Some((1, 1)));
+    }
+}
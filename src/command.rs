@@ -3,17 +3,60 @@
 // и передавать их как параметры другим объектам. Это позволяет реализовать такие
 // паттерны, как Undo/Redo, логирование и т.д.
 // Команды могут быть выполнены, отменены и повторно выполнены.
-// В этом файле определены команды для перемещения робота, поворота налево и направо,
-// а также для включения и выключения режима рисования.
-
-use std::fmt;
-
-use super::{error::Error, robot::Robot};
+// Команды выполняет любой исполнитель (RobotLike): робот, заглушка в тестах или устройство.
+// В этом файле определены команды робота, составные команды и циклы, список команд (CommandList)
+// и история для отмены и повтора (History), а преобразования списков вынесены в подмодули.
+
+use std::{
+    any::Any,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{
+    error::Error,
+    robot::{Color, Direction, Fuel, Robot, RobotLike, SavedPosition, Stamp},
+};
+
+mod binary;
+mod estimate;
+mod kind;
+#[cfg(feature = "async")]
+mod pacing;
+#[cfg(feature = "serde")]
+mod replay;
+#[cfg(feature = "serde")]
+mod serialize;
+mod turtle;
+
+pub use estimate::{CommandCost, Estimate};
+pub use kind::CommandKind;
+#[cfg(feature = "async")]
+pub use pacing::Delay;
+#[cfg(feature = "serde")]
+pub use replay::{LogEntry, ReplayLog, read_log, replay};
+
+// Сколько раз по умолчанию может повториться цикл while, чтобы цикл с условием,
+// которое никогда не станет ложным, не выполнялся бесконечно
+pub const DEFAULT_MAX_ITERATIONS: usize = 1_000_000;
 
 pub trait Command: fmt::Debug {
-    fn execute(&mut self, robot: &mut Robot) -> Result<(), Error>;
-    fn rollback(&mut self, robot: &mut Robot) -> Result<(), Error>;
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error>;
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error>;
     fn box_clone(&self) -> Box<dyn Command>;
+    fn as_any(&self) -> &dyn Any;
+
+    // Сравнивает команды по параметрам, состояние для отмены не учитывается.
+    // По умолчанию команды равны, если у них один тип и одинаковое отладочное представление.
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        self.as_any().type_id() == other.as_any().type_id()
+            && format!("{self:?}") == format!("{other:?}")
+    }
+
+    // Сколько шагов, поворотов и переключений пера сделает команда, по умолчанию ни одного
+    fn cost(&self) -> CommandCost {
+        CommandCost::default()
+    }
 }
 
 impl Clone for Box<dyn Command> {
@@ -22,55 +65,186 @@ impl Clone for Box<dyn Command> {
     }
 }
 
+// Команда в Box сама является командой, это позволяет выполнять списки любых команд
+// одними методами. Все методы передаются вложенной команде.
+impl Command for Box<dyn Command> {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        (**self).execute(robot)
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        (**self).rollback(robot)
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        (**self).box_clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        (**self).as_any()
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        (**self).eq_dyn(other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        (**self).cost()
+    }
+}
+
+impl PartialEq for dyn Command {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_dyn(other)
+    }
+}
+
+// Команды печатаются в виде текста скрипта, команды вне этого модуля - отладочным представлением
+impl fmt::Display for dyn Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = self.as_any();
+
+        macro_rules! display {
+            ($($command:ty),*) => {
+                $(if let Some(command) = command.downcast_ref::<$command>() {
+                    return fmt::Display::fmt(command, f);
+                })*
+            };
+        }
+        display!(
+            MoveCommand,
+            MoveBackwardCommand,
+            TurnLeftCommand,
+            TurnRightCommand,
+            TurnLeft45Command,
+            TurnRight45Command,
+            DownPenCommand,
+            UpPenCommand,
+            WhileCommand,
+            TeleportCommand,
+            HomeCommand,
+            SetDirectionCommand,
+            PenColorCommand,
+            PenWidthCommand,
+            RefuelCommand,
+            StampCommand,
+            WriteCommand,
+            RepeatCommand,
+            CompositeCommand,
+            TargetCommand
+        );
+        write!(f, "{self:?}")
+    }
+}
+
+// Сравнение для встроенных команд: другая команда должна быть того же типа
+fn eq_as<T: PartialEq + 'static>(command: &T, other: &dyn Command) -> bool {
+    other
+        .as_any()
+        .downcast_ref::<T>()
+        .is_some_and(|other| command == other)
+}
+
 // Команда для перемещения робота на заданное количество шагов
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MoveCommand {
     distance: u32,
 }
 
 impl Command for MoveCommand {
-    fn execute(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Moving robot {} steps", self.distance);
+        robot.move_forward_by(self.distance)
+    }
 
-        for _ in 0..self.distance {
-            robot.move_forward()?;
-        }
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back moving robot {} steps", self.distance);
+        robot.move_backward_by(self.distance)
+    }
 
-        Ok(())
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
     }
 
-    fn rollback(&mut self, robot: &mut Robot) -> Result<(), Error> {
-        log::debug!("Rolling back moving robot {} steps", self.distance);
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-        robot.turn_left();
-        robot.turn_left();
-        for _ in 0..self.distance {
-            robot.move_forward()?;
-        }
-        robot.turn_left();
-        robot.turn_left();
-        Ok(())
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::steps(self.distance)
+    }
+}
+
+impl MoveCommand {
+    pub fn new(distance: u32) -> Self {
+        Self { distance }
+    }
+}
+
+impl fmt::Display for MoveCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {}", self.distance)
+    }
+}
+
+// Команда для перемещения робота назад без разворота
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveBackwardCommand {
+    distance: u32,
+}
+
+impl Command for MoveBackwardCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Moving robot back {} steps", self.distance);
+        robot.move_backward_by(self.distance)
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back moving robot back {} steps", self.distance);
+        robot.move_forward_by(self.distance)
     }
 
     fn box_clone(&self) -> Box<dyn Command> {
         Box::new(self.clone())
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::steps(self.distance)
+    }
 }
 
-impl MoveCommand {
+impl MoveBackwardCommand {
     pub fn new(distance: u32) -> Self {
         Self { distance }
     }
 }
 
+impl fmt::Display for MoveBackwardCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "back {}", self.distance)
+    }
+}
+
 // Команда для поворота робота на лево заданное количество раз
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TurnLeftCommand {
     times: u8,
 }
 
 impl Command for TurnLeftCommand {
-    fn execute(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Turning robot left {} times", self.times);
 
         for _ in 0..self.times {
@@ -80,7 +254,7 @@ impl Command for TurnLeftCommand {
         Ok(())
     }
 
-    fn rollback(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Rolling back turning robot left {} times", self.times);
 
         for _ in 0..self.times {
@@ -93,6 +267,18 @@ impl Command for TurnLeftCommand {
     fn box_clone(&self) -> Box<dyn Command> {
         Box::new(self.clone())
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::turns(self.times)
+    }
 }
 
 impl TurnLeftCommand {
@@ -102,14 +288,20 @@ impl TurnLeftCommand {
     }
 }
 
+impl fmt::Display for TurnLeftCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "turn_left {}", self.times)
+    }
+}
+
 // Команда для поворота робота на право заданное количество раз
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TurnRightCommand {
     times: u8,
 }
 
 impl Command for TurnRightCommand {
-    fn execute(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Turning robot right {} times", self.times);
 
         for _ in 0..self.times {
@@ -119,7 +311,7 @@ impl Command for TurnRightCommand {
         Ok(())
     }
 
-    fn rollback(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Rolling back turning robot right {} times", self.times);
 
         for _ in 0..self.times {
@@ -132,6 +324,18 @@ impl Command for TurnRightCommand {
     fn box_clone(&self) -> Box<dyn Command> {
         Box::new(self.clone())
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::turns(self.times)
+    }
 }
 
 impl TurnRightCommand {
@@ -141,19 +345,145 @@ impl TurnRightCommand {
     }
 }
 
+impl fmt::Display for TurnRightCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "turn_right {}", self.times)
+    }
+}
+
+// Команда для поворота робота налево на 45 градусов заданное количество раз
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnLeft45Command {
+    times: u8,
+}
+
+impl Command for TurnLeft45Command {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Turning robot left by 45 degrees {} times", self.times);
+
+        for _ in 0..self.times {
+            robot.turn_left_45();
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!(
+            "Rolling back turning robot left by 45 degrees {} times",
+            self.times
+        );
+
+        for _ in 0..self.times {
+            robot.turn_right_45();
+        }
+
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::turns(self.times)
+    }
+}
+
+impl TurnLeft45Command {
+    pub fn new(times: u32) -> Self {
+        let times = (times % 8) as u8;
+        Self { times }
+    }
+}
+
+impl fmt::Display for TurnLeft45Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "turn_left_45 {}", self.times)
+    }
+}
+
+// Команда для поворота робота направо на 45 градусов заданное количество раз
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnRight45Command {
+    times: u8,
+}
+
+impl Command for TurnRight45Command {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Turning robot right by 45 degrees {} times", self.times);
+
+        for _ in 0..self.times {
+            robot.turn_right_45();
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!(
+            "Rolling back turning robot right by 45 degrees {} times",
+            self.times
+        );
+
+        for _ in 0..self.times {
+            robot.turn_left_45();
+        }
+
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::turns(self.times)
+    }
+}
+
+impl TurnRight45Command {
+    pub fn new(times: u32) -> Self {
+        let times = (times % 8) as u8;
+        Self { times }
+    }
+}
+
+impl fmt::Display for TurnRight45Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "turn_right_45 {}", self.times)
+    }
+}
+
 // Команда для включения режима рисования
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DownPenCommand;
 
 impl Command for DownPenCommand {
-    fn execute(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Pen down");
 
         robot.down_pen();
         Ok(())
     }
 
-    fn rollback(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Rolling back pen down");
 
         robot.up_pen();
@@ -163,21 +493,39 @@ impl Command for DownPenCommand {
     fn box_clone(&self) -> Box<dyn Command> {
         Box::new(self.clone())
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::pen_toggle()
+    }
+}
+
+impl fmt::Display for DownPenCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("down_pen")
+    }
 }
 
 // Команда для выключения режима рисования
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UpPenCommand;
 
 impl Command for UpPenCommand {
-    fn execute(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Pen up");
 
         robot.up_pen();
         Ok(())
     }
 
-    fn rollback(&mut self, robot: &mut Robot) -> Result<(), Error> {
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
         log::debug!("Rolling back pen up");
 
         robot.down_pen();
@@ -187,90 +535,1496 @@ impl Command for UpPenCommand {
     fn box_clone(&self) -> Box<dyn Command> {
         Box::new(self.clone())
     }
-}
 
-#[derive(Debug, Clone, Default)]
-pub struct CommandList {
-    commands: Vec<Box<dyn Command>>,
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        CommandCost::pen_toggle()
+    }
 }
 
-impl CommandList {
-    pub fn add_command(&mut self, command: Box<dyn Command>) {
-        self.commands.push(command);
+impl fmt::Display for UpPenCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("up_pen")
     }
+}
 
-    pub fn execute_all(&mut self, robot: &mut Robot) -> Result<(), Error> {
-        for command in &mut self.commands {
-            command.execute(robot)?;
+// Условие, которое вычисляется по состоянию робота во время выполнения команд
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    // Значение, известное заранее
+    Value(bool),
+    // Робот не может сделать шаг вперед
+    Blocked,
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    pub fn evaluate(&self, robot: &dyn RobotLike) -> bool {
+        match self {
+            Condition::Value(value) => *value,
+            Condition::Blocked => robot.is_blocked(),
+            Condition::Not(condition) => !condition.evaluate(robot),
+            Condition::And(lhs, rhs) => lhs.evaluate(robot) && rhs.evaluate(robot),
+            Condition::Or(lhs, rhs) => lhs.evaluate(robot) || rhs.evaluate(robot),
         }
-        Ok(())
     }
+}
 
-    pub fn rollback_all(&mut self, robot: &mut Robot) -> Result<(), Error> {
-        for command in self.commands.iter_mut().rev() {
-            command.rollback(robot)?;
+// Условие печатается выражением языка, скобки ставятся только там, где они нужны
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Value(value) => write!(f, "{}", u8::from(*value)),
+            Condition::Blocked => f.write_str("blocked"),
+            Condition::Not(operand) => match operand.as_ref() {
+                Condition::And(..) | Condition::Or(..) => write!(f, "not ({operand})"),
+                _ => write!(f, "not {operand}"),
+            },
+            Condition::And(lhs, rhs) => {
+                write_operand(f, lhs)?;
+                f.write_str(" and ")?;
+                write_operand(f, rhs)
+            }
+            Condition::Or(lhs, rhs) => write!(f, "{lhs} or {rhs}"),
         }
-        Ok(())
     }
+}
 
-    pub fn commands(&self) -> &[Box<dyn Command>] {
-        &self.commands
+// Операнд "and" заключается в скобки, если это "or"
+fn write_operand(f: &mut fmt::Formatter<'_>, operand: &Condition) -> fmt::Result {
+    match operand {
+        Condition::Or(..) => write!(f, "({operand})"),
+        _ => write!(f, "{operand}"),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::robot::Direction;
+// Команда для повторения списка команд, пока выполняется условие.
+// Для отмены запоминается количество повторений при каждом выполнении,
+// поэтому вложенные циклы также отменяются правильно.
+// Больше max_iterations повторений за одно выполнение цикл не делает и возвращает ошибку.
+#[derive(Debug, Clone)]
+pub struct WhileCommand {
+    condition: Condition,
+    body: CommandList,
+    iterations: Vec<u32>,
+    max_iterations: usize,
+}
 
-    #[test]
-    fn test_move_command_execute_and_rollback() {
-        let mut robot = Robot::default();
-        let mut cmd = MoveCommand::new(3);
+impl Command for WhileCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Repeating commands while {:?}", self.condition);
+
+        let mut iterations = 0;
+        let result = loop {
+            if !self.condition.evaluate(robot) {
+                break Ok(());
+            }
+            if iterations as usize >= self.max_iterations {
+                break Err(Error::IterationLimit(self.max_iterations));
+            }
+            if let Err(err) = robot.iterate().and_then(|()| self.body.execute_all(robot)) {
+                break Err(err);
+            }
+            iterations += 1;
+        };
+
+        self.iterations.push(iterations);
+        result
+    }
 
-        // Move forward 3 steps
-        assert!(cmd.execute(&mut robot).is_ok());
-        assert_eq!(robot.x(), 0);
-        assert_eq!(robot.y(), 3);
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        let iterations = self.iterations.pop().unwrap_or_default();
+        log::debug!("Rolling back {} repetitions", iterations);
 
-        // Rollback: should return to original position
-        assert!(cmd.rollback(&mut robot).is_ok());
-        assert_eq!(robot.x(), 0);
-        assert_eq!(robot.y(), 0);
+        for _ in 0..iterations {
+            self.body.rollback_all(robot)?;
+        }
+
+        Ok(())
     }
 
-    #[test]
-    fn test_turn_left_command_execute_and_rollback() {
-        let mut robot = Robot::default();
-        let mut cmd = TurnLeftCommand::new(1);
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
 
-        // Turn left once
-        assert!(cmd.execute(&mut robot).is_ok());
-        assert_eq!(robot.direction(), Direction::Left);
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-        // Rollback: should turn right, back to up
-        assert!(cmd.rollback(&mut robot).is_ok());
-        assert_eq!(robot.direction(), Direction::Up);
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
     }
 
-    #[test]
-    fn test_turn_right_command_execute_and_rollback() {
-        let mut robot = Robot::default();
-        let mut cmd = TurnRightCommand::new(2);
+    // Количество повторений известно только во время выполнения, учитывается одно
+    fn cost(&self) -> CommandCost {
+        self.body
+            .commands
+            .iter()
+            .map(|command| command.cost())
+            .sum()
+    }
+}
 
-        // Turn right twice
-        assert!(cmd.execute(&mut robot).is_ok());
-        assert_eq!(robot.direction(), Direction::Down);
+impl WhileCommand {
+    pub fn new(condition: Condition, body: CommandList) -> Self {
+        Self {
+            condition,
+            body,
+            iterations: Vec::new(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
 
-        // Rollback: should turn left twice, back to up
-        assert!(cmd.rollback(&mut robot).is_ok());
-        assert_eq!(robot.direction(), Direction::Up);
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
     }
+}
 
-    #[test]
-    fn test_down_pen_command_execute_and_rollback() {
-        let mut robot = Robot::default();
-        let mut cmd = DownPenCommand;
+impl PartialEq for WhileCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition && self.body == other.body
+    }
+}
+
+impl fmt::Display for WhileCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "while {} [ ", self.condition)?;
+        write_inline(f, &self.body.commands)?;
+        f.write_str(" ]")
+    }
+}
+
+// Команда для перемещения робота в заданную клетку без прохождения пути
+#[derive(Debug, Clone)]
+pub struct TeleportCommand {
+    x: i32,
+    y: i32,
+    // Положения до каждого выполнения команды, команда может выполняться несколько раз подряд
+    previous: Vec<SavedPosition>,
+}
+
+impl Command for TeleportCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Teleporting robot to ({}, {})", self.x, self.y);
+
+        let previous = robot.saved_position();
+        robot.set_position(self.x, self.y)?;
+        self.previous.push(previous);
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back teleporting robot to ({}, {})", self.x, self.y);
+
+        match self.previous.pop() {
+            Some(previous) => robot.restore_position(&previous),
+            None => Ok(()),
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl TeleportCommand {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self {
+            x,
+            y,
+            previous: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for TeleportCommand {
+    fn eq(&self, other: &Self) -> bool {
+        (self.x, self.y) == (other.x, other.y)
+    }
+}
+
+// Координата со знаком минус слилась бы с первой в одно выражение
+impl fmt::Display for TeleportCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.y < 0 {
+            write!(f, "goto {} ({})", self.x, self.y)
+        } else {
+            write!(f, "goto {} {}", self.x, self.y)
+        }
+    }
+}
+
+// Команда для возврата робота домой, откат возвращает прежнее положение, направление и перо
+#[derive(Debug, Clone, Default)]
+pub struct HomeCommand {
+    // Положения до каждого выполнения команды
+    previous: Vec<(SavedPosition, Direction, bool)>,
+}
+
+impl Command for HomeCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Returning robot home");
+
+        let previous = (
+            robot.saved_position(),
+            robot.direction(),
+            robot.is_drawing(),
+        );
+        robot.reset()?;
+        self.previous.push(previous);
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back returning robot home");
+
+        if let Some((position, direction, drawing)) = self.previous.pop() {
+            robot.restore_position(&position)?;
+            robot.set_direction(direction);
+            if drawing {
+                robot.down_pen();
+            }
+        }
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl HomeCommand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for HomeCommand {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for HomeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("home")
+    }
+}
+
+// Команда для разворота робота в заданном направлении
+#[derive(Debug, Clone)]
+pub struct SetDirectionCommand {
+    direction: Direction,
+    // Направления до каждого выполнения команды
+    previous: Vec<Direction>,
+}
+
+impl Command for SetDirectionCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Setting robot direction to {}", self.direction);
+
+        self.previous.push(robot.direction());
+        robot.set_direction(self.direction);
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back setting robot direction to {}", self.direction);
+
+        if let Some(direction) = self.previous.pop() {
+            robot.set_direction(direction);
+        }
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl SetDirectionCommand {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            previous: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for SetDirectionCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.direction == other.direction
+    }
+}
+
+impl fmt::Display for SetDirectionCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "face {}", self.direction)
+    }
+}
+
+// Команда для смены цвета пера
+#[derive(Debug, Clone)]
+pub struct PenColorCommand {
+    color: Color,
+    // Цвета до каждого выполнения команды
+    previous: Vec<Color>,
+}
+
+impl Command for PenColorCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Setting pen color to {}", self.color);
+
+        self.previous.push(robot.color());
+        robot.set_color(self.color);
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back setting pen color to {}", self.color);
+
+        if let Some(color) = self.previous.pop() {
+            robot.set_color(color);
+        }
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl PenColorCommand {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            previous: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for PenColorCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.color == other.color
+    }
+}
+
+impl fmt::Display for PenColorCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "color {}", self.color)
+    }
+}
+
+// Команда для смены толщины пера
+#[derive(Debug, Clone)]
+pub struct PenWidthCommand {
+    width: u32,
+    // Толщина пера до каждого выполнения команды
+    previous: Vec<u32>,
+}
+
+impl Command for PenWidthCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Setting pen width to {}", self.width);
+
+        self.previous.push(robot.pen_width());
+        robot.set_pen_width(self.width);
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back setting pen width to {}", self.width);
+
+        if let Some(width) = self.previous.pop() {
+            robot.set_pen_width(width);
+        }
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl PenWidthCommand {
+    pub fn new(width: u32) -> Self {
+        Self {
+            width,
+            previous: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for PenWidthCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+    }
+}
+
+impl fmt::Display for PenWidthCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "width {}", self.width)
+    }
+}
+
+// Команда для заправки робота топливом
+#[derive(Debug, Clone)]
+pub struct RefuelCommand {
+    amount: u32,
+    // Топливо до каждого выполнения команды
+    previous: Vec<Option<Fuel>>,
+}
+
+impl Command for RefuelCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Refueling robot with {}", self.amount);
+
+        self.previous.push(robot.fuel());
+        robot.refuel(self.amount.into());
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back refueling robot with {}", self.amount);
+
+        if let Some(fuel) = self.previous.pop() {
+            robot.set_fuel(fuel);
+        }
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl RefuelCommand {
+    pub fn new(amount: u32) -> Self {
+        Self {
+            amount,
+            previous: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for RefuelCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.amount == other.amount
+    }
+}
+
+impl fmt::Display for RefuelCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "refuel {}", self.amount)
+    }
+}
+
+// Команда, которая отпечатывает фигуру вокруг робота, не перемещая его.
+// Отпечаток остается в пути робота и при отмене, как и линии при отмене перемещений.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampCommand {
+    stamp: Stamp,
+}
+
+impl Command for StampCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Stamping {}", self.stamp);
+
+        robot.stamp(self.stamp)
+    }
+
+    fn rollback(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back stamping {}", self.stamp);
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl StampCommand {
+    pub fn new(stamp: Stamp) -> Self {
+        Self { stamp }
+    }
+
+    pub fn dot() -> Self {
+        Self::new(Stamp::Dot)
+    }
+
+    pub fn square(size: u32) -> Self {
+        Self::new(Stamp::Square(size))
+    }
+
+    pub fn circle(radius: u32) -> Self {
+        Self::new(Stamp::Circle(radius))
+    }
+
+    pub fn stamp(&self) -> Stamp {
+        self.stamp
+    }
+}
+
+impl fmt::Display for StampCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stamp {}", self.stamp)
+    }
+}
+
+// Команда, которая пишет текст печатными буквами от клетки робота по его направлению.
+// Робот не перемещается, а надпись, как и штамп, остается в пути при отмене.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteCommand {
+    text: String,
+}
+
+impl Command for WriteCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Writing {:?}", self.text);
+
+        robot.write_text(&self.text);
+        Ok(())
+    }
+
+    fn rollback(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back writing {:?}", self.text);
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+}
+
+impl WriteCommand {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for WriteCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "write \"{}\"", self.text)
+    }
+}
+
+// Команда, которая выполняет вложенную команду заданное количество раз.
+// Как и у WhileCommand, для отмены запоминается количество выполненных повторений:
+// после ошибки отменяются только они.
+#[derive(Debug, Clone)]
+pub struct RepeatCommand {
+    inner: Box<dyn Command>,
+    times: u32,
+    iterations: Vec<u32>,
+}
+
+impl Command for RepeatCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Repeating {:?} {} times", self.inner, self.times);
+
+        let mut iterations = 0;
+        let result = loop {
+            if iterations == self.times {
+                break Ok(());
+            }
+            if let Err(err) = robot.iterate().and_then(|()| self.inner.execute(robot)) {
+                break Err(err);
+            }
+            iterations += 1;
+        };
+
+        self.iterations.push(iterations);
+        result
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        let iterations = self.iterations.pop().unwrap_or_default();
+        log::debug!(
+            "Rolling back {} repetitions of {:?}",
+            iterations,
+            self.inner
+        );
+
+        for _ in 0..iterations {
+            self.inner.rollback(robot)?;
+        }
+
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        self.inner.cost().times(self.times)
+    }
+}
+
+impl RepeatCommand {
+    pub fn new(inner: Box<dyn Command>, times: u32) -> Self {
+        Self {
+            inner,
+            times,
+            iterations: Vec::new(),
+        }
+    }
+}
+
+impl PartialEq for RepeatCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.times == other.times && *self.inner == *other.inner
+    }
+}
+
+impl fmt::Display for RepeatCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "repeat {} [ {} ]", self.times, self.inner)
+    }
+}
+
+// Команда для робота с заданным именем в мире с несколькими роботами (World).
+// Мир передает вложенную команду роботу с этим именем, где бы в списке команд она ни стояла,
+// а отдельный робот выполняет ее сам, не проверяя имя.
+#[derive(Debug, Clone)]
+pub struct TargetCommand {
+    robot: String,
+    command: Box<dyn Command>,
+}
+
+impl Command for TargetCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Executing command for robot {}", self.robot);
+        let command = &mut self.command;
+        let result = robot.target(&self.robot, &mut |target| command.execute(target));
+        result.unwrap_or_else(|| self.command.execute(robot))
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back command for robot {}", self.robot);
+        let command = &mut self.command;
+        let result = robot.target(&self.robot, &mut |target| command.rollback(target));
+        result.unwrap_or_else(|| self.command.rollback(robot))
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        self.command.cost()
+    }
+}
+
+impl TargetCommand {
+    pub fn new(robot: impl Into<String>, command: Box<dyn Command>) -> Self {
+        Self {
+            robot: robot.into(),
+            command,
+        }
+    }
+
+    pub fn robot(&self) -> &str {
+        &self.robot
+    }
+
+    pub fn command(&self) -> &dyn Command {
+        self.command.as_ref()
+    }
+}
+
+impl PartialEq for TargetCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.robot == other.robot && *self.command == *other.command
+    }
+}
+
+impl fmt::Display for TargetCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Несколько команд записываются блоком, иначе адресатом была бы только первая
+        if self.command.as_any().is::<CompositeCommand>() {
+            write!(f, "robot {}: [ {} ]", self.robot, self.command)
+        } else {
+            write!(f, "robot {}: {}", self.robot, self.command)
+        }
+    }
+}
+
+// Группа команд, которая выполняется и отменяется как одна команда
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompositeCommand {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl Command for CompositeCommand {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Executing {} grouped commands", self.commands.len());
+
+        for command in &mut self.commands {
+            command.execute(robot)?;
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        log::debug!("Rolling back {} grouped commands", self.commands.len());
+
+        for command in self.commands.iter_mut().rev() {
+            command.rollback(robot)?;
+        }
+
+        Ok(())
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        eq_as(self, other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        self.commands.iter().map(|command| command.cost()).sum()
+    }
+}
+
+impl CompositeCommand {
+    pub fn new(commands: Vec<Box<dyn Command>>) -> Self {
+        Self { commands }
+    }
+
+    pub fn commands(&self) -> &[Box<dyn Command>] {
+        &self.commands
+    }
+}
+
+// Группа печатается командами через пробел, как последовательность инструкций скрипта
+impl fmt::Display for CompositeCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_inline(f, &self.commands)
+    }
+}
+
+fn write_inline(f: &mut fmt::Formatter<'_>, commands: &[Box<dyn Command>]) -> fmt::Result {
+    for (index, command) in commands.iter().enumerate() {
+        if index > 0 {
+            f.write_str(" ")?;
+        }
+        write!(f, "{command}")?;
+    }
+    Ok(())
+}
+
+impl From<CommandList> for CompositeCommand {
+    fn from(command_list: CommandList) -> Self {
+        Self::new(command_list.commands)
+    }
+}
+
+// Список команд. Обычно команды хранятся в Box<dyn Command>, а список
+// CommandList<CommandKind> хранит встроенные команды без выделения памяти под каждую.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandList<C = Box<dyn Command>> {
+    commands: Vec<C>,
+}
+
+// Пустой список создается только для Box<dyn Command>, чтобы тип элементов
+// выводился и тогда, когда в список добавляется Box с конкретной командой
+impl Default for CommandList {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<C> CommandList<C> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            commands: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn add_command(&mut self, command: C) {
+        self.commands.push(command);
+    }
+
+    pub fn add_commands(&mut self, commands: &[C])
+    where
+        C: Clone,
+    {
+        self.commands.extend_from_slice(commands);
+    }
+
+    // Методы редактирования, как и у Vec, паникуют при индексе за пределами списка
+    pub fn insert(&mut self, index: usize, command: C) {
+        self.commands.insert(index, command);
+    }
+
+    pub fn remove(&mut self, index: usize) -> C {
+        self.commands.remove(index)
+    }
+
+    // Заменяет команду и возвращает прежнюю
+    pub fn replace(&mut self, index: usize, command: C) -> C {
+        std::mem::replace(&mut self.commands[index], command)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.commands.truncate(len);
+    }
+
+    // Разделяет список: команды начиная с индекса переходят в новый список
+    pub fn split_off(&mut self, index: usize) -> Self {
+        Self {
+            commands: self.commands.split_off(index),
+        }
+    }
+
+    pub fn commands(&self) -> &[C] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl<C: Command + 'static> CommandList<C> {
+    pub fn execute_all(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        for command in &mut self.commands {
+            command.execute(robot)?;
+        }
+        Ok(())
+    }
+
+    // Выполняет все команды, сообщая наблюдателю о каждой команде и об ошибке
+    pub fn execute_all_with<O: ExecutionObserver + ?Sized>(
+        &mut self,
+        robot: &mut dyn RobotLike,
+        observer: &mut O,
+    ) -> Result<(), Error> {
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            observer.before_command(index, &*command, robot);
+            if let Err(error) = command.execute(robot) {
+                observer.on_error(index, &*command, robot, &error);
+                return Err(error);
+            }
+            observer.after_command(index, &*command, robot);
+        }
+        Ok(())
+    }
+
+    // Выполняет команды, пока не установлен флаг отмены, который проверяется перед каждой
+    // командой, например из другого потока. После каждой команды вызывается функция прогресса
+    // с количеством выполненных команд и длиной списка. Возвращает количество выполненных
+    // команд: при отмене робот остается в состоянии после последней из них.
+    pub fn execute_all_cancellable(
+        &mut self,
+        robot: &mut dyn RobotLike,
+        cancel: &AtomicBool,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<usize, Error> {
+        let total = self.commands.len();
+
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            if cancel.load(Ordering::Relaxed) {
+                log::debug!("Execution cancelled after {index} of {total} commands");
+                return Ok(index);
+            }
+            command.execute(robot)?;
+            progress(index + 1, total);
+        }
+
+        Ok(total)
+    }
+
+    // Выполняет все команды или ни одной: при ошибке робот возвращается в состояние
+    // до выполнения списка, а уже выполненные команды отменяются в обратном порядке.
+    // Для этого нужен снимок состояния, поэтому список выполняется только роботом.
+    pub fn execute_atomic(&mut self, robot: &mut Robot) -> Result<(), AtomicError> {
+        for index in 0..self.commands.len() {
+            // Неудачная команда могла выполниться частично, например пройти часть шагов
+            let before = robot.snapshot();
+            let Err(error) = self.commands[index].execute(robot) else {
+                continue;
+            };
+            robot.restore(&before);
+
+            let rollback = self.commands[..index]
+                .iter_mut()
+                .rev()
+                .try_for_each(|command| command.rollback(robot))
+                .map_err(Box::new);
+            return Err(AtomicError { error, rollback });
+        }
+        Ok(())
+    }
+
+    // Выполняет команды по одной, спрашивая перед каждой командой, что делать дальше.
+    // Возвращает количество выполненных команд, оно меньше длины списка, если выполнение прервано.
+    // Функция получает того же исполнителя, что передан в список, например робота целиком.
+    pub fn execute_stepwise<R: RobotLike>(
+        &mut self,
+        robot: &mut R,
+        mut before: impl FnMut(usize, &dyn Command, &R) -> StepAction,
+    ) -> Result<usize, Error> {
+        let mut pausing = true;
+
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            if pausing {
+                match before(index, &*command, robot) {
+                    StepAction::Step => {}
+                    StepAction::Continue => pausing = false,
+                    StepAction::Abort => return Ok(index),
+                }
+            }
+            command.execute(robot)?;
+        }
+
+        Ok(self.commands.len())
+    }
+
+    pub fn rollback_all(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        for command in self.commands.iter_mut().rev() {
+            command.rollback(robot)?;
+        }
+        Ok(())
+    }
+}
+
+impl CommandList {
+    // Объединяет соседние команды без изменения итогового состояния робота:
+    // "move 3; move 4" превращается в "move 7", "turn_left 1; turn_right 1" исчезает,
+    // а из нескольких переключений пера подряд остается только последнее.
+    // Если после удаления команд рядом оказываются объединяемые команды, они тоже объединяются.
+    pub fn optimize(&mut self) {
+        let mut optimized: Vec<Box<dyn Command>> = Vec::with_capacity(self.commands.len());
+
+        for command in self.commands.drain(..) {
+            let Some(step) = Step::of(command.as_ref()) else {
+                optimized.push(command);
+                continue;
+            };
+
+            let merged = optimized
+                .last()
+                .and_then(|last| Step::of(last.as_ref()))
+                .and_then(|previous| previous.merge(step));
+
+            match merged {
+                Some(merged) => {
+                    optimized.pop();
+                    if !merged.is_noop() {
+                        optimized.push(merged.into_command());
+                    }
+                }
+                None if step.is_noop() => {}
+                None => optimized.push(command),
+            }
+        }
+
+        self.commands = optimized;
+    }
+
+    // Сжимает список сильнее, чем optimize(). Повороты и переключения пера не влияют друг
+    // на друга, поэтому в каждой серии таких команд остается не больше одного поворота
+    // и одного переключения пера, а переключение в положение, в котором перо уже находится,
+    // удаляется. Тела повторов, групп и циклов сжимаются так же.
+    // Новые команды не помнят прежних выполнений, поэтому сжимать нужно до выполнения списка.
+    pub fn compact(&mut self) {
+        let mut compacted: Vec<Box<dyn Command>> = Vec::with_capacity(self.commands.len());
+        // Накопленный поворот и последнее переключение пера в текущей серии
+        let mut turn = 0;
+        let mut switch = None;
+        // Положение пера, известное после уже записанных команд
+        let mut drawing = None;
+
+        for command in self.commands.drain(..) {
+            match Step::of(command.as_ref()) {
+                Some(Step::Turn(times)) => turn = (turn + times) % 4,
+                Some(Step::Pen(pen)) => switch = Some(pen),
+                step => {
+                    flush_turns(&mut compacted, &mut turn, &mut switch, &mut drawing);
+                    if step.is_none() {
+                        // Неизвестная команда могла переключить перо
+                        drawing = None;
+                    }
+                    compacted.push(compact_command(command));
+                }
+            }
+        }
+        flush_turns(&mut compacted, &mut turn, &mut switch, &mut drawing);
+
+        self.commands = compacted;
+        self.optimize();
+        self.commands.shrink_to_fit();
+    }
+}
+
+// Список печатается по команде на строке
+impl<C: Command + 'static> fmt::Display for CommandList<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for command in &self.commands {
+            writeln!(f, "{}", command as &dyn Command)?;
+        }
+        Ok(())
+    }
+}
+
+// Записывает накопленные поворот и переключение пера серии
+fn flush_turns(
+    commands: &mut Vec<Box<dyn Command>>,
+    turn: &mut u8,
+    switch: &mut Option<bool>,
+    drawing: &mut Option<bool>,
+) {
+    if *turn != 0 {
+        commands.push(Step::Turn(*turn).into_command());
+        *turn = 0;
+    }
+    if let Some(pen) = switch.take()
+        && *drawing != Some(pen)
+    {
+        commands.push(Step::Pen(pen).into_command());
+        *drawing = Some(pen);
+    }
+}
+
+// Сжимает тело команды, которая содержит другие команды
+fn compact_command(command: Box<dyn Command>) -> Box<dyn Command> {
+    let any = command.as_any();
+
+    if let Some(group) = any.downcast_ref::<CompositeCommand>() {
+        let mut commands: CommandList = group.commands.iter().cloned().collect();
+        commands.compact();
+        Box::new(CompositeCommand::from(commands))
+    } else if let Some(repeat) = any.downcast_ref::<RepeatCommand>() {
+        let inner = compact_command(repeat.inner.clone());
+        Box::new(RepeatCommand::new(inner, repeat.times))
+    } else if let Some(command) = any.downcast_ref::<WhileCommand>() {
+        let mut body = command.body.clone();
+        body.compact();
+        Box::new(WhileCommand::new(command.condition.clone(), body))
+    } else {
+        command
+    }
+}
+
+impl<C> FromIterator<C> for CommandList<C> {
+    fn from_iter<I: IntoIterator<Item = C>>(iter: I) -> Self {
+        Self {
+            commands: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<C> Extend<C> for CommandList<C> {
+    fn extend<I: IntoIterator<Item = C>>(&mut self, iter: I) {
+        self.commands.extend(iter);
+    }
+}
+
+impl<C> IntoIterator for CommandList<C> {
+    type Item = C;
+    type IntoIter = std::vec::IntoIter<C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commands.into_iter()
+    }
+}
+
+impl<'a, C> IntoIterator for &'a CommandList<C> {
+    type Item = &'a C;
+    type IntoIter = std::slice::Iter<'a, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commands.iter()
+    }
+}
+
+impl<'a, C> IntoIterator for &'a mut CommandList<C> {
+    type Item = &'a mut C;
+    type IntoIter = std::slice::IterMut<'a, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commands.iter_mut()
+    }
+}
+
+// Ошибка выполнения списка команд методом execute_atomic() и результат отмены
+// выполненных до нее команд
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct AtomicError {
+    #[source]
+    pub error: Error,
+    pub rollback: Result<(), Box<Error>>,
+}
+
+// Наблюдатель за выполнением списка команд, например для журнала, анимации или статистики.
+// Все методы по умолчанию ничего не делают.
+pub trait ExecutionObserver {
+    fn before_command(&mut self, _index: usize, _command: &dyn Command, _robot: &dyn RobotLike) {}
+
+    fn after_command(&mut self, _index: usize, _command: &dyn Command, _robot: &dyn RobotLike) {}
+
+    fn on_error(
+        &mut self,
+        _index: usize,
+        _command: &dyn Command,
+        _robot: &dyn RobotLike,
+        _error: &Error,
+    ) {
+    }
+}
+
+// Решение перед очередной командой при пошаговом выполнении
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepAction {
+    // Выполнить команду и остановиться перед следующей
+    Step,
+    // Выполнить эту и все оставшиеся команды без остановок
+    Continue,
+    // Прервать выполнение, не выполняя эту команду
+    Abort,
+}
+
+// История выполненных команд для интерактивного редактирования.
+// Отмененные команды можно выполнить повторно, пока не выполнена новая команда.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    done: Vec<Box<dyn Command>>,
+    undone: Vec<Box<dyn Command>>,
+}
+
+impl History {
+    // Восстанавливает историю по сохраненным командам: выполненные команды выполняются
+    // заново, чтобы запомнить состояние для отмены, отмененные ждут повторения.
+    // Отмененные команды идут в порядке отмены, последняя отмененная - в конце.
+    pub fn restore(
+        done: Vec<Box<dyn Command>>,
+        undone: Vec<Box<dyn Command>>,
+        robot: &mut dyn RobotLike,
+    ) -> Result<Self, Error> {
+        let mut history = Self::default();
+        for command in done {
+            history.execute(command, robot)?;
+        }
+        history.undone = undone;
+        Ok(history)
+    }
+
+    // Выполняет команду и запоминает ее, отмененные команды больше нельзя повторить
+    pub fn execute(
+        &mut self,
+        mut command: Box<dyn Command>,
+        robot: &mut dyn RobotLike,
+    ) -> Result<(), Error> {
+        command.execute(robot)?;
+        self.done.push(command);
+        self.undone.clear();
+        Ok(())
+    }
+
+    // Отменяет последнюю выполненную команду, возвращает false, если отменять нечего
+    pub fn undo(&mut self, robot: &mut dyn RobotLike) -> Result<bool, Error> {
+        let Some(mut command) = self.done.pop() else {
+            return Ok(false);
+        };
+        if let Err(err) = command.rollback(robot) {
+            self.done.push(command);
+            return Err(err);
+        }
+        self.undone.push(command);
+        Ok(true)
+    }
+
+    // Повторяет последнюю отмененную команду, возвращает false, если повторять нечего
+    pub fn redo(&mut self, robot: &mut dyn RobotLike) -> Result<bool, Error> {
+        let Some(mut command) = self.undone.pop() else {
+            return Ok(false);
+        };
+        if let Err(err) = command.execute(robot) {
+            self.undone.push(command);
+            return Err(err);
+        }
+        self.done.push(command);
+        Ok(true)
+    }
+
+    // Отменяет до n последних команд, возвращает количество отмененных команд
+    pub fn undo_n(&mut self, n: usize, robot: &mut dyn RobotLike) -> Result<usize, Error> {
+        for undone in 0..n {
+            if !self.undo(robot)? {
+                return Ok(undone);
+            }
+        }
+        Ok(n)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    // Выполненные и не отмененные команды в порядке выполнения
+    pub fn commands(&self) -> &[Box<dyn Command>] {
+        &self.done
+    }
+
+    // Отмененные команды, которые можно повторить, последняя отмененная - в конце
+    pub fn undone(&self) -> &[Box<dyn Command>] {
+        &self.undone
+    }
+}
+
+// Команда в виде, удобном для объединения с соседними командами
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Move(u32),
+    Back(u32),
+    // Количество поворотов налево по модулю 4
+    Turn(u8),
+    // Опущено ли перо после команды
+    Pen(bool),
+}
+
+impl Step {
+    fn of(command: &dyn Command) -> Option<Self> {
+        let command = command.as_any();
+
+        if let Some(command) = command.downcast_ref::<MoveCommand>() {
+            Some(Step::Move(command.distance))
+        } else if let Some(command) = command.downcast_ref::<MoveBackwardCommand>() {
+            Some(Step::Back(command.distance))
+        } else if let Some(command) = command.downcast_ref::<TurnLeftCommand>() {
+            Some(Step::Turn(command.times))
+        } else if let Some(command) = command.downcast_ref::<TurnRightCommand>() {
+            Some(Step::Turn((4 - command.times) % 4))
+        } else if command.is::<DownPenCommand>() {
+            Some(Step::Pen(true))
+        } else if command.is::<UpPenCommand>() {
+            Some(Step::Pen(false))
+        } else {
+            None
+        }
+    }
+
+    fn merge(self, next: Self) -> Option<Self> {
+        match (self, next) {
+            (Step::Move(first), Step::Move(second)) => first.checked_add(second).map(Step::Move),
+            (Step::Back(first), Step::Back(second)) => first.checked_add(second).map(Step::Back),
+            (Step::Turn(first), Step::Turn(second)) => Some(Step::Turn((first + second) % 4)),
+            (Step::Pen(_), Step::Pen(drawing)) => Some(Step::Pen(drawing)),
+            _ => None,
+        }
+    }
+
+    fn is_noop(self) -> bool {
+        matches!(self, Step::Move(0) | Step::Back(0) | Step::Turn(0))
+    }
+
+    fn into_command(self) -> Box<dyn Command> {
+        match self {
+            Step::Move(distance) => Box::new(MoveCommand::new(distance)),
+            Step::Back(distance) => Box::new(MoveBackwardCommand::new(distance)),
+            Step::Turn(3) => Box::new(TurnRightCommand::new(1)),
+            Step::Turn(times) => Box::new(TurnLeftCommand::new(times.into())),
+            Step::Pen(true) => Box::new(DownPenCommand),
+            Step::Pen(false) => Box::new(UpPenCommand),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canvas::Canvas, robot::Point};
+
+    #[test]
+    fn test_turn_45_commands() {
+        let mut robot = Robot::default();
+        let mut cmd = TurnRight45Command::new(11); // 11 % 8 == 3
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!(robot.direction(), Direction::DownRight);
+        assert_eq!(cmd.to_string(), "turn_right_45 3");
+
+        let mut back = TurnLeft45Command::new(1);
+        back.execute(&mut robot).unwrap();
+        assert_eq!(robot.direction(), Direction::Right);
+        back.rollback(&mut robot).unwrap();
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.direction(), Direction::Up);
+    }
+
+    #[test]
+    fn test_move_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        let mut cmd = MoveCommand::new(3);
+
+        // Move forward 3 steps
+        assert!(cmd.execute(&mut robot).is_ok());
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.y(), 3);
+
+        // Rollback: should return to original position
+        assert!(cmd.rollback(&mut robot).is_ok());
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.y(), 0);
+    }
+
+    #[test]
+    fn test_move_backward_command_execute_and_rollback() {
+        let mut robot = Robot::new(0, 0, Direction::Left, false);
+        let mut cmd = MoveBackwardCommand::new(3);
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!(robot.x(), 3);
+        assert_eq!(robot.direction(), Direction::Left);
+
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.direction(), Direction::Left);
+    }
+
+    #[test]
+    fn test_turn_left_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        let mut cmd = TurnLeftCommand::new(1);
+
+        // Turn left once
+        assert!(cmd.execute(&mut robot).is_ok());
+        assert_eq!(robot.direction(), Direction::Left);
+
+        // Rollback: should turn right, back to up
+        assert!(cmd.rollback(&mut robot).is_ok());
+        assert_eq!(robot.direction(), Direction::Up);
+    }
+
+    #[test]
+    fn test_turn_right_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        let mut cmd = TurnRightCommand::new(2);
+
+        // Turn right twice
+        assert!(cmd.execute(&mut robot).is_ok());
+        assert_eq!(robot.direction(), Direction::Down);
+
+        // Rollback: should turn left twice, back to up
+        assert!(cmd.rollback(&mut robot).is_ok());
+        assert_eq!(robot.direction(), Direction::Up);
+    }
+
+    #[test]
+    fn test_down_pen_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        let mut cmd = DownPenCommand;
 
         // Pen down
         assert!(cmd.execute(&mut robot).is_ok());
@@ -313,4 +2067,711 @@ mod tests {
         assert!(cmd.execute(&mut robot).is_ok());
         assert_eq!(robot.direction(), Direction::Up);
     }
+
+    fn optimized(commands: Vec<Box<dyn Command>>) -> CommandList {
+        let mut command_list = CommandList::default();
+        for command in commands {
+            command_list.add_command(command);
+        }
+        command_list.optimize();
+        command_list
+    }
+
+    #[test]
+    fn test_optimize_merges_moves() {
+        let command_list = optimized(vec![
+            Box::new(MoveCommand::new(3)),
+            Box::new(MoveCommand::new(4)),
+        ]);
+
+        assert_eq!(command_list.commands().len(), 1);
+        let command = command_list.commands()[0].as_any();
+        assert_eq!(command.downcast_ref::<MoveCommand>().unwrap().distance, 7);
+    }
+
+    #[test]
+    fn test_optimize_merges_backward_moves_separately() {
+        let command_list = optimized(vec![
+            Box::new(MoveBackwardCommand::new(1)),
+            Box::new(MoveBackwardCommand::new(2)),
+            Box::new(MoveCommand::new(3)),
+        ]);
+
+        assert_eq!(command_list.commands().len(), 2);
+        let command = command_list.commands()[0].as_any();
+        assert_eq!(
+            command
+                .downcast_ref::<MoveBackwardCommand>()
+                .unwrap()
+                .distance,
+            3
+        );
+    }
+
+    #[test]
+    fn test_optimize_cancels_turns_and_merges_neighbours() {
+        let mut command_list = optimized(vec![
+            Box::new(MoveCommand::new(1)),
+            Box::new(TurnLeftCommand::new(1)),
+            Box::new(TurnRightCommand::new(1)),
+            Box::new(MoveCommand::new(2)),
+        ]);
+
+        assert_eq!(command_list.commands().len(), 1);
+        let mut robot = Robot::default();
+        command_list.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.y(), 3);
+    }
+
+    #[test]
+    fn test_optimize_keeps_last_pen_toggle() {
+        let mut command_list = optimized(vec![
+            Box::new(DownPenCommand),
+            Box::new(UpPenCommand),
+            Box::new(DownPenCommand),
+            Box::new(TurnLeftCommand::new(2)),
+            Box::new(TurnLeftCommand::new(1)),
+        ]);
+
+        assert_eq!(command_list.commands().len(), 2);
+        let mut robot = Robot::default();
+        command_list.execute_all(&mut robot).unwrap();
+        assert!(robot.is_drawing());
+        assert_eq!(robot.direction(), Direction::Right);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl ExecutionObserver for Recorder {
+        fn before_command(&mut self, index: usize, _command: &dyn Command, robot: &dyn RobotLike) {
+            self.events.push(format!("before {index} at {}", robot.y()));
+        }
+
+        fn after_command(&mut self, index: usize, _command: &dyn Command, robot: &dyn RobotLike) {
+            self.events.push(format!("after {index} at {}", robot.y()));
+        }
+
+        fn on_error(
+            &mut self,
+            index: usize,
+            _command: &dyn Command,
+            _robot: &dyn RobotLike,
+            error: &Error,
+        ) {
+            self.events.push(format!("error {index}: {error}"));
+        }
+    }
+
+    #[test]
+    fn test_execute_all_with_observer() {
+        let mut robot = Robot::new(0, i32::MAX - 2, Direction::Up, false);
+        let mut command_list: CommandList = [1, 2, 3]
+            .map(|distance| Box::new(MoveCommand::new(distance)) as Box<dyn Command>)
+            .into_iter()
+            .collect();
+
+        let mut recorder = Recorder::default();
+        let result = command_list.execute_all_with(&mut robot, &mut recorder);
+        assert!(matches!(result, Err(Error::OutOfBounds)));
+        assert_eq!(
+            recorder.events,
+            [
+                format!("before 0 at {}", i32::MAX - 2),
+                format!("after 0 at {}", i32::MAX - 1),
+                format!("before 1 at {}", i32::MAX - 1),
+                format!("error 1: {}", Error::OutOfBounds),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_all_cancellable() {
+        let mut command_list: CommandList = (0..5)
+            .map(|_| Box::new(MoveCommand::new(1)) as Box<dyn Command>)
+            .collect();
+        let cancel = AtomicBool::new(false);
+        let mut robot = Robot::default();
+
+        let mut reports = Vec::new();
+        let executed = command_list
+            .execute_all_cancellable(&mut robot, &cancel, |done, total| {
+                reports.push((done, total));
+                if done == 2 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            })
+            .unwrap();
+        assert_eq!(executed, 2);
+        assert_eq!(reports, [(1, 5), (2, 5)]);
+        assert_eq!(robot.y(), 2);
+
+        cancel.store(false, Ordering::Relaxed);
+        let executed = command_list
+            .execute_all_cancellable(&mut robot, &cancel, |_, _| {})
+            .unwrap();
+        assert_eq!(executed, 5);
+        assert_eq!(robot.y(), 7);
+    }
+
+    #[test]
+    fn test_commands_compare_by_parameters() {
+        let mut executed = TeleportCommand::new(1, 2);
+        executed.execute(&mut Robot::default()).unwrap();
+        let executed: Box<dyn Command> = Box::new(executed);
+
+        let expected: Box<dyn Command> = Box::new(TeleportCommand::new(1, 2));
+        assert!(executed == expected);
+        assert!(executed.eq_dyn(&TeleportCommand::new(1, 2)));
+        assert!(!executed.eq_dyn(&TeleportCommand::new(2, 1)));
+        assert!(!MoveCommand::new(1).eq_dyn(&MoveBackwardCommand::new(1)));
+
+        let list = |distance| -> CommandList {
+            [
+                Box::new(RepeatCommand::new(Box::new(MoveCommand::new(distance)), 2))
+                    as Box<dyn Command>,
+                Box::new(UpPenCommand),
+            ]
+            .into_iter()
+            .collect()
+        };
+        assert_eq!(list(1), list(1));
+        assert_ne!(list(1), list(2));
+    }
+
+    #[test]
+    fn test_display_commands_as_script() {
+        let mut body = CommandList::default();
+        body.add_command(Box::new(MoveCommand::new(1)));
+        body.add_command(Box::new(TurnLeftCommand::new(1)));
+
+        let command_list: CommandList = [
+            Box::new(DownPenCommand) as Box<dyn Command>,
+            Box::new(TeleportCommand::new(-1, -2)),
+            Box::new(SetDirectionCommand::new(Direction::Left)),
+            Box::new(PenColorCommand::new(Color::Red)),
+            Box::new(PenWidthCommand::new(2)),
+            Box::new(RepeatCommand::new(Box::new(MoveBackwardCommand::new(3)), 2)),
+            Box::new(CompositeCommand::new(vec![
+                Box::new(TurnRightCommand::new(1)),
+                Box::new(UpPenCommand),
+            ])),
+            Box::new(WhileCommand::new(
+                Condition::And(
+                    Box::new(Condition::Not(Box::new(Condition::Or(
+                        Box::new(Condition::Blocked),
+                        Box::new(Condition::Value(false)),
+                    )))),
+                    Box::new(Condition::Or(
+                        Box::new(Condition::Value(true)),
+                        Box::new(Condition::Blocked),
+                    )),
+                ),
+                body,
+            )),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            command_list.to_string(),
+            "\
+down_pen
+goto -1 (-2)
+face left
+color red
+width 2
+repeat 2 [ back 3 ]
+turn_right 1 up_pen
+while not (blocked or 0) and (1 or blocked) [ move 1 turn_left 1 ]
+"
+        );
+    }
+
+    fn compacted(commands: Vec<Box<dyn Command>>) -> CommandList {
+        let mut command_list: CommandList = commands.into_iter().collect();
+        command_list.compact();
+        command_list
+    }
+
+    #[test]
+    fn test_compact_folds_turns_across_pen_toggles() {
+        let mut command_list = compacted(vec![
+            Box::new(DownPenCommand),
+            Box::new(MoveCommand::new(1)),
+            Box::new(TurnLeftCommand::new(1)),
+            Box::new(DownPenCommand),
+            Box::new(TurnRightCommand::new(1)),
+            Box::new(MoveCommand::new(2)),
+            Box::new(TurnRightCommand::new(1)),
+            Box::new(UpPenCommand),
+            Box::new(TurnRightCommand::new(2)),
+        ]);
+
+        // down_pen, move 3, turn_left 1, up_pen
+        assert_eq!(command_list.len(), 4);
+        let mut robot = Robot::default();
+        command_list.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.y(), robot.direction()), (3, Direction::Left));
+        assert!(!robot.is_drawing());
+    }
+
+    #[test]
+    fn test_compact_keeps_pen_after_unknown_command() {
+        let command_list = compacted(vec![
+            Box::new(DownPenCommand),
+            Box::new(TeleportCommand::new(1, 1)),
+            Box::new(DownPenCommand),
+        ]);
+        assert_eq!(command_list.len(), 3);
+    }
+
+    #[test]
+    fn test_compact_nested_commands() {
+        let mut body = CommandList::default();
+        body.add_command(Box::new(MoveCommand::new(1)));
+        body.add_command(Box::new(MoveCommand::new(1)));
+
+        let command_list = compacted(vec![
+            Box::new(RepeatCommand::new(
+                Box::new(CompositeCommand::new(vec![
+                    Box::new(TurnLeftCommand::new(2)),
+                    Box::new(TurnLeftCommand::new(2)),
+                    Box::new(MoveCommand::new(1)),
+                ])),
+                2,
+            )),
+            Box::new(WhileCommand::new(Condition::Value(false), body)),
+        ]);
+
+        let repeat = command_list.commands()[0].as_any();
+        let group = repeat
+            .downcast_ref::<RepeatCommand>()
+            .unwrap()
+            .inner
+            .as_any();
+        assert_eq!(
+            group
+                .downcast_ref::<CompositeCommand>()
+                .unwrap()
+                .commands()
+                .len(),
+            1
+        );
+        let command = command_list.commands()[1].as_any();
+        assert_eq!(
+            command.downcast_ref::<WhileCommand>().unwrap().body.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_while_command_execute_and_rollback() {
+        let mut robot = Robot::new(0, i32::MAX - 3, Direction::Up, false);
+        let mut body = CommandList::default();
+        body.add_command(Box::new(MoveCommand::new(1)));
+        let mut cmd = WhileCommand::new(Condition::Not(Box::new(Condition::Blocked)), body);
+
+        // Move until the edge of the grid
+        assert!(cmd.execute(&mut robot).is_ok());
+        assert_eq!(robot.y(), i32::MAX);
+
+        // Rollback: should return to original position
+        assert!(cmd.rollback(&mut robot).is_ok());
+        assert_eq!(robot.y(), i32::MAX - 3);
+    }
+
+    #[test]
+    fn test_while_command_iteration_limit() {
+        let mut robot = Robot::default();
+        let mut body = CommandList::default();
+        body.add_command(Box::new(TurnLeftCommand::new(1)));
+        body.add_command(Box::new(MoveCommand::new(1)));
+        let mut cmd = WhileCommand::new(Condition::Value(true), body).with_max_iterations(6);
+
+        assert!(matches!(
+            cmd.execute(&mut robot),
+            Err(Error::IterationLimit(6))
+        ));
+        assert_eq!((robot.x(), robot.y()), (-1, -1));
+
+        // Отменяются только выполненные повторения
+        assert!(cmd.rollback(&mut robot).is_ok());
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert_eq!(robot.direction(), Direction::Up);
+    }
+
+    #[test]
+    fn test_execute_stepwise() {
+        let mut command_list = CommandList::default();
+        command_list.add_command(Box::new(MoveCommand::new(1)));
+        command_list.add_command(Box::new(MoveCommand::new(2)));
+        command_list.add_command(Box::new(TurnLeftCommand::new(1)));
+
+        let mut robot = Robot::default();
+        let mut seen = Vec::new();
+        let executed = command_list
+            .execute_stepwise(&mut robot, |index, _, robot| {
+                seen.push((index, robot.y()));
+                if index == 1 {
+                    StepAction::Abort
+                } else {
+                    StepAction::Step
+                }
+            })
+            .unwrap();
+        assert_eq!(executed, 1);
+        assert_eq!(seen, [(0, 0), (1, 1)]);
+        assert_eq!(robot.y(), 1);
+
+        let mut robot = Robot::default();
+        let mut pauses = 0;
+        let executed = command_list
+            .execute_stepwise(&mut robot, |_, _, _| {
+                pauses += 1;
+                StepAction::Continue
+            })
+            .unwrap();
+        assert_eq!((executed, pauses), (3, 1));
+        assert_eq!(robot.direction(), Direction::Left);
+    }
+
+    #[test]
+    fn test_history_undo_redo() {
+        let mut robot = Robot::default();
+        let mut history = History::default();
+        history
+            .execute(Box::new(MoveCommand::new(2)), &mut robot)
+            .unwrap();
+        history
+            .execute(Box::new(TurnRightCommand::new(1)), &mut robot)
+            .unwrap();
+        history
+            .execute(Box::new(MoveCommand::new(3)), &mut robot)
+            .unwrap();
+        assert_eq!((robot.x(), robot.y()), (3, 2));
+
+        assert_eq!(history.undo_n(2, &mut robot).unwrap(), 2);
+        assert_eq!((robot.x(), robot.y()), (0, 2));
+        assert_eq!(robot.direction(), Direction::Up);
+
+        assert!(history.redo(&mut robot).unwrap());
+        assert_eq!(robot.direction(), Direction::Right);
+
+        // Новая команда отбрасывает оставшиеся отмененные команды
+        history
+            .execute(Box::new(MoveCommand::new(1)), &mut robot)
+            .unwrap();
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut robot).unwrap());
+        assert_eq!((robot.x(), robot.y()), (1, 2));
+
+        assert_eq!(history.undo_n(10, &mut robot).unwrap(), 3);
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_history_restore() {
+        let mut robot = Robot::default();
+        let mut history = History::restore(
+            vec![
+                Box::new(MoveCommand::new(2)),
+                Box::new(TurnRightCommand::new(1)),
+            ],
+            vec![Box::new(MoveCommand::new(5))],
+            &mut robot,
+        )
+        .unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 2));
+        assert_eq!(history.commands().len(), 2);
+        assert_eq!(history.undone().len(), 1);
+
+        assert!(history.redo(&mut robot).unwrap());
+        assert_eq!((robot.x(), robot.y()), (5, 2));
+        assert_eq!(history.undo_n(3, &mut robot).unwrap(), 3);
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+    }
+
+    #[test]
+    fn test_composite_command_is_one_undo_step() {
+        let mut robot = Robot::default();
+        let mut square_corner = CompositeCommand::new(vec![
+            Box::new(DownPenCommand),
+            Box::new(MoveCommand::new(2)),
+            Box::new(TurnRightCommand::new(1)),
+        ]);
+
+        square_corner.execute(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 2));
+        assert_eq!(robot.direction(), Direction::Right);
+        assert!(robot.is_drawing());
+
+        let mut history = History::default();
+        history
+            .execute(square_corner.box_clone(), &mut robot)
+            .unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 2));
+
+        assert!(history.undo(&mut robot).unwrap());
+        square_corner.rollback(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert_eq!(robot.direction(), Direction::Up);
+        assert!(!robot.is_drawing());
+    }
+
+    #[test]
+    fn test_repeat_command() {
+        let mut robot = Robot::default();
+        let side = CompositeCommand::new(vec![
+            Box::new(MoveCommand::new(2)),
+            Box::new(TurnRightCommand::new(1)),
+        ]);
+        let mut cmd = RepeatCommand::new(Box::new(side), 3);
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 0));
+        assert_eq!(robot.direction(), Direction::Left);
+
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert_eq!(robot.direction(), Direction::Up);
+    }
+
+    #[test]
+    fn test_repeat_command_rollback_after_error() {
+        let mut robot = Robot::new(0, i32::MAX - 3, Direction::Up, false);
+        let mut cmd = RepeatCommand::new(Box::new(MoveCommand::new(2)), 5);
+
+        assert!(matches!(cmd.execute(&mut robot), Err(Error::OutOfBounds)));
+        assert_eq!(robot.y(), i32::MAX);
+
+        // Отменяется одно выполненное повторение, неудачный шаг остается
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.y(), i32::MAX - 2);
+    }
+
+    #[test]
+    fn test_teleport_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        robot.down_pen();
+        let mut cmd = TeleportCommand::new(5, -3);
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (5, -3));
+        assert_eq!(robot.direction(), Direction::Up);
+
+        robot.set_position(1, 1).unwrap();
+        cmd.execute(&mut robot).unwrap();
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (1, 1));
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert!(robot.is_drawing());
+
+        robot.set_world(crate::world::World::new().with_obstacle(5, -3));
+        assert!(matches!(
+            cmd.execute(&mut robot),
+            Err(Error::Blocked(5, -3))
+        ));
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+    }
+
+    #[test]
+    fn test_home_command_execute_and_rollback() {
+        let mut robot = Robot::new(1, 2, Direction::Right, true);
+        robot.move_forward().unwrap();
+        robot.turn_left();
+        let mut cmd = HomeCommand::new();
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (1, 2));
+        assert_eq!(robot.direction(), Direction::Right);
+        assert!(!robot.is_drawing());
+        assert_eq!(cmd.to_string(), "home");
+
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 2));
+        assert_eq!(robot.direction(), Direction::Up);
+        assert!(robot.is_drawing());
+    }
+
+    #[test]
+    fn test_refuel_command_execute_and_rollback() {
+        let mut robot = crate::robot::RobotBuilder::new()
+            .fuel(Fuel::new(1, 1, 0))
+            .build();
+        let mut cmd = RefuelCommand::new(5);
+
+        cmd.execute(&mut robot).unwrap();
+        robot.move_forward_by(6).unwrap();
+        assert!(matches!(robot.move_forward(), Err(Error::OutOfFuel)));
+
+        robot.set_fuel(Some(Fuel::new(3, 1, 0)));
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.fuel(), Some(Fuel::new(1, 1, 0)));
+        assert_eq!(cmd.to_string(), "refuel 5");
+    }
+
+    #[test]
+    fn test_stamp_command() {
+        let mut robot = Robot::new(5, 5, Direction::Up, false);
+        let mut cmd = StampCommand::square(3);
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (5, 5));
+        assert_eq!(robot.path().len(), 4);
+        assert_eq!(robot.path()[0].from, Point::new(4, 4));
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.path().len(), 4);
+
+        StampCommand::circle(2).execute(&mut robot).unwrap();
+        StampCommand::dot().execute(&mut robot).unwrap();
+        assert_eq!(robot.path().len(), 4 + 12 + 1);
+        assert_eq!(StampCommand::circle(2).to_string(), "stamp circle 2");
+
+        assert!(matches!(
+            StampCommand::circle(u32::MAX).execute(&mut robot),
+            Err(Error::StampTooLarge(stamp)) if stamp == "circle 4294967295"
+        ));
+        assert_eq!(robot.path().len(), 4 + 12 + 1);
+    }
+
+    #[test]
+    fn test_write_command() {
+        let mut robot = Robot::new(0, 0, Direction::Right, false);
+        let mut cmd = WriteCommand::new("HI");
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        let canvas = Canvas::from_path(robot.path());
+        assert_eq!(
+            canvas.render_ascii(),
+            concat!(
+                "*.*.***\n",
+                "*.*..*.\n",
+                "***..*.\n",
+                "*.*..*.\n",
+                "*.*.***\n",
+            )
+        );
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.path().len(), canvas.segments().count());
+        assert_eq!(cmd.to_string(), "write \"HI\"");
+    }
+
+    #[test]
+    fn test_set_direction_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        robot.turn_right();
+        let mut cmd = SetDirectionCommand::new(Direction::Down);
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!(robot.direction(), Direction::Down);
+        robot.turn_left();
+        cmd.execute(&mut robot).unwrap();
+
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.direction(), Direction::Right);
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.direction(), Direction::Right);
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+    }
+
+    #[test]
+    fn test_pen_color_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        let mut red = PenColorCommand::new(Color::Red);
+        let mut green = PenColorCommand::new(Color::Green);
+
+        red.execute(&mut robot).unwrap();
+        green.execute(&mut robot).unwrap();
+        assert_eq!(robot.color(), Color::Green);
+
+        green.rollback(&mut robot).unwrap();
+        assert_eq!(robot.color(), Color::Red);
+        red.rollback(&mut robot).unwrap();
+        assert_eq!(robot.color(), Color::Black);
+    }
+
+    #[test]
+    fn test_pen_width_command_execute_and_rollback() {
+        let mut robot = Robot::default();
+        let mut cmd = PenWidthCommand::new(4);
+
+        cmd.execute(&mut robot).unwrap();
+        assert_eq!(robot.pen_width(), 4);
+        cmd.rollback(&mut robot).unwrap();
+        assert_eq!(robot.pen_width(), 1);
+    }
+
+    #[test]
+    fn test_command_list_editing() {
+        let mut commands: CommandList = [1, 2, 3]
+            .map(|distance| Box::new(MoveCommand::new(distance)) as Box<dyn Command>)
+            .into_iter()
+            .collect();
+
+        commands.insert(1, Box::new(TurnRightCommand::new(1)));
+        let removed = commands.remove(2);
+        assert!(removed.as_any().is::<MoveCommand>());
+        let replaced = commands.replace(0, Box::new(DownPenCommand));
+        assert!(replaced.as_any().is::<MoveCommand>());
+        assert_eq!(commands.len(), 3);
+
+        let mut tail = commands.split_off(1);
+        assert_eq!((commands.len(), tail.len()), (1, 2));
+        assert!(commands.commands()[0].as_any().is::<DownPenCommand>());
+        assert!(tail.commands()[0].as_any().is::<TurnRightCommand>());
+
+        tail.truncate(1);
+        let mut robot = Robot::default();
+        tail.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.y(), robot.direction()), (0, Direction::Right));
+    }
+
+    #[test]
+    fn test_command_list_from_iterator() {
+        let mut commands: CommandList = (1..=3)
+            .map(|distance| Box::new(MoveCommand::new(distance)) as Box<dyn Command>)
+            .collect();
+        assert_eq!(commands.len(), 3);
+
+        commands.extend([Box::new(TurnRightCommand::new(1)) as Box<dyn Command>]);
+        commands.extend(CommandList::default());
+        assert_eq!(commands.len(), 4);
+        assert!(!commands.is_empty());
+        assert!(CommandList::default().is_empty());
+
+        let mut robot = Robot::default();
+        for command in &mut commands {
+            command.execute(&mut robot).unwrap();
+        }
+        assert_eq!((robot.y(), robot.direction()), (6, Direction::Right));
+        assert_eq!((&commands).into_iter().count(), 4);
+        assert_eq!(commands.into_iter().count(), 4);
+    }
+
+    #[test]
+    fn test_execute_atomic_rolls_back_on_error() {
+        let mut command_list = CommandList::default();
+        command_list.add_command(Box::new(DownPenCommand));
+        command_list.add_command(Box::new(TurnRightCommand::new(1)));
+        command_list.add_command(Box::new(MoveCommand::new(5)));
+
+        let mut robot = Robot::new(i32::MAX - 2, 0, Direction::Up, false);
+        let err = command_list.execute_atomic(&mut robot).unwrap_err();
+        assert!(matches!(err.error, Error::OutOfBounds));
+        assert!(err.rollback.is_ok());
+        assert_eq!((robot.x(), robot.y()), (i32::MAX - 2, 0));
+        assert_eq!(robot.direction(), Direction::Up);
+        assert!(!robot.is_drawing());
+
+        let mut robot = Robot::default();
+        command_list.execute_atomic(&mut robot).unwrap();
+        assert_eq!(robot.x(), 5);
+    }
 }
@@ -0,0 +1,471 @@
+// Компактная двоичная запись списков команд для хранения и передачи больших программ.
+// Запись начинается с заголовка: сигнатура "RCMD" и номер версии формата.
+// Затем идет число команд и сами команды: номер команды и ее параметры.
+// Числа записываются переменной длиной (varint, по 7 бит в байте, младшие байты первыми),
+// числа со знаком - зигзагом, чтобы малые отрицательные числа тоже занимали один байт.
+// Направление и цвет записываются номером в Direction::ALL и Color::ALL,
+// строка - длиной и байтами UTF-8, вложенные команды и условия - так же, как в списке.
+// Номера команд и условий не меняются между версиями, новые получают следующие номера.
+// Как и при сериализации, команды, определенные вне этого модуля, записать нельзя.
+
+use std::io::{Read, Write};
+
+use super::*;
+
+const MAGIC: &[u8; 4] = b"RCMD";
+const VERSION: u8 = 1;
+// Ограничение вложенности, чтобы испорченные данные не переполнили стек
+const MAX_DEPTH: usize = 64;
+
+impl<C: Command + 'static> CommandList<C> {
+    pub fn encode<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        let mut encoder = Encoder { writer };
+        encoder.list(self.commands.iter().map(|command| command as &dyn Command))
+    }
+}
+
+impl<C: From<Box<dyn Command>>> CommandList<C> {
+    pub fn decode<R: Read>(reader: R) -> Result<Self, Error> {
+        let mut decoder = Decoder { reader, depth: 0 };
+        let mut magic = [0; 4];
+        decoder.reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("missing header"));
+        }
+        let version = decoder.byte()?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported version {version}")));
+        }
+
+        let commands = decoder.list()?;
+        Ok(Self {
+            commands: commands.into_iter().map(C::from).collect(),
+        })
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidEncoding(message.into())
+}
+
+struct Encoder<W> {
+    writer: W,
+}
+
+impl<W: Write> Encoder<W> {
+    fn list<'a>(
+        &mut self,
+        commands: impl ExactSizeIterator<Item = &'a dyn Command>,
+    ) -> Result<(), Error> {
+        self.number(commands.len() as u64)?;
+        for command in commands {
+            self.command(command)?;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, command: &dyn Command) -> Result<(), Error> {
+        let any = command.as_any();
+
+        if let Some(command) = any.downcast_ref::<MoveCommand>() {
+            self.tag(0)?;
+            self.number(command.distance.into())
+        } else if let Some(command) = any.downcast_ref::<MoveBackwardCommand>() {
+            self.tag(1)?;
+            self.number(command.distance.into())
+        } else if let Some(command) = any.downcast_ref::<TurnLeftCommand>() {
+            self.tag(2)?;
+            self.number(command.times.into())
+        } else if let Some(command) = any.downcast_ref::<TurnRightCommand>() {
+            self.tag(3)?;
+            self.number(command.times.into())
+        } else if let Some(command) = any.downcast_ref::<TurnLeft45Command>() {
+            self.tag(4)?;
+            self.number(command.times.into())
+        } else if let Some(command) = any.downcast_ref::<TurnRight45Command>() {
+            self.tag(5)?;
+            self.number(command.times.into())
+        } else if any.is::<DownPenCommand>() {
+            self.tag(6)
+        } else if any.is::<UpPenCommand>() {
+            self.tag(7)
+        } else if let Some(command) = any.downcast_ref::<TeleportCommand>() {
+            self.tag(8)?;
+            self.signed(command.x)?;
+            self.signed(command.y)
+        } else if any.is::<HomeCommand>() {
+            self.tag(9)
+        } else if let Some(command) = any.downcast_ref::<SetDirectionCommand>() {
+            self.tag(10)?;
+            self.index(Direction::ALL.iter().position(|&d| d == command.direction))
+        } else if let Some(command) = any.downcast_ref::<PenColorCommand>() {
+            self.tag(11)?;
+            self.index(Color::ALL.iter().position(|&c| c == command.color))
+        } else if let Some(command) = any.downcast_ref::<PenWidthCommand>() {
+            self.tag(12)?;
+            self.number(command.width.into())
+        } else if let Some(command) = any.downcast_ref::<RefuelCommand>() {
+            self.tag(13)?;
+            self.number(command.amount.into())
+        } else if let Some(command) = any.downcast_ref::<StampCommand>() {
+            match command.stamp {
+                Stamp::Dot => self.tag(14),
+                Stamp::Square(size) => {
+                    self.tag(15)?;
+                    self.number(size.into())
+                }
+                Stamp::Circle(radius) => {
+                    self.tag(16)?;
+                    self.number(radius.into())
+                }
+            }
+        } else if let Some(command) = any.downcast_ref::<WriteCommand>() {
+            self.tag(17)?;
+            self.string(&command.text)
+        } else if let Some(command) = any.downcast_ref::<RepeatCommand>() {
+            self.tag(18)?;
+            self.number(command.times.into())?;
+            self.command(command.inner.as_ref())
+        } else if let Some(command) = any.downcast_ref::<CompositeCommand>() {
+            self.tag(19)?;
+            self.list(command.commands.iter().map(|command| command.as_ref()))
+        } else if let Some(command) = any.downcast_ref::<WhileCommand>() {
+            self.tag(20)?;
+            self.condition(&command.condition)?;
+            self.list(command.body.commands.iter().map(|command| command.as_ref()))
+        } else if let Some(command) = any.downcast_ref::<TargetCommand>() {
+            self.tag(21)?;
+            self.string(&command.robot)?;
+            self.command(command.command.as_ref())
+        } else {
+            Err(Error::UnencodableCommand(format!("{command:?}")))
+        }
+    }
+
+    fn condition(&mut self, condition: &Condition) -> Result<(), Error> {
+        match condition {
+            Condition::Value(value) => {
+                self.tag(0)?;
+                self.tag(u8::from(*value))
+            }
+            Condition::Blocked => self.tag(1),
+            Condition::Not(operand) => {
+                self.tag(2)?;
+                self.condition(operand)
+            }
+            Condition::And(lhs, rhs) => {
+                self.tag(3)?;
+                self.condition(lhs)?;
+                self.condition(rhs)
+            }
+            Condition::Or(lhs, rhs) => {
+                self.tag(4)?;
+                self.condition(lhs)?;
+                self.condition(rhs)
+            }
+        }
+    }
+
+    fn tag(&mut self, tag: u8) -> Result<(), Error> {
+        self.writer.write_all(&[tag])?;
+        Ok(())
+    }
+
+    // Номер в Direction::ALL или Color::ALL, значение всегда есть в таблице
+    fn index(&mut self, index: Option<usize>) -> Result<(), Error> {
+        self.tag(index.unwrap_or(0) as u8)
+    }
+
+    fn number(&mut self, mut value: u64) -> Result<(), Error> {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.tag(byte);
+            }
+            self.tag(byte | 0x80)?;
+        }
+    }
+
+    fn signed(&mut self, value: i32) -> Result<(), Error> {
+        self.number(u64::from(((value << 1) ^ (value >> 31)) as u32))
+    }
+
+    fn string(&mut self, text: &str) -> Result<(), Error> {
+        self.number(text.len() as u64)?;
+        self.writer.write_all(text.as_bytes())?;
+        Ok(())
+    }
+}
+
+struct Decoder<R> {
+    reader: R,
+    depth: usize,
+}
+
+impl<R: Read> Decoder<R> {
+    fn list(&mut self) -> Result<Vec<Box<dyn Command>>, Error> {
+        let count = self.number()?;
+        // Число команд не используется для выделения памяти: оно еще не проверено
+        let mut commands = Vec::new();
+        for _ in 0..count {
+            commands.push(self.command()?);
+        }
+        Ok(commands)
+    }
+
+    fn command(&mut self) -> Result<Box<dyn Command>, Error> {
+        self.enter()?;
+        let tag = self.byte()?;
+        let command: Box<dyn Command> = match tag {
+            0 => Box::new(MoveCommand::new(self.u32()?)),
+            1 => Box::new(MoveBackwardCommand::new(self.u32()?)),
+            2 => Box::new(TurnLeftCommand::new(self.u32()?)),
+            3 => Box::new(TurnRightCommand::new(self.u32()?)),
+            4 => Box::new(TurnLeft45Command::new(self.u32()?)),
+            5 => Box::new(TurnRight45Command::new(self.u32()?)),
+            6 => Box::new(DownPenCommand),
+            7 => Box::new(UpPenCommand),
+            8 => Box::new(TeleportCommand::new(self.signed()?, self.signed()?)),
+            9 => Box::new(HomeCommand::new()),
+            10 => Box::new(SetDirectionCommand::new(self.entry(&Direction::ALL)?)),
+            11 => Box::new(PenColorCommand::new(self.entry(&Color::ALL)?)),
+            12 => Box::new(PenWidthCommand::new(self.u32()?)),
+            13 => Box::new(RefuelCommand::new(self.u32()?)),
+            14 => Box::new(StampCommand::dot()),
+            15 => Box::new(StampCommand::square(self.u32()?)),
+            16 => Box::new(StampCommand::circle(self.u32()?)),
+            17 => Box::new(WriteCommand::new(self.string()?)),
+            18 => {
+                let times = self.u32()?;
+                Box::new(RepeatCommand::new(self.command()?, times))
+            }
+            19 => Box::new(CompositeCommand::new(self.list()?)),
+            20 => {
+                let condition = self.condition()?;
+                let body = CommandList {
+                    commands: self.list()?,
+                };
+                Box::new(WhileCommand::new(condition, body))
+            }
+            21 => {
+                let robot = self.string()?;
+                Box::new(TargetCommand::new(robot, self.command()?))
+            }
+            _ => return Err(invalid(format!("unknown command {tag}"))),
+        };
+        self.depth -= 1;
+        Ok(command)
+    }
+
+    fn condition(&mut self) -> Result<Condition, Error> {
+        self.enter()?;
+        let tag = self.byte()?;
+        let condition = match tag {
+            0 => match self.byte()? {
+                0 => Condition::Value(false),
+                1 => Condition::Value(true),
+                value => return Err(invalid(format!("invalid boolean {value}"))),
+            },
+            1 => Condition::Blocked,
+            2 => Condition::Not(Box::new(self.condition()?)),
+            3 => Condition::And(Box::new(self.condition()?), Box::new(self.condition()?)),
+            4 => Condition::Or(Box::new(self.condition()?), Box::new(self.condition()?)),
+            _ => return Err(invalid(format!("unknown condition {tag}"))),
+        };
+        self.depth -= 1;
+        Ok(condition)
+    }
+
+    fn enter(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(format!("nesting deeper than {MAX_DEPTH}")));
+        }
+        Ok(())
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn entry<T: Copy>(&mut self, table: &[T]) -> Result<T, Error> {
+        let index = self.byte()?;
+        table
+            .get(usize::from(index))
+            .copied()
+            .ok_or_else(|| invalid(format!("invalid index {index}")))
+    }
+
+    fn number(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u64::from(byte & 0x7f);
+            if shift == 63 && bits > 1 {
+                break;
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("number is too large"))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let value = self.number()?;
+        u32::try_from(value).map_err(|_| invalid(format!("number {value} is too large")))
+    }
+
+    fn signed(&mut self) -> Result<i32, Error> {
+        let value = self.u32()?;
+        Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let len = self.number()?;
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(invalid("unexpected end of data"));
+        }
+        String::from_utf8(bytes).map_err(|_| invalid("string is not valid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(commands: &CommandList) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        commands.encode(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(MoveCommand::new(300)));
+        commands.add_command(Box::new(TeleportCommand::new(-1, 2)));
+        commands.add_command(Box::new(PenColorCommand::new(Color::Red)));
+
+        assert_eq!(
+            encode(&commands),
+            [b'R', b'C', b'M', b'D', 1, 3, 0, 0xac, 0x02, 8, 1, 4, 11, 2]
+        );
+        assert_eq!(encode(&CommandList::default()), *b"RCMD\x01\x00");
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let mut body = CommandList::default();
+        body.add_command(Box::new(MoveCommand::new(1)));
+
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(DownPenCommand));
+        commands.add_command(Box::new(MoveBackwardCommand::new(2)));
+        commands.add_command(Box::new(TeleportCommand::new(i32::MIN, i32::MAX)));
+        commands.add_command(Box::new(HomeCommand::new()));
+        commands.add_command(Box::new(SetDirectionCommand::new(Direction::UpLeft)));
+        commands.add_command(Box::new(PenWidthCommand::new(3)));
+        commands.add_command(Box::new(RefuelCommand::new(u32::MAX)));
+        commands.add_command(Box::new(StampCommand::dot()));
+        commands.add_command(Box::new(StampCommand::square(4)));
+        commands.add_command(Box::new(StampCommand::circle(2)));
+        commands.add_command(Box::new(WriteCommand::new("ПРИВЕТ")));
+        commands.add_command(Box::new(TurnLeft45Command::new(3)));
+        commands.add_command(Box::new(RepeatCommand::new(
+            Box::new(TurnRightCommand::new(1)),
+            2,
+        )));
+        commands.add_command(Box::new(CompositeCommand::new(vec![
+            Box::new(TurnLeftCommand::new(1)),
+            Box::new(UpPenCommand),
+            Box::new(TurnRight45Command::new(1)),
+        ])));
+        commands.add_command(Box::new(WhileCommand::new(
+            Condition::And(
+                Box::new(Condition::Value(true)),
+                Box::new(Condition::Or(
+                    Box::new(Condition::Not(Box::new(Condition::Blocked))),
+                    Box::new(Condition::Value(false)),
+                )),
+            ),
+            body,
+        )));
+        commands.add_command(Box::new(TargetCommand::new(
+            "b",
+            Box::new(MoveCommand::new(1)),
+        )));
+
+        let bytes = encode(&commands);
+        let restored: CommandList = CommandList::decode(bytes.as_slice()).unwrap();
+        assert_eq!(restored, commands);
+        let kinds: CommandList<CommandKind> = CommandList::decode(bytes.as_slice()).unwrap();
+        assert!(matches!(kinds.commands()[0], CommandKind::DownPen(_)));
+
+        let mut encoded = Vec::new();
+        kinds.encode(&mut encoded).unwrap();
+        assert_eq!(encoded, bytes);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let decode = |bytes: &[u8]| {
+            CommandList::<Box<dyn Command>>::decode(bytes)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert!(decode(b"JSON\x01\x00").contains("missing header"));
+        assert!(decode(b"RCMD\x02\x00").contains("unsupported version 2"));
+        assert!(decode(b"RCMD\x01\x01\x63").contains("unknown command 99"));
+        assert!(decode(b"RCMD\x01\x01\x0b\x08").contains("invalid index 8"));
+        assert!(decode(b"RCMD\x01\x01\x00\x80\x80\x80\x80\x10").contains("too large"));
+        assert!(decode(b"RCMD\x01\x01\x11\x05AB").contains("unexpected end"));
+        // Данные оборвались посреди списка
+        assert!(decode(b"RCMD\x01\x02\x06").starts_with("Read error"));
+
+        let mut nested = b"RCMD\x01\x01".to_vec();
+        nested.extend([18, 1].repeat(MAX_DEPTH + 1));
+        assert!(decode(&nested).contains("nesting deeper"));
+    }
+
+    #[test]
+    fn test_custom_command_is_not_encodable() {
+        #[derive(Debug, Clone)]
+        struct Custom;
+
+        impl Command for Custom {
+            fn execute(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn rollback(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn box_clone(&self) -> Box<dyn Command> {
+                Box::new(self.clone())
+            }
+
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+        }
+
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(Custom));
+        assert!(matches!(
+            commands.encode(Vec::new()),
+            Err(Error::UnencodableCommand(_))
+        ));
+    }
+}
@@ -0,0 +1,326 @@
+// Оценка программы без выполнения: сколько шагов, поворотов и переключений пера
+// она сделает и куда сместится робот.
+// Повторы не разворачиваются: смещение за одно повторение зависит только от направления
+// в его начале, поэтому после первого совпадения направлений оставшиеся круги складываются сразу.
+
+use std::ops::{Add, AddAssign};
+
+use super::*;
+
+// Количество действий, которые выполнит команда
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CommandCost {
+    pub steps: u64,
+    pub turns: u64,
+    pub pen_toggles: u64,
+}
+
+impl CommandCost {
+    pub fn steps(steps: u32) -> Self {
+        Self {
+            steps: steps.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn turns(turns: u8) -> Self {
+        Self {
+            turns: turns.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn pen_toggle() -> Self {
+        Self {
+            pen_toggles: 1,
+            ..Self::default()
+        }
+    }
+
+    // Стоимость команды, выполненной несколько раз подряд
+    pub fn times(self, times: u32) -> Self {
+        let times = u64::from(times);
+        Self {
+            steps: self.steps.saturating_mul(times),
+            turns: self.turns.saturating_mul(times),
+            pen_toggles: self.pen_toggles.saturating_mul(times),
+        }
+    }
+}
+
+impl Add for CommandCost {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            steps: self.steps.saturating_add(other.steps),
+            turns: self.turns.saturating_add(other.turns),
+            pen_toggles: self.pen_toggles.saturating_add(other.pen_toggles),
+        }
+    }
+}
+
+impl AddAssign for CommandCost {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl std::iter::Sum for CommandCost {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+
+// Результат оценки списка команд
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    pub cost: CommandCost,
+    // Смещение робота относительно начального положения. Неизвестно, если программа
+    // переносит робота в точку или его перемещение зависит от условий во время выполнения.
+    pub displacement: Option<(i64, i64)>,
+    pub direction: Option<Direction>,
+    // Ложно, если в списке есть циклы "пока" или команды вне этого модуля:
+    // тело цикла учтено в стоимости один раз, а стоимость неизвестных команд - как ее оценили они сами
+    pub exact: bool,
+}
+
+impl<C: Command + 'static> CommandList<C> {
+    // Оценивает выполнение списка роботом, который смотрит в указанном направлении
+    pub fn estimate(&self, direction: Direction) -> Estimate {
+        let mut state = State {
+            position: Some((0, 0)),
+            direction: Some(direction),
+            exact: true,
+        };
+        for command in &self.commands {
+            state.apply(command);
+        }
+
+        Estimate {
+            cost: self.commands.iter().map(|command| command.cost()).sum(),
+            displacement: state.position,
+            direction: state.direction,
+            exact: state.exact,
+        }
+    }
+}
+
+// Известная часть состояния робота во время оценки
+#[derive(Debug, Clone, Copy)]
+struct State {
+    position: Option<(i64, i64)>,
+    direction: Option<Direction>,
+    exact: bool,
+}
+
+impl State {
+    fn apply(&mut self, command: &dyn Command) {
+        let any = command.as_any();
+
+        if let Some(command) = any.downcast_ref::<MoveCommand>() {
+            self.advance(command.distance, false);
+        } else if let Some(command) = any.downcast_ref::<MoveBackwardCommand>() {
+            self.advance(command.distance, true);
+        } else if let Some(command) = any.downcast_ref::<TurnLeftCommand>() {
+            for _ in 0..command.times {
+                self.direction = self.direction.map(Direction::left);
+            }
+        } else if let Some(command) = any.downcast_ref::<TurnRightCommand>() {
+            for _ in 0..command.times {
+                self.direction = self.direction.map(Direction::right);
+            }
+        } else if let Some(command) = any.downcast_ref::<TurnLeft45Command>() {
+            for _ in 0..command.times {
+                self.direction = self.direction.map(Direction::left_45);
+            }
+        } else if let Some(command) = any.downcast_ref::<TurnRight45Command>() {
+            for _ in 0..command.times {
+                self.direction = self.direction.map(Direction::right_45);
+            }
+        } else if let Some(command) = any.downcast_ref::<SetDirectionCommand>() {
+            self.direction = Some(command.direction);
+        } else if any.is::<TeleportCommand>() {
+            self.position = None;
+        } else if any.is::<HomeCommand>() {
+            // Домашнее положение известно только роботу
+            self.position = None;
+            self.direction = None;
+        } else if let Some(command) = any.downcast_ref::<RepeatCommand>() {
+            self.repeat(command.inner.as_ref(), command.times);
+        } else if let Some(command) = any.downcast_ref::<CompositeCommand>() {
+            for command in &command.commands {
+                self.apply(command.as_ref());
+            }
+        } else if let Some(command) = any.downcast_ref::<TargetCommand>() {
+            // Отдельный робот выполняет команду для любого имени сам
+            self.apply(command.command());
+        } else if any.is::<DownPenCommand>()
+            || any.is::<UpPenCommand>()
+            || any.is::<PenColorCommand>()
+            || any.is::<PenWidthCommand>()
+            || any.is::<RefuelCommand>()
+            || any.is::<StampCommand>()
+            || any.is::<WriteCommand>()
+        {
+            // Перо, топливо, штампы и надписи не влияют на положение робота
+        } else {
+            // Цикл "пока" и неизвестные команды могут куда угодно переместить и развернуть робота
+            self.position = None;
+            self.direction = None;
+            self.exact = false;
+        }
+    }
+
+    fn advance(&mut self, distance: u32, backward: bool) {
+        let Some(direction) = self.direction else {
+            self.position = None;
+            return;
+        };
+        let direction = if backward {
+            direction.opposite()
+        } else {
+            direction
+        };
+
+        let (dx, dy) = direction.delta();
+        let distance = i64::from(distance);
+        self.position = self.position.and_then(|(x, y)| {
+            Some((
+                x.checked_add(i64::from(dx) * distance)?,
+                y.checked_add(i64::from(dy) * distance)?,
+            ))
+        });
+    }
+
+    fn repeat(&mut self, command: &dyn Command, times: u32) {
+        // Состояние в начале каждого выполненного повторения
+        let mut starts: Vec<State> = Vec::new();
+
+        for done in 0..times {
+            let previous = starts
+                .iter()
+                .position(|start| start.direction == self.direction);
+
+            if let Some(previous) = previous {
+                let period = done - previous as u32;
+                let cycles = i64::from((times - done) / period);
+                self.position = skip_cycles(self.position, starts[previous].position, cycles);
+                for _ in 0..(times - done) % period {
+                    self.apply(command);
+                }
+                return;
+            }
+
+            starts.push(*self);
+            self.apply(command);
+        }
+    }
+}
+
+// Положение после еще нескольких кругов, каждый из которых сдвигает робота так же,
+// как круг от положения then до положения now
+fn skip_cycles(
+    now: Option<(i64, i64)>,
+    then: Option<(i64, i64)>,
+    cycles: i64,
+) -> Option<(i64, i64)> {
+    let ((x, y), (x0, y0)) = (now?, then?);
+    let shift = |now: i64, then: i64| now.checked_sub(then)?.checked_mul(cycles);
+    Some((x.checked_add(shift(x, x0)?)?, y.checked_add(shift(y, y0)?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(commands: Vec<Box<dyn Command>>) -> CommandList {
+        commands.into_iter().collect()
+    }
+
+    fn square() -> Box<dyn Command> {
+        Box::new(CompositeCommand::new(vec![
+            Box::new(MoveCommand::new(3)),
+            Box::new(TurnRightCommand::new(1)),
+        ]))
+    }
+
+    #[test]
+    fn test_estimate_matches_execution() {
+        let command_list = list(vec![
+            Box::new(DownPenCommand),
+            Box::new(RepeatCommand::new(square(), 7)),
+            Box::new(MoveBackwardCommand::new(2)),
+            Box::new(SetDirectionCommand::new(Direction::Right)),
+            Box::new(RepeatCommand::new(Box::new(MoveCommand::new(2)), 1000)),
+            Box::new(UpPenCommand),
+        ]);
+
+        let estimate = command_list.estimate(Direction::Up);
+        assert_eq!(
+            estimate.cost,
+            CommandCost {
+                steps: 3 * 7 + 2 + 2000,
+                turns: 7,
+                pen_toggles: 2,
+            }
+        );
+        assert!(estimate.exact);
+
+        let mut robot = Robot::default();
+        command_list.clone().execute_all(&mut robot).unwrap();
+        assert_eq!(
+            estimate.displacement,
+            Some((robot.x().into(), robot.y().into()))
+        );
+        assert_eq!(estimate.direction, Some(robot.direction()));
+    }
+
+    #[test]
+    fn test_estimate_large_repeat_of_square() {
+        let command_list = list(vec![Box::new(RepeatCommand::new(square(), u32::MAX))]);
+
+        // u32::MAX = 4k + 3, поэтому робот проходит три стороны квадрата
+        let estimate = command_list.estimate(Direction::Up);
+        assert_eq!(estimate.displacement, Some((3, 0)));
+        assert_eq!(estimate.direction, Some(Direction::Left));
+        assert_eq!(estimate.cost.turns, u64::from(u32::MAX));
+    }
+
+    #[test]
+    fn test_estimate_unknown_movement() {
+        let estimate = list(vec![
+            Box::new(TeleportCommand::new(5, 5)),
+            Box::new(TurnLeftCommand::new(1)),
+        ])
+        .estimate(Direction::Up);
+        assert_eq!(estimate.displacement, None);
+        assert_eq!(estimate.direction, Some(Direction::Left));
+        assert!(estimate.exact);
+
+        let mut body = CommandList::default();
+        body.add_command(Box::new(MoveCommand::new(1)));
+        let estimate = list(vec![
+            Box::new(WhileCommand::new(Condition::Blocked, body)),
+            Box::new(SetDirectionCommand::new(Direction::Down)),
+        ])
+        .estimate(Direction::Up);
+        assert_eq!(estimate.cost.steps, 1);
+        assert_eq!(estimate.displacement, None);
+        assert_eq!(estimate.direction, Some(Direction::Down));
+        assert!(!estimate.exact);
+    }
+
+    #[test]
+    fn test_estimate_target_command() {
+        let estimate = list(vec![
+            Box::new(TargetCommand::new("a", square())),
+            Box::new(TargetCommand::new("b", Box::new(MoveCommand::new(2)))),
+        ])
+        .estimate(Direction::Up);
+        assert_eq!(estimate.cost.steps, 5);
+        assert_eq!(estimate.displacement, Some((2, 3)));
+        assert_eq!(estimate.direction, Some(Direction::Right));
+        assert!(estimate.exact);
+    }
+}
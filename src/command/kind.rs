@@ -0,0 +1,199 @@
+// Встроенные команды одним перечислением.
+// Список CommandList<CommandKind> хранит команды прямо в векторе, без выделения памяти
+// под каждую команду, а в Box<dyn Command> остаются только команды вне этого модуля.
+// Перечисление само реализует Command, поэтому такой список выполняется так же,
+// как обычный, а as_any() возвращает вложенную команду.
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub enum CommandKind {
+    Move(MoveCommand),
+    MoveBackward(MoveBackwardCommand),
+    TurnLeft(TurnLeftCommand),
+    TurnRight(TurnRightCommand),
+    TurnLeft45(TurnLeft45Command),
+    TurnRight45(TurnRight45Command),
+    DownPen(DownPenCommand),
+    UpPen(UpPenCommand),
+    While(WhileCommand),
+    Teleport(TeleportCommand),
+    Home(HomeCommand),
+    SetDirection(SetDirectionCommand),
+    PenColor(PenColorCommand),
+    PenWidth(PenWidthCommand),
+    Refuel(RefuelCommand),
+    Stamp(StampCommand),
+    Write(WriteCommand),
+    Repeat(RepeatCommand),
+    Composite(CompositeCommand),
+    Target(TargetCommand),
+    // Команда, определенная вне этого модуля
+    Custom(Box<dyn Command>),
+}
+
+macro_rules! kinds {
+    ($($kind:ident($command:ty)),* $(,)?) => {
+        impl CommandKind {
+            pub fn as_command(&self) -> &(dyn Command + 'static) {
+                match self {
+                    $(CommandKind::$kind(command) => command,)*
+                    CommandKind::Custom(command) => command.as_ref(),
+                }
+            }
+
+            pub fn as_command_mut(&mut self) -> &mut (dyn Command + 'static) {
+                match self {
+                    $(CommandKind::$kind(command) => command,)*
+                    CommandKind::Custom(command) => command.as_mut(),
+                }
+            }
+        }
+
+        $(impl From<$command> for CommandKind {
+            fn from(command: $command) -> Self {
+                CommandKind::$kind(command)
+            }
+        })*
+
+        impl From<CommandKind> for Box<dyn Command> {
+            fn from(kind: CommandKind) -> Self {
+                match kind {
+                    $(CommandKind::$kind(command) => Box::new(command),)*
+                    CommandKind::Custom(command) => command,
+                }
+            }
+        }
+
+        // Встроенная команда переносится в свой вариант, остальные остаются в Box
+        impl From<Box<dyn Command>> for CommandKind {
+            fn from(command: Box<dyn Command>) -> Self {
+                let any = command.as_any();
+                $(if let Some(command) = any.downcast_ref::<$command>() {
+                    return CommandKind::$kind(command.clone());
+                })*
+                CommandKind::Custom(command)
+            }
+        }
+    };
+}
+
+kinds!(
+    Move(MoveCommand),
+    MoveBackward(MoveBackwardCommand),
+    TurnLeft(TurnLeftCommand),
+    TurnRight(TurnRightCommand),
+    TurnLeft45(TurnLeft45Command),
+    TurnRight45(TurnRight45Command),
+    DownPen(DownPenCommand),
+    UpPen(UpPenCommand),
+    While(WhileCommand),
+    Teleport(TeleportCommand),
+    Home(HomeCommand),
+    SetDirection(SetDirectionCommand),
+    PenColor(PenColorCommand),
+    PenWidth(PenWidthCommand),
+    Refuel(RefuelCommand),
+    Stamp(StampCommand),
+    Write(WriteCommand),
+    Repeat(RepeatCommand),
+    Composite(CompositeCommand),
+    Target(TargetCommand),
+);
+
+impl Command for CommandKind {
+    fn execute(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        self.as_command_mut().execute(robot)
+    }
+
+    fn rollback(&mut self, robot: &mut dyn RobotLike) -> Result<(), Error> {
+        self.as_command_mut().rollback(robot)
+    }
+
+    fn box_clone(&self) -> Box<dyn Command> {
+        self.as_command().box_clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self.as_command().as_any()
+    }
+
+    fn eq_dyn(&self, other: &dyn Command) -> bool {
+        self.as_command().eq_dyn(other)
+    }
+
+    fn cost(&self) -> CommandCost {
+        self.as_command().cost()
+    }
+}
+
+impl PartialEq for CommandKind {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_dyn(other)
+    }
+}
+
+impl fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_command(), f)
+    }
+}
+
+impl CommandList<CommandKind> {
+    pub fn new_kinds() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl From<CommandList> for CommandList<CommandKind> {
+    fn from(command_list: CommandList) -> Self {
+        command_list.into_iter().map(CommandKind::from).collect()
+    }
+}
+
+impl From<CommandList<CommandKind>> for CommandList {
+    fn from(command_list: CommandList<CommandKind>) -> Self {
+        command_list.into_iter().map(Box::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_list_executes_like_boxed_list() {
+        let mut kinds = CommandList::new_kinds();
+        kinds.add_command(DownPenCommand.into());
+        kinds.add_command(MoveCommand::new(2).into());
+        kinds.add_command(TurnRightCommand::new(1).into());
+        kinds.add_command(TeleportCommand::new(5, -1).into());
+        kinds.add_command(CommandKind::Custom(Box::new(UpPenCommand)));
+
+        let mut boxed: CommandList = kinds.clone().into();
+        assert!(boxed.commands()[4].as_any().is::<UpPenCommand>());
+        assert_eq!(CommandList::<CommandKind>::from(boxed.clone()), kinds);
+        assert!(matches!(
+            CommandList::<CommandKind>::from(boxed.clone()).commands()[4],
+            CommandKind::UpPen(_)
+        ));
+
+        let mut robot = Robot::default();
+        kinds.execute_all(&mut robot).unwrap();
+        let mut expected = Robot::default();
+        boxed.execute_all(&mut expected).unwrap();
+        assert_eq!(
+            (robot.x(), robot.y(), robot.direction(), robot.is_drawing()),
+            (5, -1, Direction::Right, false)
+        );
+        assert_eq!(
+            (expected.x(), expected.y(), expected.direction()),
+            (5, -1, Direction::Right)
+        );
+
+        kinds.rollback_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert_eq!(kinds.to_string(), boxed.to_string());
+        assert_eq!(kinds.estimate(Direction::Up).cost.steps, 2);
+    }
+}
@@ -0,0 +1,134 @@
+// Асинхронное выполнение списка с паузой между командами.
+// Пауза ожидается без блокировки потока исполнителя, поэтому графический интерфейс
+// или драйвер устройства может показывать движение робота по шагам.
+// Таймер не зависит от конкретного исполнителя: поток таймера только будит задачу,
+// поэтому future можно выполнять в tokio, async-std или простом block_on.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::*;
+
+impl<C: Command + 'static> CommandList<C> {
+    // Выполняет команды по очереди, ожидая паузу между ними.
+    // После последней команды пауза не ожидается.
+    pub async fn execute_all_async(
+        &mut self,
+        robot: &mut dyn RobotLike,
+        pace: Duration,
+    ) -> Result<(), Error> {
+        for (index, command) in self.commands.iter_mut().enumerate() {
+            if index > 0 && !pace.is_zero() {
+                Delay::new(pace).await;
+            }
+            command.execute(robot)?;
+        }
+        Ok(())
+    }
+}
+
+// Future, который завершается через заданное время
+#[derive(Debug)]
+pub struct Delay {
+    deadline: Instant,
+    // Задача, которую разбудит поток таймера. Поток запускается при первом ожидании.
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Delay {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            deadline: Instant::now() + duration,
+            waker: None,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        match &self.waker {
+            // Задачу могли перенести в другой поток исполнителя
+            Some(waker) => waker.lock().unwrap().clone_from(cx.waker()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let deadline = self.deadline;
+                let timer = Arc::clone(&waker);
+                thread::spawn(move || {
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    timer.lock().unwrap().wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Wake;
+
+    use super::*;
+
+    // Поток ждет, пока задачу не разбудят
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_execute_all_async_waits_between_commands() {
+        let mut command_list = CommandList::default();
+        command_list.add_command(Box::new(MoveCommand::new(1)));
+        command_list.add_command(Box::new(TurnRightCommand::new(1)));
+        command_list.add_command(Box::new(MoveCommand::new(2)));
+
+        let mut robot = Robot::default();
+        let start = Instant::now();
+        block_on(command_list.execute_all_async(&mut robot, Duration::from_millis(20))).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!((robot.x(), robot.y()), (2, 1));
+    }
+
+    #[test]
+    fn test_execute_all_async_stops_on_error() {
+        let mut command_list = CommandList::default();
+        command_list.add_command(Box::new(MoveCommand::new(1)));
+        command_list.add_command(Box::new(TeleportCommand::new(i32::MAX, 0)));
+        command_list.add_command(Box::new(SetDirectionCommand::new(Direction::Right)));
+        command_list.add_command(Box::new(MoveCommand::new(1)));
+        command_list.add_command(Box::new(TurnLeftCommand::new(1)));
+
+        let mut robot = Robot::default();
+        let result = block_on(command_list.execute_all_async(&mut robot, Duration::ZERO));
+        assert!(result.is_err());
+        assert_eq!(robot.direction(), Direction::Right);
+    }
+}
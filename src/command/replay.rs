@@ -0,0 +1,278 @@
+// Журнал выполнения для проверки и восстановления после сбоя.
+// ReplayLog выполняет команды и дописывает в конец журнала строку на каждую команду:
+// {"time":<миллисекунды от начала эпохи Unix>,"command":{...},"error":null или "текст"}.
+// Команда записывается так же, как при сериализации, а строка сбрасывается на диск сразу,
+// поэтому после сбоя в журнале остаются все выполненные команды.
+// Команда, которую нельзя записать, не выполняется.
+// replay выполняет команды журнала по порядку и так восстанавливает состояние робота.
+// Команда, завершившаяся ошибкой, тоже выполняется: до ошибки она могла сдвинуть робота.
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{BufRead, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor},
+    ser::SerializeStruct,
+};
+
+use super::{
+    serialize::{required, set},
+    *,
+};
+use crate::json;
+
+const ENTRY_FIELDS: &[&str] = &["time", "command", "error"];
+
+// Запись журнала: время выполнения, команда и текст ошибки, если она была
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    time: u64,
+    command: Box<dyn Command>,
+    error: Option<String>,
+}
+
+impl LogEntry {
+    // Миллисекунды от начала эпохи Unix
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn command(&self) -> &(dyn Command + 'static) {
+        self.command.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplayLog<W: Write = File> {
+    writer: W,
+}
+
+impl ReplayLog {
+    // Открывает журнал для дописывания, файл создается, если его нет
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write> ReplayLog<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn execute(
+        &mut self,
+        command: &mut (dyn Command + 'static),
+        robot: &mut dyn RobotLike,
+    ) -> Result<(), Error> {
+        let unencodable = |command: &dyn Command| Error::UnencodableCommand(format!("{command:?}"));
+        json::to_string(&*command).map_err(|_| unencodable(command))?;
+        let result = command.execute(robot);
+
+        let entry = LogEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis() as u64),
+            command: command.box_clone(),
+            error: result.as_ref().err().map(ToString::to_string),
+        };
+        let line = json::to_string(&entry).map_err(|_| unencodable(command))?;
+        writeln!(self.writer, "{line}")?;
+        self.writer.flush()?;
+        result
+    }
+
+    // Выполняет команды списка по порядку до первой ошибки
+    pub fn execute_all<C: Command + 'static>(
+        &mut self,
+        commands: &mut CommandList<C>,
+        robot: &mut dyn RobotLike,
+    ) -> Result<(), Error> {
+        for command in commands.commands.iter_mut() {
+            self.execute(command, robot)?;
+        }
+        Ok(())
+    }
+}
+
+// Читает все записи журнала, пустые строки пропускаются
+pub fn read_log(log: impl BufRead) -> Result<Vec<LogEntry>, Error> {
+    let mut entries = Vec::new();
+    for (index, line) in log.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry =
+            json::from_str(&line).map_err(|err| Error::InvalidLog(index + 1, err.to_string()))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// Выполняет команды журнала и возвращает их количество. Команда, которая раньше
+// выполнилась успешно, а теперь завершилась ошибкой, прерывает восстановление.
+pub fn replay(log: impl BufRead, robot: &mut dyn RobotLike) -> Result<usize, Error> {
+    let entries = read_log(log)?;
+    let count = entries.len();
+    for mut entry in entries {
+        let result = entry.command.execute(robot);
+        if entry.error.is_none() {
+            result?;
+        }
+    }
+    Ok(count)
+}
+
+impl Serialize for LogEntry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entry = serializer.serialize_struct("LogEntry", ENTRY_FIELDS.len())?;
+        entry.serialize_field("time", &self.time)?;
+        entry.serialize_field("command", &self.command)?;
+        entry.serialize_field("error", &self.error)?;
+        entry.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LogEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(EntryVisitor)
+    }
+}
+
+struct EntryVisitor;
+
+impl<'de> Visitor<'de> for EntryVisitor {
+    type Value = LogEntry;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a replay log entry")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<LogEntry, A::Error> {
+        let (mut time, mut command, mut error) = (None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "time" => set(&mut time, "time", &mut map)?,
+                "command" => set(&mut command, "command", &mut map)?,
+                "error" => set(&mut error, "error", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, ENTRY_FIELDS)),
+            }
+        }
+
+        Ok(LogEntry {
+            time: required(time, "time")?,
+            command: required(command, "command")?,
+            error: required(error, "error")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn test_replay_log() {
+        let world = Arc::new(World::from_ascii_map("#\n.\n.").unwrap());
+        let mut robot = Robot::default();
+        robot.set_world(world.clone());
+
+        let mut log = ReplayLog::new(Vec::new());
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(DownPenCommand));
+        commands.add_command(Box::new(MoveCommand::new(1)));
+        log.execute_all(&mut commands, &mut robot).unwrap();
+        // Робот упирается в препятствие после одного шага
+        assert!(log.execute(&mut MoveCommand::new(3), &mut robot).is_err());
+        assert_eq!((robot.x(), robot.y()), (0, 1));
+
+        let bytes = log.into_inner();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with(r#"{"time":"#));
+        assert!(lines[1].ends_with(r#","command":{"cmd":"move","distance":1},"error":null}"#));
+        assert!(lines[2].ends_with(r#""error":"Blocked at (0, 2)"}"#));
+
+        let entries = read_log(bytes.as_slice()).unwrap();
+        assert!(entries[0].time() > 0);
+        assert_eq!(entries[2].error(), Some("Blocked at (0, 2)"));
+        assert_eq!(entries[2].command().to_string(), "move 3");
+
+        let mut restored = Robot::default();
+        restored.set_world(world);
+        assert_eq!(replay(bytes.as_slice(), &mut restored).unwrap(), 3);
+        assert_eq!((restored.x(), restored.y()), (0, 1));
+        assert_eq!(restored.path(), robot.path());
+
+        // Без препятствия успешная раньше команда тоже выполнится, а ошибка не повторится
+        let mut free = Robot::default();
+        assert_eq!(replay(bytes.as_slice(), &mut free).unwrap(), 3);
+        assert_eq!((free.x(), free.y()), (0, 4));
+
+        // Команда, которую нельзя записать, не выполняется
+        #[derive(Debug, Clone)]
+        struct Custom;
+
+        impl Command for Custom {
+            fn execute(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+                panic!("custom command must not be executed");
+            }
+
+            fn rollback(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn box_clone(&self) -> Box<dyn Command> {
+                Box::new(self.clone())
+            }
+
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+        }
+
+        let mut log = ReplayLog::new(Vec::new());
+        assert!(matches!(
+            log.execute(&mut Custom, &mut free),
+            Err(Error::UnencodableCommand(_))
+        ));
+        assert!(log.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_replay_log_errors() {
+        let log = "\n{\"time\":1,\"command\":{\"cmd\":\"move\",\"distance\":2},\"error\":null}\nnot json\n";
+        assert!(matches!(
+            read_log(log.as_bytes()),
+            Err(Error::InvalidLog(3, _))
+        ));
+
+        // Успешная раньше команда не выполнилась при восстановлении
+        let log = r#"{"time":1,"command":{"cmd":"move","distance":3},"error":null}"#;
+        let mut robot = Robot::default();
+        robot.set_world(World::from_ascii_map("#\n.").unwrap());
+        assert!(matches!(
+            replay(log.as_bytes(), &mut robot),
+            Err(Error::Blocked(0, 1))
+        ));
+    }
+}
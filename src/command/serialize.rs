@@ -0,0 +1,526 @@
+// Сериализация команд для сохранения и передачи списков команд.
+// Каждая команда записывается отображением с именем команды в ключе "cmd"
+// и ее параметрами в остальных ключах, например {"cmd":"move","distance":10}.
+// Условия записываются так же, с именем операции в ключе "op".
+// Состояние для отмены не сохраняется: прочитанные команды еще не выполнялись.
+// Команды, определенные вне этого модуля, сериализовать нельзя.
+// Список команд записывается в JSON массивом таких отображений (to_json, from_json),
+// поэтому программы может создавать другой инструмент, минуя язык скриптов.
+
+use std::fmt;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor},
+    ser::{self, SerializeMap},
+};
+
+use super::*;
+use crate::json::{self, JsonError};
+
+const COMMANDS: &[&str] = &[
+    "move",
+    "back",
+    "turn_left",
+    "turn_right",
+    "turn_left_45",
+    "turn_right_45",
+    "down_pen",
+    "up_pen",
+    "goto",
+    "home",
+    "face",
+    "color",
+    "width",
+    "refuel",
+    "dot",
+    "square",
+    "circle",
+    "write",
+    "repeat",
+    "group",
+    "while",
+    "robot",
+];
+const COMMAND_FIELDS: &[&str] = &[
+    "cmd",
+    "distance",
+    "times",
+    "x",
+    "y",
+    "direction",
+    "color",
+    "width",
+    "amount",
+    "size",
+    "radius",
+    "text",
+    "command",
+    "commands",
+    "condition",
+    "body",
+    "robot",
+];
+const OPERATIONS: &[&str] = &["value", "blocked", "not", "and", "or"];
+const CONDITION_FIELDS: &[&str] = &["op", "value", "operand", "lhs", "rhs"];
+
+// Записывает отображение с тегом и параметрами
+macro_rules! tagged {
+    ($serializer:expr, $key:literal: $tag:literal $(, $field:literal: $value:expr)* $(,)?) => {{
+        let mut map = $serializer.serialize_map(Some([$key $(, $field)*].len()))?;
+        map.serialize_entry($key, $tag)?;
+        $(map.serialize_entry($field, $value)?;)*
+        map.end()
+    }};
+}
+
+impl Serialize for dyn Command {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let command = self.as_any();
+
+        if let Some(command) = command.downcast_ref::<MoveCommand>() {
+            tagged!(serializer, "cmd": "move", "distance": &command.distance)
+        } else if let Some(command) = command.downcast_ref::<MoveBackwardCommand>() {
+            tagged!(serializer, "cmd": "back", "distance": &command.distance)
+        } else if let Some(command) = command.downcast_ref::<TurnLeftCommand>() {
+            tagged!(serializer, "cmd": "turn_left", "times": &command.times)
+        } else if let Some(command) = command.downcast_ref::<TurnRightCommand>() {
+            tagged!(serializer, "cmd": "turn_right", "times": &command.times)
+        } else if let Some(command) = command.downcast_ref::<TurnLeft45Command>() {
+            tagged!(serializer, "cmd": "turn_left_45", "times": &command.times)
+        } else if let Some(command) = command.downcast_ref::<TurnRight45Command>() {
+            tagged!(serializer, "cmd": "turn_right_45", "times": &command.times)
+        } else if command.is::<DownPenCommand>() {
+            tagged!(serializer, "cmd": "down_pen")
+        } else if command.is::<UpPenCommand>() {
+            tagged!(serializer, "cmd": "up_pen")
+        } else if let Some(command) = command.downcast_ref::<TeleportCommand>() {
+            tagged!(serializer, "cmd": "goto", "x": &command.x, "y": &command.y)
+        } else if command.is::<HomeCommand>() {
+            tagged!(serializer, "cmd": "home")
+        } else if let Some(command) = command.downcast_ref::<SetDirectionCommand>() {
+            tagged!(serializer, "cmd": "face", "direction": &command.direction)
+        } else if let Some(command) = command.downcast_ref::<PenColorCommand>() {
+            tagged!(serializer, "cmd": "color", "color": &command.color)
+        } else if let Some(command) = command.downcast_ref::<PenWidthCommand>() {
+            tagged!(serializer, "cmd": "width", "width": &command.width)
+        } else if let Some(command) = command.downcast_ref::<RefuelCommand>() {
+            tagged!(serializer, "cmd": "refuel", "amount": &command.amount)
+        } else if let Some(command) = command.downcast_ref::<StampCommand>() {
+            match command.stamp {
+                Stamp::Dot => tagged!(serializer, "cmd": "dot"),
+                Stamp::Square(size) => tagged!(serializer, "cmd": "square", "size": &size),
+                Stamp::Circle(radius) => tagged!(serializer, "cmd": "circle", "radius": &radius),
+            }
+        } else if let Some(command) = command.downcast_ref::<WriteCommand>() {
+            tagged!(serializer, "cmd": "write", "text": &command.text)
+        } else if let Some(command) = command.downcast_ref::<RepeatCommand>() {
+            tagged!(serializer, "cmd": "repeat", "times": &command.times, "command": &command.inner)
+        } else if let Some(command) = command.downcast_ref::<CompositeCommand>() {
+            tagged!(serializer, "cmd": "group", "commands": &command.commands)
+        } else if let Some(command) = command.downcast_ref::<TargetCommand>() {
+            tagged!(serializer, "cmd": "robot", "robot": &command.robot, "command": &command.command)
+        } else if let Some(command) = command.downcast_ref::<WhileCommand>() {
+            tagged!(
+                serializer,
+                "cmd": "while",
+                "condition": &command.condition,
+                "body": &command.body,
+            )
+        } else {
+            Err(ser::Error::custom(format!(
+                "command {self:?} cannot be serialized"
+            )))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn Command> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(CommandVisitor)
+    }
+}
+
+// Параметры команды, ключи могут идти в любом порядке
+#[derive(Default)]
+struct CommandFields {
+    cmd: Option<String>,
+    distance: Option<u32>,
+    times: Option<u32>,
+    x: Option<i32>,
+    y: Option<i32>,
+    direction: Option<Direction>,
+    color: Option<Color>,
+    width: Option<u32>,
+    amount: Option<u32>,
+    size: Option<u32>,
+    radius: Option<u32>,
+    text: Option<String>,
+    command: Option<Box<dyn Command>>,
+    commands: Option<Vec<Box<dyn Command>>>,
+    condition: Option<Condition>,
+    body: Option<CommandList>,
+    robot: Option<String>,
+}
+
+struct CommandVisitor;
+
+impl<'de> Visitor<'de> for CommandVisitor {
+    type Value = Box<dyn Command>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a robot command")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = CommandFields::default();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "cmd" => set(&mut fields.cmd, "cmd", &mut map)?,
+                "distance" => set(&mut fields.distance, "distance", &mut map)?,
+                "times" => set(&mut fields.times, "times", &mut map)?,
+                "x" => set(&mut fields.x, "x", &mut map)?,
+                "y" => set(&mut fields.y, "y", &mut map)?,
+                "direction" => set(&mut fields.direction, "direction", &mut map)?,
+                "color" => set(&mut fields.color, "color", &mut map)?,
+                "width" => set(&mut fields.width, "width", &mut map)?,
+                "amount" => set(&mut fields.amount, "amount", &mut map)?,
+                "size" => set(&mut fields.size, "size", &mut map)?,
+                "radius" => set(&mut fields.radius, "radius", &mut map)?,
+                "text" => set(&mut fields.text, "text", &mut map)?,
+                "command" => set(&mut fields.command, "command", &mut map)?,
+                "commands" => set(&mut fields.commands, "commands", &mut map)?,
+                "condition" => set(&mut fields.condition, "condition", &mut map)?,
+                "body" => set(&mut fields.body, "body", &mut map)?,
+                "robot" => set(&mut fields.robot, "robot", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, COMMAND_FIELDS)),
+            }
+        }
+
+        let cmd = required(fields.cmd, "cmd")?;
+        let command: Box<dyn Command> = match cmd.as_str() {
+            "move" => Box::new(MoveCommand::new(required(fields.distance, "distance")?)),
+            "back" => Box::new(MoveBackwardCommand::new(required(
+                fields.distance,
+                "distance",
+            )?)),
+            "turn_left" => Box::new(TurnLeftCommand::new(required(fields.times, "times")?)),
+            "turn_right" => Box::new(TurnRightCommand::new(required(fields.times, "times")?)),
+            "turn_left_45" => Box::new(TurnLeft45Command::new(required(fields.times, "times")?)),
+            "turn_right_45" => Box::new(TurnRight45Command::new(required(fields.times, "times")?)),
+            "down_pen" => Box::new(DownPenCommand),
+            "up_pen" => Box::new(UpPenCommand),
+            "home" => Box::new(HomeCommand::new()),
+            "goto" => Box::new(TeleportCommand::new(
+                required(fields.x, "x")?,
+                required(fields.y, "y")?,
+            )),
+            "face" => Box::new(SetDirectionCommand::new(required(
+                fields.direction,
+                "direction",
+            )?)),
+            "color" => Box::new(PenColorCommand::new(required(fields.color, "color")?)),
+            "width" => Box::new(PenWidthCommand::new(required(fields.width, "width")?)),
+            "refuel" => Box::new(RefuelCommand::new(required(fields.amount, "amount")?)),
+            "dot" => Box::new(StampCommand::dot()),
+            "square" => Box::new(StampCommand::square(required(fields.size, "size")?)),
+            "circle" => Box::new(StampCommand::circle(required(fields.radius, "radius")?)),
+            "write" => Box::new(WriteCommand::new(required(fields.text, "text")?)),
+            "repeat" => Box::new(RepeatCommand::new(
+                required(fields.command, "command")?,
+                required(fields.times, "times")?,
+            )),
+            "group" => Box::new(CompositeCommand::new(required(
+                fields.commands,
+                "commands",
+            )?)),
+            "while" => Box::new(WhileCommand::new(
+                required(fields.condition, "condition")?,
+                required(fields.body, "body")?,
+            )),
+            "robot" => Box::new(TargetCommand::new(
+                required(fields.robot, "robot")?,
+                required(fields.command, "command")?,
+            )),
+            _ => return Err(de::Error::unknown_variant(&cmd, COMMANDS)),
+        };
+        Ok(command)
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Condition::Value(value) => tagged!(serializer, "op": "value", "value": value),
+            Condition::Blocked => tagged!(serializer, "op": "blocked"),
+            Condition::Not(operand) => tagged!(serializer, "op": "not", "operand": operand),
+            Condition::And(lhs, rhs) => tagged!(serializer, "op": "and", "lhs": lhs, "rhs": rhs),
+            Condition::Or(lhs, rhs) => tagged!(serializer, "op": "or", "lhs": lhs, "rhs": rhs),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ConditionVisitor)
+    }
+}
+
+#[derive(Default)]
+struct ConditionFields {
+    op: Option<String>,
+    value: Option<bool>,
+    operand: Option<Box<Condition>>,
+    lhs: Option<Box<Condition>>,
+    rhs: Option<Box<Condition>>,
+}
+
+struct ConditionVisitor;
+
+impl<'de> Visitor<'de> for ConditionVisitor {
+    type Value = Condition;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a condition")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = ConditionFields::default();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "op" => set(&mut fields.op, "op", &mut map)?,
+                "value" => set(&mut fields.value, "value", &mut map)?,
+                "operand" => set(&mut fields.operand, "operand", &mut map)?,
+                "lhs" => set(&mut fields.lhs, "lhs", &mut map)?,
+                "rhs" => set(&mut fields.rhs, "rhs", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, CONDITION_FIELDS)),
+            }
+        }
+
+        let op = required(fields.op, "op")?;
+        match op.as_str() {
+            "value" => Ok(Condition::Value(required(fields.value, "value")?)),
+            "blocked" => Ok(Condition::Blocked),
+            "not" => Ok(Condition::Not(required(fields.operand, "operand")?)),
+            "and" => Ok(Condition::And(
+                required(fields.lhs, "lhs")?,
+                required(fields.rhs, "rhs")?,
+            )),
+            "or" => Ok(Condition::Or(
+                required(fields.lhs, "lhs")?,
+                required(fields.rhs, "rhs")?,
+            )),
+            _ => Err(de::Error::unknown_variant(&op, OPERATIONS)),
+        }
+    }
+}
+
+// Список команд записывается последовательностью команд
+// Перечисление записывается так же, как вложенная в него команда
+impl Serialize for CommandKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_command().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CommandKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Box::<dyn Command>::deserialize(deserializer).map(CommandKind::from)
+    }
+}
+
+impl<C: Serialize> Serialize for CommandList<C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.commands.serialize(serializer)
+    }
+}
+
+impl<'de, C: Deserialize<'de>> Deserialize<'de> for CommandList<C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let commands = Vec::deserialize(deserializer)?;
+        Ok(Self { commands })
+    }
+}
+
+impl<C> CommandList<C> {
+    pub fn to_json(&self) -> Result<String, JsonError>
+    where
+        C: Serialize,
+    {
+        json::to_string(self)
+    }
+
+    pub fn from_json(input: &str) -> Result<Self, JsonError>
+    where
+        C: for<'de> Deserialize<'de>,
+    {
+        json::from_str(input)
+    }
+}
+
+// Читает значение ключа, ключ может встретиться только один раз
+pub(super) fn set<'de, T: Deserialize<'de>, A: MapAccess<'de>>(
+    slot: &mut Option<T>,
+    name: &'static str,
+    map: &mut A,
+) -> Result<(), A::Error> {
+    if slot.is_some() {
+        return Err(de::Error::duplicate_field(name));
+    }
+    *slot = Some(map.next_value()?);
+    Ok(())
+}
+
+pub(super) fn required<T, E: de::Error>(value: Option<T>, name: &'static str) -> Result<T, E> {
+    value.ok_or_else(|| E::missing_field(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_format() {
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(MoveCommand::new(10)));
+        commands.add_command(Box::new(PenColorCommand::new(Color::Red)));
+        commands.add_command(Box::new(WhileCommand::new(
+            Condition::Not(Box::new(Condition::Blocked)),
+            CommandList::default(),
+        )));
+
+        assert_eq!(
+            json::to_string(&commands).unwrap(),
+            concat!(
+                r#"[{"cmd":"move","distance":10},{"cmd":"color","color":"red"},"#,
+                r#"{"cmd":"while","condition":{"op":"not","operand":{"op":"blocked"}},"body":[]}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut body = CommandList::default();
+        body.add_command(Box::new(MoveCommand::new(1)));
+        body.add_command(Box::new(TurnRightCommand::new(1)));
+
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(DownPenCommand));
+        commands.add_command(Box::new(MoveBackwardCommand::new(2)));
+        commands.add_command(Box::new(TeleportCommand::new(-3, 4)));
+        commands.add_command(Box::new(HomeCommand::new()));
+        commands.add_command(Box::new(SetDirectionCommand::new(Direction::Left)));
+        commands.add_command(Box::new(PenWidthCommand::new(3)));
+        commands.add_command(Box::new(RefuelCommand::new(7)));
+        commands.add_command(Box::new(StampCommand::dot()));
+        commands.add_command(Box::new(StampCommand::square(4)));
+        commands.add_command(Box::new(StampCommand::circle(2)));
+        commands.add_command(Box::new(WriteCommand::new("HELLO")));
+        commands.add_command(Box::new(TurnRight45Command::new(3)));
+        commands.add_command(Box::new(RepeatCommand::new(
+            Box::new(TurnLeftCommand::new(3)),
+            2,
+        )));
+        commands.add_command(Box::new(CompositeCommand::new(vec![
+            Box::new(MoveCommand::new(5)),
+            Box::new(UpPenCommand),
+        ])));
+        commands.add_command(Box::new(WhileCommand::new(
+            Condition::And(
+                Box::new(Condition::Value(true)),
+                Box::new(Condition::Or(
+                    Box::new(Condition::Blocked),
+                    Box::new(Condition::Value(false)),
+                )),
+            ),
+            body,
+        )));
+        commands.add_command(Box::new(TargetCommand::new(
+            "b",
+            Box::new(MoveCommand::new(1)),
+        )));
+
+        let json = json::to_string(&commands).unwrap();
+        let restored: CommandList = json::from_str(&json).unwrap();
+        assert_eq!(json::to_string(&restored).unwrap(), json);
+        let kinds: CommandList<CommandKind> = json::from_str(&json).unwrap();
+        assert!(matches!(kinds.commands()[0], CommandKind::DownPen(_)));
+        assert_eq!(json::to_string(&kinds).unwrap(), json);
+
+        // Прочитанные команды выполняются так же, как исходные
+        let mut expected = Robot::default();
+        commands.execute_all(&mut expected).unwrap();
+        let mut robot = Robot::default();
+        restored.clone().execute_all(&mut robot).unwrap();
+        assert_eq!(
+            (robot.x(), robot.y(), robot.direction()),
+            (expected.x(), expected.y(), expected.direction())
+        );
+    }
+
+    #[test]
+    fn test_json_script() {
+        let json =
+            r#"[{"cmd":"down_pen"},{"cmd":"move","distance":3},{"cmd":"turn_left","times":1}]"#;
+        let mut commands: CommandList = CommandList::from_json(json).unwrap();
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands.to_json().unwrap(), json);
+
+        let mut robot = Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 3));
+
+        let kinds = CommandList::<CommandKind>::from_json(json).unwrap();
+        assert!(matches!(kinds.commands()[1], CommandKind::Move(_)));
+        assert!(CommandList::<Box<dyn Command>>::from_json("[{}]").is_err());
+        assert!(CommandList::<Box<dyn Command>>::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        let parse = |json| {
+            json::from_str::<CommandList>(json)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        // Порядок ключей не важен
+        assert!(json::from_str::<CommandList>(r#"[{"distance":1,"cmd":"move"}]"#).is_ok());
+        assert!(parse(r#"[{"cmd":"jump"}]"#).contains("unknown variant `jump`"));
+        assert!(parse(r#"[{"cmd":"move"}]"#).contains("missing field `distance`"));
+        assert!(parse(r#"[{"cmd":"move","distance":-1}]"#).contains("invalid value"));
+        assert!(parse(r#"[{"cmd":"up_pen","speed":1}]"#).contains("unknown field `speed`"));
+        assert!(parse(r#"[{"cmd":"up_pen","cmd":"down_pen"}]"#).contains("duplicate field"));
+    }
+
+    #[test]
+    fn test_custom_command_is_not_serializable() {
+        #[derive(Debug, Clone)]
+        struct Custom;
+
+        impl Command for Custom {
+            fn execute(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn rollback(&mut self, _robot: &mut dyn RobotLike) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn box_clone(&self) -> Box<dyn Command> {
+                Box::new(self.clone())
+            }
+
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+        }
+
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(Custom));
+        assert!(json::to_string(&commands).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+// Программа на Python turtle, которая рисует то же, что список команд, для показа рисунков
+// там, где есть только Python.
+// Программа повторяет робота по умолчанию: начало в (0, 0), взгляд вверх, перо поднято,
+// дом в начале координат. Клетка робота занимает SCALE точек экрана, а диагональный
+// шаг длиннее прямого, поэтому шаги делает функция move программы.
+// Перенос в точку не рисует и делается функцией jump. Циклы записываются циклами for,
+// смена цвета и толщины пера - вызовами pencolor и pensize, текст - вызовом write.
+// Мира и границ у черепахи нет, поэтому нельзя записать условия с датчиком "blocked",
+// адресованные другим роботам команды, фигуры кроме точки и команды вне этого модуля.
+
+use std::fmt::Write as _;
+
+use super::*;
+
+const PRELUDE: &str = "\
+import math
+import turtle
+
+# Screen pixels per robot cell
+SCALE = 10
+
+t = turtle.Turtle()
+t.penup()
+t.setheading(90)
+
+
+def move(steps):
+    # Diagonal steps of the robot are longer
+    t.forward(steps * SCALE * (math.sqrt(2) if t.heading() % 90 else 1))
+
+
+def jump(x, y):
+    down = t.isdown()
+    t.penup()
+    t.goto(x * SCALE, y * SCALE)
+    if down:
+        t.pendown()
+
+";
+
+impl<C: Command + 'static> CommandList<C> {
+    pub fn to_turtle_script(&self) -> Result<String, Error> {
+        let mut script = Script {
+            text: PRELUDE.to_string(),
+            indent: 0,
+        };
+        script.block(self.commands.iter().map(|command| command as &dyn Command))?;
+        script.text.push_str("\nturtle.done()\n");
+        Ok(script.text)
+    }
+}
+
+struct Script {
+    text: String,
+    indent: usize,
+}
+
+impl Script {
+    // Тело цикла не может быть пустым, поэтому пустой блок записывается как pass
+    fn block<'a>(&mut self, commands: impl Iterator<Item = &'a dyn Command>) -> Result<(), Error> {
+        let start = self.text.len();
+        for command in commands {
+            self.command(command)?;
+        }
+        if self.text.len() == start {
+            self.line("pass");
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, command: &dyn Command) -> Result<(), Error> {
+        let any = command.as_any();
+
+        if let Some(command) = any.downcast_ref::<MoveCommand>() {
+            self.line(format!("move({})", command.distance));
+        } else if let Some(command) = any.downcast_ref::<MoveBackwardCommand>() {
+            self.line(format!("move(-{})", command.distance));
+        } else if let Some(command) = any.downcast_ref::<TurnLeftCommand>() {
+            self.line(format!("t.left({})", u64::from(command.times) * 90));
+        } else if let Some(command) = any.downcast_ref::<TurnRightCommand>() {
+            self.line(format!("t.right({})", u64::from(command.times) * 90));
+        } else if let Some(command) = any.downcast_ref::<TurnLeft45Command>() {
+            self.line(format!("t.left({})", u64::from(command.times) * 45));
+        } else if let Some(command) = any.downcast_ref::<TurnRight45Command>() {
+            self.line(format!("t.right({})", u64::from(command.times) * 45));
+        } else if any.is::<DownPenCommand>() {
+            self.line("t.pendown()");
+        } else if any.is::<UpPenCommand>() {
+            self.line("t.penup()");
+        } else if let Some(command) = any.downcast_ref::<TeleportCommand>() {
+            self.line(format!("jump({}, {})", command.x, command.y));
+        } else if any.is::<HomeCommand>() {
+            self.line("t.penup()");
+            self.line("t.goto(0, 0)");
+            self.line("t.setheading(90)");
+        } else if let Some(command) = any.downcast_ref::<SetDirectionCommand>() {
+            self.line(format!("t.setheading({})", heading(command.direction)));
+        } else if let Some(command) = any.downcast_ref::<PenColorCommand>() {
+            self.line(format!("t.pencolor(\"{}\")", command.color));
+        } else if let Some(command) = any.downcast_ref::<PenWidthCommand>() {
+            self.line(format!("t.pensize({})", command.width));
+        } else if let Some(command) = any.downcast_ref::<RefuelCommand>() {
+            // Топлива у черепахи нет, команда остается в программе комментарием
+            self.line(format!("pass  # refuel {}", command.amount));
+        } else if let Some(StampCommand {
+            stamp: Stamp::Dot, ..
+        }) = any.downcast_ref::<StampCommand>()
+        {
+            self.line("t.dot(SCALE)");
+        } else if let Some(command) = any.downcast_ref::<WriteCommand>() {
+            self.line(format!("t.write({})", quote(&command.text)));
+        } else if let Some(command) = any.downcast_ref::<RepeatCommand>() {
+            self.line(format!("for _ in range({}):", command.times));
+            self.nested(|script| script.block([command.inner.as_ref()].into_iter()))?;
+        } else if let Some(command) = any.downcast_ref::<CompositeCommand>() {
+            self.block(command.commands.iter().map(|command| command.as_ref()))?;
+        } else if let Some(command) = any.downcast_ref::<WhileCommand>() {
+            let condition = condition(&command.condition)
+                .ok_or_else(|| Error::UnencodableCommand(format!("{command:?}")))?;
+            self.line(format!("while {condition}:"));
+            let body = command.body.commands.iter().map(|command| command.as_ref());
+            self.nested(|script| script.block(body))?;
+        } else {
+            return Err(Error::UnencodableCommand(format!("{command:?}")));
+        }
+        Ok(())
+    }
+
+    fn nested(&mut self, body: impl FnOnce(&mut Self) -> Result<(), Error>) -> Result<(), Error> {
+        self.indent += 1;
+        let result = body(self);
+        self.indent -= 1;
+        result
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        let _ = writeln!(self.text, "{:1$}{2}", "", self.indent * 4, line.as_ref());
+    }
+}
+
+// Курс черепахи: 0 - направо, 90 - вверх, углы растут против часовой стрелки
+fn heading(direction: Direction) -> u32 {
+    match direction {
+        Direction::Right => 0,
+        Direction::UpRight => 45,
+        Direction::Up => 90,
+        Direction::UpLeft => 135,
+        Direction::Left => 180,
+        Direction::DownLeft => 225,
+        Direction::Down => 270,
+        Direction::DownRight => 315,
+    }
+}
+
+// Условие без датчиков, известное до выполнения
+fn condition(condition: &Condition) -> Option<String> {
+    let text = match condition {
+        Condition::Value(true) => "True".to_string(),
+        Condition::Value(false) => "False".to_string(),
+        Condition::Blocked => return None,
+        Condition::Not(operand) => format!("not ({})", self::condition(operand)?),
+        Condition::And(lhs, rhs) => format!(
+            "({}) and ({})",
+            self::condition(lhs)?,
+            self::condition(rhs)?
+        ),
+        Condition::Or(lhs, rhs) => {
+            format!("({}) or ({})", self::condition(lhs)?, self::condition(rhs)?)
+        }
+    };
+    Some(text)
+}
+
+// Строка Python в кавычках
+fn quote(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\x{:02x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    // Программа без общего начала и конца
+    fn body(commands: &CommandList) -> String {
+        let script = commands.to_turtle_script().unwrap();
+        assert!(script.starts_with(PRELUDE));
+        assert!(script.ends_with("\nturtle.done()\n"));
+        script[PRELUDE.len()..script.len() - "\nturtle.done()\n".len()].to_string()
+    }
+
+    #[test]
+    fn test_to_turtle_script() {
+        let mut commands = Interpreter::new(
+            "down_pen color red move 2 turn_right 1 back 1 turn_left_45 3 \
+             goto -5 4 up_pen face down write \"hi\"",
+        )
+        .interpret()
+        .unwrap();
+        commands.add_command(Box::new(RepeatCommand::new(
+            Box::new(CompositeCommand::new(vec![
+                Box::new(MoveCommand::new(1)),
+                Box::new(RefuelCommand::new(3)),
+            ])),
+            4,
+        )));
+        commands.add_command(Box::new(HomeCommand::new()));
+
+        assert_eq!(
+            body(&commands),
+            "t.pendown()\n\
+             t.pencolor(\"red\")\n\
+             move(2)\n\
+             t.right(90)\n\
+             move(-1)\n\
+             t.left(135)\n\
+             jump(-5, 4)\n\
+             t.penup()\n\
+             t.setheading(270)\n\
+             t.write(\"hi\")\n\
+             for _ in range(4):\n    move(1)\n    pass  # refuel 3\n\
+             t.penup()\n\
+             t.goto(0, 0)\n\
+             t.setheading(90)\n"
+        );
+    }
+
+    #[test]
+    fn test_to_turtle_script_blocks() {
+        let body_commands = CommandList::default();
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(WhileCommand::new(
+            Condition::Not(Box::new(Condition::Value(true))),
+            body_commands.clone(),
+        )));
+        assert_eq!(body(&commands), "while not (True):\n    pass\n");
+        assert_eq!(body(&CommandList::default()), "pass\n");
+
+        commands.add_command(Box::new(WhileCommand::new(
+            Condition::Blocked,
+            body_commands,
+        )));
+        assert!(matches!(
+            commands.to_turtle_script(),
+            Err(Error::UnencodableCommand(_))
+        ));
+        assert_eq!(quote("a\"b\\\n\t"), r#""a\"b\\\n\x09""#);
+    }
+}
@@ -1,22 +1,255 @@
-use crate::interpreter::Token;
+use std::{fmt::Write, io};
+
+use crate::interpreter::{Span, Token};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Out of bounds")]
     OutOfBounds,
 
-    #[error("Unexpected character: {0}")]
-    UnexpectedCharacter(char),
+    #[error("Blocked at ({0}, {1})")]
+    Blocked(i32, i32),
+
+    #[error("Out of fuel")]
+    OutOfFuel,
+
+    #[error("Unknown robot {0}")]
+    UnknownRobot(String),
+
+    #[error("World has no robots")]
+    NoRobots,
+
+    #[error("Invalid map character {0:?} at line {1}, column {2}")]
+    InvalidMapCharacter(char, usize, usize),
+
+    #[error("Unexpected character: {0} at {1}")]
+    UnexpectedCharacter(char, Span),
+
+    #[error("Unexpected token: {0:?} at {1}")]
+    UnexpectedToken(Token, Span),
+
+    #[error("Invalid command at {0}")]
+    InvalidCommand(Span),
+
+    #[error("Undefined command {0} at {1}")]
+    UndefinedCommand(String, Span),
+
+    #[error("Invalid command parameter {0} at {1}")]
+    InvalidCommandParameter(String, Span),
+
+    #[error("Unknown parameter {0} at {1}")]
+    UnknownParameter(String, Span),
+
+    #[error("Unclosed block at {0}")]
+    UnclosedBlock(Span),
+
+    #[error("Maximum nesting depth {0} exceeded at {1}")]
+    MaxDepthExceeded(usize, Span),
+
+    #[error("Program expands to more than {0} commands at {1}")]
+    TooManyCommands(usize, Span),
+
+    #[error("Loop exceeded {0} iterations")]
+    IterationLimit(usize),
+
+    #[error("Execution exceeded {0} steps")]
+    StepLimit(usize),
+
+    #[error("Undefined variable {0} at {1}")]
+    UndefinedVariable(String, Span),
+
+    #[error("Division by zero at {0}")]
+    DivisionByZero(Span),
+
+    #[error("Arithmetic overflow at {0}")]
+    ArithmeticOverflow(Span),
+
+    #[error("Unterminated comment at {0}")]
+    UnterminatedComment(Span),
+
+    #[error("Unterminated string at {0}")]
+    UnterminatedString(Span),
+
+    #[error("Cannot include {0}: {1} at {2}")]
+    IncludeFailed(String, io::Error, Span),
+
+    #[error("Include cycle through {0} at {1}")]
+    IncludeCycle(String, Span),
+
+    #[error("Variable {0} cannot change in a while loop checked at run time at {1}")]
+    AssignmentInWhile(String, Span),
+
+    #[error("Sensors can only be used in while conditions with not, and, or at {0}")]
+    InvalidSensorUse(Span),
+
+    #[error("Stamp {0} is too large")]
+    StampTooLarge(String),
+
+    #[error("Image {0}x{1} is too large")]
+    ImageTooLarge(usize, usize),
+
+    #[error("Command {0} cannot be encoded")]
+    UnencodableCommand(String),
+
+    #[error("Invalid encoded commands: {0}")]
+    InvalidEncoding(String),
+
+    #[error("Invalid session: {0}")]
+    InvalidSession(String),
+
+    #[error("Invalid replay log at line {0}: {1}")]
+    InvalidLog(usize, String),
+
+    #[error("Invalid script call: {0}")]
+    InvalidScriptCall(String),
+
+    #[error("Read error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    // Положение ошибки в исходном тексте, если ошибка возникла при разборе скрипта
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::OutOfBounds
+            | Error::Blocked(..)
+            | Error::OutOfFuel
+            | Error::InvalidMapCharacter(..)
+            | Error::UnknownRobot(_)
+            | Error::NoRobots
+            | Error::ImageTooLarge(..)
+            | Error::StampTooLarge(_)
+            | Error::UnencodableCommand(_)
+            | Error::InvalidEncoding(_)
+            | Error::InvalidSession(_)
+            | Error::InvalidLog(..)
+            | Error::InvalidScriptCall(_)
+            | Error::IterationLimit(_)
+            | Error::StepLimit(_)
+            | Error::Io(_) => None,
+            Error::UnexpectedCharacter(_, span)
+            | Error::UnexpectedToken(_, span)
+            | Error::InvalidCommand(span)
+            | Error::UndefinedCommand(_, span)
+            | Error::InvalidCommandParameter(_, span)
+            | Error::UnknownParameter(_, span)
+            | Error::UnclosedBlock(span)
+            | Error::MaxDepthExceeded(_, span)
+            | Error::TooManyCommands(_, span)
+            | Error::UndefinedVariable(_, span)
+            | Error::DivisionByZero(span)
+            | Error::ArithmeticOverflow(span)
+            | Error::UnterminatedComment(span)
+            | Error::UnterminatedString(span)
+            | Error::IncludeFailed(_, _, span)
+            | Error::IncludeCycle(_, span)
+            | Error::AssignmentInWhile(_, span)
+            | Error::InvalidSensorUse(span) => Some(*span),
+        }
+    }
+}
+
+// Оформление сообщений об ошибках: с feature "color" используются цвета ANSI
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    error: &'static str,
+    gutter: &'static str,
+    caret: &'static str,
+    reset: &'static str,
+}
+
+const PLAIN: Style = Style {
+    error: "",
+    gutter: "",
+    caret: "",
+    reset: "",
+};
+
+const COLORED: Style = Style {
+    error: "\x1b[1;31m",
+    gutter: "\x1b[1;34m",
+    caret: "\x1b[1;31m",
+    reset: "\x1b[0m",
+};
+
+impl Error {
+    // Сообщение об ошибке со строкой исходного текста, в которой она произошла,
+    // и знаками "^" под ошибочным фрагментом
+    pub fn annotate(&self, source: &str) -> String {
+        let style = if cfg!(feature = "color") {
+            COLORED
+        } else {
+            PLAIN
+        };
+        self.render(source, style)
+    }
+
+    fn render(&self, source: &str, style: Style) -> String {
+        let mut output = format!("{}error{}: {self}\n", style.error, style.reset);
+
+        // Ошибка выполнения или ошибка в подключенном файле, строки которого нет в source
+        let Some(span) = self.span() else {
+            return output;
+        };
+        let Some(line) = source.lines().nth(span.line - 1) else {
+            return output;
+        };
+
+        let width = source
+            .get(span.start..span.end)
+            .map_or(1, |text| text.chars().take_while(|&ch| ch != '\n').count())
+            .max(1);
+        let number = span.line.to_string();
+        let padding = " ".repeat(number.len());
+        let indent: String = line
+            .chars()
+            .take(span.column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let _ = writeln!(output, "{}{padding} |{}", style.gutter, style.reset);
+        let _ = writeln!(output, "{}{number} |{} {line}", style.gutter, style.reset);
+        let _ = writeln!(
+            output,
+            "{}{padding} |{} {indent}{}{}{}",
+            style.gutter,
+            style.reset,
+            style.caret,
+            "^".repeat(width),
+            style.reset
+        );
+        output
+    }
+}
 
-    #[error("Unexpected token: {0:?}")]
-    UnexpectedToken(Token),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
 
-    #[error("Invalid command")]
-    InvalidCommand,
+    fn plain(source: &str) -> String {
+        Interpreter::new(source)
+            .interpret()
+            .unwrap_err()
+            .render(source, PLAIN)
+    }
 
-    #[error("Undefined command {0}")]
-    UndefinedCommand(String),
+    #[test]
+    fn test_annotate_points_at_token() {
+        let expected = "\
+error: Undefined command fly at line 2, column 3
+  |
+2 |   fly 3
+  |   ^^^
+";
+        assert_eq!(plain("move 1\n  fly 3\nmove 2"), expected);
+    }
 
-    #[error("Invalid command parameter {0}")]
-    InvalidCommandParameter(String),
+    #[test]
+    fn test_annotate_without_span() {
+        assert_eq!(
+            Error::OutOfBounds.render("move 1", PLAIN),
+            "error: Out of bounds\n"
+        );
+    }
 }
@@ -0,0 +1,406 @@
+// Функции для C, чтобы встраивать робота и интерпретатор в программы на других языках.
+// Объявления - в заголовке include/robot.h, тест ниже проверяет, что заголовок
+// объявляет все функции этого модуля.
+// Робот передается непрозрачным указателем: robot_new создает его, robot_free удаляет.
+// Направление и цвет передаются номерами в Direction::ALL и Color::ALL.
+// Функции с ошибкой возвращают -1, а текст ошибки доступен через robot_last_error
+// до следующего вызова в том же потоке. Строки, которые возвращает модуль,
+// освобождаются функцией robot_string_free.
+// Скрипт можно разобрать отдельно (robot_interpret) в список команд, который выполняется
+// любым роботом и освобождается функцией command_list_free.
+// Рисунок робота строится заново при каждом вызове robot_canvas_color_at, поэтому
+// для чтения многих клеток его строят один раз (robot_canvas_new) и читают canvas_color_at,
+// а затем освобождают функцией canvas_free.
+// На этих функциях построен модуль Python python/robot.py. Он заменяет привязки PyO3,
+// которого нет среди зависимостей крейта, и проверяется своими доктестами.
+// Крейт по умолчанию собирается только библиотекой Rust, динамическую библиотеку
+// для C собирает команда cargo rustc --release --lib --crate-type cdylib --features ffi.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char, c_int},
+    ptr,
+};
+
+use super::{
+    canvas::Canvas,
+    command::CommandList,
+    interpreter::Interpreter,
+    robot::{Color, Direction, Robot},
+};
+
+// Состояние робота для C
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RobotStatus {
+    pub x: i32,
+    pub y: i32,
+    // Номер в Direction::ALL
+    pub direction: c_int,
+    // Номер в Color::ALL
+    pub color: c_int,
+    pub pen_down: bool,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// Запоминает ошибку и возвращает код ошибки
+fn fail(message: impl Into<String>) -> c_int {
+    // Нулевой байт в тексте ошибки заменяется, чтобы строка для C не обрывалась
+    let message = message.into().replace('\0', " ");
+    LAST_ERROR.with(|error| *error.borrow_mut() = CString::new(message).ok());
+    -1
+}
+
+fn index<T: PartialEq>(table: &[T], value: T) -> c_int {
+    table.iter().position(|known| *known == value).unwrap_or(0) as c_int
+}
+
+// Строка для C, строки модуля не содержат нулевых байтов
+fn c_string(text: String) -> *mut c_char {
+    CString::new(text.replace('\0', " ")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+// Создает робота в точке (x, y), возвращает NULL при неверном направлении
+#[unsafe(no_mangle)]
+pub extern "C" fn robot_new(x: i32, y: i32, direction: c_int) -> *mut Robot {
+    let Some(&direction) = usize::try_from(direction)
+        .ok()
+        .and_then(|index| Direction::ALL.get(index))
+    else {
+        fail(format!("Invalid direction {direction}"));
+        return ptr::null_mut();
+    };
+    Box::into_raw(Box::new(Robot::new(x, y, direction, false)))
+}
+
+/// # Safety
+/// robot должен быть получен из robot_new и еще не освобожден, или быть NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_free(robot: *mut Robot) {
+    if !robot.is_null() {
+        drop(unsafe { Box::from_raw(robot) });
+    }
+}
+
+/// # Safety
+/// robot должен быть получен из robot_new, script - строкой C в UTF-8
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_execute_script(robot: *mut Robot, script: *const c_char) -> c_int {
+    if robot.is_null() || script.is_null() {
+        return fail("Null pointer");
+    }
+    let robot = unsafe { &mut *robot };
+    let Ok(source) = unsafe { CStr::from_ptr(script) }.to_str() else {
+        return fail("Script is not valid UTF-8");
+    };
+
+    let mut commands = match Interpreter::new(source).interpret() {
+        Ok(commands) => commands,
+        Err(err) => return fail(err.annotate(source).trim_end()),
+    };
+    match commands.execute_all(robot) {
+        Ok(()) => 0,
+        Err(err) => fail(err.to_string()),
+    }
+}
+
+/// Разбирает скрипт в список команд, возвращает NULL при ошибке
+///
+/// # Safety
+/// script должен быть строкой C в UTF-8
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_interpret(script: *const c_char) -> *mut CommandList {
+    if script.is_null() {
+        fail("Null pointer");
+        return ptr::null_mut();
+    }
+    let Ok(source) = unsafe { CStr::from_ptr(script) }.to_str() else {
+        fail("Script is not valid UTF-8");
+        return ptr::null_mut();
+    };
+    match Interpreter::new(source).interpret() {
+        Ok(commands) => Box::into_raw(Box::new(commands)),
+        Err(err) => {
+            fail(err.annotate(source).trim_end());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// commands должен быть получен из robot_interpret и еще не освобожден, или быть NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn command_list_free(commands: *mut CommandList) {
+    if !commands.is_null() {
+        drop(unsafe { Box::from_raw(commands) });
+    }
+}
+
+/// # Safety
+/// commands должен быть получен из robot_interpret
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn command_list_len(commands: *const CommandList) -> c_int {
+    if commands.is_null() {
+        return fail("Null pointer");
+    }
+    c_int::try_from(unsafe { &*commands }.len()).unwrap_or(c_int::MAX)
+}
+
+/// Команды текстом скрипта, по команде в строке, строку нужно освободить robot_string_free
+///
+/// # Safety
+/// commands должен быть получен из robot_interpret
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn command_list_to_string(commands: *const CommandList) -> *mut c_char {
+    if commands.is_null() {
+        fail("Null pointer");
+        return ptr::null_mut();
+    }
+    c_string(unsafe { &*commands }.to_string())
+}
+
+/// Выполняет копию списка, поэтому один список можно выполнить несколько раз
+///
+/// # Safety
+/// commands должен быть получен из robot_interpret, robot - из robot_new
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn command_list_execute(
+    commands: *const CommandList,
+    robot: *mut Robot,
+) -> c_int {
+    if commands.is_null() || robot.is_null() {
+        return fail("Null pointer");
+    }
+    let mut commands = unsafe { &*commands }.clone();
+    match commands.execute_all(unsafe { &mut *robot }) {
+        Ok(()) => 0,
+        Err(err) => fail(err.to_string()),
+    }
+}
+
+/// # Safety
+/// robot должен быть получен из robot_new, status - указывать на RobotStatus
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_get_state(robot: *const Robot, status: *mut RobotStatus) -> c_int {
+    if robot.is_null() || status.is_null() {
+        return fail("Null pointer");
+    }
+    let robot = unsafe { &*robot };
+    let state = RobotStatus {
+        x: robot.x(),
+        y: robot.y(),
+        direction: index(&Direction::ALL, robot.direction()),
+        color: index(&Color::ALL, robot.color()),
+        pen_down: robot.is_drawing(),
+    };
+    unsafe { status.write(state) };
+    0
+}
+
+/// Цвет клетки рисунка робота: номер в Color::ALL или -1 для пустой клетки
+///
+/// # Safety
+/// robot должен быть получен из robot_new
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_canvas_color_at(robot: *const Robot, x: i32, y: i32) -> c_int {
+    if robot.is_null() {
+        return fail("Null pointer");
+    }
+    color_at(&Canvas::from_robot(unsafe { &*robot }), x, y)
+}
+
+/// Рисунок робота на момент вызова, освобождается canvas_free
+///
+/// # Safety
+/// robot должен быть получен из robot_new
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_canvas_new(robot: *const Robot) -> *mut Canvas {
+    if robot.is_null() {
+        fail("Null pointer");
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(Canvas::from_robot(unsafe { &*robot })))
+}
+
+/// # Safety
+/// canvas должен быть получен из robot_canvas_new и еще не освобожден, или быть NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn canvas_free(canvas: *mut Canvas) {
+    if !canvas.is_null() {
+        drop(unsafe { Box::from_raw(canvas) });
+    }
+}
+
+/// Цвет клетки рисунка: номер в Color::ALL или -1 для пустой клетки
+///
+/// # Safety
+/// canvas должен быть получен из robot_canvas_new
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn canvas_color_at(canvas: *const Canvas, x: i32, y: i32) -> c_int {
+    if canvas.is_null() {
+        return fail("Null pointer");
+    }
+    color_at(unsafe { &*canvas }, x, y)
+}
+
+fn color_at(canvas: &Canvas, x: i32, y: i32) -> c_int {
+    canvas
+        .color_at(x, y)
+        .map_or(-1, |color| index(&Color::ALL, color))
+}
+
+/// Рисунок робота текстом, строку нужно освободить robot_string_free
+///
+/// # Safety
+/// robot должен быть получен из robot_new
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_canvas_ascii(robot: *const Robot) -> *mut c_char {
+    if robot.is_null() {
+        fail("Null pointer");
+        return ptr::null_mut();
+    }
+    c_string(Canvas::from_robot(unsafe { &*robot }).render_ascii())
+}
+
+// Текст последней ошибки в этом потоке или NULL, строку освобождать не нужно
+#[unsafe(no_mangle)]
+pub extern "C" fn robot_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// # Safety
+/// text должен быть получен из функции этого модуля и еще не освобожден, или быть NULL
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn robot_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(unsafe { CString::from_raw(text) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = include_str!("../include/robot.h");
+
+    fn last_error() -> String {
+        let error = robot_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_ffi_robot() {
+        let robot = robot_new(1, 2, 3);
+        assert!(!robot.is_null());
+        unsafe {
+            let script = c"down_pen\nmove 2\nturn_left 1";
+            assert_eq!(robot_execute_script(robot, script.as_ptr()), 0);
+
+            let mut status = RobotStatus::default();
+            assert_eq!(robot_get_state(robot, &mut status), 0);
+            assert_eq!(
+                status,
+                RobotStatus {
+                    x: 3,
+                    y: 2,
+                    direction: 0,
+                    color: 0,
+                    pen_down: true,
+                }
+            );
+
+            assert_eq!(robot_canvas_color_at(robot, 2, 2), 0);
+            assert_eq!(robot_canvas_color_at(robot, 2, 3), -1);
+            let ascii = robot_canvas_ascii(robot);
+            assert_eq!(
+                CStr::from_ptr(ascii).to_str().unwrap(),
+                Canvas::from_robot(&*robot).render_ascii()
+            );
+            robot_string_free(ascii);
+
+            // Рисунок не меняется после новых команд робота
+            let canvas = robot_canvas_new(robot);
+            assert!(!canvas.is_null());
+            assert_eq!(robot_execute_script(robot, c"move 1".as_ptr()), 0);
+            assert_eq!(canvas_color_at(canvas, 2, 2), 0);
+            assert_eq!(canvas_color_at(canvas, 3, 3), -1);
+            assert_eq!(robot_canvas_color_at(robot, 3, 3), 0);
+            canvas_free(canvas);
+            assert!(robot_canvas_new(ptr::null()).is_null());
+            assert_eq!(canvas_color_at(ptr::null(), 0, 0), -1);
+
+            assert_eq!(robot_execute_script(robot, c"fly 1".as_ptr()), -1);
+            assert!(last_error().contains("Undefined command fly"));
+            assert_eq!(robot_get_state(robot, ptr::null_mut()), -1);
+            assert_eq!(last_error(), "Null pointer");
+
+            robot_free(robot);
+        }
+
+        assert!(robot_new(0, 0, 8).is_null());
+        assert_eq!(last_error(), "Invalid direction 8");
+    }
+
+    #[test]
+    fn test_ffi_command_list() {
+        unsafe {
+            let commands = robot_interpret(c"repeat 2 [ move 1 ]".as_ptr());
+            assert!(!commands.is_null());
+            assert_eq!(command_list_len(commands), 2);
+            let text = command_list_to_string(commands);
+            assert_eq!(CStr::from_ptr(text).to_str().unwrap(), "move 1\nmove 1\n");
+            robot_string_free(text);
+
+            let robot = robot_new(0, 0, 0);
+            assert_eq!(command_list_execute(commands, robot), 0);
+            assert_eq!(command_list_execute(commands, robot), 0);
+            let mut status = RobotStatus::default();
+            robot_get_state(robot, &mut status);
+            assert_eq!((status.x, status.y), (0, 4));
+            robot_free(robot);
+            command_list_free(commands);
+
+            assert!(robot_interpret(c"move".as_ptr()).is_null());
+            assert!(last_error().contains("error"));
+        }
+    }
+
+    #[test]
+    fn test_header_declares_functions() {
+        for name in [
+            "robot_new",
+            "robot_free",
+            "robot_execute_script",
+            "robot_get_state",
+            "robot_canvas_color_at",
+            "robot_canvas_new",
+            "canvas_free",
+            "canvas_color_at",
+            "robot_canvas_ascii",
+            "robot_last_error",
+            "robot_string_free",
+            "robot_interpret",
+            "command_list_free",
+            "command_list_len",
+            "command_list_to_string",
+            "command_list_execute",
+        ] {
+            assert!(
+                HEADER.contains(&format!("{name}(")),
+                "{name} is not declared"
+            );
+        }
+        assert!(HEADER.contains("} RobotStatus;"));
+    }
+}
@@ -0,0 +1,478 @@
+// Импорт программ из других языков в список команд робота.
+// from_turtle() переводит подмножество Python turtle, на котором написаны учебные программы:
+// forward/fd, backward/back/bk, right/rt, left/lt, penup/pu/up, pendown/pd/down,
+// goto/setpos/setposition, setheading/seth, home, color/pencolor, pensize/width
+// и циклы "for <имя> in range(<n>):" с телом по отступу.
+// Вызовы записываются с черепахой или без нее: "t.forward(10)", "turtle.fd(10)", "fd(10)".
+// Строки импорта, создание черепахи и экрана ("t = turtle.Turtle()") и вызовы, которые
+// не влияют на рисунок (speed, shape, hideturtle, done и т.п.), пропускаются.
+// Черепаха начинает с опущенным пером, смотря направо, поэтому список начинается
+// командами "face right" и "down_pen". Шаг черепахи - одна клетка,
+// а углы должны быть кратны 45 градусам, как повороты робота.
+// Робот переносится в точку без рисования, поэтому goto и home допускаются только
+// с поднятым пером. Аргументы - только числа и строки, выражения и переменные не поддерживаются.
+
+use super::{
+    command::{
+        Command, CommandList, CompositeCommand, DownPenCommand, MoveBackwardCommand, MoveCommand,
+        PenColorCommand, PenWidthCommand, RepeatCommand, SetDirectionCommand, TeleportCommand,
+        TurnLeft45Command, TurnLeftCommand, TurnRight45Command, TurnRightCommand, UpPenCommand,
+    },
+    error::Error,
+    interpreter::Span,
+    robot::{Color, Direction},
+};
+
+// Вызовы, которые не меняют рисунок
+const IGNORED: &[&str] = &[
+    "speed",
+    "shape",
+    "hideturtle",
+    "ht",
+    "showturtle",
+    "st",
+    "done",
+    "mainloop",
+    "exitonclick",
+    "bgcolor",
+    "title",
+    "setup",
+    "tracer",
+    "update",
+    "delay",
+];
+
+pub fn from_turtle(source: &str) -> Result<CommandList, Error> {
+    let mut importer = Importer {
+        lines: lines(source),
+        next: 0,
+        pen_down: true,
+    };
+    let mut commands: Vec<Box<dyn Command>> = vec![
+        Box::new(SetDirectionCommand::new(Direction::Right)),
+        Box::new(DownPenCommand),
+    ];
+    commands.extend(importer.block(0)?);
+    if let Some(line) = importer.lines.get(importer.next) {
+        // Строка с меньшим отступом, чем у первой строки программы
+        return Err(Error::InvalidCommand(line.span));
+    }
+    Ok(commands.into_iter().collect())
+}
+
+// Непустая строка программы без комментария
+#[derive(Debug, Clone, Copy)]
+struct Line<'a> {
+    indent: usize,
+    text: &'a str,
+    span: Span,
+}
+
+fn lines(source: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (index, line) in source.split('\n').enumerate() {
+        let code = strip_comment(line).trim_end();
+        let text = code.trim_start();
+        if !text.is_empty() {
+            let indent = code.len() - text.len();
+            lines.push(Line {
+                indent,
+                text,
+                span: Span {
+                    start: start + indent,
+                    end: start + code.len(),
+                    line: index + 1,
+                    column: code[..indent].chars().count() + 1,
+                },
+            });
+        }
+        start += line.len() + 1;
+    }
+    lines
+}
+
+// Строка без комментария "#", знак внутри строки в кавычках комментарием не считается
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..index],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+struct Importer<'a> {
+    lines: Vec<Line<'a>>,
+    next: usize,
+    // Опущено ли перо черепахи, чтобы проверять переносы в точку
+    pen_down: bool,
+}
+
+impl Importer<'_> {
+    // Строки с заданным отступом до строки с меньшим отступом
+    fn block(&mut self, indent: usize) -> Result<Vec<Box<dyn Command>>, Error> {
+        let mut commands = Vec::new();
+        while let Some(&line) = self.lines.get(self.next) {
+            if line.indent < indent {
+                break;
+            }
+            if line.indent > indent {
+                return Err(Error::InvalidCommand(line.span));
+            }
+            self.next += 1;
+            if line.text.starts_with("for ") {
+                commands.extend(self.repeat(line)?);
+            } else {
+                commands.extend(self.statement(line)?);
+            }
+        }
+        Ok(commands)
+    }
+
+    // Цикл записывается командой повтора, как "repeat" в языке команд
+    fn repeat(&mut self, line: Line<'_>) -> Result<Vec<Box<dyn Command>>, Error> {
+        let times = loop_times(line)?;
+        let Some(&body) = self
+            .lines
+            .get(self.next)
+            .filter(|body| body.indent > line.indent)
+        else {
+            return Err(Error::UnclosedBlock(line.span));
+        };
+
+        let (start, pen_down) = (self.next, self.pen_down);
+        let mut commands = self.block(body.indent)?;
+        if self.pen_down != pen_down && times > 1 {
+            // Следующие повторы начинаются с пером после первого повтора, переносы в точку
+            // в теле проверяются и для них
+            let end = self.next;
+            self.next = start;
+            commands = self.block(body.indent)?;
+            self.next = end;
+        }
+
+        Ok(vec![Box::new(RepeatCommand::new(
+            Box::new(CompositeCommand::new(commands)),
+            times,
+        ))])
+    }
+
+    fn statement(&mut self, line: Line<'_>) -> Result<Vec<Box<dyn Command>>, Error> {
+        let text = line.text;
+        if text.starts_with("import ") || text.starts_with("from ") {
+            return Ok(Vec::new());
+        }
+        // Создание черепахи или экрана
+        if let Some((_, value)) = text.split_once('=') {
+            let value = value.trim();
+            if let Some((name, args)) = call(value)
+                && matches!(name.rsplit('.').next(), Some("Turtle" | "Screen"))
+                && args.trim().is_empty()
+            {
+                return Ok(Vec::new());
+            }
+            return Err(Error::InvalidCommand(line.span));
+        }
+
+        let Some((name, args)) = call(text) else {
+            return Err(Error::InvalidCommand(line.span));
+        };
+        let name = name.rsplit('.').next().unwrap_or(name);
+        let args = arguments(args);
+        let number = |index: usize| number(&args, index, line.span);
+        let arity = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(Error::InvalidCommandParameter(text.to_string(), line.span))
+            }
+        };
+
+        let command: Box<dyn Command> = match name {
+            name if IGNORED.contains(&name) => return Ok(Vec::new()),
+            "forward" | "fd" | "backward" | "back" | "bk" => {
+                arity(1)?;
+                let distance = number(0)?;
+                let forward = matches!(name, "forward" | "fd") == (distance >= 0);
+                let steps = u32::try_from(distance.unsigned_abs())
+                    .map_err(|_| Error::InvalidCommandParameter(args[0].to_string(), line.span))?;
+                if forward {
+                    Box::new(MoveCommand::new(steps))
+                } else {
+                    Box::new(MoveBackwardCommand::new(steps))
+                }
+            }
+            "right" | "rt" | "left" | "lt" => {
+                arity(1)?;
+                let degrees = number(0)?;
+                if degrees % 45 != 0 {
+                    return Err(Error::InvalidCommandParameter(
+                        args[0].to_string(),
+                        line.span,
+                    ));
+                }
+                // Отрицательный угол - поворот в другую сторону
+                let right = matches!(name, "right" | "rt") == (degrees >= 0);
+                let degrees = (degrees.unsigned_abs() % 360) as u32;
+                return Ok(turn(right, degrees).into_iter().collect());
+            }
+            "penup" | "pu" | "up" => {
+                arity(0)?;
+                self.pen_down = false;
+                Box::new(UpPenCommand)
+            }
+            "pendown" | "pd" | "down" => {
+                arity(0)?;
+                self.pen_down = true;
+                Box::new(DownPenCommand)
+            }
+            "goto" | "setpos" | "setposition" => {
+                arity(2)?;
+                self.check_pen_up(line)?;
+                let coordinate = |index: usize| {
+                    i32::try_from(number(index)?).map_err(|_| {
+                        Error::InvalidCommandParameter(args[index].to_string(), line.span)
+                    })
+                };
+                Box::new(TeleportCommand::new(coordinate(0)?, coordinate(1)?))
+            }
+            "home" => {
+                arity(0)?;
+                self.check_pen_up(line)?;
+                return Ok(vec![
+                    Box::new(TeleportCommand::new(0, 0)),
+                    Box::new(SetDirectionCommand::new(Direction::Right)),
+                ]);
+            }
+            "setheading" | "seth" => {
+                arity(1)?;
+                Box::new(SetDirectionCommand::new(heading(number(0)?).ok_or_else(
+                    || Error::InvalidCommandParameter(args[0].to_string(), line.span),
+                )?))
+            }
+            // Второй аргумент color - цвет заливки, заливки у робота нет
+            "color" | "pencolor" => {
+                if args.is_empty() || args.len() > 2 || (name == "pencolor" && args.len() > 1) {
+                    return Err(Error::InvalidCommandParameter(text.to_string(), line.span));
+                }
+                let color = color(args[0]).ok_or_else(|| {
+                    Error::InvalidCommandParameter(args[0].to_string(), line.span)
+                })?;
+                Box::new(PenColorCommand::new(color))
+            }
+            "pensize" | "width" => {
+                arity(1)?;
+                let width = u32::try_from(number(0)?)
+                    .map_err(|_| Error::InvalidCommandParameter(args[0].to_string(), line.span))?;
+                Box::new(PenWidthCommand::new(width))
+            }
+            _ => return Err(Error::UndefinedCommand(name.to_string(), line.span)),
+        };
+        Ok(vec![command])
+    }
+
+    fn check_pen_up(&self, line: Line<'_>) -> Result<(), Error> {
+        if self.pen_down {
+            return Err(Error::InvalidCommandParameter(
+                line.text.to_string(),
+                line.span,
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Имя и аргументы вызова "name(args)"
+fn call(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once('(')?;
+    let args = rest.strip_suffix(')')?;
+    let name = name.trim();
+    let valid = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    (!name.is_empty() && name.chars().all(valid)).then_some((name, args))
+}
+
+fn arguments(args: &str) -> Vec<&str> {
+    if args.trim().is_empty() {
+        return Vec::new();
+    }
+    args.split(',').map(str::trim).collect()
+}
+
+// Целое число, дробное допускается без дробной части: "90.0"
+fn number(args: &[&str], index: usize, span: Span) -> Result<i64, Error> {
+    let arg = args[index];
+    let invalid = || Error::InvalidCommandParameter(arg.to_string(), span);
+    if let Ok(value) = arg.parse::<i64>() {
+        return Ok(value);
+    }
+    let value = arg.parse::<f64>().map_err(|_| invalid())?;
+    if value.fract() != 0.0 || value.abs() > i64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(value as i64)
+}
+
+// "for i in range(4):" и "for i in range(1, 5):", возвращает количество повторов
+fn loop_times(line: Line<'_>) -> Result<u32, Error> {
+    let invalid = || Error::InvalidCommand(line.span);
+    let header = line.text.strip_suffix(':').ok_or_else(invalid)?;
+    let (_, range) = header.split_once(" in ").ok_or_else(invalid)?;
+    let (name, args) = call(range.trim()).ok_or_else(invalid)?;
+    if name != "range" {
+        return Err(invalid());
+    }
+    let args = arguments(args);
+    let (start, end) = match args.len() {
+        1 => (0, number(&args, 0, line.span)?),
+        2 => (number(&args, 0, line.span)?, number(&args, 1, line.span)?),
+        _ => return Err(invalid()),
+    };
+    let times = end.saturating_sub(start).max(0);
+    u32::try_from(times).map_err(|_| Error::InvalidCommandParameter(range.to_string(), line.span))
+}
+
+// Поворот на угол от 0 до 359 градусов, кратный 45
+fn turn(right: bool, degrees: u32) -> Option<Box<dyn Command>> {
+    let command: Box<dyn Command> = match (right, degrees) {
+        (_, 0) => return None,
+        (true, degrees) if degrees % 90 == 0 => Box::new(TurnRightCommand::new(degrees / 90)),
+        (false, degrees) if degrees % 90 == 0 => Box::new(TurnLeftCommand::new(degrees / 90)),
+        (true, degrees) => Box::new(TurnRight45Command::new(degrees / 45)),
+        (false, degrees) => Box::new(TurnLeft45Command::new(degrees / 45)),
+    };
+    Some(command)
+}
+
+// Курс черепахи: 0 - направо, 90 - вверх, углы растут против часовой стрелки
+fn heading(degrees: i64) -> Option<Direction> {
+    if degrees % 45 != 0 {
+        return None;
+    }
+    let direction = match degrees.rem_euclid(360) {
+        0 => Direction::Right,
+        45 => Direction::UpRight,
+        90 => Direction::Up,
+        135 => Direction::UpLeft,
+        180 => Direction::Left,
+        225 => Direction::DownLeft,
+        270 => Direction::Down,
+        _ => Direction::DownRight,
+    };
+    Some(direction)
+}
+
+fn color(arg: &str) -> Option<Color> {
+    let name = arg
+        .strip_prefix('"')
+        .and_then(|arg| arg.strip_suffix('"'))
+        .or_else(|| {
+            arg.strip_prefix('\'')
+                .and_then(|arg| arg.strip_suffix('\''))
+        })?;
+    Color::ALL
+        .into_iter()
+        .find(|color| color.to_string() == name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::Robot;
+
+    #[test]
+    fn test_from_turtle() {
+        let source = "\
+import turtle
+t = turtle.Turtle()
+t.speed(0)  # быстрее
+
+t.color('red')
+for i in range(4):
+    t.forward(3)
+    t.left(90)
+t.penup()
+t.goto(-2, 5)
+t.pendown()
+t.setheading(270)
+fd(2)
+turtle.done()
+";
+        let mut commands = from_turtle(source).unwrap();
+        assert_eq!(commands.commands().len(), 9);
+
+        let mut robot = Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (-2, 3));
+        assert_eq!(robot.direction(), Direction::Down);
+        assert_eq!(robot.color(), Color::Red);
+        assert!(robot.is_drawing());
+        // Квадрат и отрезок после переноса
+        assert_eq!(robot.path().len(), 5);
+        assert_eq!(robot.path()[1].from.x, 3);
+    }
+
+    #[test]
+    fn test_from_turtle_turns() {
+        let source = "right(45)\nlt(-90)\nleft(450)\nright(-135.0)\nrt(0)\nseth(-45)";
+        let commands = from_turtle(source).unwrap();
+        let text = commands.to_string();
+        assert_eq!(
+            text,
+            "face right\ndown_pen\nturn_right_45 1\nturn_right 1\nturn_left 1\n\
+             turn_left_45 3\nface down_right\n"
+        );
+    }
+
+    #[test]
+    fn test_from_turtle_nested_loops() {
+        let source = "for _ in range(2):\n  for _ in range(1, 4):\n    fd(1)\n  rt(90)\nfd(1)";
+        let mut commands = from_turtle(source).unwrap();
+        // Начало программы, внешний цикл и шаг после него
+        assert_eq!(commands.commands().len(), 2 + 1 + 1);
+        let mut robot = Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        // Два раза по три шага с поворотом и последний шаг
+        assert_eq!((robot.x(), robot.y()), (2, -3));
+
+        // Перо поднимается только в первом повторе, перенос во втором допустим
+        let source = "for i in range(2):\n    goto(1, 1)\n    penup()";
+        assert!(from_turtle(source).is_err());
+        let source = "penup()\nfor i in range(2):\n    goto(1, 1)\n    pendown()";
+        assert!(matches!(
+            from_turtle(source),
+            Err(Error::InvalidCommandParameter(text, span)) if text == "goto(1, 1)" && span.line == 3
+        ));
+    }
+
+    #[test]
+    fn test_from_turtle_errors() {
+        assert!(matches!(
+            from_turtle("t.circle(5)"),
+            Err(Error::UndefinedCommand(name, span)) if name == "circle" && span.column == 1
+        ));
+        assert!(matches!(
+            from_turtle("forward(10)\nleft(30)"),
+            Err(Error::InvalidCommandParameter(arg, span)) if arg == "30" && span.line == 2
+        ));
+        assert!(matches!(
+            from_turtle("forward(size)"),
+            Err(Error::InvalidCommandParameter(arg, _)) if arg == "size"
+        ));
+        assert!(matches!(
+            from_turtle("for i in range(3):\nfd(1)"),
+            Err(Error::UnclosedBlock(_))
+        ));
+        assert!(matches!(
+            from_turtle("fd(1)\n  fd(2)"),
+            Err(Error::InvalidCommand(span)) if span.line == 2 && span.column == 3
+        ));
+        assert!(from_turtle("x = 5").is_err());
+        assert!(from_turtle("pencolor('orange')").is_err());
+        assert!(from_turtle("home()").is_err());
+    }
+}
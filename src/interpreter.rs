@@ -1,149 +1,790 @@
-// Пример реализации интерпретатора для языка команд
-// Этот код включает в себя реализацию сканера, который разбирает входную строку на токены,
-// а также интерпретатор, который собирает батч команд на основе токенов.
-// В этом примере мы используем простые команды, такие как "move", "turn_left", "turn_right",
-// "down_pen", "up_pen" и числа для указания расстояния или угла поворота.
+// Интерпретатор языка команд робота.
+// Сканер разбирает входную строку на токены, парсер строит из них синтаксическое дерево
+// (модуль ast), а интерпретатор превращает дерево в список команд. Программу можно также
+// скомпилировать в байткод (модуль bytecode) и выполнить без разворачивания циклов и процедур.
+// Кроме основного языка интерпретатор понимает подмножество UCBLogo (Interpreter::logo)
+// и русские ключевые слова (Interpreter::with_locale), а реестр (CommandRegistry) добавляет
+// новые команды без изменения крейта.
+// Описание языка выводит команда "help" консоли (пример robot_interpreter).
 
-use std::str;
+use std::{
+    collections::HashMap,
+    fmt, io, iter,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use crate::{command::*, error::Error};
+use crate::{command::*, error::Error, robot::Robot};
+
+pub mod ast;
+pub mod bytecode;
+pub mod format;
+mod include;
+mod lower;
+mod parser;
+pub mod random;
+pub mod registry;
+
+pub use format::format;
+pub use registry::CommandRegistry;
+
+use ast::Program;
+use bytecode::Bytecode;
+use lower::{Environment, Lowering};
+use parser::Parser;
+use random::RandomSource;
+
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+// Наибольшее количество развернутых инструкций программы, включая повторы циклов
+pub const DEFAULT_MAX_COMMANDS: usize = 1_000_000;
+
+// Диалект языка команд
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    // Собственный язык: повороты задаются количеством поворотов на 90 градусов
+    Native,
+    // Подмножество UCBLogo: "forward", "right 90", "penup", "to ... end",
+    // повороты задаются в градусах, кратных 90
+    Logo,
+}
+
+// Язык ключевых слов собственного диалекта
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Ru,
+}
 
 pub struct Interpreter<'a> {
-    scanner: Scanner<'a>,
+    parser: Parser<'a>,
+    // Переменные и процедуры, объявленные в уже разобранных программах
+    env: Environment,
+    max_depth: usize,
+    max_commands: usize,
 }
 
 impl<'a> Interpreter<'a> {
     pub fn new(input: &'a str) -> Self {
-        let scanner = Scanner::new(input);
-        Self { scanner }
+        Self::with_scanner(Scanner::new(input), Dialect::Native)
+    }
+
+    // Интерпретатор для программ на подмножестве Logo
+    pub fn logo(input: &'a str) -> Self {
+        Self::with_scanner(
+            Scanner::with_keywords(input, Keywords::logo()),
+            Dialect::Logo,
+        )
+    }
+
+    // Интерпретатор, читающий программу из потока по мере разбора
+    pub fn from_reader(reader: impl io::BufRead + 'a) -> Self {
+        Self::with_scanner(
+            Scanner::from_reader(reader, Keywords::default()),
+            Dialect::Native,
+        )
+    }
+
+    fn with_scanner(scanner: Scanner<'a>, dialect: Dialect) -> Self {
+        Self {
+            parser: Parser::new(scanner, dialect, DEFAULT_MAX_DEPTH),
+            env: Environment::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_commands: DEFAULT_MAX_COMMANDS,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.parser.set_max_depth(max_depth);
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Ограничивает размер развернутой программы: одна инструкция или один повтор
+    // цикла считается за одну команду
+    pub fn with_max_commands(mut self, max_commands: usize) -> Self {
+        self.max_commands = max_commands;
+        self
+    }
+
+    // Заменяет таблицу ключевых слов таблицей для указанного языка,
+    // добавленные ранее псевдонимы при этом сбрасываются
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        *self.parser.scanner_mut().keywords_mut() = Keywords::for_locale(locale);
+        self
+    }
+
+    // Каталоги, в которых ищутся подключаемые файлы, после каталога подключающего файла
+    pub fn with_search_path<P: Into<PathBuf>>(
+        mut self,
+        paths: impl IntoIterator<Item = P>,
+    ) -> Self {
+        let paths = paths.into_iter().map(Into::into).collect();
+        self.parser.includes_mut().set_search_path(paths);
+        self
+    }
+
+    // Заменяет чтение подключаемых файлов с диска, загрузчик должен вернуть
+    // ошибку NotFound, если файла нет, тогда поиск продолжится в следующем каталоге
+    pub fn with_loader(mut self, loader: impl FnMut(&Path) -> io::Result<String> + 'a) -> Self {
+        self.parser.includes_mut().set_loader(Box::new(loader));
+        self
+    }
+
+    pub fn with_rng(mut self, rng: impl RandomSource + 'static) -> Self {
+        self.env.set_rng(Box::new(rng));
+        self
+    }
+
+    // Подключает реестр дополнительных команд, которые разбираются как ключевые слова
+    pub fn with_registry(mut self, registry: CommandRegistry) -> Self {
+        let registry = Rc::new(registry);
+        self.parser.set_registry(Rc::clone(&registry));
+        self.env.set_registry(registry);
+        self
+    }
+
+    // Регистрирует дополнительное имя для ключевого слова, например "forward" для "move"
+    pub fn add_alias(&mut self, alias: &str, token: Token) {
+        self.parser
+            .scanner_mut()
+            .keywords_mut()
+            .add_alias(alias, token);
     }
 
+    // Разбирает и сразу разворачивает инструкции по одной, поэтому ошибка в начале
+    // программы обнаруживается раньше синтаксических ошибок в ее конце
     pub fn interpret(&mut self) -> Result<CommandList, Error> {
         let mut command_list = CommandList::default();
+        let mut lowering =
+            Lowering::new(&mut self.env, self.max_depth).with_max_commands(self.max_commands);
 
-        while let Some(token) = self.next_token()? {
-            match token {
-                Token::Move => {
-                    let distance = match self.next_token()? {
-                        Some(Token::Number(distance)) => distance,
-                        Some(token) => return Err(Error::UnexpectedToken(token)),
-                        None => return Err(Error::InvalidCommand),
-                    };
-                    command_list.add_command(Box::new(MoveCommand::new(distance)));
-                }
-                Token::TurnLeft | Token::TurnRight => {
-                    let angle = match self.next_token()? {
-                        Some(Token::Number(angle)) => angle,
-                        Some(token) => return Err(Error::UnexpectedToken(token)),
-                        None => return Err(Error::InvalidCommand),
-                    };
-                    match token {
-                        Token::TurnLeft => {
-                            command_list.add_command(Box::new(TurnLeftCommand::new(angle)))
-                        }
-                        Token::TurnRight => {
-                            command_list.add_command(Box::new(TurnRightCommand::new(angle)))
-                        }
-                        _ => unreachable!(),
-                    };
-                }
-                Token::DownPen => {
-                    command_list.add_command(Box::new(DownPenCommand));
-                }
-                Token::UpPen => {
-                    command_list.add_command(Box::new(UpPenCommand));
+        while let Some(statement) = self.parser.statement()? {
+            lowering.lower_statement(&statement, &mut command_list)?;
+        }
+
+        Ok(command_list)
+    }
+
+    // Собирает все ошибки программы: после ошибки разбор продолжается со следующей инструкции
+    pub fn interpret_all_errors(&mut self) -> (CommandList, Vec<Error>) {
+        let mut command_list = CommandList::default();
+        let mut errors = Vec::new();
+
+        loop {
+            match self.interpret_next() {
+                Ok(Some(commands)) => command_list.add_commands(commands.commands()),
+                Ok(None) => break,
+                Err(err) => {
+                    errors.push(err);
+                    while let Err(err) = self.parser.synchronize() {
+                        errors.push(err);
+                    }
                 }
-                _ => return Err(Error::UnexpectedToken(token)),
             }
         }
 
-        Ok(command_list)
+        (command_list, errors)
+    }
+
+    // Разбирает и разворачивает следующую инструкцию программы
+    pub fn interpret_next(&mut self) -> Result<Option<CommandList>, Error> {
+        let Some(statement) = self.parser.statement()? else {
+            return Ok(None);
+        };
+
+        let mut command_list = CommandList::default();
+        Lowering::new(&mut self.env, self.max_depth)
+            .with_max_commands(self.max_commands)
+            .lower_statement(&statement, &mut command_list)?;
+        Ok(Some(command_list))
+    }
+
+    // Разбирает весь вход в синтаксическое дерево без построения команд
+    pub fn parse(&mut self) -> Result<Program, Error> {
+        self.parser.parse_program()
+    }
+
+    // Строит список команд по синтаксическому дереву, разворачивая циклы и процедуры
+    pub fn lower(&mut self, program: &Program) -> Result<CommandList, Error> {
+        Lowering::new(&mut self.env, self.max_depth)
+            .with_max_commands(self.max_commands)
+            .lower_program(program)
+    }
+
+    // Разбирает весь вход и компилирует его в байткод для виртуальной машины
+    pub fn compile(&mut self) -> Result<Bytecode, Error> {
+        let program = self.parse()?;
+        Ok(Bytecode::compile(&program))
+    }
+
+    // Проверяет программу без перемещения робота: команды выполняются на копии робота,
+    // а ошибка выполнения, например выход за границы поля, попадает в отчет
+    pub fn check(&mut self, robot: &Robot) -> Result<CheckReport, Error> {
+        let mut commands = self.interpret()?;
+        // Наблюдатели не должны видеть пробное выполнение
+        let mut robot = robot.clone();
+        robot.clear_observers();
+        let mut current = 0;
+
+        let result = commands.execute_stepwise(&mut robot, |index, _, _| {
+            current = index;
+            StepAction::Step
+        });
+        let violation = result.err().map(|error| Violation {
+            index: current,
+            error,
+        });
+
+        Ok(CheckReport {
+            commands: commands.commands().len(),
+            robot,
+            violation,
+        })
+    }
+
+    pub fn variable(&self, name: &str) -> Option<i64> {
+        self.env.variable(name)
+    }
+}
+
+// Выдает команды каждой инструкции программы сразу после ее разбора
+impl Iterator for Interpreter<'_> {
+    type Item = Result<CommandList, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.interpret_next().transpose()
+    }
+}
+
+// Результат проверки программы методом Interpreter::check()
+#[derive(Debug)]
+pub struct CheckReport {
+    // Количество команд после разворачивания циклов и процедур
+    pub commands: usize,
+    // Состояние робота после выполнения команд или в момент ошибки
+    pub robot: Robot,
+    pub violation: Option<Violation>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.violation.is_none()
+    }
+}
+
+// Команда, которую выполнить не удалось, и ошибка выполнения
+#[derive(Debug)]
+pub struct Violation {
+    pub index: usize,
+    pub error: Error,
+}
+
+// Положение фрагмента исходного текста: байтовые смещения начала и конца,
+// а также номер строки и столбца начала (нумерация с единицы)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            end: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Span {
+    // Объединяет два фрагмента в один, от начала текущего до конца другого
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+// Таблица ключевых слов языка вместе с их псевдонимами.
+// Ключевые слова не зависят от регистра: "move", "MOVE" и "Move" равнозначны.
+#[derive(Debug, Clone)]
+pub struct Keywords {
+    words: HashMap<String, Token>,
+}
+
+impl Default for Keywords {
+    fn default() -> Self {
+        Self::from_words([
+            ("move", Token::Move),
+            ("back", Token::Back),
+            ("goto", Token::Goto),
+            ("home", Token::Home),
+            ("face", Token::Face),
+            ("color", Token::Color),
+            ("width", Token::Width),
+            ("refuel", Token::Refuel),
+            ("stamp", Token::Stamp),
+            ("write", Token::Write),
+            ("turn_left", Token::TurnLeft),
+            ("turn_right", Token::TurnRight),
+            ("turn_left_45", Token::TurnLeft45),
+            ("turn_right_45", Token::TurnRight45),
+            ("down_pen", Token::DownPen),
+            ("up_pen", Token::UpPen),
+            ("repeat", Token::Repeat),
+            ("def", Token::Def),
+            ("end", Token::End),
+            ("let", Token::Let),
+            ("if", Token::If),
+            ("else", Token::Else),
+            ("not", Token::Not),
+            ("and", Token::And),
+            ("or", Token::Or),
+            ("include", Token::Include),
+            ("random", Token::Random),
+            ("while", Token::While),
+            ("blocked", Token::Blocked),
+            ("robot", Token::Robot),
+            // Короткие псевдонимы в духе Logo
+            ("fd", Token::Move),
+            ("bk", Token::Back),
+            ("lt", Token::TurnLeft),
+            ("rt", Token::TurnRight),
+            ("pd", Token::DownPen),
+            ("pu", Token::UpPen),
+        ])
+    }
+}
+
+impl Keywords {
+    // Ключевые слова подмножества UCBLogo
+    pub fn logo() -> Self {
+        Self::from_words([
+            ("forward", Token::Move),
+            ("fd", Token::Move),
+            ("back", Token::Back),
+            ("bk", Token::Back),
+            ("setxy", Token::Goto),
+            ("home", Token::Home),
+            ("setpensize", Token::Width),
+            ("left", Token::TurnLeft),
+            ("lt", Token::TurnLeft),
+            ("right", Token::TurnRight),
+            ("rt", Token::TurnRight),
+            ("pendown", Token::DownPen),
+            ("pd", Token::DownPen),
+            ("penup", Token::UpPen),
+            ("pu", Token::UpPen),
+            ("repeat", Token::Repeat),
+            ("to", Token::Def),
+            ("end", Token::End),
+            ("if", Token::If),
+            ("not", Token::Not),
+            ("and", Token::And),
+            ("or", Token::Or),
+        ])
+    }
+
+    // Ключевые слова собственного диалекта на русском языке
+    pub fn russian() -> Self {
+        Self::from_words([
+            ("вперед", Token::Move),
+            ("вперёд", Token::Move),
+            ("назад", Token::Back),
+            ("перейти", Token::Goto),
+            ("домой", Token::Home),
+            ("смотреть", Token::Face),
+            ("цвет", Token::Color),
+            ("толщина", Token::Width),
+            ("заправить", Token::Refuel),
+            ("штамп", Token::Stamp),
+            ("написать", Token::Write),
+            ("налево", Token::TurnLeft),
+            ("направо", Token::TurnRight),
+            ("налево_45", Token::TurnLeft45),
+            ("направо_45", Token::TurnRight45),
+            ("перо_вниз", Token::DownPen),
+            ("перо_вверх", Token::UpPen),
+            ("повторить", Token::Repeat),
+            ("процедура", Token::Def),
+            ("конец", Token::End),
+            ("пусть", Token::Let),
+            ("если", Token::If),
+            ("иначе", Token::Else),
+            ("не", Token::Not),
+            ("и", Token::And),
+            ("или", Token::Or),
+            ("подключить", Token::Include),
+            ("случайно", Token::Random),
+            ("пока", Token::While),
+            ("препятствие", Token::Blocked),
+            ("робот", Token::Robot),
+        ])
+    }
+
+    pub fn for_locale(locale: Locale) -> Self {
+        match locale {
+            Locale::En => Self::default(),
+            Locale::Ru => Self::russian(),
+        }
+    }
+
+    fn from_words<const N: usize>(words: [(&str, Token); N]) -> Self {
+        let mut keywords = Self {
+            words: HashMap::new(),
+        };
+        for (word, token) in words {
+            keywords.add_alias(word, token);
+        }
+        keywords
+    }
+
+    pub fn add_alias(&mut self, alias: &str, token: Token) {
+        self.words.insert(alias.to_lowercase(), token);
     }
 
-    fn next_token(&mut self) -> Result<Option<Token>, Error> {
-        self.scanner.next_token()
+    pub fn get(&self, word: &str) -> Option<&Token> {
+        self.words.get(&word.to_lowercase())
     }
 }
 
 pub struct Scanner<'a> {
-    source: str::Chars<'a>,
+    source: Box<dyn Iterator<Item = io::Result<char>> + 'a>,
+    peeked: Option<char>,
+    // Первая ошибка чтения, она возвращается после токенов, прочитанных до нее
+    io_error: Option<io::Error>,
+    // Текущее положение сканера, из которого строятся положения токенов
+    position: Span,
+    keywords: Keywords,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(input: &'a str) -> Self {
-        let source = input.chars();
-        Self { source }
+        Self::with_keywords(input, Keywords::default())
+    }
+
+    pub fn with_keywords(input: &'a str, keywords: Keywords) -> Self {
+        Self::with_source(Box::new(input.chars().map(Ok)), keywords)
+    }
+
+    pub fn from_reader(reader: impl io::BufRead + 'a, keywords: Keywords) -> Self {
+        Self::with_source(Box::new(ReaderChars::new(reader)), keywords)
+    }
+
+    fn with_source(
+        source: Box<dyn Iterator<Item = io::Result<char>> + 'a>,
+        keywords: Keywords,
+    ) -> Self {
+        Self {
+            source,
+            peeked: None,
+            io_error: None,
+            position: Span::default(),
+            keywords,
+        }
+    }
+
+    pub fn keywords(&self) -> &Keywords {
+        &self.keywords
+    }
+
+    pub fn keywords_mut(&mut self) -> &mut Keywords {
+        &mut self.keywords
     }
 
-    pub fn next_token(&mut self) -> Result<Option<Token>, Error> {
+    pub fn next_token(&mut self) -> Result<Option<(Token, Span)>, Error> {
         let token = loop {
+            let start = self.position;
             let ch = self.next_char();
 
-            match ch {
+            let token = match ch {
                 None => break None,
-                Some(ch) if ch.is_alphabetic() => break Some(self.scan_keyword(ch)?),
-                Some(ch) if ch.is_ascii_digit() => break Some(self.scan_number(ch)?),
+                Some(ch) if ch.is_alphabetic() => self.scan_keyword(ch, start)?,
+                Some(ch) if ch.is_ascii_digit() => self.scan_number(ch, start)?,
                 Some(ch) if ch.is_whitespace() => continue,
+                Some('#') => {
+                    self.skip_line_comment();
+                    continue;
+                }
+                Some('/') if self.next_char_if('*') => {
+                    self.skip_block_comment(start)?;
+                    continue;
+                }
+                Some('"') => self.scan_string(start)?,
+                Some('[') => Token::LeftBracket,
+                Some(']') => Token::RightBracket,
+                Some('(') => Token::LeftParen,
+                Some(')') => Token::RightParen,
+                Some(':') => Token::Colon,
+                Some('=') if self.next_char_if('=') => Token::Equal,
+                Some('=') => Token::Assign,
+                Some('!') if self.next_char_if('=') => Token::NotEqual,
+                Some('<') if self.next_char_if('=') => Token::LessEqual,
+                Some('<') => Token::Less,
+                Some('>') if self.next_char_if('=') => Token::GreaterEqual,
+                Some('>') => Token::Greater,
+                Some('+') => Token::Plus,
+                Some('-') => Token::Minus,
+                Some('*') => Token::Star,
+                Some('/') => Token::Slash,
                 Some(ch) => {
-                    return Err(Error::UnexpectedCharacter(ch));
+                    return Err(Error::UnexpectedCharacter(ch, self.span_from(start)));
                 }
-            }
+            };
+
+            break Some((token, self.span_from(start)));
         };
 
-        Ok(token)
+        match self.io_error.take() {
+            Some(err) if token.is_none() => Err(Error::Io(err)),
+            Some(err) => {
+                self.io_error = Some(err);
+                Ok(token)
+            }
+            None => Ok(token),
+        }
     }
 
     fn next_char(&mut self) -> Option<char> {
-        self.source.next()
+        let ch = self.peeked.take().or_else(|| self.read_char())?;
+
+        self.position.start += ch.len_utf8();
+        self.position.end = self.position.start;
+        if ch == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+
+        Some(ch)
     }
 
-    fn scan_keyword(&mut self, ch: char) -> Result<Token, Error> {
-        let mut buffer = ch.to_string();
+    fn peek_char(&mut self) -> Option<char> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_char();
+        }
+        self.peeked
+    }
+
+    // Ошибка чтения запоминается и завершает поток символов
+    fn read_char(&mut self) -> Option<char> {
+        match self.source.next()? {
+            Ok(ch) => Some(ch),
+            Err(err) => {
+                self.io_error = Some(err);
+                self.source = Box::new(iter::empty());
+                None
+            }
+        }
+    }
+
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.position.start,
+            ..start
+        }
+    }
 
-        while let Some(next_ch) = self.next_char() {
-            if !next_ch.is_whitespace() {
-                buffer.push(next_ch);
-            } else {
+    // Пропускает комментарий "# ..." до конца строки
+    fn skip_line_comment(&mut self) {
+        while let Some(ch) = self.next_char() {
+            if ch == '\n' {
                 break;
             }
         }
+    }
+
+    // Пропускает комментарий "/* ... */"
+    fn skip_block_comment(&mut self, start: Span) -> Result<(), Error> {
+        while let Some(ch) = self.next_char() {
+            if ch == '*' && self.next_char_if('/') {
+                return Ok(());
+            }
+        }
+
+        Err(Error::UnterminatedComment(self.span_from(start)))
+    }
 
-        match buffer.as_str() {
-            "move" => Ok(Token::Move),
-            "turn_left" => Ok(Token::TurnLeft),
-            "turn_right" => Ok(Token::TurnRight),
-            "down_pen" => Ok(Token::DownPen),
-            "up_pen" => Ok(Token::UpPen),
-            _ => Err(Error::UndefinedCommand(buffer)),
+    // Поглощает следующий символ, только если он совпадает с ожидаемым
+    fn next_char_if(&mut self, expected: char) -> bool {
+        if self.peek_char() == Some(expected) {
+            self.next_char();
+            true
+        } else {
+            false
         }
     }
 
-    fn scan_number(&mut self, ch: char) -> Result<Token, Error> {
+    fn scan_keyword(&mut self, ch: char, start: Span) -> Result<Token, Error> {
         let mut buffer = ch.to_string();
 
-        while let Some(next_ch) = self.next_char() {
-            if !next_ch.is_whitespace() {
-                buffer.push(next_ch);
-            } else {
+        self.scan_word(&mut buffer);
+
+        match self.keywords.get(&buffer) {
+            Some(token) => Ok(token.clone()),
+            None if is_identifier(&buffer) => Ok(Token::Identifier(buffer)),
+            None => Err(Error::UndefinedCommand(buffer, self.span_from(start))),
+        }
+    }
+
+    // Дочитывает слово до пробела, скобки или оператора, не поглощая сам разделитель
+    fn scan_word(&mut self, buffer: &mut String) {
+        while let Some(next_ch) = self.peek_char() {
+            if next_ch.is_whitespace() || is_delimiter(next_ch) {
                 break;
             }
+            buffer.push(next_ch);
+            self.next_char();
         }
+    }
+
+    // Строка в двойных кавычках, которая должна закончиться в той же строке текста
+    fn scan_string(&mut self, start: Span) -> Result<Token, Error> {
+        let mut buffer = String::new();
+
+        loop {
+            match self.peek_char() {
+                Some('"') => {
+                    self.next_char();
+                    return Ok(Token::Str(buffer));
+                }
+                Some('\n') | None => return Err(Error::UnterminatedString(self.span_from(start))),
+                Some(ch) => {
+                    buffer.push(ch);
+                    self.next_char();
+                }
+            }
+        }
+    }
+
+    fn scan_number(&mut self, ch: char, start: Span) -> Result<Token, Error> {
+        let mut buffer = ch.to_string();
+
+        self.scan_word(&mut buffer);
 
         match buffer.parse::<u32>() {
             Ok(number) => Ok(Token::Number(number)),
-            Err(_) => Err(Error::InvalidCommandParameter(buffer)),
+            Err(_) => Err(Error::InvalidCommandParameter(
+                buffer,
+                self.span_from(start),
+            )),
         }
     }
 }
 
-#[derive(Debug)]
+// Символы потока, который читается построчно
+struct ReaderChars<R> {
+    reader: R,
+    line: String,
+    position: usize,
+}
+
+impl<R: io::BufRead> ReaderChars<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: io::BufRead> Iterator for ReaderChars<R> {
+    type Item = io::Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(ch) = self.line[self.position..].chars().next() {
+                self.position += ch.len_utf8();
+                return Some(Ok(ch));
+            }
+
+            self.line.clear();
+            self.position = 0;
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+fn is_delimiter(ch: char) -> bool {
+    matches!(
+        ch,
+        '[' | ']' | '(' | ')' | ':' | '=' | '!' | '<' | '>' | '+' | '-' | '*' | '/' | '#' | '"'
+    )
+}
+
+fn is_identifier(word: &str) -> bool {
+    word.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     Move,
+    Back,
+    Goto,
+    Home,
+    Face,
+    Color,
+    Width,
+    Refuel,
+    Stamp,
+    Write,
     TurnLeft,
     TurnRight,
+    TurnLeft45,
+    TurnRight45,
     DownPen,
     UpPen,
+    Repeat,
+    Def,
+    End,
+    Let,
+    If,
+    Else,
+    Not,
+    And,
+    Or,
+    Include,
+    Random,
+    While,
+    Blocked,
+    Robot,
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    Colon,
+    Assign,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Identifier(String),
     Number(u32),
+    Str(String),
 }
 #[cfg(test)]
 mod tests {
@@ -195,34 +836,1141 @@ mod tests {
     fn test_invalid_command() {
         let mut interpreter = Interpreter::new("fly 10");
         let result = interpreter.interpret();
-        assert!(matches!(result, Err(Error::UndefinedCommand(_))));
+        assert!(matches!(result, Err(Error::UndefinedCommand(..))));
     }
 
     #[test]
     fn test_missing_number_after_move() {
         let mut interpreter = Interpreter::new("move");
         let result = interpreter.interpret();
-        assert!(matches!(result, Err(Error::InvalidCommand)));
+        assert!(matches!(result, Err(Error::InvalidCommand(_))));
     }
 
     #[test]
     fn test_unexpected_token() {
         let mut interpreter = Interpreter::new("move up_pen");
         let result = interpreter.interpret();
-        assert!(matches!(result, Err(Error::UnexpectedToken(_))));
+        assert!(matches!(result, Err(Error::UnexpectedToken(..))));
     }
 
     #[test]
     fn test_invalid_character() {
         let mut scanner = Scanner::new("move@10");
         let result = scanner.next_token();
-        assert!(matches!(result, Err(Error::UndefinedCommand(_))));
+        assert!(matches!(result, Err(Error::UndefinedCommand(..))));
     }
 
     #[test]
     fn test_invalid_number() {
         let mut scanner = Scanner::new("123abc");
         let token = scanner.next_token();
-        assert!(matches!(token, Err(Error::InvalidCommandParameter(_))));
+        assert!(matches!(token, Err(Error::InvalidCommandParameter(..))));
+    }
+
+    #[test]
+    fn test_repeat_command() {
+        let mut interpreter = Interpreter::new("repeat 4 [ move 10 turn_right 1 ]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 8);
+    }
+
+    #[test]
+    fn test_repeat_without_spaces_around_brackets() {
+        let mut interpreter = Interpreter::new("repeat 2 [move 1]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_nested_repeat_command() {
+        let mut interpreter = Interpreter::new("repeat 3 [ down_pen repeat 2 [ move 1 ] up_pen ]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 12);
+    }
+
+    #[test]
+    fn test_repeat_draws_square() {
+        let mut interpreter = Interpreter::new("repeat 4 [ move 10 turn_right 1 ]");
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.y(), 0);
+    }
+
+    #[test]
+    fn test_unclosed_repeat_block() {
+        let mut interpreter = Interpreter::new("repeat 2 [ move 1");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UnclosedBlock(_))));
+    }
+
+    #[test]
+    fn test_repeat_without_block() {
+        let mut interpreter = Interpreter::new("repeat 2 move 1");
+        let result = interpreter.interpret();
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedToken(Token::Move, _))
+        ));
+    }
+
+    #[test]
+    fn test_unmatched_closing_bracket() {
+        let mut interpreter = Interpreter::new("move 1 ]");
+        let result = interpreter.interpret();
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedToken(Token::RightBracket, _))
+        ));
+    }
+
+    #[test]
+    fn test_procedure_definition_and_call() {
+        let mut interpreter =
+            Interpreter::new("def square repeat 4 [ move 10 turn_right 1 ] end square square");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 16);
+    }
+
+    #[test]
+    fn test_procedure_definition_alone_emits_nothing() {
+        let mut interpreter = Interpreter::new("def step move 1 end");
+        let commands = interpreter.interpret().unwrap();
+        assert!(commands.commands().is_empty());
+    }
+
+    #[test]
+    fn test_procedure_calls_other_procedure() {
+        let mut interpreter = Interpreter::new("def step move 1 end def steps step step end steps");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_procedure_called_before_definition() {
+        let mut interpreter = Interpreter::new("square def square move 1 end");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UndefinedCommand(name, _)) if name == "square"));
+    }
+
+    #[test]
+    fn test_unclosed_procedure() {
+        let mut interpreter = Interpreter::new("def square move 1");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UnclosedBlock(_))));
+    }
+
+    #[test]
+    fn test_nested_procedure_definition() {
+        let mut interpreter = Interpreter::new("def outer def inner move 1 end end");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UnexpectedToken(Token::Def, _))));
+    }
+
+    #[test]
+    fn test_recursive_procedure_exceeds_depth() {
+        let mut interpreter = Interpreter::new("def forever move 1 forever end forever");
+        let result = interpreter.interpret();
+        assert!(matches!(
+            result,
+            Err(Error::MaxDepthExceeded(DEFAULT_MAX_DEPTH, _))
+        ));
+    }
+
+    #[test]
+    fn test_custom_max_depth() {
+        let source = "repeat 1 [ repeat 1 [ repeat 1 [ move 1 ] ] ]";
+        let mut interpreter = Interpreter::new(source).with_max_depth(2);
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::MaxDepthExceeded(2, _))));
+
+        let mut interpreter = Interpreter::new(source).with_max_depth(3);
+        assert_eq!(interpreter.interpret().unwrap().commands().len(), 1);
+    }
+
+    #[test]
+    fn test_deep_expressions_are_limited() {
+        let deep = 100_000;
+        for source in [
+            format!("move {}1{}", "(".repeat(deep), ")".repeat(deep)),
+            format!("move {}1", "not ".repeat(deep)),
+            format!("move {}1", "-".repeat(deep)),
+            format!("move {}1 1", "random 1 ".repeat(deep)),
+            format!("move 0{}", " + 1".repeat(deep)),
+            format!("move 1{}", " * 1".repeat(deep)),
+            format!("while 1{} [ move 1 ]", " or 0".repeat(deep)),
+        ] {
+            let result = Interpreter::new(&source).interpret();
+            assert!(matches!(
+                result,
+                Err(Error::MaxDepthExceeded(DEFAULT_MAX_DEPTH, _))
+            ));
+        }
+        assert_eq!(first_move_distance("move ((2)) + --1"), 3);
+    }
+
+    #[test]
+    fn test_max_commands() {
+        let mut interpreter = Interpreter::new("repeat 4000000000 [ move 1 ]");
+        let result = interpreter.interpret();
+        assert!(matches!(
+            result,
+            Err(Error::TooManyCommands(DEFAULT_MAX_COMMANDS, _))
+        ));
+
+        let mut interpreter = Interpreter::new("repeat 3 [ move 1 ]").with_max_commands(7);
+        assert_eq!(interpreter.interpret().unwrap().commands().len(), 3);
+        let mut interpreter = Interpreter::new("repeat 4 [ move 1 ]").with_max_commands(7);
+        assert!(interpreter.interpret().is_err());
+    }
+
+    fn first_move_distance(source: &str) -> u32 {
+        run(source).y() as u32
+    }
+
+    #[test]
+    fn test_let_and_variable_argument() {
+        assert_eq!(first_move_distance("let n = 5 move n * 2"), 10);
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        assert_eq!(first_move_distance("move 2 + 3 * 4"), 14);
+        assert_eq!(first_move_distance("move (2 + 3) * 4"), 20);
+        assert_eq!(first_move_distance("move 10 - 4 - 3"), 3);
+        assert_eq!(first_move_distance("move 20 / 3"), 6);
+    }
+
+    #[test]
+    fn test_expressions_without_spaces() {
+        assert_eq!(first_move_distance("let n=3 move n*(n+1)"), 12);
+    }
+
+    #[test]
+    fn test_variable_reassignment() {
+        let mut interpreter = Interpreter::new("let n = 1 let n = n + 1");
+        interpreter.interpret().unwrap();
+        assert_eq!(interpreter.variable("n"), Some(2));
+    }
+
+    #[test]
+    fn test_variable_changes_inside_repeat() {
+        assert_eq!(
+            first_move_distance("let n = 0 repeat 3 [ let n = n + 1 move n ]"),
+            6
+        );
+    }
+
+    #[test]
+    fn test_variable_used_as_repeat_count() {
+        let mut interpreter = Interpreter::new("let sides = 4 repeat sides [ move 1 ]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 4);
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let mut interpreter = Interpreter::new("move n");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UndefinedVariable(name, _)) if name == "n"));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let mut interpreter = Interpreter::new("move 1 / 0");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::DivisionByZero(_))));
+    }
+
+    #[test]
+    fn test_negative_argument() {
+        let mut interpreter = Interpreter::new("turn_left 1 - 2");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(value, _)) if value == "-1"));
+    }
+
+    #[test]
+    fn test_arithmetic_overflow() {
+        let mut interpreter = Interpreter::new("let n = 4294967295 move n * n * n");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::ArithmeticOverflow(_))));
+    }
+
+    #[test]
+    fn test_let_without_assign() {
+        let mut interpreter = Interpreter::new("let n 5");
+        let result = interpreter.interpret();
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedToken(Token::Number(5), _))
+        ));
+    }
+
+    #[test]
+    fn test_if_condition_true() {
+        assert_eq!(
+            first_move_distance("let n = 5 if n > 3 [ move 1 ] else [ move 2 ]"),
+            1
+        );
+    }
+
+    #[test]
+    fn test_if_condition_false() {
+        assert_eq!(
+            first_move_distance("let n = 2 if n > 3 [ move 1 ] else [ move 2 ]"),
+            2
+        );
+    }
+
+    #[test]
+    fn test_if_without_else() {
+        let mut interpreter = Interpreter::new("if 1 == 2 [ move 1 ] move 3");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 1);
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let mut interpreter = Interpreter::new(
+            "let a = 1 == 1 let b = 1 != 1 let c = 2 <= 2 let d = 3 >= 4 let e = 1 < 2",
+        );
+        interpreter.interpret().unwrap();
+        assert_eq!(interpreter.variable("a"), Some(1));
+        assert_eq!(interpreter.variable("b"), Some(0));
+        assert_eq!(interpreter.variable("c"), Some(1));
+        assert_eq!(interpreter.variable("d"), Some(0));
+        assert_eq!(interpreter.variable("e"), Some(1));
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let mut interpreter = Interpreter::new(
+            "let a = 1 and 0 let b = 1 or 0 let c = not 0 let d = not 1 < 2 or 1 and 1",
+        );
+        interpreter.interpret().unwrap();
+        assert_eq!(interpreter.variable("a"), Some(0));
+        assert_eq!(interpreter.variable("b"), Some(1));
+        assert_eq!(interpreter.variable("c"), Some(1));
+        assert_eq!(interpreter.variable("d"), Some(1));
+    }
+
+    #[test]
+    fn test_if_inside_repeat() {
+        let source = "let i = 0 repeat 4 [ let i = i + 1 if i / 2 * 2 == i [ move 1 ] ]";
+        let mut interpreter = Interpreter::new(source);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_recursive_procedure_with_condition() {
+        let source =
+            "let n = 3 def countdown if n > 0 [ move 1 let n = n - 1 countdown ] end countdown";
+        let mut interpreter = Interpreter::new(source);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 3);
+    }
+
+    #[test]
+    fn test_skipped_branch_is_not_evaluated() {
+        let mut interpreter = Interpreter::new("if 0 [ move undefined ] else [ move 1 ]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 1);
+    }
+
+    #[test]
+    fn test_if_without_block() {
+        let mut interpreter = Interpreter::new("if 1 move 1");
+        let result = interpreter.interpret();
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedToken(Token::Move, _))
+        ));
+    }
+
+    #[test]
+    fn test_single_exclamation_mark() {
+        let mut scanner = Scanner::new("! 1");
+        let result = scanner.next_token();
+        assert!(matches!(result, Err(Error::UnexpectedCharacter('!', _))));
+    }
+
+    #[test]
+    fn test_line_comment() {
+        let source = "# square\nmove 1 # first side\nturn_right 1#second\n# done";
+        let mut interpreter = Interpreter::new(source);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let source = "move 1 /* multi\nline * comment */ move /* inline */ 2 /**/";
+        let mut interpreter = Interpreter::new(source);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_division_is_not_comment() {
+        assert_eq!(first_move_distance("move 8 / 2"), 4);
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut interpreter = Interpreter::new("move 1 /* never closed");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UnterminatedComment(_))));
+    }
+
+    fn run(source: &str) -> crate::robot::Robot {
+        let mut interpreter = Interpreter::new(source);
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        robot
+    }
+
+    #[test]
+    fn test_move_with_negative_distance() {
+        let robot = run("move -5");
+        assert_eq!(robot.y(), -5);
+        assert_eq!(robot.direction(), crate::robot::Direction::Up);
+    }
+
+    #[test]
+    fn test_back_command() {
+        let robot = run("move 10 back 3");
+        assert_eq!(robot.y(), 7);
+        assert_eq!(robot.direction(), crate::robot::Direction::Up);
+    }
+
+    #[test]
+    fn test_back_is_single_command() {
+        let commands = Interpreter::new("back 3 move -2").interpret().unwrap();
+        assert_eq!(commands.commands().len(), 2);
+        assert!(
+            commands
+                .commands()
+                .iter()
+                .all(|command| command.as_any().is::<MoveBackwardCommand>())
+        );
+    }
+
+    #[test]
+    fn test_back_with_negative_distance() {
+        let robot = run("back -2");
+        assert_eq!(robot.y(), 2);
+    }
+
+    #[test]
+    fn test_backward_move_rollback() {
+        let mut interpreter = Interpreter::new("turn_right 1 back 4");
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.x(), -4);
+        commands.rollback_all(&mut robot).unwrap();
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.direction(), crate::robot::Direction::Up);
+    }
+
+    #[test]
+    fn test_goto_command() {
+        let robot = run("let n = 2 down_pen goto n * 3 (-n) move 1");
+        assert_eq!((robot.x(), robot.y()), (6, -1));
+
+        let robot = run("goto x=1 y=4");
+        assert_eq!((robot.x(), robot.y()), (1, 4));
+    }
+
+    #[test]
+    fn test_home_command() {
+        let robot = run("down_pen goto 3 4 turn_right 1 home move 1");
+        assert_eq!((robot.x(), robot.y()), (0, 1));
+        assert_eq!(robot.direction(), crate::robot::Direction::Up);
+        assert!(!robot.is_drawing());
+
+        let mut interpreter = Interpreter::new("домой").with_locale(Locale::Ru);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.to_string(), "home\n");
+    }
+
+    #[test]
+    fn test_face_command() {
+        let robot = run("turn_left 1 face Right move 2 face down move 1");
+        assert_eq!((robot.x(), robot.y()), (2, -1));
+        assert_eq!(robot.direction(), crate::robot::Direction::Down);
+
+        let result = Interpreter::new("face north").interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(name, _)) if name == "north"));
+        let result = Interpreter::new("face 1").interpret();
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedToken(Token::Number(1), _))
+        ));
+    }
+
+    #[test]
+    fn test_color_command() {
+        let robot = run("color red down_pen move 1 color Blue");
+        assert_eq!(robot.color(), crate::robot::Color::Blue);
+
+        let mut interpreter = Interpreter::new("цвет зелёный").with_locale(Locale::Ru);
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.color(), crate::robot::Color::Green);
+
+        let result = Interpreter::new("color purple").interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(name, _)) if name == "purple"));
+    }
+
+    #[test]
+    fn test_width_command() {
+        let robot = run("let w = 2 width w + 1 down_pen move 1");
+        assert_eq!(robot.pen_width(), 3);
+
+        let result = Interpreter::new("width 0").interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(value, _)) if value == "0"));
+    }
+
+    #[test]
+    fn test_refuel_command() {
+        let mut interpreter = Interpreter::new("заправить 2 + 3").with_locale(Locale::Ru);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.to_string(), "refuel 5\n");
+
+        let result = Interpreter::new("refuel -1").interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(value, _)) if value == "-1"));
+    }
+
+    #[test]
+    fn test_stamp_commands() {
+        let robot = run("goto 3 3 stamp dot stamp square 1 + 2 stamp circle 2");
+        assert_eq!((robot.x(), robot.y()), (3, 3));
+        assert_eq!(robot.path().len(), 1 + 4 + 12);
+
+        let mut interpreter =
+            Interpreter::new("штамп точка штамп квадрат 2 штамп круг 1").with_locale(Locale::Ru);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(
+            commands.to_string(),
+            "stamp dot\nstamp square 2\nstamp circle 1\n"
+        );
+
+        let result = Interpreter::new("stamp star 3").interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(name, _)) if name == "star"));
+    }
+
+    #[test]
+    fn test_write_command() {
+        let robot = run("face right write \"Hi!\"");
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        // Отрезки строк "H", "I" и "!", буква "I" начинается через пустой столбец
+        assert_eq!(robot.path().len(), 9 + 5 + 4);
+        assert_eq!(robot.path()[9].from, crate::robot::Point::new(4, 4));
+
+        let mut interpreter = Interpreter::new("написать \"привет\"").with_locale(Locale::Ru);
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.to_string(), "write \"привет\"\n");
+
+        let result = Interpreter::new("write 5").interpret();
+        assert!(matches!(
+            result,
+            Err(Error::UnexpectedToken(Token::Number(5), _))
+        ));
+    }
+
+    #[test]
+    fn test_goto_coordinate_out_of_range() {
+        let mut interpreter = Interpreter::new("goto 0 4294967296");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(..))));
+    }
+
+    #[test]
+    fn test_move_distance_too_large() {
+        let mut interpreter = Interpreter::new("move -4294967296");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(..))));
+    }
+
+    fn error_span(source: &str) -> Span {
+        let mut interpreter = Interpreter::new(source);
+        interpreter.interpret().unwrap_err().span().unwrap()
+    }
+
+    #[test]
+    fn test_scanner_token_spans() {
+        let mut scanner = Scanner::new("move 10\n  turn_left 1");
+        let (token, span) = scanner.next_token().unwrap().unwrap();
+        assert_eq!(token, Token::Move);
+        assert_eq!((span.start, span.end, span.line, span.column), (0, 4, 1, 1));
+
+        let (token, span) = scanner.next_token().unwrap().unwrap();
+        assert_eq!(token, Token::Number(10));
+        assert_eq!((span.start, span.end, span.line, span.column), (5, 7, 1, 6));
+
+        let (token, span) = scanner.next_token().unwrap().unwrap();
+        assert_eq!(token, Token::TurnLeft);
+        assert_eq!(
+            (span.start, span.end, span.line, span.column),
+            (10, 19, 2, 3)
+        );
+    }
+
+    #[test]
+    fn test_undefined_command_span() {
+        let span = error_span("move 1\nfly 10");
+        assert_eq!(
+            (span.start, span.end, span.line, span.column),
+            (7, 10, 2, 1)
+        );
+    }
+
+    #[test]
+    fn test_unexpected_character_span() {
+        let span = error_span("move 1 @");
+        assert_eq!((span.start, span.line, span.column), (7, 1, 8));
+    }
+
+    #[test]
+    fn test_invalid_argument_span_covers_expression() {
+        let span = error_span("turn_left 1 - 2");
+        assert_eq!((span.start, span.end), (10, 15));
+    }
+
+    #[test]
+    fn test_error_span_inside_procedure_body() {
+        let span = error_span("def broken\n  move x\nend\nbroken");
+        assert_eq!((span.line, span.column), (2, 8));
+    }
+
+    #[test]
+    fn test_unclosed_block_points_at_opening_bracket() {
+        let span = error_span("repeat 2 [ move 1");
+        assert_eq!((span.start, span.column), (9, 10));
+    }
+
+    #[test]
+    fn test_missing_argument_points_at_last_token() {
+        let span = error_span("down_pen move");
+        assert_eq!((span.start, span.end), (9, 13));
+    }
+
+    #[test]
+    fn test_span_tracks_multibyte_characters() {
+        let span = error_span("/* путь */ fly");
+        assert_eq!((span.start, span.column), (15, 12));
+    }
+
+    #[test]
+    fn test_error_message_contains_position() {
+        let mut interpreter = Interpreter::new("move 1\n  fly");
+        let error = interpreter.interpret().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Undefined command fly at line 2, column 3"
+        );
+    }
+
+    #[test]
+    fn test_named_parameters() {
+        let robot = run("move distance=10 turn_left times=1 move distance = 2 + 3");
+        assert_eq!(robot.x(), -5);
+        assert_eq!(robot.y(), 10);
+    }
+
+    #[test]
+    fn test_named_repeat_count() {
+        let mut interpreter = Interpreter::new("repeat times=3 [ move 1 ]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 3);
+    }
+
+    #[test]
+    fn test_positional_variable_argument_still_works() {
+        let robot = run("let distance = 4 move distance back distance / 2");
+        assert_eq!(robot.y(), 2);
+    }
+
+    #[test]
+    fn test_unknown_parameter_name() {
+        let mut interpreter = Interpreter::new("turn_left distance=2");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UnknownParameter(name, _)) if name == "distance"));
+    }
+
+    #[test]
+    fn test_unknown_parameter_span() {
+        let span = error_span("move steps=1");
+        assert_eq!((span.start, span.end), (5, 10));
+    }
+
+    #[test]
+    fn test_case_insensitive_keywords() {
+        let mut interpreter = Interpreter::new("MOVE 1 Move 2 REPEAT 2 [ Turn_Left 1 ] Down_Pen");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 5);
+    }
+
+    #[test]
+    fn test_builtin_aliases() {
+        let robot = run("pd fd 5 rt 1 fd 2 bk 1 lt 1 pu");
+        assert_eq!(robot.x(), 1);
+        assert_eq!(robot.y(), 5);
+        assert!(!robot.is_drawing());
+    }
+
+    #[test]
+    fn test_runtime_alias() {
+        let mut interpreter = Interpreter::new("forward 3 FORWARD 2");
+        interpreter.add_alias("forward", Token::Move);
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.y(), 5);
+    }
+
+    #[test]
+    fn test_identifiers_are_case_sensitive() {
+        let mut interpreter = Interpreter::new("def step move 1 end STEP");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UndefinedCommand(name, _)) if name == "STEP"));
+    }
+
+    #[test]
+    fn test_logo_square() {
+        let mut interpreter = Interpreter::logo("pendown repeat 4 [ forward 10 right 90 ] penup");
+        let mut commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 10);
+
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert!(!robot.is_drawing());
+    }
+
+    #[test]
+    fn test_logo_angles_in_degrees() {
+        let mut interpreter = Interpreter::logo("left 180 rt 270");
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.direction(), crate::robot::Direction::Right);
+    }
+
+    #[test]
+    fn test_logo_angle_must_be_multiple_of_90() {
+        let mut interpreter = Interpreter::logo("right 45");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(value, _)) if value == "45"));
+    }
+
+    #[test]
+    fn test_logo_procedure() {
+        let source = "to square\n  repeat 4 [ fd 5 rt 90 ]\nend\nSQUARE square";
+        let mut interpreter = Interpreter::logo(source);
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UndefinedCommand(name, _)) if name == "SQUARE"));
+
+        let mut interpreter =
+            Interpreter::logo("to square repeat 4 [ fd 5 rt 90 ] end square square");
+        assert_eq!(interpreter.interpret().unwrap().commands().len(), 16);
+    }
+
+    #[test]
+    fn test_native_keywords_are_not_logo() {
+        let mut interpreter = Interpreter::logo("move 10");
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UndefinedCommand(name, _)) if name == "move"));
+    }
+
+    #[test]
+    fn test_parse_then_lower() {
+        let mut interpreter = Interpreter::new("let n = 2 repeat n [ move 1 ]");
+        let program = interpreter.parse().unwrap();
+        assert_eq!(program.statements.len(), 2);
+
+        let commands = interpreter.lower(&program).unwrap();
+        assert_eq!(commands.commands().len(), 2);
+        assert_eq!(interpreter.variable("n"), Some(2));
+    }
+
+    #[test]
+    fn test_interpret_from_reader() {
+        let source = "let n = 2\nrepeat n [\n  move n\n  turn_right 1\n]\n".as_bytes();
+        let mut interpreter = Interpreter::from_reader(io::BufReader::with_capacity(4, source));
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 2));
+    }
+
+    #[test]
+    fn test_interpreter_yields_commands_per_statement() {
+        let interpreter = Interpreter::new("move 1 repeat 2 [ move 2 ] fly");
+        let results: Vec<_> = interpreter.collect();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().commands().len(), 1);
+        assert_eq!(results[1].as_ref().unwrap().commands().len(), 2);
+        assert!(matches!(&results[2], Err(Error::UndefinedCommand(name, _)) if name == "fly"));
+    }
+
+    #[test]
+    fn test_reader_error_is_reported_after_read_tokens() {
+        struct FailingReader;
+
+        impl io::Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk failure"))
+            }
+        }
+
+        let reader = io::Read::chain("move 1 turn_left 1\n".as_bytes(), FailingReader);
+        let mut interpreter = Interpreter::from_reader(io::BufReader::new(reader));
+        assert!(interpreter.next().unwrap().is_ok());
+        assert!(matches!(interpreter.next(), Some(Err(Error::Io(_)))));
+    }
+
+    #[test]
+    fn test_reader_invalid_utf8() {
+        let mut interpreter = Interpreter::from_reader(&b"move \xff"[..]);
+        assert!(matches!(interpreter.interpret(), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_interpret_all_errors() {
+        let source = "move 1\nfly 2\nmove x\nrepeat 2 [ move 1 @ ]\nturn_left 1 move 3 )";
+        let mut interpreter = Interpreter::new(source);
+        let (mut commands, errors) = interpreter.interpret_all_errors();
+
+        assert_eq!(errors.len(), 4, "{errors:?}");
+        assert!(matches!(&errors[0], Error::UndefinedCommand(name, _) if name == "fly"));
+        assert!(matches!(&errors[1], Error::UndefinedVariable(name, _) if name == "x"));
+        assert!(matches!(errors[2], Error::UnexpectedCharacter('@', _)));
+        assert!(matches!(
+            errors[3],
+            Error::UnexpectedToken(Token::RightParen, _)
+        ));
+        assert_eq!(errors[2].span().unwrap().line, 4);
+
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (-3, 1));
+    }
+
+    #[test]
+    fn test_interpret_all_errors_without_errors() {
+        let mut interpreter = Interpreter::new("move 1 turn_left 1");
+        let (commands, errors) = interpreter.interpret_all_errors();
+        assert!(errors.is_empty());
+        assert_eq!(commands.commands().len(), 2);
+    }
+
+    #[test]
+    fn test_russian_locale() {
+        let source = "перо_вниз\nпусть n = 2\nпроцедура сторона вперёд n направо 1 конец\n\
+                      повторить 4 [ сторона ]\nесли не n == 2 [ назад 1 ] иначе [ НАЛЕВО 1 ]\nПеро_вверх";
+        let mut interpreter = Interpreter::new(source).with_locale(Locale::Ru);
+        let mut commands = interpreter.interpret().unwrap();
+
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert_eq!(robot.direction(), crate::robot::Direction::Left);
+        assert!(!robot.is_drawing());
+    }
+
+    #[test]
+    fn test_russian_goto_and_face() {
+        let mut interpreter =
+            Interpreter::new("перейти 3 4 смотреть вниз вперед 1").with_locale(Locale::Ru);
+        let mut commands = interpreter.interpret().unwrap();
+
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (3, 3));
+        assert_eq!(robot.direction(), crate::robot::Direction::Down);
+    }
+
+    #[test]
+    fn test_russian_locale_replaces_english_keywords() {
+        let mut interpreter = Interpreter::new("move 1").with_locale(Locale::Ru);
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UndefinedCommand(name, _)) if name == "move"));
+    }
+
+    fn virtual_files(files: &[(&str, &str)]) -> impl FnMut(&Path) -> io::Result<String> + 'static {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect();
+        move |path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+
+    #[test]
+    fn test_include_from_search_path() {
+        let files = virtual_files(&[
+            (
+                "lib/shapes.robot",
+                "include \"side.robot\"\ndef square repeat 4 [ side ] end",
+            ),
+            ("lib/side.robot", "def side move 2 turn_right 1 end"),
+        ]);
+        let source = "include \"shapes.robot\" square move 1";
+        let mut interpreter = Interpreter::new(source)
+            .with_search_path(["std", "lib"])
+            .with_loader(files);
+        let mut commands = interpreter.interpret().unwrap();
+
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 1));
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let files = virtual_files(&[
+            ("a.robot", "move 1 include \"lib/b.robot\""),
+            ("lib/b.robot", "include \"../a.robot\""),
+        ]);
+        let mut interpreter = Interpreter::new("include \"a.robot\"").with_loader(files);
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::IncludeCycle(path, _)) if path == "../a.robot"));
+    }
+
+    #[test]
+    fn test_include_missing_file() {
+        let mut interpreter =
+            Interpreter::new("move 1\ninclude \"missing.robot\"").with_loader(virtual_files(&[]));
+        let result = interpreter.interpret();
+        let Err(Error::IncludeFailed(path, err, span)) = result else {
+            panic!("expected include error, got {result:?}");
+        };
+        assert_eq!(path, "missing.robot");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(span.line, 2);
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut scanner = Scanner::new("include \"lib.robot\nmove 1");
+        scanner.next_token().unwrap();
+        assert!(matches!(
+            scanner.next_token(),
+            Err(Error::UnterminatedString(_))
+        ));
+    }
+
+    fn random_walk(seed: u64) -> (i32, i32) {
+        let source = "repeat 20 [ move random 1 10 turn_right random 0 3 ]";
+        let mut interpreter = Interpreter::new(source).with_rng(random::SplitMix64::new(seed));
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        (robot.x(), robot.y())
+    }
+
+    #[test]
+    fn test_random_is_reproducible_with_seed() {
+        assert_eq!(random_walk(42), random_walk(42));
+        assert_ne!(random_walk(42), random_walk(43));
+    }
+
+    #[test]
+    fn test_random_bounds() {
+        let mut interpreter = Interpreter::new("let n = random (2 * 3) (2 * 3) * 3");
+        interpreter.interpret().unwrap();
+        assert_eq!(interpreter.variable("n"), Some(6 * 3));
+
+        let mut interpreter = Interpreter::new("move random 5 1");
+        let result = interpreter.interpret();
+        assert!(
+            matches!(result, Err(Error::InvalidCommandParameter(value, _)) if value == "random 5 1")
+        );
+    }
+
+    #[test]
+    fn test_while_runs_until_blocked() {
+        let source = "down_pen while not blocked [ move 1 ] turn_right 1 move 2";
+        let mut commands = Interpreter::new(source).interpret().unwrap();
+        assert_eq!(commands.commands().len(), 4);
+
+        let start = crate::robot::Robot::new(0, i32::MAX - 4, crate::robot::Direction::Up, false);
+        let mut robot = start.clone();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, i32::MAX));
+
+        commands.rollback_all(&mut robot).unwrap();
+        assert_eq!((robot.x(), robot.y()), (start.x(), start.y()));
+    }
+
+    #[test]
+    fn test_while_with_constant_condition() {
+        let mut interpreter =
+            Interpreter::new("let n = 0 while n > 0 and not blocked [ move 1 ] move 1");
+        let mut commands = interpreter.interpret().unwrap();
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.y(), 1);
+    }
+
+    #[test]
+    fn test_while_with_variable_condition() {
+        let mut interpreter = Interpreter::new("let n = 3 while n > 0 [ move 1 let n = n - 1 ]");
+        let commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 3);
+        assert_eq!(interpreter.variable("n"), Some(0));
+
+        // Тело цикла с датчиком разворачивается один раз и не может менять переменные
+        let source = "let n = 0 def step let n = n + 1 end while not blocked [ move 1 step ]";
+        let result = Interpreter::new(source).interpret();
+        let Err(Error::AssignmentInWhile(name, span)) = result else {
+            panic!("expected assignment error, got {result:?}");
+        };
+        assert_eq!((name.as_str(), span.start), ("n", 19));
+    }
+
+    #[test]
+    fn test_sensor_outside_while_condition() {
+        let mut interpreter = Interpreter::new("if blocked [ move 1 ]");
+        assert!(matches!(
+            interpreter.interpret(),
+            Err(Error::InvalidSensorUse(_))
+        ));
+
+        let mut interpreter = Interpreter::new("while blocked + 1 [ move 1 ]");
+        let result = interpreter.interpret();
+        let Err(Error::InvalidSensorUse(span)) = result else {
+            panic!("expected sensor error, got {result:?}");
+        };
+        assert_eq!((span.start, span.end), (6, 17));
+    }
+
+    #[test]
+    fn test_check_does_not_move_robot() {
+        let robot = crate::robot::Robot::default();
+        let report = Interpreter::new("repeat 2 [ move 3 turn_right 1 ]")
+            .check(&robot)
+            .unwrap();
+
+        assert!(report.is_ok());
+        assert_eq!(report.commands, 4);
+        assert_eq!((report.robot.x(), report.robot.y()), (3, 3));
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+    }
+
+    #[test]
+    fn test_check_reports_out_of_bounds() {
+        let robot = crate::robot::RobotBuilder::new().x(i32::MIN + 2).build();
+        let report = Interpreter::new("move 2 turn_left 1 move 1 move 5 move 1")
+            .check(&robot)
+            .unwrap();
+
+        let violation = report.violation.unwrap();
+        assert_eq!(violation.index, 3);
+        assert!(matches!(violation.error, Error::OutOfBounds));
+        assert_eq!((report.robot.x(), report.robot.y()), (i32::MIN, 2));
+        assert_eq!(report.commands, 5);
+    }
+
+    #[test]
+    fn test_displayed_commands_interpret_to_same_list() {
+        let source = "down_pen repeat 2 [ move 2 turn_right 1 ] goto 3 (-4) face down \
+                      color blue width 3 back 1 while not blocked and 1 [ move 1 ]";
+        let commands = Interpreter::new(source).interpret().unwrap();
+        let text = commands.to_string();
+        assert_eq!(Interpreter::new(&text).interpret().unwrap(), commands);
+    }
+
+    fn jump_registry() -> CommandRegistry {
+        CommandRegistry::new().with("jump", &["height"], |args| {
+            Box::new(TeleportCommand::new(0, args[0] as i32))
+        })
+    }
+
+    #[test]
+    fn test_registered_command() {
+        let mut interpreter =
+            Interpreter::new("let h = 2 JUMP h * 3 move 1 jump height=1 def hop jump 7 end hop")
+                .with_registry(jump_registry());
+        let mut commands = interpreter.interpret().unwrap();
+        assert_eq!(commands.commands().len(), 4);
+
+        let mut robot = crate::robot::Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(robot.y(), 7);
+    }
+
+    #[test]
+    fn test_registered_command_errors() {
+        let mut interpreter = Interpreter::new("jump").with_registry(jump_registry());
+        assert!(matches!(
+            interpreter.interpret(),
+            Err(Error::InvalidCommand(_))
+        ));
+
+        let mut interpreter = Interpreter::new("jump size=1").with_registry(jump_registry());
+        let result = interpreter.interpret();
+        assert!(matches!(result, Err(Error::UnknownParameter(name, _)) if name == "size"));
+
+        // Без реестра имя остается вызовом неизвестной процедуры
+        let result = Interpreter::new("jump").interpret();
+        assert!(matches!(result, Err(Error::UndefinedCommand(name, _)) if name == "jump"));
+    }
+
+    #[test]
+    fn test_robot_target() {
+        let mut commands = Interpreter::new("robot a: move 2 robot b: repeat 2 [ rt 1 ] move 1")
+            .interpret()
+            .unwrap();
+        assert_eq!(commands.commands().len(), 3);
+        assert_eq!(
+            commands.to_string(),
+            "robot a: move 2\nrobot b: [ turn_right 1 turn_right 1 ]\nmove 1\n"
+        );
+        // Текст команд разбирается в те же команды
+        let reparsed = Interpreter::new(&commands.to_string()).interpret().unwrap();
+        assert_eq!(reparsed, commands);
+        let block = Interpreter::new("robot a: [ move 2 ] robot b: [ rt 1 rt 1 ] move 1")
+            .interpret()
+            .unwrap();
+        assert_eq!(block.to_string(), reparsed.to_string());
+
+        let mut world = crate::world::World::new()
+            .with_robot(
+                "b",
+                crate::robot::Robot::new(5, 0, crate::robot::Direction::Up, false),
+            )
+            .with_robot("a", crate::robot::Robot::default());
+        world.execute_all(&mut commands).unwrap();
+        let a = world.robot("a").unwrap();
+        let b = world.robot("b").unwrap();
+        assert_eq!((a.x(), a.y()), (0, 2));
+        assert_eq!(
+            (b.x(), b.y(), b.direction()),
+            (5, -1, crate::robot::Direction::Down)
+        );
+
+        assert!(matches!(
+            Interpreter::new("robot a move 1").interpret(),
+            Err(Error::UnexpectedToken(Token::Move, _))
+        ));
+
+        // Вложенные адресаты ограничены глубиной, как блоки
+        let source = format!("{}move 1", "robot a: ".repeat(100_000));
+        assert!(matches!(
+            Interpreter::new(&source).interpret(),
+            Err(Error::MaxDepthExceeded(DEFAULT_MAX_DEPTH, _))
+        ));
     }
 }
@@ -0,0 +1,174 @@
+// Синтаксическое дерево программы на языке команд.
+// Парсер строит дерево из токенов, а отдельный проход превращает его в список команд.
+// Дерево можно анализировать и преобразовывать до выполнения, например для форматирования
+// или оптимизации программ.
+
+use super::Span;
+use crate::robot::{Color, Direction};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+}
+
+// Инструкция программы вместе с ее положением в исходном тексте
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StmtKind {
+    Move(Expr),
+    Back(Expr),
+    Goto {
+        x: Expr,
+        y: Expr,
+    },
+    Face(Direction),
+    Color(Color),
+    Width(Expr),
+    Refuel(Expr),
+    Stamp(StampShape),
+    Write(String),
+    TurnLeft(Turn),
+    TurnRight(Turn),
+    // Количество поворотов на 45 градусов
+    TurnLeft45(Expr),
+    TurnRight45(Expr),
+    DownPen,
+    UpPen,
+    Home,
+    Repeat {
+        times: Expr,
+        body: Vec<Stmt>,
+    },
+    Def {
+        name: String,
+        body: Vec<Stmt>,
+    },
+    Let {
+        name: String,
+        value: Expr,
+    },
+    If {
+        condition: Expr,
+        then_body: Vec<Stmt>,
+        else_body: Option<Vec<Stmt>>,
+    },
+    // Условие проверяется во время выполнения, поэтому может использовать датчики робота
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+    },
+    Call(String),
+    // Команда из реестра с аргументами в порядке ее параметров
+    Custom {
+        name: String,
+        args: Vec<Expr>,
+    },
+    // Инструкция или блок инструкций для робота мира с указанным именем
+    Target {
+        robot: String,
+        body: Vec<Stmt>,
+    },
+    // Подключенный файл хранится уже разобранным
+    Include {
+        path: String,
+        program: Program,
+    },
+}
+
+// Аргумент поворота вместе с единицами, в которых он записан
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Turn {
+    pub amount: Expr,
+    pub unit: TurnUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnUnit {
+    // Количество поворотов на 90 градусов
+    QuarterTurns,
+    // Градусы, кратные 90, как в Logo
+    Degrees,
+}
+
+// Фигура штампа вместе с ее размером
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StampShape {
+    Dot,
+    // Сторона квадрата
+    Square(Expr),
+    // Радиус окружности
+    Circle(Expr),
+}
+
+// Выражение вместе с его положением в исходном тексте
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprKind {
+    Number(i64),
+    Variable(String),
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Sensor(Sensor),
+    // Случайное число от min до max включительно
+    Random {
+        min: Box<Expr>,
+        max: Box<Expr>,
+    },
+}
+
+// Датчики робота, значение которых известно только во время выполнения
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Blocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
@@ -0,0 +1,736 @@
+// Компилятор синтаксического дерева в байткод и виртуальная машина для его выполнения.
+// В отличие от превращения дерева в список команд, циклы и процедуры не разворачиваются:
+// байткод содержит переходы и вызовы, а машина выполняет их прямо над роботом.
+// Это позволяет выполнять большие сгенерированные программы без выделения тысяч команд.
+// Машина управляет одним роботом, поэтому инструкции "robot <name>:" выполняются им же.
+
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    Span,
+    ast::*,
+    lower::{
+        apply_binary, apply_unary, coordinate, pen_width, quarter_turns, random, steps, unsigned,
+    },
+    random::{RandomSource, SplitMix64},
+    registry::CommandRegistry,
+};
+use crate::{
+    command::DEFAULT_MAX_ITERATIONS,
+    error::Error,
+    robot::{Color, Direction, Robot, Stamp},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    // Кладет число на стек
+    Push(i64),
+    // Кладет на стек значение переменной с указанным номером имени
+    Load(usize),
+    // Снимает значение со стека и сохраняет его в переменную
+    Store(usize),
+    Unary(UnaryOp),
+    Binary(BinaryOp),
+    // Кладет на стек показание датчика робота, 1 или 0
+    Sense(Sensor),
+    // Снимает со стека границы и кладет случайное число между ними
+    Random,
+    // Снимает со стека расстояние и перемещает робота, отрицательное значение - назад
+    Move,
+    // Снимает со стека координаты и переносит робота в эту клетку
+    Goto,
+    Face(Direction),
+    Color(Color),
+    // Снимает со стека толщину пера
+    Width,
+    Refuel,
+    Dot,
+    // Снимают со стека сторону квадрата и радиус окружности
+    Square,
+    Circle,
+    // Пишет текст с указанным номером в таблице имен
+    Write(usize),
+    // Снимают со стека аргумент поворота
+    TurnLeft(TurnUnit),
+    TurnRight(TurnUnit),
+    // Снимают со стека количество поворотов на 45 градусов
+    TurnLeft45,
+    TurnRight45,
+    DownPen,
+    UpPen,
+    Home,
+    // Проверяет, что на вершине стека допустимое количество повторений
+    Count,
+    // Если счетчик на вершине стека равен нулю, снимает его и переходит по адресу,
+    // иначе уменьшает счетчик на единицу
+    Loop(usize),
+    Jump(usize),
+    // Считает повторение цикла while и останавливает программу после ограничения
+    Iterate,
+    // Снимает значение со стека и переходит по адресу, если оно равно нулю
+    JumpIfFalse(usize),
+    // Связывает имя процедуры с адресом ее тела
+    Define { name: usize, entry: usize },
+    // Вызывает процедуру по номеру имени
+    Call(usize),
+    // Снимает со стека аргументы и выполняет команду из реестра по номеру имени
+    Custom { name: usize, args: usize },
+    // Возвращается из процедуры или завершает программу
+    Return,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bytecode {
+    code: Vec<OpCode>,
+    // Положение в исходном тексте для каждой инструкции, используется в ошибках
+    spans: Vec<Span>,
+    // Имена переменных и процедур, на которые ссылаются инструкции
+    names: Vec<String>,
+}
+
+impl Bytecode {
+    pub fn compile(program: &Program) -> Self {
+        let mut compiler = Compiler::default();
+        compiler.block(&program.statements);
+        compiler.emit(OpCode::Return, Span::default());
+        compiler.bytecode
+    }
+
+    pub fn code(&self) -> &[OpCode] {
+        &self.code
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    bytecode: Bytecode,
+    name_indices: HashMap<String, usize>,
+}
+
+impl Compiler {
+    fn block(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        let span = statement.span;
+        match &statement.kind {
+            StmtKind::Move(distance) => {
+                self.expression(distance);
+                self.emit(OpCode::Move, distance.span);
+            }
+            StmtKind::Back(distance) => {
+                self.expression(distance);
+                self.emit(OpCode::Unary(UnaryOp::Neg), distance.span);
+                self.emit(OpCode::Move, distance.span);
+            }
+            StmtKind::Goto { x, y } => {
+                self.expression(x);
+                self.expression(y);
+                self.emit(OpCode::Goto, span);
+            }
+            StmtKind::Face(direction) => {
+                self.emit(OpCode::Face(*direction), span);
+            }
+            StmtKind::Color(color) => {
+                self.emit(OpCode::Color(*color), span);
+            }
+            StmtKind::Width(width) => {
+                self.expression(width);
+                self.emit(OpCode::Width, width.span);
+            }
+            StmtKind::Refuel(amount) => {
+                self.expression(amount);
+                self.emit(OpCode::Refuel, amount.span);
+            }
+            StmtKind::Stamp(StampShape::Dot) => {
+                self.emit(OpCode::Dot, span);
+            }
+            StmtKind::Stamp(StampShape::Square(size)) => {
+                self.expression(size);
+                self.emit(OpCode::Square, size.span);
+            }
+            StmtKind::Stamp(StampShape::Circle(radius)) => {
+                self.expression(radius);
+                self.emit(OpCode::Circle, radius.span);
+            }
+            StmtKind::Write(text) => {
+                let text = self.name(text);
+                self.emit(OpCode::Write(text), span);
+            }
+            StmtKind::TurnLeft(turn) => {
+                self.expression(&turn.amount);
+                self.emit(OpCode::TurnLeft(turn.unit), turn.amount.span);
+            }
+            StmtKind::TurnRight(turn) => {
+                self.expression(&turn.amount);
+                self.emit(OpCode::TurnRight(turn.unit), turn.amount.span);
+            }
+            StmtKind::TurnLeft45(times) => {
+                self.expression(times);
+                self.emit(OpCode::TurnLeft45, times.span);
+            }
+            StmtKind::TurnRight45(times) => {
+                self.expression(times);
+                self.emit(OpCode::TurnRight45, times.span);
+            }
+            StmtKind::DownPen => {
+                self.emit(OpCode::DownPen, span);
+            }
+            StmtKind::UpPen => {
+                self.emit(OpCode::UpPen, span);
+            }
+            StmtKind::Home => {
+                self.emit(OpCode::Home, span);
+            }
+            StmtKind::Repeat { times, body } => {
+                self.expression(times);
+                self.emit(OpCode::Count, times.span);
+                let start = self.emit(OpCode::Loop(0), span);
+                self.block(body);
+                self.emit(OpCode::Jump(start), span);
+                self.patch(start);
+            }
+            StmtKind::Def { name, body } => {
+                let name = self.name(name);
+                // Тело процедуры начинается сразу за переходом, который его обходит
+                let entry = self.bytecode.code.len() + 2;
+                self.emit(OpCode::Define { name, entry }, span);
+                let skip = self.emit(OpCode::Jump(0), span);
+                self.block(body);
+                self.emit(OpCode::Return, span);
+                self.patch(skip);
+            }
+            StmtKind::Let { name, value } => {
+                self.expression(value);
+                let name = self.name(name);
+                self.emit(OpCode::Store(name), span);
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.expression(condition);
+                let jump_to_else = self.emit(OpCode::JumpIfFalse(0), span);
+                self.block(then_body);
+                match else_body {
+                    Some(else_body) => {
+                        let jump_to_end = self.emit(OpCode::Jump(0), span);
+                        self.patch(jump_to_else);
+                        self.block(else_body);
+                        self.patch(jump_to_end);
+                    }
+                    None => self.patch(jump_to_else),
+                }
+            }
+            StmtKind::While { condition, body } => {
+                let start = self.bytecode.code.len();
+                self.expression(condition);
+                let jump_to_end = self.emit(OpCode::JumpIfFalse(0), span);
+                self.emit(OpCode::Iterate, span);
+                self.block(body);
+                self.emit(OpCode::Jump(start), span);
+                self.patch(jump_to_end);
+            }
+            StmtKind::Call(name) => {
+                let name = self.name(name);
+                self.emit(OpCode::Call(name), span);
+            }
+            StmtKind::Custom { name, args } => {
+                for arg in args {
+                    self.expression(arg);
+                }
+                let name = self.name(name);
+                let args = args.len();
+                self.emit(OpCode::Custom { name, args }, span);
+            }
+            StmtKind::Target { body, .. } => self.block(body),
+            StmtKind::Include { program, .. } => self.block(&program.statements),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Number(number) => {
+                self.emit(OpCode::Push(*number), expr.span);
+            }
+            ExprKind::Variable(name) => {
+                let name = self.name(name);
+                self.emit(OpCode::Load(name), expr.span);
+            }
+            ExprKind::Unary { op, operand } => {
+                self.expression(operand);
+                self.emit(OpCode::Unary(*op), expr.span);
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                self.expression(lhs);
+                self.expression(rhs);
+                self.emit(OpCode::Binary(*op), expr.span);
+            }
+            ExprKind::Sensor(sensor) => {
+                self.emit(OpCode::Sense(*sensor), expr.span);
+            }
+            ExprKind::Random { min, max } => {
+                self.expression(min);
+                self.expression(max);
+                self.emit(OpCode::Random, expr.span);
+            }
+        }
+    }
+
+    fn emit(&mut self, op: OpCode, span: Span) -> usize {
+        self.bytecode.code.push(op);
+        self.bytecode.spans.push(span);
+        self.bytecode.code.len() - 1
+    }
+
+    // Направляет переход по указанному адресу на текущий конец кода
+    fn patch(&mut self, address: usize) {
+        let target = self.bytecode.code.len();
+        match &mut self.bytecode.code[address] {
+            OpCode::Loop(to) | OpCode::Jump(to) | OpCode::JumpIfFalse(to) => *to = target,
+            op => unreachable!("cannot patch {op:?}"),
+        }
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.name_indices.get(name) {
+            return index;
+        }
+
+        let index = self.bytecode.names.len();
+        self.bytecode.names.push(name.to_string());
+        self.name_indices.insert(name.to_string(), index);
+        index
+    }
+}
+
+pub struct Vm<'b> {
+    bytecode: &'b Bytecode,
+    stack: Vec<i64>,
+    // Значения переменных и адреса процедур по номеру имени
+    variables: Vec<Option<i64>>,
+    procedures: Vec<Option<usize>>,
+    // Адреса возврата из процедур
+    call_stack: Vec<usize>,
+    max_depth: usize,
+    // Сколько всего раз могут повториться циклы while за одно выполнение программы
+    max_iterations: usize,
+    rng: Box<dyn RandomSource>,
+    registry: Rc<CommandRegistry>,
+}
+
+impl<'b> Vm<'b> {
+    pub fn new(bytecode: &'b Bytecode) -> Self {
+        let names = bytecode.names.len();
+        Self {
+            bytecode,
+            stack: Vec::new(),
+            variables: vec![None; names],
+            procedures: vec![None; names],
+            call_stack: Vec::new(),
+            max_depth: super::DEFAULT_MAX_DEPTH,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            rng: Box::new(SplitMix64::from_time()),
+            registry: Rc::default(),
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn with_rng(mut self, rng: impl RandomSource + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    pub fn with_registry(mut self, registry: CommandRegistry) -> Self {
+        self.registry = Rc::new(registry);
+        self
+    }
+
+    pub fn variable(&self, name: &str) -> Option<i64> {
+        let index = self.bytecode.names.iter().position(|known| known == name)?;
+        self.variables[index]
+    }
+
+    pub fn run(&mut self, robot: &mut Robot) -> Result<(), Error> {
+        let mut pc = 0;
+        let mut iterations = 0;
+
+        loop {
+            let op = self.bytecode.code[pc];
+            let span = self.bytecode.spans[pc];
+            pc += 1;
+
+            match op {
+                OpCode::Push(value) => self.stack.push(value),
+                OpCode::Load(name) => match self.variables[name] {
+                    Some(value) => self.stack.push(value),
+                    None => {
+                        let name = self.bytecode.names[name].clone();
+                        return Err(Error::UndefinedVariable(name, span));
+                    }
+                },
+                OpCode::Store(name) => self.variables[name] = Some(self.pop()),
+                OpCode::Unary(op) => {
+                    let value = self.pop();
+                    self.stack.push(apply_unary(op, value, span)?);
+                }
+                OpCode::Binary(op) => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(apply_binary(op, lhs, rhs, span)?);
+                }
+                OpCode::Sense(Sensor::Blocked) => self.stack.push(robot.is_blocked().into()),
+                OpCode::Random => {
+                    let max = self.pop();
+                    let min = self.pop();
+                    self.stack.push(random(self.rng.as_mut(), min, max, span)?);
+                }
+                OpCode::Move => {
+                    let distance = self.pop();
+                    let steps = steps(distance, span)?;
+                    if distance < 0 {
+                        robot.move_backward_by(steps)?;
+                    } else {
+                        robot.move_forward_by(steps)?;
+                    }
+                }
+                OpCode::Goto => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    robot.set_position(coordinate(x, span)?, coordinate(y, span)?)?;
+                }
+                OpCode::Face(direction) => robot.set_direction(direction),
+                OpCode::Color(color) => robot.set_color(color),
+                OpCode::Width => {
+                    let width = self.pop();
+                    robot.set_pen_width(pen_width(width, span)?);
+                }
+                OpCode::Refuel => {
+                    let amount = self.pop();
+                    robot.refuel(unsigned(amount, span)?.into());
+                }
+                OpCode::Dot => robot.stamp(Stamp::Dot)?,
+                OpCode::Square => {
+                    let size = self.pop();
+                    robot.stamp(Stamp::Square(unsigned(size, span)?))?;
+                }
+                OpCode::Circle => {
+                    let radius = self.pop();
+                    robot.stamp(Stamp::Circle(unsigned(radius, span)?))?;
+                }
+                OpCode::Write(text) => robot.write_text(&self.bytecode.names[text]),
+                OpCode::TurnLeft(unit) => {
+                    let amount = self.pop();
+                    for _ in 0..quarter_turns(amount, unit, span)? % 4 {
+                        robot.turn_left();
+                    }
+                }
+                OpCode::TurnRight(unit) => {
+                    let amount = self.pop();
+                    for _ in 0..quarter_turns(amount, unit, span)? % 4 {
+                        robot.turn_right();
+                    }
+                }
+                OpCode::TurnLeft45 => {
+                    let times = self.pop();
+                    for _ in 0..unsigned(times, span)? % 8 {
+                        robot.turn_left_45();
+                    }
+                }
+                OpCode::TurnRight45 => {
+                    let times = self.pop();
+                    for _ in 0..unsigned(times, span)? % 8 {
+                        robot.turn_right_45();
+                    }
+                }
+                OpCode::DownPen => robot.down_pen(),
+                OpCode::UpPen => robot.up_pen(),
+                OpCode::Home => robot.reset()?,
+                OpCode::Count => {
+                    let times = self.pop();
+                    self.stack.push(unsigned(times, span)?.into());
+                }
+                OpCode::Loop(end) => match self.stack.last_mut() {
+                    Some(0) => {
+                        self.stack.pop();
+                        pc = end;
+                    }
+                    Some(counter) => *counter -= 1,
+                    None => unreachable!("loop counter is missing"),
+                },
+                OpCode::Jump(target) => pc = target,
+                OpCode::Iterate => {
+                    if iterations >= self.max_iterations {
+                        return Err(Error::IterationLimit(self.max_iterations));
+                    }
+                    iterations += 1;
+                }
+                OpCode::JumpIfFalse(target) => {
+                    if self.pop() == 0 {
+                        pc = target;
+                    }
+                }
+                OpCode::Define { name, entry } => self.procedures[name] = Some(entry),
+                OpCode::Call(name) => {
+                    let Some(entry) = self.procedures[name] else {
+                        let name = self.bytecode.names[name].clone();
+                        return Err(Error::UndefinedCommand(name, span));
+                    };
+                    if self.call_stack.len() >= self.max_depth {
+                        return Err(Error::MaxDepthExceeded(self.max_depth, span));
+                    }
+                    self.call_stack.push(pc);
+                    pc = entry;
+                }
+                OpCode::Custom { name, args } => {
+                    let args = self.stack.split_off(self.stack.len() - args);
+                    let name = &self.bytecode.names[name];
+                    let Some(mut command) = self.registry.create(name, &args) else {
+                        return Err(Error::UndefinedCommand(name.clone(), span));
+                    };
+                    command.execute(robot)?;
+                }
+                OpCode::Return => match self.call_stack.pop() {
+                    Some(address) => pc = address,
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    fn pop(&mut self) -> i64 {
+        self.stack.pop().expect("stack underflow")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::robot::Direction;
+
+    fn run(source: &str) -> (Robot, Bytecode) {
+        let program = Interpreter::new(source).parse().unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut robot = Robot::default();
+        Vm::new(&bytecode).run(&mut robot).unwrap();
+        (robot, bytecode)
+    }
+
+    fn run_error(source: &str) -> Error {
+        let program = Interpreter::new(source).parse().unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut robot = Robot::default();
+        Vm::new(&bytecode).run(&mut robot).unwrap_err()
+    }
+
+    #[test]
+    fn test_vm_square() {
+        let (robot, _) = run("down_pen repeat 4 [ move 10 turn_right 1 ] up_pen");
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        assert_eq!(robot.direction(), Direction::Up);
+        assert!(!robot.is_drawing());
+    }
+
+    #[test]
+    fn test_vm_loop_is_not_expanded() {
+        let (robot, bytecode) = run("repeat 1000 [ repeat 1000 [ move 1 ] turn_left 4 ]");
+        assert_eq!(robot.y(), 1_000_000);
+        assert!(bytecode.code().len() < 20);
+    }
+
+    #[test]
+    fn test_vm_zero_repeat() {
+        let (robot, _) = run("repeat 0 [ move 1 ] move 2");
+        assert_eq!(robot.y(), 2);
+    }
+
+    #[test]
+    fn test_vm_variables_and_conditions() {
+        let source =
+            "let i = 0 repeat 4 [ let i = i + 1 if i / 2 * 2 == i [ move i ] else [ back 1 ] ]";
+        let (robot, _) = run(source);
+        assert_eq!(robot.y(), 2 + 4 - 2);
+    }
+
+    #[test]
+    fn test_vm_recursive_procedure() {
+        let source =
+            "let n = 3 def countdown if n > 0 [ move 1 let n = n - 1 countdown ] end countdown";
+        let (robot, _) = run(source);
+        assert_eq!(robot.y(), 3);
+    }
+
+    #[test]
+    fn test_vm_matches_lowering() {
+        let source = "def side move 3 turn_right 1 end repeat 3 [ side ] back 2 \
+                      goto 5 (-1) face left width 2 turn_left 2 move 1 \
+                      turn_right_45 3 move 2 face up_left turn_left_45 9 move 1";
+        let (vm_robot, _) = run(source);
+
+        let mut commands = Interpreter::new(source).interpret().unwrap();
+        let mut robot = Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+
+        assert_eq!((vm_robot.x(), vm_robot.y()), (robot.x(), robot.y()));
+        assert_eq!(vm_robot.direction(), robot.direction());
+        assert_eq!(vm_robot.pen_width(), robot.pen_width());
+    }
+
+    #[test]
+    fn test_vm_while_with_variables_matches_lowering() {
+        let source = "let n = 3 while n > 0 [ move 1 let n = n - 1 ] \
+                      let i = 0 while i < 2 and random 1 1 == 1 [ turn_right 1 let i = i + 1 ]";
+        let (vm_robot, _) = run(source);
+
+        let mut commands = Interpreter::new(source).interpret().unwrap();
+        let mut robot = Robot::default();
+        commands.execute_all(&mut robot).unwrap();
+
+        assert_eq!((robot.x(), robot.y()), (0, 3));
+        assert_eq!(robot.direction(), Direction::Down);
+        assert_eq!((vm_robot.x(), vm_robot.y()), (robot.x(), robot.y()));
+        assert_eq!(vm_robot.direction(), robot.direction());
+    }
+
+    #[test]
+    fn test_vm_variable_lookup() {
+        let program = Interpreter::new("let n = 6 * 7").parse().unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut vm = Vm::new(&bytecode);
+        vm.run(&mut Robot::default()).unwrap();
+        assert_eq!(vm.variable("n"), Some(42));
+        assert_eq!(vm.variable("m"), None);
+    }
+
+    #[test]
+    fn test_vm_undefined_procedure() {
+        let error = run_error("square def square move 1 end");
+        assert!(matches!(error, Error::UndefinedCommand(name, _) if name == "square"));
+    }
+
+    #[test]
+    fn test_vm_undefined_variable() {
+        let error = run_error("move n");
+        assert!(matches!(error, Error::UndefinedVariable(name, _) if name == "n"));
+    }
+
+    #[test]
+    fn test_vm_recursion_limit() {
+        let error = run_error("def forever move 1 forever end forever");
+        assert!(
+            matches!(error, Error::MaxDepthExceeded(depth, _) if depth == super::super::DEFAULT_MAX_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_vm_iteration_limit() {
+        let program = Interpreter::new("while 1 [ turn_left 1 ]").parse().unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut robot = Robot::default();
+        let error = Vm::new(&bytecode)
+            .with_max_iterations(4)
+            .run(&mut robot)
+            .unwrap_err();
+        assert!(matches!(error, Error::IterationLimit(4)));
+        assert_eq!(robot.direction(), Direction::Up);
+
+        // Циклы repeat не ограничены
+        let (robot, _) = run("let i = 0 while i < 3 [ repeat 10 [ move 1 ] let i = i + 1 ]");
+        assert_eq!(robot.y(), 30);
+    }
+
+    #[test]
+    fn test_vm_invalid_repeat_count() {
+        let error = run_error("repeat 0 - 1 [ move 1 ]");
+        assert!(matches!(error, Error::InvalidCommandParameter(value, _) if value == "-1"));
+    }
+
+    #[test]
+    fn test_vm_out_of_bounds() {
+        let program = Interpreter::new("move 2").parse().unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut robot = Robot::new(0, i32::MAX - 1, Direction::Up, false);
+        let result = Vm::new(&bytecode).run(&mut robot);
+        assert!(matches!(result, Err(Error::OutOfBounds)));
+        assert_eq!(robot.y(), i32::MAX);
+    }
+
+    #[test]
+    fn test_vm_random_matches_lowering_with_same_seed() {
+        use crate::interpreter::random::SplitMix64;
+
+        let source = "repeat 10 [ move random 0 5 turn_left random 1 2 ]";
+        let program = Interpreter::new(source).parse().unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut vm_robot = Robot::default();
+        Vm::new(&bytecode)
+            .with_rng(SplitMix64::new(3))
+            .run(&mut vm_robot)
+            .unwrap();
+
+        let mut interpreter = Interpreter::new(source).with_rng(SplitMix64::new(3));
+        let mut robot = Robot::default();
+        interpreter
+            .interpret()
+            .unwrap()
+            .execute_all(&mut robot)
+            .unwrap();
+
+        assert_eq!((vm_robot.x(), vm_robot.y()), (robot.x(), robot.y()));
+    }
+
+    #[test]
+    fn test_vm_while_and_sensor_in_condition() {
+        let program = Interpreter::new("while not blocked [ move 1 ] if blocked [ turn_left 1 ]")
+            .parse()
+            .unwrap();
+        let bytecode = Bytecode::compile(&program);
+        let mut robot = Robot::new(0, i32::MAX - 2, Direction::Up, false);
+        Vm::new(&bytecode).run(&mut robot).unwrap();
+        assert_eq!(robot.y(), i32::MAX);
+        assert_eq!(robot.direction(), Direction::Left);
+    }
+
+    #[test]
+    fn test_vm_registered_command() {
+        let registry = CommandRegistry::new().with("jump", &["x", "y"], |args| {
+            Box::new(crate::command::TeleportCommand::new(
+                args[0] as i32,
+                args[1] as i32,
+            ))
+        });
+        let bytecode = Interpreter::new("repeat 2 [ move 1 ] jump 5 (-1) move 1")
+            .with_registry(registry.clone())
+            .compile()
+            .unwrap();
+
+        let mut robot = Robot::default();
+        Vm::new(&bytecode)
+            .with_registry(registry)
+            .run(&mut robot)
+            .unwrap();
+        assert_eq!((robot.x(), robot.y()), (5, 0));
+
+        // Машина без реестра не знает команду
+        let error = Vm::new(&bytecode).run(&mut Robot::default()).unwrap_err();
+        assert!(matches!(error, Error::UndefinedCommand(name, _) if name == "jump"));
+    }
+}
@@ -0,0 +1,344 @@
+// Форматирование программ: программа разбирается заново и печатается в каноническом виде.
+// Каждая инструкция пишется на отдельной строке, тела блоков и процедур сдвигаются на
+// четыре пробела, операторы отделяются пробелами, а скобки в выражениях остаются только там,
+// где они нужны. Комментарии при форматировании не сохраняются.
+
+use std::fmt::Write;
+
+use super::{Interpreter, ast::*};
+use crate::error::Error;
+
+const INDENT: &str = "    ";
+
+pub fn format(source: &str) -> Result<String, Error> {
+    // Подключаемые файлы не читаются, от директивы нужен только путь
+    let program = Interpreter::new(source)
+        .with_loader(|_| Ok(String::new()))
+        .parse()?;
+    Ok(format_program(&program))
+}
+
+pub fn format_program(program: &Program) -> String {
+    let mut output = String::new();
+    write_block(&mut output, &program.statements, 0);
+    output
+}
+
+fn write_block(output: &mut String, statements: &[Stmt], depth: usize) {
+    for statement in statements {
+        write_statement(output, statement, depth);
+    }
+}
+
+fn write_statement(output: &mut String, statement: &Stmt, depth: usize) {
+    output.push_str(&INDENT.repeat(depth));
+
+    match &statement.kind {
+        StmtKind::Move(distance) => write_command(output, "move", distance),
+        StmtKind::Back(distance) => write_command(output, "back", distance),
+        StmtKind::Goto { x, y } => {
+            // Вторая координата со знаком минус слилась бы с первой в одно выражение
+            let y = expression(y);
+            if y.starts_with('-') {
+                let _ = write!(output, "goto {} ({y})", expression(x));
+            } else {
+                let _ = write!(output, "goto {} {y}", expression(x));
+            }
+        }
+        StmtKind::Face(direction) => {
+            let _ = write!(output, "face {direction}");
+        }
+        StmtKind::Color(color) => {
+            let _ = write!(output, "color {color}");
+        }
+        StmtKind::Width(width) => write_command(output, "width", width),
+        StmtKind::Refuel(amount) => write_command(output, "refuel", amount),
+        StmtKind::Stamp(StampShape::Dot) => output.push_str("stamp dot"),
+        StmtKind::Stamp(StampShape::Square(size)) => write_command(output, "stamp square", size),
+        StmtKind::Stamp(StampShape::Circle(radius)) => {
+            write_command(output, "stamp circle", radius)
+        }
+        StmtKind::Write(text) => {
+            let _ = write!(output, "write \"{text}\"");
+        }
+        StmtKind::TurnLeft(turn) => write_command(output, "turn_left", &turn.amount),
+        StmtKind::TurnRight(turn) => write_command(output, "turn_right", &turn.amount),
+        StmtKind::TurnLeft45(times) => write_command(output, "turn_left_45", times),
+        StmtKind::TurnRight45(times) => write_command(output, "turn_right_45", times),
+        StmtKind::DownPen => output.push_str("down_pen"),
+        StmtKind::UpPen => output.push_str("up_pen"),
+        StmtKind::Home => output.push_str("home"),
+        StmtKind::Repeat { times, body } => {
+            write_command(output, "repeat", times);
+            write_body(output, body, depth);
+        }
+        StmtKind::Def { name, body } => {
+            let _ = writeln!(output, "def {name}");
+            write_block(output, body, depth + 1);
+            output.push_str(&INDENT.repeat(depth));
+            output.push_str("end");
+        }
+        StmtKind::Let { name, value } => {
+            let _ = write!(output, "let {name} = {}", expression(value));
+        }
+        StmtKind::If {
+            condition,
+            then_body,
+            else_body,
+        } => {
+            write_command(output, "if", condition);
+            write_body(output, then_body, depth);
+            if let Some(else_body) = else_body {
+                output.push_str(" else");
+                write_body(output, else_body, depth);
+            }
+        }
+        StmtKind::While { condition, body } => {
+            write_command(output, "while", condition);
+            write_body(output, body, depth);
+        }
+        StmtKind::Call(name) => output.push_str(name),
+        StmtKind::Custom { name, args } => {
+            output.push_str(name);
+            for (index, arg) in args.iter().enumerate() {
+                // Аргумент со знаком минус слился бы с предыдущим в одно выражение
+                let arg = expression(arg);
+                if index > 0 && arg.starts_with('-') {
+                    let _ = write!(output, " ({arg})");
+                } else {
+                    let _ = write!(output, " {arg}");
+                }
+            }
+        }
+        StmtKind::Target { robot, body } => {
+            let _ = write!(output, "robot {robot}:");
+            if let [statement] = body.as_slice() {
+                // Одна инструкция печатается в той же строке, без своего отступа и перевода строки
+                let mut line = String::new();
+                write_statement(&mut line, statement, depth);
+                let _ = write!(output, " {}", line.trim_start().trim_end());
+            } else {
+                write_body(output, body, depth);
+            }
+        }
+        StmtKind::Include { path, .. } => {
+            let _ = write!(output, "include \"{path}\"");
+        }
+    }
+
+    output.push('\n');
+}
+
+fn write_command(output: &mut String, keyword: &str, argument: &Expr) {
+    let _ = write!(output, "{keyword} {}", expression(argument));
+}
+
+// Печатает блок "[ ... ]" после инструкции, пустой блок остается на той же строке
+fn write_body(output: &mut String, body: &[Stmt], depth: usize) {
+    if body.is_empty() {
+        output.push_str(" [ ]");
+        return;
+    }
+
+    output.push_str(" [\n");
+    write_block(output, body, depth + 1);
+    output.push_str(&INDENT.repeat(depth));
+    output.push(']');
+}
+
+// Приоритеты выражений в порядке грамматики парсера, от слабого к сильному
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+const COMPARISON: u8 = 4;
+const SUM: u8 = 5;
+const TERM: u8 = 6;
+const FACTOR: u8 = 7;
+
+fn expression(expr: &Expr) -> String {
+    let mut output = String::new();
+    write_expression(&mut output, expr, OR);
+    output
+}
+
+// Печатает выражение, заключая его в скобки, если его приоритет ниже требуемого
+fn write_expression(output: &mut String, expr: &Expr, min_precedence: u8) {
+    let precedence = precedence(expr);
+    if precedence < min_precedence {
+        output.push('(');
+        write_expression(output, expr, OR);
+        output.push(')');
+        return;
+    }
+
+    match &expr.kind {
+        ExprKind::Number(number) => {
+            let _ = write!(output, "{number}");
+        }
+        ExprKind::Variable(name) => output.push_str(name),
+        ExprKind::Sensor(Sensor::Blocked) => output.push_str("blocked"),
+        ExprKind::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => {
+            output.push('-');
+            write_expression(output, operand, FACTOR);
+        }
+        ExprKind::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } => {
+            output.push_str("not ");
+            write_expression(output, operand, NOT);
+        }
+        ExprKind::Binary { op, lhs, rhs } => {
+            // Сравнения не объединяются в цепочки, остальные операции левоассоциативны
+            let lhs_precedence = if precedence == COMPARISON {
+                SUM
+            } else {
+                precedence
+            };
+            write_expression(output, lhs, lhs_precedence);
+            let _ = write!(output, " {} ", operator(*op));
+            write_expression(output, rhs, precedence + 1);
+        }
+        ExprKind::Random { min, max } => {
+            output.push_str("random ");
+            write_expression(output, min, FACTOR);
+            output.push(' ');
+            write_expression(output, max, FACTOR);
+        }
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match &expr.kind {
+        ExprKind::Unary {
+            op: UnaryOp::Not, ..
+        } => NOT,
+        ExprKind::Binary { op, .. } => match op {
+            BinaryOp::Or => OR,
+            BinaryOp::And => AND,
+            BinaryOp::Add | BinaryOp::Sub => SUM,
+            BinaryOp::Mul | BinaryOp::Div => TERM,
+            _ => COMPARISON,
+        },
+        _ => FACTOR,
+    }
+}
+
+fn operator(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::Less => "<",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::Greater => ">",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::And => "and",
+        BinaryOp::Or => "or",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_blocks_and_procedures() {
+        let source = "def side MOVE   10 rt 1 end   repeat 4[side]\n\
+                      if n>1 [down_pen] else [ ] while not blocked [move 1] up_pen FACE Left color Red";
+        let expected = "\
+def side
+    move 10
+    turn_right 1
+end
+repeat 4 [
+    side
+]
+if n > 1 [
+    down_pen
+] else [ ]
+while not blocked [
+    move 1
+]
+up_pen
+face left
+color red
+";
+        assert_eq!(format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_nested_blocks() {
+        let source = "repeat 2 [ repeat 3 [ move 1 ] let x = 1 ]";
+        let expected = "repeat 2 [\n    repeat 3 [\n        move 1\n    ]\n    let x = 1\n]\n";
+        assert_eq!(format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_keeps_only_required_parentheses() {
+        let source = "let a = ((1 + 2)) * (3 - (4 - 5)) / -(6) \
+                      let b = not (a > 1 or a < (0)) and (a + 1 == 2) \
+                      move random (a + 1) 10 goto -(a) (-a - 1)";
+        let expected = "\
+let a = (1 + 2) * (3 - (4 - 5)) / -6
+let b = not (a > 1 or a < 0) and a + 1 == 2
+move random (a + 1) 10
+goto -a (-a - 1)
+";
+        assert_eq!(format(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        let source = "include \"lib.robot\" # library\n\
+                      def step back 1 /* back */ turn_left 1 end\n\
+                      repeat times=2 [ if a - (b - c) <= 2 * (d + 1) [ step ] ]";
+        let formatted = format(source).unwrap();
+        assert!(formatted.starts_with("include \"lib.robot\"\n"));
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_registered_command() {
+        let registry = super::super::CommandRegistry::new().with("jump", &["x", "y"], |_| {
+            Box::new(crate::command::UpPenCommand)
+        });
+        let program = Interpreter::new("jump x=1+1 y=-2")
+            .with_registry(registry)
+            .parse()
+            .unwrap();
+        assert_eq!(format_program(&program), "jump 1 + 1 (-2)\n");
+    }
+
+    #[test]
+    fn test_format_robot_target() {
+        let source = "repeat 2 [ ROBOT a:move 1 robot b : repeat 2 [ rt 1 ] robot c: [ ] ] \
+                      robot a: [ move 1 rt 1 ]";
+        let expected = "\
+repeat 2 [
+    robot a: move 1
+    robot b: repeat 2 [
+        turn_right 1
+    ]
+    robot c: [ ]
+]
+robot a: [
+    move 1
+    turn_right 1
+]
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_reports_syntax_errors() {
+        assert!(matches!(format("move"), Err(Error::InvalidCommand(_))));
+    }
+}
@@ -0,0 +1,130 @@
+// Подключение файлов директивой "include".
+// Файл ищется сначала рядом с подключающим его файлом, затем в каталогах пути поиска.
+// Чтение файлов выполняет загрузчик, который можно заменить, например чтобы
+// подключать скрипты из памяти или архива вместо файловой системы.
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+pub type Loader<'a> = Box<dyn FnMut(&Path) -> io::Result<String> + 'a>;
+
+pub struct Includes<'a> {
+    loader: Loader<'a>,
+    search_path: Vec<PathBuf>,
+    // Файлы, которые подключаются в данный момент, для обнаружения циклов
+    stack: Vec<PathBuf>,
+}
+
+impl Default for Includes<'_> {
+    fn default() -> Self {
+        Self {
+            loader: Box::new(|path| fs::read_to_string(path)),
+            // Пустой каталог означает путь относительно текущего каталога процесса
+            search_path: vec![PathBuf::new()],
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Includes<'a> {
+    pub fn set_loader(&mut self, loader: Loader<'a>) {
+        self.loader = loader;
+    }
+
+    pub fn set_search_path(&mut self, search_path: Vec<PathBuf>) {
+        self.search_path = search_path;
+    }
+
+    // Находит и читает файл, возвращает путь, по которому он был найден, и его содержимое
+    pub fn load(&mut self, name: &str) -> io::Result<(PathBuf, String)> {
+        let mut last_error = io::Error::from(io::ErrorKind::NotFound);
+
+        for candidate in self.candidates(name) {
+            match (self.loader)(&candidate) {
+                Ok(source) => return Ok((candidate, source)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => last_error = err,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    pub fn is_active(&self, path: &Path) -> bool {
+        self.stack.iter().any(|active| active == path)
+    }
+
+    pub fn enter(&mut self, path: PathBuf) {
+        self.stack.push(path);
+    }
+
+    pub fn leave(&mut self) {
+        self.stack.pop();
+    }
+
+    fn candidates(&self, name: &str) -> Vec<PathBuf> {
+        let name = Path::new(name);
+        if name.is_absolute() {
+            return vec![normalize(name)];
+        }
+
+        let current_dir = self.stack.last().and_then(|path| path.parent());
+        current_dir
+            .into_iter()
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| normalize(&dir.join(name)))
+            .collect()
+    }
+}
+
+// Убирает из пути "." и "..", чтобы один и тот же файл всегда имел один путь
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("lib/./shapes/../square.robot")),
+            PathBuf::from("lib/square.robot")
+        );
+        assert_eq!(
+            normalize(Path::new("../square.robot")),
+            PathBuf::from("../square.robot")
+        );
+    }
+
+    #[test]
+    fn test_search_order() {
+        let mut includes = Includes::default();
+        includes.set_search_path(vec![PathBuf::from("std"), PathBuf::from("lib")]);
+        includes.enter(PathBuf::from("scripts/main.robot"));
+
+        assert_eq!(
+            includes.candidates("shapes.robot"),
+            [
+                PathBuf::from("scripts/shapes.robot"),
+                PathBuf::from("std/shapes.robot"),
+                PathBuf::from("lib/shapes.robot"),
+            ]
+        );
+    }
+}
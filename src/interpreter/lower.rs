@@ -0,0 +1,606 @@
+// Превращение синтаксического дерева в список команд.
+// Циклы и вызовы процедур разворачиваются, выражения вычисляются в окружении
+// переменных, а условия выбирают одну из веток в момент разворачивания.
+// Цикл "while" с переменными или случайными числами в условии разворачивается так же,
+// с проверкой условия перед каждым повтором. Остальные циклы "while" проверяются во время
+// выполнения: тело разворачивается один раз, а условие с датчиками робота становится
+// условием команды, поэтому переменные в таком теле менять нельзя.
+
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    Span,
+    ast::*,
+    random::{RandomSource, SplitMix64},
+    registry::CommandRegistry,
+};
+use crate::{command::*, error::Error};
+
+// Переменные и процедуры, объявленные в программе, и источник случайных чисел
+#[derive(Debug)]
+pub struct Environment {
+    variables: HashMap<String, i64>,
+    procedures: HashMap<String, Rc<[Stmt]>>,
+    rng: Box<dyn RandomSource>,
+    registry: Rc<CommandRegistry>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            variables: HashMap::new(),
+            procedures: HashMap::new(),
+            rng: Box::new(SplitMix64::from_time()),
+            registry: Rc::default(),
+        }
+    }
+}
+
+impl Environment {
+    pub fn variable(&self, name: &str) -> Option<i64> {
+        self.variables.get(name).copied()
+    }
+
+    pub fn set_rng(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+
+    pub fn set_registry(&mut self, registry: Rc<CommandRegistry>) {
+        self.registry = registry;
+    }
+}
+
+pub struct Lowering<'e> {
+    env: &'e mut Environment,
+    depth: usize,
+    max_depth: usize,
+    // Развернутые инструкции и повторы циклов, чтобы "repeat 4000000000 [ ... ]"
+    // завершался ошибкой, а не занимал всю память
+    expanded: usize,
+    max_commands: usize,
+}
+
+impl<'e> Lowering<'e> {
+    pub fn new(env: &'e mut Environment, max_depth: usize) -> Self {
+        Self {
+            env,
+            depth: 0,
+            max_depth,
+            expanded: 0,
+            max_commands: super::DEFAULT_MAX_COMMANDS,
+        }
+    }
+
+    pub fn with_max_commands(mut self, max_commands: usize) -> Self {
+        self.max_commands = max_commands;
+        self
+    }
+
+    pub fn lower_program(&mut self, program: &Program) -> Result<CommandList, Error> {
+        let mut command_list = CommandList::default();
+        self.lower_block(&program.statements, &mut command_list)?;
+        Ok(command_list)
+    }
+
+    fn lower_block(
+        &mut self,
+        statements: &[Stmt],
+        command_list: &mut CommandList,
+    ) -> Result<(), Error> {
+        for statement in statements {
+            self.lower_statement(statement, command_list)?;
+        }
+        Ok(())
+    }
+
+    pub fn lower_statement(
+        &mut self,
+        statement: &Stmt,
+        command_list: &mut CommandList,
+    ) -> Result<(), Error> {
+        self.expand(statement.span)?;
+        match &statement.kind {
+            StmtKind::Move(distance) => {
+                let value = self.evaluate(distance)?;
+                add_move(command_list, value, distance.span)?;
+            }
+            StmtKind::Back(distance) => {
+                let value = self.evaluate(distance)?;
+                let value = value
+                    .checked_neg()
+                    .ok_or(Error::ArithmeticOverflow(distance.span))?;
+                add_move(command_list, value, distance.span)?;
+            }
+            StmtKind::Goto { x, y } => {
+                let x = self.coordinate(x)?;
+                let y = self.coordinate(y)?;
+                command_list.add_command(Box::new(TeleportCommand::new(x, y)));
+            }
+            StmtKind::Face(direction) => {
+                command_list.add_command(Box::new(SetDirectionCommand::new(*direction)));
+            }
+            StmtKind::Color(color) => {
+                command_list.add_command(Box::new(PenColorCommand::new(*color)));
+            }
+            StmtKind::Width(width) => {
+                let value = self.evaluate(width)?;
+                let width = pen_width(value, width.span)?;
+                command_list.add_command(Box::new(PenWidthCommand::new(width)));
+            }
+            StmtKind::Refuel(amount) => {
+                let amount = self.unsigned(amount)?;
+                command_list.add_command(Box::new(RefuelCommand::new(amount)));
+            }
+            StmtKind::Stamp(StampShape::Dot) => {
+                command_list.add_command(Box::new(StampCommand::dot()))
+            }
+            StmtKind::Stamp(StampShape::Square(size)) => {
+                let size = self.unsigned(size)?;
+                command_list.add_command(Box::new(StampCommand::square(size)));
+            }
+            StmtKind::Stamp(StampShape::Circle(radius)) => {
+                let radius = self.unsigned(radius)?;
+                command_list.add_command(Box::new(StampCommand::circle(radius)));
+            }
+            StmtKind::Write(text) => command_list.add_command(Box::new(WriteCommand::new(text))),
+            StmtKind::TurnLeft(turn) => {
+                let times = self.quarter_turns(turn)?;
+                command_list.add_command(Box::new(TurnLeftCommand::new(times)));
+            }
+            StmtKind::TurnRight(turn) => {
+                let times = self.quarter_turns(turn)?;
+                command_list.add_command(Box::new(TurnRightCommand::new(times)));
+            }
+            StmtKind::TurnLeft45(times) => {
+                let times = self.unsigned(times)?;
+                command_list.add_command(Box::new(TurnLeft45Command::new(times)));
+            }
+            StmtKind::TurnRight45(times) => {
+                let times = self.unsigned(times)?;
+                command_list.add_command(Box::new(TurnRight45Command::new(times)));
+            }
+            StmtKind::DownPen => command_list.add_command(Box::new(DownPenCommand)),
+            StmtKind::UpPen => command_list.add_command(Box::new(UpPenCommand)),
+            StmtKind::Home => command_list.add_command(Box::new(HomeCommand::new())),
+            StmtKind::Repeat { times, body } => {
+                let times = self.unsigned(times)?;
+                for _ in 0..times {
+                    self.expand(statement.span)?;
+                    self.nested(statement.span, |lowering| {
+                        lowering.lower_block(body, command_list)
+                    })?;
+                }
+            }
+            StmtKind::Def { name, body } => {
+                self.env
+                    .procedures
+                    .insert(name.clone(), body.as_slice().into());
+            }
+            StmtKind::Let { name, value } => {
+                let value = self.evaluate(value)?;
+                self.env.variables.insert(name.clone(), value);
+            }
+            StmtKind::If {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let body = if self.evaluate(condition)? != 0 {
+                    then_body.as_slice()
+                } else {
+                    else_body.as_deref().unwrap_or_default()
+                };
+                self.nested(statement.span, |lowering| {
+                    lowering.lower_block(body, command_list)
+                })?;
+            }
+            StmtKind::While { condition, body }
+                if !uses_sensors(condition) && !is_constant(condition) =>
+            {
+                while self.evaluate(condition)? != 0 {
+                    self.expand(statement.span)?;
+                    self.nested(statement.span, |lowering| {
+                        lowering.lower_block(body, command_list)
+                    })?;
+                }
+            }
+            StmtKind::While { condition, body } => {
+                if let Some((name, span)) = self.assignment(body, &mut Vec::new()) {
+                    return Err(Error::AssignmentInWhile(name.to_string(), span));
+                }
+                let condition = self.condition(condition)?;
+                let mut body_commands = CommandList::default();
+                self.nested(statement.span, |lowering| {
+                    lowering.lower_block(body, &mut body_commands)
+                })?;
+                command_list.add_command(Box::new(WhileCommand::new(condition, body_commands)));
+            }
+            StmtKind::Call(name) => {
+                let body = match self.env.procedures.get(name) {
+                    Some(body) => Rc::clone(body),
+                    None => return Err(Error::UndefinedCommand(name.clone(), statement.span)),
+                };
+                self.nested(statement.span, |lowering| {
+                    lowering.lower_block(&body, command_list)
+                })?;
+            }
+            StmtKind::Custom { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match self.env.registry.create(name, &args) {
+                    Some(command) => command_list.add_command(command),
+                    None => return Err(Error::UndefinedCommand(name.clone(), statement.span)),
+                }
+            }
+            StmtKind::Target { robot, body } => {
+                let mut body_commands = CommandList::default();
+                self.lower_block(body, &mut body_commands)?;
+                let mut commands: Vec<_> = body_commands.into_iter().collect();
+                let command: Box<dyn Command> = match commands.len() {
+                    0 => return Ok(()),
+                    1 => commands.remove(0),
+                    _ => Box::new(CompositeCommand::new(commands)),
+                };
+                command_list.add_command(Box::new(TargetCommand::new(robot.clone(), command)));
+            }
+            StmtKind::Include { program, .. } => {
+                self.lower_block(&program.statements, command_list)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Следит за ограничением глубины вложенности циклов, ветвлений и вызовов процедур
+    fn nested<T>(
+        &mut self,
+        span: Span,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::MaxDepthExceeded(self.max_depth, span));
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expand(&mut self, span: Span) -> Result<(), Error> {
+        if self.expanded >= self.max_commands {
+            return Err(Error::TooManyCommands(self.max_commands, span));
+        }
+        self.expanded += 1;
+        Ok(())
+    }
+
+    // Части условия без датчиков вычисляются сразу, датчики можно сочетать только
+    // логическими операциями, результат которых проверяется во время выполнения
+    fn condition(&mut self, expr: &Expr) -> Result<Condition, Error> {
+        if !uses_sensors(expr) {
+            return Ok(Condition::Value(self.evaluate(expr)? != 0));
+        }
+
+        match &expr.kind {
+            ExprKind::Sensor(Sensor::Blocked) => Ok(Condition::Blocked),
+            ExprKind::Unary {
+                op: UnaryOp::Not,
+                operand,
+            } => Ok(Condition::Not(Box::new(self.condition(operand)?))),
+            ExprKind::Binary {
+                op: op @ (BinaryOp::And | BinaryOp::Or),
+                lhs,
+                rhs,
+            } => {
+                let lhs = Box::new(self.condition(lhs)?);
+                let rhs = Box::new(self.condition(rhs)?);
+                Ok(match op {
+                    BinaryOp::And => Condition::And(lhs, rhs),
+                    _ => Condition::Or(lhs, rhs),
+                })
+            }
+            _ => Err(Error::InvalidSensorUse(expr.span)),
+        }
+    }
+
+    // Первое присваивание переменной в инструкциях, в том числе в вызванных процедурах
+    fn assignment<'s>(
+        &'s self,
+        statements: &'s [Stmt],
+        visited: &mut Vec<&'s str>,
+    ) -> Option<(&'s str, Span)> {
+        statements
+            .iter()
+            .find_map(|statement| match &statement.kind {
+                StmtKind::Let { name, .. } => Some((name.as_str(), statement.span)),
+                StmtKind::Repeat { body, .. }
+                | StmtKind::While { body, .. }
+                | StmtKind::Def { body, .. }
+                | StmtKind::Target { body, .. } => self.assignment(body, visited),
+                StmtKind::If {
+                    then_body,
+                    else_body,
+                    ..
+                } => self
+                    .assignment(then_body, visited)
+                    .or_else(|| self.assignment(else_body.as_deref().unwrap_or_default(), visited)),
+                StmtKind::Include { program, .. } => self.assignment(&program.statements, visited),
+                StmtKind::Call(name) if !visited.contains(&name.as_str()) => {
+                    visited.push(name);
+                    let body = self.env.procedures.get(name)?;
+                    self.assignment(body, visited)
+                }
+                _ => None,
+            })
+    }
+
+    fn quarter_turns(&mut self, turn: &Turn) -> Result<u32, Error> {
+        let amount = self.evaluate(&turn.amount)?;
+        quarter_turns(amount, turn.unit, turn.amount.span)
+    }
+
+    // Вычисляет аргумент команды, который должен быть неотрицательным числом
+    fn unsigned(&mut self, expr: &Expr) -> Result<u32, Error> {
+        let value = self.evaluate(expr)?;
+        unsigned(value, expr.span)
+    }
+
+    fn coordinate(&mut self, expr: &Expr) -> Result<i32, Error> {
+        let value = self.evaluate(expr)?;
+        coordinate(value, expr.span)
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<i64, Error> {
+        evaluate(self.env, expr)
+    }
+}
+
+pub fn evaluate(env: &mut Environment, expr: &Expr) -> Result<i64, Error> {
+    match &expr.kind {
+        ExprKind::Number(number) => Ok(*number),
+        ExprKind::Variable(name) => env
+            .variable(name)
+            .ok_or_else(|| Error::UndefinedVariable(name.clone(), expr.span)),
+        ExprKind::Unary { op, operand } => {
+            let value = evaluate(env, operand)?;
+            apply_unary(*op, value, expr.span)
+        }
+        ExprKind::Binary { op, lhs, rhs } => {
+            let lhs = evaluate(env, lhs)?;
+            let rhs = evaluate(env, rhs)?;
+            apply_binary(*op, lhs, rhs, expr.span)
+        }
+        ExprKind::Sensor(_) => Err(Error::InvalidSensorUse(expr.span)),
+        ExprKind::Random { min, max } => {
+            let min = evaluate(env, min)?;
+            let max = evaluate(env, max)?;
+            random(env.rng.as_mut(), min, max, expr.span)
+        }
+    }
+}
+
+// Логические операции и сравнения возвращают 1 или 0, любое ненулевое значение истинно
+pub fn apply_unary(op: UnaryOp, value: i64, span: Span) -> Result<i64, Error> {
+    match op {
+        UnaryOp::Neg => value.checked_neg().ok_or(Error::ArithmeticOverflow(span)),
+        UnaryOp::Not => Ok(i64::from(value == 0)),
+    }
+}
+
+pub fn apply_binary(op: BinaryOp, lhs: i64, rhs: i64, span: Span) -> Result<i64, Error> {
+    let overflow = || Error::ArithmeticOverflow(span);
+
+    match op {
+        BinaryOp::Add => lhs.checked_add(rhs).ok_or_else(overflow),
+        BinaryOp::Sub => lhs.checked_sub(rhs).ok_or_else(overflow),
+        BinaryOp::Mul => lhs.checked_mul(rhs).ok_or_else(overflow),
+        BinaryOp::Div if rhs == 0 => Err(Error::DivisionByZero(span)),
+        BinaryOp::Div => lhs.checked_div(rhs).ok_or_else(overflow),
+        BinaryOp::Equal => Ok(i64::from(lhs == rhs)),
+        BinaryOp::NotEqual => Ok(i64::from(lhs != rhs)),
+        BinaryOp::Less => Ok(i64::from(lhs < rhs)),
+        BinaryOp::LessEqual => Ok(i64::from(lhs <= rhs)),
+        BinaryOp::Greater => Ok(i64::from(lhs > rhs)),
+        BinaryOp::GreaterEqual => Ok(i64::from(lhs >= rhs)),
+        BinaryOp::And => Ok(i64::from(lhs != 0 && rhs != 0)),
+        BinaryOp::Or => Ok(i64::from(lhs != 0 || rhs != 0)),
+    }
+}
+
+fn uses_sensors(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Number(_) | ExprKind::Variable(_) => false,
+        ExprKind::Sensor(_) => true,
+        ExprKind::Unary { operand, .. } => uses_sensors(operand),
+        ExprKind::Binary { lhs, rhs, .. } => uses_sensors(lhs) || uses_sensors(rhs),
+        ExprKind::Random { min, max } => uses_sensors(min) || uses_sensors(max),
+    }
+}
+
+// Выражение из одних чисел, значение которого не меняется между повторами цикла
+fn is_constant(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Number(_) => true,
+        ExprKind::Variable(_) | ExprKind::Sensor(_) | ExprKind::Random { .. } => false,
+        ExprKind::Unary { operand, .. } => is_constant(operand),
+        ExprKind::Binary { lhs, rhs, .. } => is_constant(lhs) && is_constant(rhs),
+    }
+}
+
+pub fn random(rng: &mut dyn RandomSource, min: i64, max: i64, span: Span) -> Result<i64, Error> {
+    if min > max {
+        return Err(Error::InvalidCommandParameter(
+            format!("random {min} {max}"),
+            span,
+        ));
+    }
+    Ok(rng.range(min, max))
+}
+
+pub fn unsigned(value: i64, span: Span) -> Result<u32, Error> {
+    u32::try_from(value).map_err(|_| Error::InvalidCommandParameter(value.to_string(), span))
+}
+
+// Координата клетки должна помещаться в диапазон координат робота
+pub fn coordinate(value: i64, span: Span) -> Result<i32, Error> {
+    i32::try_from(value).map_err(|_| Error::InvalidCommandParameter(value.to_string(), span))
+}
+
+pub fn pen_width(value: i64, span: Span) -> Result<u32, Error> {
+    match unsigned(value, span)? {
+        0 => Err(Error::InvalidCommandParameter(value.to_string(), span)),
+        width => Ok(width),
+    }
+}
+
+// Переводит аргумент поворота в количество поворотов на 90 градусов
+pub fn quarter_turns(amount: i64, unit: TurnUnit, span: Span) -> Result<u32, Error> {
+    let amount = unsigned(amount, span)?;
+    match unit {
+        TurnUnit::QuarterTurns => Ok(amount),
+        TurnUnit::Degrees if amount % 90 == 0 => Ok(amount / 90),
+        TurnUnit::Degrees => Err(Error::InvalidCommandParameter(amount.to_string(), span)),
+    }
+}
+
+// Количество шагов для перемещения на расстояние со знаком
+pub fn steps(distance: i64, span: Span) -> Result<u32, Error> {
+    u32::try_from(distance.unsigned_abs())
+        .map_err(|_| Error::InvalidCommandParameter(distance.to_string(), span))
+}
+
+// Отрицательное расстояние означает движение назад без разворота
+fn add_move(command_list: &mut CommandList, distance: i64, span: Span) -> Result<(), Error> {
+    let steps = steps(distance, span)?;
+
+    if distance < 0 {
+        command_list.add_command(Box::new(MoveBackwardCommand::new(steps)));
+    } else {
+        command_list.add_command(Box::new(MoveCommand::new(steps)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::DEFAULT_MAX_DEPTH;
+
+    fn number(value: i64) -> Expr {
+        Expr::new(ExprKind::Number(value), Span::default())
+    }
+
+    fn statement(kind: StmtKind) -> Stmt {
+        Stmt::new(kind, Span::default())
+    }
+
+    #[test]
+    fn test_lower_hand_built_program() {
+        let program = Program {
+            statements: vec![statement(StmtKind::Repeat {
+                times: number(3),
+                body: vec![
+                    statement(StmtKind::Move(number(2))),
+                    statement(StmtKind::TurnRight(Turn {
+                        amount: number(1),
+                        unit: TurnUnit::QuarterTurns,
+                    })),
+                ],
+            })],
+        };
+
+        let mut env = Environment::default();
+        let commands = Lowering::new(&mut env, DEFAULT_MAX_DEPTH)
+            .lower_program(&program)
+            .unwrap();
+        assert_eq!(commands.commands().len(), 6);
+    }
+
+    #[test]
+    fn test_environment_is_shared_between_programs() {
+        let mut env = Environment::default();
+
+        let first = Program {
+            statements: vec![statement(StmtKind::Let {
+                name: "n".to_string(),
+                value: number(4),
+            })],
+        };
+        Lowering::new(&mut env, DEFAULT_MAX_DEPTH)
+            .lower_program(&first)
+            .unwrap();
+
+        let second = Program {
+            statements: vec![statement(StmtKind::Let {
+                name: "m".to_string(),
+                value: Expr::new(
+                    ExprKind::Binary {
+                        op: BinaryOp::Mul,
+                        lhs: Box::new(Expr::new(
+                            ExprKind::Variable("n".to_string()),
+                            Span::default(),
+                        )),
+                        rhs: Box::new(number(2)),
+                    },
+                    Span::default(),
+                ),
+            })],
+        };
+        Lowering::new(&mut env, DEFAULT_MAX_DEPTH)
+            .lower_program(&second)
+            .unwrap();
+        assert_eq!(env.variable("m"), Some(8));
+    }
+
+    #[test]
+    fn test_degrees_must_be_multiple_of_90() {
+        let program = Program {
+            statements: vec![statement(StmtKind::TurnLeft(Turn {
+                amount: number(30),
+                unit: TurnUnit::Degrees,
+            }))],
+        };
+
+        let mut env = Environment::default();
+        let result = Lowering::new(&mut env, DEFAULT_MAX_DEPTH).lower_program(&program);
+        assert!(matches!(result, Err(Error::InvalidCommandParameter(value, _)) if value == "30"));
+    }
+
+    #[test]
+    fn test_expansion_limit() {
+        let repeat = |times, body| {
+            statement(StmtKind::Repeat {
+                times: number(times),
+                body,
+            })
+        };
+        let program = Program {
+            statements: vec![repeat(
+                4_000_000_000,
+                vec![statement(StmtKind::Move(number(1)))],
+            )],
+        };
+        let mut env = Environment::default();
+        let result = Lowering::new(&mut env, DEFAULT_MAX_DEPTH).lower_program(&program);
+        assert!(matches!(
+            result,
+            Err(Error::TooManyCommands(
+                crate::interpreter::DEFAULT_MAX_COMMANDS,
+                _
+            ))
+        ));
+
+        // Пустой цикл тоже считается, иначе он бы долго ничего не разворачивал
+        let program = Program {
+            statements: vec![repeat(10, vec![])],
+        };
+        let mut lowering = Lowering::new(&mut env, DEFAULT_MAX_DEPTH).with_max_commands(10);
+        assert!(lowering.lower_program(&program).is_err());
+        let mut lowering = Lowering::new(&mut env, DEFAULT_MAX_DEPTH).with_max_commands(11);
+        assert!(lowering.lower_program(&program).unwrap().is_empty());
+    }
+}
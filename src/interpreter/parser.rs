@@ -0,0 +1,702 @@
+// Парсер строит синтаксическое дерево программы из потока токенов сканера.
+// На этом этапе проверяется только синтаксис: имена процедур и переменных,
+// а также значения аргументов проверяются при превращении дерева в команды.
+
+use std::{io, mem, rc::Rc};
+
+use super::{CommandRegistry, Dialect, Scanner, Span, Token, ast::*, include::Includes};
+use crate::{
+    error::Error,
+    robot::{Color, Direction},
+};
+
+pub struct Parser<'a> {
+    scanner: Scanner<'a>,
+    dialect: Dialect,
+    // Заглянутые вперед токены, следующий токен находится в конце
+    peeked: Vec<(Token, Span)>,
+    // Положение последнего прочитанного токена, используется для ошибок конца ввода
+    last_span: Span,
+    depth: usize,
+    max_depth: usize,
+    includes: Includes<'a>,
+    registry: Rc<CommandRegistry>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(scanner: Scanner<'a>, dialect: Dialect, max_depth: usize) -> Self {
+        Self {
+            scanner,
+            dialect,
+            peeked: Vec::new(),
+            last_span: Span::default(),
+            depth: 0,
+            max_depth,
+            includes: Includes::default(),
+            registry: Rc::default(),
+        }
+    }
+
+    pub fn scanner_mut(&mut self) -> &mut Scanner<'a> {
+        &mut self.scanner
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn includes_mut(&mut self) -> &mut Includes<'a> {
+        &mut self.includes
+    }
+
+    pub fn set_registry(&mut self, registry: Rc<CommandRegistry>) {
+        self.registry = registry;
+    }
+
+    pub fn parse_program(&mut self) -> Result<Program, Error> {
+        let mut statements = Vec::new();
+        while let Some(statement) = self.statement()? {
+            statements.push(statement);
+        }
+        Ok(Program { statements })
+    }
+
+    // Разбирает одну инструкцию верхнего уровня, возвращает None в конце входа
+    pub fn statement(&mut self) -> Result<Option<Stmt>, Error> {
+        let Some((token, span)) = self.next_token()? else {
+            return Ok(None);
+        };
+
+        let kind = match token {
+            Token::Move => StmtKind::Move(self.argument("distance")?),
+            Token::Back => StmtKind::Back(self.argument("distance")?),
+            Token::Goto => {
+                let x = self.argument("x")?;
+                let y = self.argument("y")?;
+                StmtKind::Goto { x, y }
+            }
+            Token::Face => StmtKind::Face(self.direction()?),
+            Token::Color => StmtKind::Color(self.color()?),
+            Token::Width => StmtKind::Width(self.argument("width")?),
+            Token::Refuel => StmtKind::Refuel(self.argument("amount")?),
+            Token::Stamp => StmtKind::Stamp(self.stamp_shape()?),
+            Token::Write => StmtKind::Write(self.string()?),
+            Token::TurnLeft => StmtKind::TurnLeft(self.turn()?),
+            Token::TurnRight => StmtKind::TurnRight(self.turn()?),
+            Token::TurnLeft45 => StmtKind::TurnLeft45(self.argument("times")?),
+            Token::TurnRight45 => StmtKind::TurnRight45(self.argument("times")?),
+            Token::DownPen => StmtKind::DownPen,
+            Token::UpPen => StmtKind::UpPen,
+            Token::Home => StmtKind::Home,
+            Token::Repeat => {
+                let times = self.argument("times")?;
+                let body = self.block()?;
+                StmtKind::Repeat { times, body }
+            }
+            Token::Def => self.procedure(span)?,
+            Token::Let => {
+                let name = self.expect_identifier()?;
+                self.expect_token(Token::Assign)?;
+                let value = self.expression()?;
+                StmtKind::Let { name, value }
+            }
+            Token::If => {
+                let condition = self.expression()?;
+                let then_body = self.block()?;
+                let else_body = match self.peek_token()? {
+                    Some((Token::Else, _)) => {
+                        self.next_token()?;
+                        Some(self.block()?)
+                    }
+                    _ => None,
+                };
+                StmtKind::If {
+                    condition,
+                    then_body,
+                    else_body,
+                }
+            }
+            Token::While => {
+                let condition = self.expression()?;
+                let body = self.block()?;
+                StmtKind::While { condition, body }
+            }
+            Token::Robot => {
+                let robot = self.expect_identifier()?;
+                self.expect_token(Token::Colon)?;
+                let body = match self.peek_token()? {
+                    Some((Token::LeftBracket, _)) => self.block()?,
+                    _ => vec![
+                        self.nested(span, Self::statement)?
+                            .ok_or_else(|| self.end_of_input())?,
+                    ],
+                };
+                StmtKind::Target { robot, body }
+            }
+            Token::Include => self.include(span)?,
+            Token::Identifier(name) => self.call(name)?,
+            _ => return Err(Error::UnexpectedToken(token, span)),
+        };
+
+        Ok(Some(Stmt::new(kind, span.to(self.last_span))))
+    }
+
+    // Имя без ключевого слова - команда из реестра или вызов процедуры
+    fn call(&mut self, name: String) -> Result<StmtKind, Error> {
+        let registry = Rc::clone(&self.registry);
+        let Some(params) = registry.params(&name) else {
+            return Ok(StmtKind::Call(name));
+        };
+
+        let args = params
+            .iter()
+            .map(|param| self.argument(param))
+            .collect::<Result<_, _>>()?;
+        Ok(StmtKind::Custom { name, args })
+    }
+
+    // Пропускает токены после ошибки до начала следующей инструкции, чтобы продолжить разбор.
+    // Ошибки сканера при пропуске возвращаются по одной, после каждой можно продолжить пропуск.
+    pub fn synchronize(&mut self) -> Result<(), Error> {
+        while let Some((token, _)) = self.peek_token()? {
+            if starts_statement(token) {
+                break;
+            }
+            self.next_token()?;
+        }
+        Ok(())
+    }
+
+    // Разбирает блок "[ ... ]" вместе с вложенными блоками
+    fn block(&mut self) -> Result<Vec<Stmt>, Error> {
+        let open_span = self.expect_token(Token::LeftBracket)?;
+        self.nested(open_span, |parser| {
+            let mut body = Vec::new();
+            loop {
+                match parser.peek_token()? {
+                    Some((Token::RightBracket, _)) => {
+                        parser.next_token()?;
+                        break;
+                    }
+                    Some(_) => body.extend(parser.statement()?),
+                    None => return Err(Error::UnclosedBlock(open_span)),
+                }
+            }
+            Ok(body)
+        })
+    }
+
+    // Разбирает процедуру "def <name> ... end", вложенные объявления процедур запрещены
+    fn procedure(&mut self, def_span: Span) -> Result<StmtKind, Error> {
+        let name = self.expect_identifier()?;
+        let body = self.nested(def_span, |parser| {
+            let mut body = Vec::new();
+            loop {
+                match parser.peek_token()? {
+                    Some((Token::End, _)) => {
+                        parser.next_token()?;
+                        break;
+                    }
+                    Some((Token::Def, span)) => {
+                        return Err(Error::UnexpectedToken(Token::Def, *span));
+                    }
+                    Some(_) => body.extend(parser.statement()?),
+                    None => return Err(Error::UnclosedBlock(def_span)),
+                }
+            }
+            Ok(body)
+        })?;
+
+        Ok(StmtKind::Def { name, body })
+    }
+
+    // Разбирает подключаемый файл "include "<path>"" тем же диалектом и ключевыми словами.
+    // Подключение файла, который уже подключается выше по цепочке, считается циклом.
+    fn include(&mut self, include_span: Span) -> Result<StmtKind, Error> {
+        let path = self.string()?;
+
+        let (resolved, source) = self
+            .includes
+            .load(&path)
+            .map_err(|err| Error::IncludeFailed(path.clone(), err, include_span))?;
+        if self.includes.is_active(&resolved) {
+            return Err(Error::IncludeCycle(path, include_span));
+        }
+        if self.depth >= self.max_depth {
+            return Err(Error::MaxDepthExceeded(self.max_depth, include_span));
+        }
+
+        let scanner =
+            Scanner::from_reader(io::Cursor::new(source), self.scanner.keywords().clone());
+        let mut parser = Parser::new(scanner, self.dialect, self.max_depth);
+        parser.depth = self.depth + 1;
+        parser.registry = Rc::clone(&self.registry);
+        parser.includes = mem::take(&mut self.includes);
+        parser.includes.enter(resolved);
+
+        let program = parser.parse_program();
+
+        self.includes = mem::take(&mut parser.includes);
+        self.includes.leave();
+        Ok(StmtKind::Include {
+            path,
+            program: program?,
+        })
+    }
+
+    // Следит за ограничением глубины вложенности блоков и выражений
+    fn nested<T>(
+        &mut self,
+        span: Span,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::MaxDepthExceeded(self.max_depth, span));
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Аргумент поворота: в собственном языке количество поворотов, в Logo градусы
+    fn turn(&mut self) -> Result<Turn, Error> {
+        let (name, unit) = match self.dialect {
+            Dialect::Native => ("times", TurnUnit::QuarterTurns),
+            Dialect::Logo => ("angle", TurnUnit::Degrees),
+        };
+        let amount = self.argument(name)?;
+        Ok(Turn { amount, unit })
+    }
+
+    // Направление записывается словом, в английском или русском варианте
+    fn direction(&mut self) -> Result<Direction, Error> {
+        let (name, span) = self.word()?;
+        match name.to_lowercase().as_str() {
+            "up" | "вверх" => Ok(Direction::Up),
+            "down" | "вниз" => Ok(Direction::Down),
+            "left" | "влево" => Ok(Direction::Left),
+            "right" | "вправо" => Ok(Direction::Right),
+            "up_left" | "вверх_влево" => Ok(Direction::UpLeft),
+            "up_right" | "вверх_вправо" => Ok(Direction::UpRight),
+            "down_left" | "вниз_влево" => Ok(Direction::DownLeft),
+            "down_right" | "вниз_вправо" => Ok(Direction::DownRight),
+            _ => Err(Error::InvalidCommandParameter(name, span)),
+        }
+    }
+
+    // Фигура штампа записывается словом, за квадратом и окружностью следует их размер
+    fn stamp_shape(&mut self) -> Result<StampShape, Error> {
+        let (name, span) = self.word()?;
+        match name.to_lowercase().as_str() {
+            "dot" | "точка" => Ok(StampShape::Dot),
+            "square" | "квадрат" => Ok(StampShape::Square(self.argument("size")?)),
+            "circle" | "круг" => Ok(StampShape::Circle(self.argument("radius")?)),
+            _ => Err(Error::InvalidCommandParameter(name, span)),
+        }
+    }
+
+    // Цвет пера записывается названием, в английском или русском варианте
+    fn color(&mut self) -> Result<Color, Error> {
+        let (name, span) = self.word()?;
+        let color = match name.to_lowercase().as_str() {
+            "черный" | "чёрный" => Some(Color::Black),
+            "белый" => Some(Color::White),
+            "красный" => Some(Color::Red),
+            "зеленый" | "зелёный" => Some(Color::Green),
+            "синий" => Some(Color::Blue),
+            "желтый" | "жёлтый" => Some(Color::Yellow),
+            "голубой" => Some(Color::Cyan),
+            "пурпурный" => Some(Color::Magenta),
+            name => Color::ALL
+                .into_iter()
+                .find(|color| color.to_string() == name),
+        };
+        color.ok_or(Error::InvalidCommandParameter(name, span))
+    }
+
+    // Строка в кавычках, например текст надписи или путь к файлу
+    fn string(&mut self) -> Result<String, Error> {
+        match self.next_token()? {
+            Some((Token::Str(text), _)) => Ok(text),
+            Some((token, span)) => Err(Error::UnexpectedToken(token, span)),
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    // Слово-аргумент команды, например направление или цвет
+    fn word(&mut self) -> Result<(String, Span), Error> {
+        match self.next_token()? {
+            Some((Token::Identifier(name), span)) => Ok((name, span)),
+            Some((token, span)) => Err(Error::UnexpectedToken(token, span)),
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    // Аргумент команды можно передать по имени, например "move distance=10",
+    // тогда имя должно совпадать с именем параметра команды
+    fn argument(&mut self, name: &str) -> Result<Expr, Error> {
+        if let Some((Token::Identifier(_), _)) = self.peek_token()? {
+            let identifier = self.next_token()?;
+            match (identifier, self.peek_token()?) {
+                (Some((Token::Identifier(key), span)), Some((Token::Assign, _))) => {
+                    if key != name {
+                        return Err(Error::UnknownParameter(key, span));
+                    }
+                    self.next_token()?;
+                }
+                // Идентификатор оказался началом выражения, например переменной
+                (Some(identifier), _) => self.peeked.push(identifier),
+                (None, _) => unreachable!(),
+            }
+        }
+
+        self.expression()
+    }
+
+    fn expect_token(&mut self, expected: Token) -> Result<Span, Error> {
+        match self.next_token()? {
+            Some((token, span)) if token == expected => Ok(span),
+            Some((token, span)) => Err(Error::UnexpectedToken(token, span)),
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, Error> {
+        match self.next_token()? {
+            Some((Token::Identifier(name), _)) => Ok(name),
+            Some((token, span)) => Err(Error::UnexpectedToken(token, span)),
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    // expression := conjunction ("or" conjunction)*
+    fn expression(&mut self) -> Result<Expr, Error> {
+        self.chain(Self::conjunction, |token| {
+            matches!(token, Token::Or).then_some(BinaryOp::Or)
+        })
+    }
+
+    // conjunction := negation ("and" negation)*
+    fn conjunction(&mut self) -> Result<Expr, Error> {
+        self.chain(Self::negation, |token| {
+            matches!(token, Token::And).then_some(BinaryOp::And)
+        })
+    }
+
+    // negation := "not" negation | comparison
+    fn negation(&mut self) -> Result<Expr, Error> {
+        if let Some((Token::Not, span)) = self.peek_token()? {
+            let span = *span;
+            self.next_token()?;
+            let operand = self.nested(span, Self::negation)?;
+            return Ok(unary(UnaryOp::Not, span, operand));
+        }
+
+        self.comparison()
+    }
+
+    // comparison := sum (("==" | "!=" | "<" | "<=" | ">" | ">=") sum)?
+    fn comparison(&mut self) -> Result<Expr, Error> {
+        let expr = self.sum()?;
+
+        let op = match self.peek_token()? {
+            Some((Token::Equal, _)) => BinaryOp::Equal,
+            Some((Token::NotEqual, _)) => BinaryOp::NotEqual,
+            Some((Token::Less, _)) => BinaryOp::Less,
+            Some((Token::LessEqual, _)) => BinaryOp::LessEqual,
+            Some((Token::Greater, _)) => BinaryOp::Greater,
+            Some((Token::GreaterEqual, _)) => BinaryOp::GreaterEqual,
+            _ => return Ok(expr),
+        };
+        self.next_token()?;
+        let rhs = self.sum()?;
+
+        Ok(binary(op, expr, rhs))
+    }
+
+    // sum := term (("+" | "-") term)*
+    fn sum(&mut self) -> Result<Expr, Error> {
+        self.chain(Self::term, |token| match token {
+            Token::Plus => Some(BinaryOp::Add),
+            Token::Minus => Some(BinaryOp::Sub),
+            _ => None,
+        })
+    }
+
+    // term := factor (("*" | "/") factor)*
+    fn term(&mut self) -> Result<Expr, Error> {
+        self.chain(Self::factor, |token| match token {
+            Token::Star => Some(BinaryOp::Mul),
+            Token::Slash => Some(BinaryOp::Div),
+            _ => None,
+        })
+    }
+
+    // Цепочка операций с левой ассоциативностью: каждая следующая операция углубляет
+    // дерево выражения, поэтому тоже учитывается в глубине вложенности
+    fn chain(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, Error>,
+        operator: fn(&Token) -> Option<BinaryOp>,
+    ) -> Result<Expr, Error> {
+        let depth = self.depth;
+        let result = self.chain_operands(operand, operator);
+        self.depth = depth;
+        result
+    }
+
+    fn chain_operands(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, Error>,
+        operator: fn(&Token) -> Option<BinaryOp>,
+    ) -> Result<Expr, Error> {
+        let mut expr = operand(self)?;
+
+        while let Some((op, span)) = self
+            .peek_token()?
+            .and_then(|(token, span)| Some((operator(token)?, *span)))
+        {
+            self.next_token()?;
+            if self.depth >= self.max_depth {
+                return Err(Error::MaxDepthExceeded(self.max_depth, span));
+            }
+            self.depth += 1;
+            let rhs = operand(self)?;
+            expr = binary(op, expr, rhs);
+        }
+
+        Ok(expr)
+    }
+
+    // factor := number | variable | sensor | "(" expression ")" | "-" factor
+    //         | "random" factor factor
+    fn factor(&mut self) -> Result<Expr, Error> {
+        match self.next_token()? {
+            Some((Token::Number(number), span)) => {
+                Ok(Expr::new(ExprKind::Number(number.into()), span))
+            }
+            Some((Token::Identifier(name), span)) => Ok(Expr::new(ExprKind::Variable(name), span)),
+            Some((Token::Minus, span)) => {
+                let operand = self.nested(span, Self::factor)?;
+                Ok(unary(UnaryOp::Neg, span, operand))
+            }
+            Some((Token::Blocked, span)) => Ok(Expr::new(ExprKind::Sensor(Sensor::Blocked), span)),
+            Some((Token::Random, span)) => {
+                let min = self.nested(span, Self::factor)?;
+                let max = self.nested(span, Self::factor)?;
+                let span = span.to(max.span);
+                Ok(Expr::new(
+                    ExprKind::Random {
+                        min: Box::new(min),
+                        max: Box::new(max),
+                    },
+                    span,
+                ))
+            }
+            Some((Token::LeftParen, span)) => {
+                let expr = self.nested(span, Self::expression)?;
+                let close_span = self.expect_token(Token::RightParen)?;
+                Ok(Expr::new(expr.kind, span.to(close_span)))
+            }
+            Some((token, span)) => Err(Error::UnexpectedToken(token, span)),
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    fn end_of_input(&self) -> Error {
+        Error::InvalidCommand(self.last_span)
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&(Token, Span)>, Error> {
+        if self.peeked.is_empty()
+            && let Some(token) = self.scanner.next_token()?
+        {
+            self.peeked.push(token);
+        }
+        Ok(self.peeked.last())
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, Span)>, Error> {
+        let token = match self.peeked.pop() {
+            Some(token) => Some(token),
+            None => self.scanner.next_token()?,
+        };
+        if let Some((_, span)) = &token {
+            self.last_span = *span;
+        }
+        Ok(token)
+    }
+}
+
+fn starts_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Move
+            | Token::Back
+            | Token::Goto
+            | Token::Home
+            | Token::Face
+            | Token::Color
+            | Token::Width
+            | Token::Refuel
+            | Token::Stamp
+            | Token::Write
+            | Token::TurnLeft
+            | Token::TurnRight
+            | Token::TurnLeft45
+            | Token::TurnRight45
+            | Token::DownPen
+            | Token::UpPen
+            | Token::Repeat
+            | Token::Def
+            | Token::Let
+            | Token::If
+            | Token::While
+            | Token::Robot
+            | Token::Include
+            | Token::Identifier(_)
+    )
+}
+
+fn unary(op: UnaryOp, span: Span, operand: Expr) -> Expr {
+    let span = span.to(operand.span);
+    Expr::new(
+        ExprKind::Unary {
+            op,
+            operand: Box::new(operand),
+        },
+        span,
+    )
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    let span = lhs.span.to(rhs.span);
+    Expr::new(
+        ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::DEFAULT_MAX_DEPTH;
+
+    fn parse(source: &str) -> Program {
+        let mut parser = Parser::new(Scanner::new(source), Dialect::Native, DEFAULT_MAX_DEPTH);
+        parser.parse_program().unwrap()
+    }
+
+    fn kinds(statements: &[Stmt]) -> Vec<&StmtKind> {
+        statements.iter().map(|statement| &statement.kind).collect()
+    }
+
+    #[test]
+    fn test_parse_simple_statements() {
+        let program = parse("move 10 turn_left 1 down_pen up_pen");
+        assert_eq!(program.statements.len(), 4);
+        assert!(matches!(
+            &program.statements[0].kind,
+            StmtKind::Move(Expr {
+                kind: ExprKind::Number(10),
+                ..
+            })
+        ));
+        assert!(matches!(
+            &program.statements[1].kind,
+            StmtKind::TurnLeft(Turn {
+                unit: TurnUnit::QuarterTurns,
+                ..
+            })
+        ));
+        assert_eq!(
+            kinds(&program.statements[2..]),
+            [&StmtKind::DownPen, &StmtKind::UpPen]
+        );
+    }
+
+    #[test]
+    fn test_parse_nested_blocks() {
+        let program = parse("repeat 2 [ if n > 1 [ move 1 ] else [ square ] ]");
+        let StmtKind::Repeat { body, .. } = &program.statements[0].kind else {
+            panic!("expected repeat");
+        };
+        let StmtKind::If {
+            then_body,
+            else_body,
+            ..
+        } = &body[0].kind
+        else {
+            panic!("expected if");
+        };
+        assert_eq!(then_body.len(), 1);
+        assert_eq!(
+            kinds(else_body.as_ref().unwrap()),
+            [&StmtKind::Call("square".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_procedure() {
+        let program = parse("def step move 1 end step");
+        assert!(matches!(
+            &program.statements[0].kind,
+            StmtKind::Def { name, body } if name == "step" && body.len() == 1
+        ));
+        assert_eq!(
+            kinds(&program.statements[1..]),
+            [&StmtKind::Call("step".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_expression_tree() {
+        let program = parse("let n = 1 + 2 * 3");
+        let StmtKind::Let { value, .. } = &program.statements[0].kind else {
+            panic!("expected let");
+        };
+        let ExprKind::Binary { op, rhs, .. } = &value.kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(*op, BinaryOp::Add);
+        assert!(matches!(
+            rhs.kind,
+            ExprKind::Binary {
+                op: BinaryOp::Mul,
+                ..
+            }
+        ));
+        assert_eq!((value.span.start, value.span.end), (8, 17));
+    }
+
+    #[test]
+    fn test_statement_span_covers_arguments() {
+        let program = parse("move (1 + 2)\nup_pen");
+        let span = program.statements[0].span;
+        assert_eq!((span.start, span.end), (0, 12));
+        assert_eq!(program.statements[1].span.line, 2);
+    }
+
+    #[test]
+    fn test_logo_turn_unit() {
+        let mut parser = Parser::new(
+            Scanner::with_keywords("right 90", super::super::Keywords::logo()),
+            Dialect::Logo,
+            DEFAULT_MAX_DEPTH,
+        );
+        let program = parser.parse_program().unwrap();
+        assert!(matches!(
+            &program.statements[0].kind,
+            StmtKind::TurnRight(Turn {
+                unit: TurnUnit::Degrees,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+// Источник случайных чисел для выражения "random <min> <max>".
+// Интерпретатор принимает любой источник, поэтому в тестах можно задать начальное значение
+// генератора и получать одинаковые результаты при каждом запуске.
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub trait RandomSource: fmt::Debug {
+    fn next_u64(&mut self) -> u64;
+
+    // Случайное число из отрезка [min, max], границы должны быть упорядочены
+    fn range(&mut self, min: i64, max: i64) -> i64 {
+        let width = max.abs_diff(min).wrapping_add(1);
+        let offset = match width {
+            // Отрезок покрывает все значения i64
+            0 => self.next_u64(),
+            width => self.next_u64() % width,
+        };
+        min.wrapping_add_unsigned(offset)
+    }
+}
+
+// Простой генератор SplitMix64, его качества достаточно для рисунков
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // Генератор с начальным значением из текущего времени
+    pub fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Self::new(seed)
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut first = SplitMix64::new(7);
+        let mut second = SplitMix64::new(7);
+        for _ in 0..10 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let mut rng = SplitMix64::new(1);
+        for _ in 0..1000 {
+            let value = rng.range(-3, 3);
+            assert!((-3..=3).contains(&value));
+        }
+        assert_eq!(rng.range(5, 5), 5);
+    }
+}
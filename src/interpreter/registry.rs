@@ -0,0 +1,109 @@
+// Реестр дополнительных команд языка.
+// Команда регистрируется под именем вместе с именами параметров и функцией, которая
+// строит команду по вычисленным аргументам, например "jump" с параметром "height".
+// Зарегистрированные имена разбираются как ключевые слова: аргументы записываются
+// так же, как у встроенных команд, в том числе по имени ("jump height=2").
+// Имена не зависят от регистра, встроенные ключевые слова имеют приоритет над реестром.
+
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::command::Command;
+
+type Factory = dyn Fn(&[i64]) -> Box<dyn Command>;
+
+#[derive(Clone)]
+struct Entry {
+    params: Vec<String>,
+    factory: Rc<Factory>,
+}
+
+#[derive(Clone, Default)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Entry>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Регистрирует команду, функция получает аргументы в порядке параметров.
+    // Команда с тем же именем заменяется.
+    pub fn register(
+        &mut self,
+        name: &str,
+        params: &[&str],
+        factory: impl Fn(&[i64]) -> Box<dyn Command> + 'static,
+    ) {
+        let entry = Entry {
+            params: params.iter().map(|param| param.to_string()).collect(),
+            factory: Rc::new(factory),
+        };
+        self.commands.insert(name.to_lowercase(), entry);
+    }
+
+    pub fn with(
+        mut self,
+        name: &str,
+        params: &[&str],
+        factory: impl Fn(&[i64]) -> Box<dyn Command> + 'static,
+    ) -> Self {
+        self.register(name, params, factory);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.contains_key(&name.to_lowercase())
+    }
+
+    // Имена параметров команды или None, если команда не зарегистрирована
+    pub fn params(&self, name: &str) -> Option<&[String]> {
+        self.commands
+            .get(&name.to_lowercase())
+            .map(|entry| entry.params.as_slice())
+    }
+
+    // Строит команду или возвращает None, если команды нет
+    // или количество аргументов не совпадает с количеством ее параметров
+    pub fn create(&self, name: &str, args: &[i64]) -> Option<Box<dyn Command>> {
+        let entry = self.commands.get(&name.to_lowercase())?;
+        if entry.params.len() != args.len() {
+            return None;
+        }
+        Some((entry.factory)(args))
+    }
+}
+
+impl fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.commands.keys().collect();
+        names.sort();
+        f.debug_struct("CommandRegistry")
+            .field("commands", &names)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::MoveCommand, robot::Robot};
+
+    #[test]
+    fn test_register_and_create() {
+        let registry = CommandRegistry::new().with("Jump", &["height"], |args| {
+            Box::new(MoveCommand::new(args[0] as u32 * 2))
+        });
+
+        assert!(registry.contains("jump"));
+        assert!(registry.contains("JUMP"));
+        assert_eq!(registry.params("jump"), Some(&["height".to_string()][..]));
+        assert!(registry.create("fly", &[]).is_none());
+        assert!(registry.create("jump", &[]).is_none());
+
+        let mut robot = Robot::default();
+        let mut command = registry.create("jump", &[3]).unwrap();
+        command.execute(&mut robot).unwrap();
+        assert_eq!(robot.y(), 6);
+    }
+}
@@ -1,4 +1,18 @@
+pub mod canvas;
 pub mod command;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod import;
 pub mod interpreter;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod robot;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod world;
@@ -1,18 +1,48 @@
 // Имеем некого робота, который может двигаться по координатной сетке.
-// Он может двигаться в четырех направлениях но только вперед: вверх, вниз, влево и вправо.
-// Он может поворачивать налево и направо.
-// Он может поднимать и опускать перо, чтобы рисовать линии.
+// Он может двигаться вперед и назад в четырех направлениях и по диагоналям,
+// поворачивать налево и направо на 90 или на 45 градусов.
+// Он может поднимать и опускать перо, чтобы рисовать линии, шаги с опущенным пером
+// запоминаются как отрезки пути (Segment).
+// Роботу можно задать поле (Bounds), мир с препятствиями (World), топливо (Fuel)
+// и наблюдателей (RobotObserver). Тип координат задается параметром (Coordinate),
+// а для рисунков под любым углом есть робот с курсом в градусах (PreciseRobot).
 
-use std::fmt;
+use std::{collections::HashSet, fmt, sync::Arc};
 
-use super::error::Error;
+use super::{error::Error, world::World};
+
+mod coordinate;
+mod like;
+mod observer;
+mod point;
+mod precise;
+#[cfg(feature = "serde")]
+mod serialize;
+mod stamp;
+mod text;
+mod visited;
+
+pub use coordinate::Coordinate;
+pub use like::{Action, RobotLike, SavedPosition, StepBudget};
+use observer::Observers;
+pub use observer::{RobotEvent, RobotObserver};
+pub use point::Point;
+pub use precise::{PreciseRobot, PreciseSegment};
+pub use stamp::Stamp;
 
 #[derive(Debug, Clone)]
-pub struct Robot {
-    x: i32,
-    y: i32,
+pub struct Robot<C: Coordinate = i32> {
+    position: Point<C>,
     direction: Direction,
-    drawing: bool,
+    pen: Pen,
+    bounds: Bounds<C>,
+    wrap: WrapMode,
+    world: Arc<World>,
+    path: Vec<Segment<C>>,
+    stats: Stats<C>,
+    home: Home<C>,
+    fuel: Option<Fuel>,
+    observers: Observers<C>,
 }
 
 impl Default for Robot {
@@ -21,113 +51,709 @@ impl Default for Robot {
     }
 }
 
-impl Robot {
-    pub fn new(x: i32, y: i32, direction: Direction, drawing: bool) -> Self {
+impl<C: Coordinate> Robot<C> {
+    pub fn new(x: C, y: C, direction: Direction, drawing: bool) -> Self {
         Self {
-            x,
-            y,
+            position: Point::new(x, y),
             direction,
-            drawing,
+            pen: Pen::new().with_down(drawing),
+            bounds: Bounds::default(),
+            wrap: WrapMode::default(),
+            world: Arc::default(),
+            path: Vec::new(),
+            stats: Stats::starting_at(Point::new(x, y)),
+            home: Home {
+                position: Point::new(x, y),
+                direction,
+            },
+            fuel: None,
+            observers: Observers::default(),
         }
     }
 
-    pub fn x(&self) -> i32 {
-        self.x
+    // Сохраняет положение, направление, перо, топливо и длину нарисованного пути.
+    // Поле, режим краев и мир не сохраняются.
+    pub fn snapshot(&self) -> RobotState<C> {
+        RobotState {
+            position: self.position,
+            direction: self.direction,
+            pen: self.pen,
+            fuel: self.fuel,
+            path_len: self.path.len(),
+        }
+    }
+
+    // Возвращает сохраненное состояние, отрезки нарисованные после сохранения удаляются
+    pub fn restore(&mut self, state: &RobotState<C>) {
+        self.position = state.position;
+        self.direction = state.direction;
+        self.pen = state.pen;
+        self.fuel = state.fuel;
+        self.path.truncate(state.path_len);
+        log::info!("Restore at {} facing {}", self.position, self.direction);
+        self.observers.notify(RobotEvent::Restored);
+    }
+
+    // Наблюдатель остается у копий робота
+    pub fn add_observer(&mut self, observer: Arc<dyn RobotObserver<C>>) {
+        self.observers.add(observer);
+    }
+
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    pub fn position(&self) -> Point<C> {
+        self.position
+    }
+
+    pub fn x(&self) -> C {
+        self.position.x
     }
 
-    pub fn y(&self) -> i32 {
-        self.y
+    pub fn y(&self) -> C {
+        self.position.y
     }
 
     pub fn direction(&self) -> Direction {
         self.direction
     }
 
+    pub fn pen(&self) -> Pen {
+        self.pen
+    }
+
+    pub fn set_pen(&mut self, pen: Pen) {
+        if self.pen != pen {
+            log::info!("Pen {pen:?}");
+            self.pen = pen;
+            self.pen_changed();
+        }
+    }
+
     pub fn is_drawing(&self) -> bool {
-        self.drawing
+        self.pen.down
+    }
+
+    pub fn color(&self) -> Color {
+        self.pen.color
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        if self.pen.color != color {
+            log::info!("Pen color {color}");
+            self.pen.color = color;
+            self.pen_changed();
+        }
+    }
+
+    pub fn pen_width(&self) -> u32 {
+        self.pen.width
+    }
+
+    pub fn set_pen_width(&mut self, width: u32) {
+        if self.pen.width != width {
+            log::info!("Pen width {width}");
+            self.pen.width = width;
+            self.pen_changed();
+        }
+    }
+
+    pub fn pen_mode(&self) -> PenMode {
+        self.pen.mode
+    }
+
+    pub fn set_pen_mode(&mut self, mode: PenMode) {
+        if self.pen.mode != mode {
+            log::info!("Pen mode {mode:?}");
+            self.pen.mode = mode;
+            self.pen_changed();
+        }
+    }
+
+    pub fn bounds(&self) -> Bounds<C> {
+        self.bounds
+    }
+
+    pub fn set_bounds(&mut self, bounds: Bounds<C>) {
+        self.bounds = bounds;
+    }
+
+    pub fn wrap(&self) -> WrapMode {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: WrapMode) {
+        self.wrap = wrap;
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    // Мир можно разделить между несколькими роботами
+    pub fn set_world(&mut self, world: impl Into<Arc<World>>) {
+        self.world = world.into();
+    }
+
+    // Отрезки, нарисованные роботом, в порядке рисования
+    pub fn path(&self) -> &[Segment<C>] {
+        &self.path
+    }
+
+    pub fn clear_path(&mut self) {
+        self.path.clear();
+    }
+
+    pub fn home(&self) -> Home<C> {
+        self.home
+    }
+
+    pub fn set_home(&mut self, home: Home<C>) {
+        self.home = home;
+    }
+
+    // Возвращает робота домой с поднятым пером, путь не стирается
+    pub fn reset(&mut self) -> Result<(), Error> {
+        log::info!("Reset to home {}", self.home.position);
+        self.set_position(self.home.position.x, self.home.position.y)?;
+        self.up_pen();
+        self.set_direction(self.home.direction);
+        Ok(())
+    }
+
+    // None - робот не расходует топливо
+    pub fn fuel(&self) -> Option<Fuel> {
+        self.fuel
+    }
+
+    pub fn set_fuel(&mut self, fuel: Option<Fuel>) {
+        self.fuel = fuel;
+    }
+
+    // Доливает топливо, роботу без топлива заправка не нужна
+    pub fn refuel(&mut self, amount: u64) {
+        if let Some(fuel) = &mut self.fuel {
+            fuel.level = fuel.level.saturating_add(amount);
+            log::info!("Refuel to {}", fuel.level);
+        }
+    }
+
+    pub fn stats(&self) -> &Stats<C> {
+        &self.stats
+    }
+
+    // Начинает статистику заново с текущей клетки
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::starting_at(self.position);
+    }
+
+    // Датчик препятствия: робот не может сделать шаг вперед
+    pub fn is_blocked(&self) -> bool {
+        self.neighbour(self.direction).is_err()
     }
 
     pub fn move_forward(&mut self) -> Result<(), Error> {
-        match self.direction {
-            Direction::Up => {
-                if self.y == i32::MAX {
-                    return Err(Error::OutOfBounds);
-                }
+        self.check_fuel()?;
+        let (to, wrapped) = self.neighbour(self.direction)?;
+        let from = self.position;
+        self.position = to;
 
-                self.y += 1
-            }
-            Direction::Right => {
-                if self.x == i32::MAX {
-                    return Err(Error::OutOfBounds);
-                }
+        log::info!("Move to forward at {to}");
+        self.moved_from(from, self.direction, 1, wrapped);
+
+        Ok(())
+    }
 
-                self.x += 1
+    // Шаг назад, робот остается повернутым в прежнюю сторону
+    pub fn move_backward(&mut self) -> Result<(), Error> {
+        self.check_fuel()?;
+        let (to, wrapped) = self.neighbour(self.direction.opposite())?;
+        let from = self.position;
+        self.position = to;
+
+        log::info!("Move to backward at {to}");
+        self.moved_from(from, self.direction.opposite(), 1, wrapped);
+
+        Ok(())
+    }
+
+    // Перемещение вперед на несколько шагов. Робот останавливается там же, где остановился бы
+    // при пошаговом движении: перед препятствием, у края поля или без топлива,
+    // и возвращает ту же ошибку.
+    pub fn move_forward_by(&mut self, steps: u32) -> Result<(), Error> {
+        self.move_by(self.direction, steps)
+    }
+
+    pub fn move_backward_by(&mut self, steps: u32) -> Result<(), Error> {
+        self.move_by(self.direction.opposite(), steps)
+    }
+
+    // Проходит свободную часть пути одним переходом, а следующий шаг делает обычным шагом:
+    // он либо переходит через край тора, либо возвращает ошибку
+    fn move_by(&mut self, direction: Direction, steps: u32) -> Result<(), Error> {
+        let mut remaining = steps;
+        while remaining > 0 {
+            let reachable = self.reachable(direction, remaining);
+            if reachable > 0 {
+                let from = self.position;
+                self.position = self.offset(direction, reachable);
+                log::info!("Move {reachable} steps to {}", self.position);
+                self.moved_from(from, direction, reachable, false);
+                remaining -= reachable;
             }
-            Direction::Down => {
-                if self.y == i32::MIN {
-                    return Err(Error::OutOfBounds);
-                }
 
-                self.y -= 1
+            if remaining > 0 {
+                self.check_fuel()?;
+                let (to, wrapped) = self.neighbour(direction)?;
+                let from = self.position;
+                self.position = to;
+                log::info!("Move to {to}");
+                self.moved_from(from, direction, 1, wrapped);
+                remaining -= 1;
             }
-            Direction::Left => {
-                if self.x == i32::MIN {
-                    return Err(Error::OutOfBounds);
-                }
+        }
+        Ok(())
+    }
+
+    // Сколько шагов из limit можно пройти, не выходя за поле, не входя в препятствие
+    // и не расходуя топливо до конца
+    fn reachable(&self, direction: Direction, limit: u32) -> u32 {
+        let limit = match self.fuel {
+            Some(fuel) => limit.min(fuel.steps().try_into().unwrap_or(u32::MAX)),
+            None => limit,
+        };
+        let limit = self.obstacle_limit(direction, limit);
+        // Поле - прямоугольник: если в нем обе точки, то и весь отрезок между ними
+        let inside = |steps| {
+            self.position
+                .checked_offset(direction, steps)
+                .is_some_and(|point| self.bounds.contains(point.x, point.y))
+        };
+        if !inside(0) {
+            return 0;
+        }
+        if inside(limit) {
+            return limit;
+        }
 
-                self.x -= 1
+        // Двоичный поиск последней точки внутри поля
+        let (mut low, mut high) = (0, limit);
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            if inside(middle) {
+                low = middle;
+            } else {
+                high = middle;
             }
         }
+        low
+    }
+
+    // Число шагов до клетки перед ближайшим препятствием на пути, но не больше limit
+    fn obstacle_limit(&self, direction: Direction, limit: u32) -> u32 {
+        if self.world.obstacles().next().is_none() {
+            return limit;
+        }
+        // Вне сетки мира путь проверяется по шагам
+        let (Some(x), Some(y)) = (self.position.x.world_cell(), self.position.y.world_cell())
+        else {
+            return 0;
+        };
+
+        let (dx, dy) = direction.delta();
+        self.world
+            .obstacles()
+            .filter_map(|(obstacle_x, obstacle_y)| {
+                let (rel_x, rel_y) = (
+                    i64::from(obstacle_x) - i64::from(x),
+                    i64::from(obstacle_y) - i64::from(y),
+                );
+                // Компоненты направления равны -1, 0 или 1, поэтому умножение заменяет деление
+                let steps = if dx != 0 {
+                    rel_x * i64::from(dx)
+                } else {
+                    rel_y * i64::from(dy)
+                };
+                let on_path =
+                    steps > 0 && rel_x == steps * i64::from(dx) && rel_y == steps * i64::from(dy);
+                on_path.then_some(steps - 1)
+            })
+            .fold(i64::from(limit), i64::min) as u32
+    }
+
+    fn check_fuel(&self) -> Result<(), Error> {
+        match self.fuel {
+            Some(fuel) if fuel.steps() == 0 => Err(Error::OutOfFuel),
+            _ => Ok(()),
+        }
+    }
+
+    fn offset(&self, direction: Direction, steps: u32) -> Point<C> {
+        self.position
+            .checked_offset(direction, steps)
+            .expect("reachable point")
+    }
+
+    // Сообщает о перемещении на steps шагов и запоминает отрезок до текущей клетки,
+    // если перо опущено. Переход через край тора не рисуется: клетки не соседние.
+    fn moved_from(&mut self, from: Point<C>, direction: Direction, steps: u32, wrapped: bool) {
+        self.stats.distance += u64::from(steps);
+        if let Some(fuel) = &mut self.fuel {
+            fuel.level -= fuel.step_cost * u64::from(steps);
+        }
+        if wrapped {
+            self.stats.visit(self.position, direction);
+        } else {
+            self.stats.visit_run(from, direction, steps);
+        }
+        self.observers.notify(RobotEvent::Moved {
+            from,
+            to: self.position,
+            drawing: self.pen.down,
+        });
+
+        if self.pen.down && !wrapped {
+            log::info!("Drawing at {}", self.position);
+            let segment = Segment {
+                from,
+                to: self.position,
+                color: self.pen.color,
+                width: self.pen.width,
+                mode: self.pen.mode,
+            };
+            self.path.push(segment);
+            self.observers.notify(RobotEvent::Drawn(segment));
+        }
+    }
 
-        log::info!("Move to forward at ({}, {})", self.x, self.y);
-        if self.drawing {
-            log::info!("Drawing at ({}, {})", self.x, self.y);
+    // Соседняя клетка в указанном направлении и признак перехода через край тора
+    // или ошибка, если в клетку нельзя войти
+    fn neighbour(&self, direction: Direction) -> Result<(Point<C>, bool), Error> {
+        let next = self
+            .position
+            .checked_add(direction)
+            .filter(|next| self.bounds.contains(next.x, next.y));
+        let (next, wrapped) = match (next, self.wrap) {
+            (Some(next), _) => (next, false),
+            (None, WrapMode::Torus) => (self.bounds.wrap(self.position, direction), true),
+            (None, WrapMode::Stop) => return Err(Error::OutOfBounds),
+        };
+
+        if let (Some(x), Some(y)) = (next.x.world_cell(), next.y.world_cell())
+            && self.world.is_obstacle(x, y)
+        {
+            return Err(Error::Blocked(x, y));
         }
+        Ok((next, wrapped))
+    }
 
+    // Переносит робота в указанную клетку без рисования.
+    // Клетка должна быть внутри поля и не занята препятствием мира, как при шаге.
+    pub fn set_position(&mut self, x: C, y: C) -> Result<(), Error> {
+        if !self.bounds.contains(x, y) {
+            return Err(Error::OutOfBounds);
+        }
+        if let (Some(x), Some(y)) = (x.world_cell(), y.world_cell())
+            && self.world.is_obstacle(x, y)
+        {
+            return Err(Error::Blocked(x, y));
+        }
+        let from = self.position;
+        self.position = Point::new(x, y);
+        log::info!("Teleport to {}", self.position);
+        self.stats.visit(self.position, self.direction);
+        self.observers.notify(RobotEvent::Moved {
+            from,
+            to: self.position,
+            drawing: false,
+        });
         Ok(())
     }
 
+    pub fn set_direction(&mut self, direction: Direction) {
+        log::info!("Face {direction}");
+        self.turn_to(direction);
+    }
+
     pub fn turn_left(&mut self) {
-        self.direction = match self.direction {
-            Direction::Up => Direction::Left,
-            Direction::Left => Direction::Down,
-            Direction::Down => Direction::Right,
-            Direction::Right => Direction::Up,
-        };
-        log::info!("Turn left to {}", self.direction);
+        log::info!("Turn left to {}", self.direction.left());
+        self.turn_to(self.direction.left());
     }
 
     pub fn turn_right(&mut self) {
-        self.direction = match self.direction {
-            Direction::Up => Direction::Right,
-            Direction::Right => Direction::Down,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
+        log::info!("Turn right to {}", self.direction.right());
+        self.turn_to(self.direction.right());
+    }
+
+    pub fn turn_left_45(&mut self) {
+        log::info!("Turn left to {}", self.direction.left_45());
+        self.turn_to(self.direction.left_45());
+    }
+
+    pub fn turn_right_45(&mut self) {
+        log::info!("Turn right to {}", self.direction.right_45());
+        self.turn_to(self.direction.right_45());
+    }
+
+    fn turn_to(&mut self, direction: Direction) {
+        let from = self.direction;
+        self.direction = direction;
+        if from != direction {
+            self.stats.turns += 1;
+            if let Some(fuel) = &mut self.fuel {
+                fuel.level = fuel.level.saturating_sub(fuel.turn_cost);
+            }
+        }
+        self.observers.notify(RobotEvent::Turned {
+            from,
+            to: direction,
+        });
+    }
+
+    // Отпечатывает фигуру текущим пером, не перемещаясь.
+    // Клетки, не представимые в типе координат, пропускаются.
+    pub fn stamp(&mut self, stamp: Stamp) -> Result<(), Error> {
+        if let Stamp::Circle(radius) = stamp
+            && radius > stamp::MAX_RADIUS
+        {
+            return Err(Error::StampTooLarge(stamp.to_string()));
+        }
+        log::info!("Stamp {stamp} at {}", self.position);
+        self.mark(stamp.offsets());
+        Ok(())
+    }
+
+    // Пишет текст печатными буквами текущим пером, не перемещаясь.
+    // Строка идет по направлению робота, первая буква стоит нижним левым углом в его клетке.
+    pub fn write_text(&mut self, text: &str) {
+        log::info!("Write {text:?} at {}", self.position);
+        self.mark(text::offsets(text, self.direction));
+    }
+
+    // Рисует отрезки, концы которых заданы смещениями от клетки робота
+    fn mark(&mut self, offsets: Vec<((i64, i64), (i64, i64))>) {
+        let offset = |(dx, dy): (i64, i64)| {
+            Some(Point::new(
+                self.position.x.checked_step(dx)?,
+                self.position.y.checked_step(dy)?,
+            ))
         };
-        log::info!("Turn right to {}", self.direction);
+
+        for (from, to) in offsets {
+            let (Some(from), Some(to)) = (offset(from), offset(to)) else {
+                continue;
+            };
+            let segment = Segment {
+                from,
+                to,
+                color: self.pen.color,
+                width: self.pen.width,
+                mode: self.pen.mode,
+            };
+            self.path.push(segment);
+            self.observers.notify(RobotEvent::Drawn(segment));
+        }
     }
 
     pub fn down_pen(&mut self) {
-        if !self.drawing {
+        if !self.pen.down {
             log::info!("Pen down");
-            self.drawing = true;
+            self.pen.down = true;
+            self.pen_changed();
         }
     }
 
     pub fn up_pen(&mut self) {
-        if self.drawing {
+        if self.pen.down {
             log::info!("Pen up");
-            self.drawing = false;
+            self.pen.down = false;
+            self.pen_changed();
+        }
+    }
+
+    fn pen_changed(&self) {
+        self.observers.notify(RobotEvent::PenChanged(self.pen));
+    }
+}
+
+// Статистика робота. Она не возвращается при восстановлении состояния и откате команд:
+// откат тоже двигает робота.
+// Посещенные клетки хранятся прямыми участками пути, поэтому перемещение на любое
+// количество шагов учитывается сразу, а клетки перечисляются только при запросе.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats<C: Coordinate = i32> {
+    distance: u64,
+    turns: u64,
+    runs: Vec<Run<C>>,
+}
+
+// Клетка start и steps клеток после нее в направлении direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Run<C: Coordinate> {
+    start: Point<C>,
+    direction: Direction,
+    steps: u32,
+}
+
+impl<C: Coordinate> Run<C> {
+    fn line(self) -> visited::Line {
+        let (dx, dy) = self.direction.delta();
+        visited::Line {
+            start: (self.start.x.cell().into(), self.start.y.cell().into()),
+            step: (dx.into(), dy.into()),
+            steps: self.steps.into(),
+        }
+    }
+
+    fn cells(self) -> impl Iterator<Item = (C::Cell, C::Cell)> {
+        (0..=self.steps).filter_map(move |step| {
+            let point = self.start.checked_offset(self.direction, step)?;
+            Some((point.x.cell(), point.y.cell()))
+        })
+    }
+}
+
+impl<C: Coordinate> Stats<C> {
+    fn starting_at(position: Point<C>) -> Self {
+        let mut stats = Self {
+            distance: 0,
+            turns: 0,
+            runs: Vec::new(),
+        };
+        stats.visit(position, Direction::Up);
+        stats
+    }
+
+    fn visit(&mut self, position: Point<C>, direction: Direction) {
+        self.visit_run(position, direction, 0);
+    }
+
+    // Участок, продолжающий последний в том же направлении, удлиняет его, поэтому
+    // при движении по шагам хранится один участок на каждую прямую линию пути
+    fn visit_run(&mut self, start: Point<C>, direction: Direction, steps: u32) {
+        if let Some(last) = self.runs.last_mut()
+            && last.start.checked_offset(last.direction, last.steps) == Some(start)
+            && (last.direction == direction || last.steps == 0)
+            && let Some(total) = last.steps.checked_add(steps)
+        {
+            last.direction = direction;
+            last.steps = total;
+            return;
         }
+
+        self.runs.push(Run {
+            start,
+            direction,
+            steps,
+        });
+    }
+
+    fn cells(&self) -> HashSet<(C::Cell, C::Cell)> {
+        self.runs.iter().flat_map(|run| run.cells()).collect()
+    }
+
+    // Число сделанных шагов, переносы в точку не считаются
+    pub fn distance(&self) -> u64 {
+        self.distance
+    }
+
+    // Число поворотов, поворот в то же направление не считается
+    pub fn turns(&self) -> u64 {
+        self.turns
+    }
+
+    // Число разных клеток, в которых побывал робот, включая начальную.
+    // Считается по участкам пути, поэтому не зависит от длины пройденных линий.
+    pub fn cells_visited(&self) -> usize {
+        visited::count(self.runs.iter().map(|run| run.line()))
+    }
+
+    pub fn is_visited(&self, x: C, y: C) -> bool {
+        let cell = (x.cell().into(), y.cell().into());
+        self.runs.iter().any(|run| run.line().contains(cell))
+    }
+
+    pub fn visited(&self) -> impl Iterator<Item = (C::Cell, C::Cell)> + '_ {
+        self.cells().into_iter()
+    }
+}
+
+impl<C: Coordinate> fmt::Display for Stats<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "distance: {}, turns: {}, cells visited: {}",
+            self.distance,
+            self.turns,
+            self.cells_visited()
+        )
+    }
+}
+
+// Состояние робота для людей: "Robot at (3, -1) facing left, pen down"
+impl<C: Coordinate> fmt::Display for Robot<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pen = if self.pen.down { "down" } else { "up" };
+        write!(
+            f,
+            "Robot at {} facing {}, pen {pen}",
+            self.position, self.direction
+        )
     }
 }
 
+// Запас топлива и его расход на один шаг и на один поворот
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fuel {
+    pub level: u64,
+    pub step_cost: u64,
+    pub turn_cost: u64,
+}
+
+impl Fuel {
+    pub fn new(level: u64, step_cost: u64, turn_cost: u64) -> Self {
+        Self {
+            level,
+            step_cost,
+            turn_cost,
+        }
+    }
+
+    // На сколько шагов хватит топлива
+    pub fn steps(&self) -> u64 {
+        self.level.checked_div(self.step_cost).unwrap_or(u64::MAX)
+    }
+}
+
+// Домашнее положение робота
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Home<C: Coordinate = i32> {
+    pub position: Point<C>,
+    pub direction: Direction,
+}
+
+// Сохраненное состояние робота
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RobotState<C: Coordinate = i32> {
+    position: Point<C>,
+    direction: Direction,
+    pen: Pen,
+    fuel: Option<Fuel>,
+    path_len: usize,
+}
+
 #[derive(Debug, Clone)]
-pub struct RobotBuilder {
-    x: i32,
-    y: i32,
+pub struct RobotBuilder<C: Coordinate = i32> {
+    x: C,
+    y: C,
     direction: Direction,
-    drawing: bool,
+    pen: Pen,
+    bounds: Bounds<C>,
+    wrap: WrapMode,
+    world: Arc<World>,
+    home: Option<Home<C>>,
+    fuel: Option<Fuel>,
 }
 
 impl Default for RobotBuilder {
@@ -138,20 +764,32 @@ impl Default for RobotBuilder {
 
 impl RobotBuilder {
     pub fn new() -> Self {
+        Self::at(0, 0)
+    }
+}
+
+impl<C: Coordinate> RobotBuilder<C> {
+    // Тип координат строителя выводится из начального положения: RobotBuilder::at(0.5, 0.0)
+    pub fn at(x: C, y: C) -> Self {
         Self {
-            x: 0,
-            y: 0,
+            x,
+            y,
             direction: Direction::Up,
-            drawing: false,
+            pen: Pen::new(),
+            bounds: Bounds::default(),
+            wrap: WrapMode::default(),
+            world: Arc::default(),
+            home: None,
+            fuel: None,
         }
     }
 
-    pub fn x(mut self, x: i32) -> Self {
+    pub fn x(mut self, x: C) -> Self {
         self.x = x;
         self
     }
 
-    pub fn y(mut self, y: i32) -> Self {
+    pub fn y(mut self, y: C) -> Self {
         self.y = y;
         self
     }
@@ -162,21 +800,295 @@ impl RobotBuilder {
     }
 
     pub fn drawing(mut self, drawing: bool) -> Self {
-        self.drawing = drawing;
+        self.pen.down = drawing;
         self
     }
 
-    pub fn build(self) -> Robot {
-        Robot::new(self.x, self.y, self.direction, self.drawing)
+    pub fn color(mut self, color: Color) -> Self {
+        self.pen.color = color;
+        self
+    }
+
+    pub fn pen_width(mut self, pen_width: u32) -> Self {
+        self.pen.width = pen_width;
+        self
+    }
+
+    pub fn pen_mode(mut self, mode: PenMode) -> Self {
+        self.pen.mode = mode;
+        self
+    }
+
+    pub fn pen(mut self, pen: Pen) -> Self {
+        self.pen = pen;
+        self
+    }
+
+    pub fn bounds(mut self, bounds: Bounds<C>) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn world(mut self, world: impl Into<Arc<World>>) -> Self {
+        self.world = world.into();
+        self
+    }
+
+    // Без явного дома домом становится начальное положение
+    pub fn home(mut self, home: Home<C>) -> Self {
+        self.home = Some(home);
+        self
+    }
+
+    pub fn fuel(mut self, fuel: Fuel) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn build(self) -> Robot<C> {
+        let mut robot = Robot::new(self.x, self.y, self.direction, false);
+        robot.pen = self.pen;
+        robot.bounds = self.bounds;
+        robot.wrap = self.wrap;
+        robot.world = self.world;
+        if let Some(home) = self.home {
+            robot.home = home;
+        }
+        robot.fuel = self.fuel;
+        robot
     }
 }
 
+// Границы поля, крайние клетки входят в поле
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds<C: Coordinate = i32> {
+    pub min_x: C,
+    pub max_x: C,
+    pub min_y: C,
+    pub max_y: C,
+}
+
+// По умолчанию поле занимает всю сетку
+impl<C: Coordinate> Default for Bounds<C> {
+    fn default() -> Self {
+        Self::new(C::MIN, C::MAX, C::MIN, C::MAX)
+    }
+}
+
+impl<C: Coordinate> Bounds<C> {
+    pub fn new(min_x: C, max_x: C, min_y: C, max_y: C) -> Self {
+        Self {
+            min_x,
+            max_x,
+            min_y,
+            max_y,
+        }
+    }
+
+    pub fn contains(&self, x: C, y: C) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y)
+    }
+
+    // Клетка, в которую попадает робот, делая шаг из клетки (x, y) в указанном направлении:
+    // координата, вышедшая за край поля, переходит на противоположный край
+    fn wrap(&self, point: Point<C>, direction: Direction) -> Point<C> {
+        let (dx, dy) = direction.delta();
+        Point::new(
+            wrap_axis(point.x, dx, self.min_x, self.max_x),
+            wrap_axis(point.y, dy, self.min_y, self.max_y),
+        )
+    }
+}
+
+fn wrap_axis<C: Coordinate>(value: C, delta: i32, min: C, max: C) -> C {
+    match value.checked_step(delta.into()) {
+        Some(next) if (min..=max).contains(&next) => next,
+        _ if delta > 0 => min,
+        _ => max,
+    }
+}
+
+// Что происходит при шаге за край поля
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    // Робот останавливается с ошибкой OutOfBounds
+    #[default]
+    Stop,
+    // Противоположные края поля склеены
+    Torus,
+}
+
+// Перо робота
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pen {
+    down: bool,
+    color: Color,
+    width: u32,
+    mode: PenMode,
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pen {
+    // Поднятое черное перо толщиной 1
+    pub fn new() -> Self {
+        Self {
+            down: false,
+            color: Color::default(),
+            width: 1,
+            mode: PenMode::default(),
+        }
+    }
+
+    pub fn with_down(mut self, down: bool) -> Self {
+        self.down = down;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: PenMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.down
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn mode(&self) -> PenMode {
+        self.mode
+    }
+}
+
+// Что делает опущенное перо
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PenMode {
+    #[default]
+    Draw,
+    // Перо стирает то, что нарисовано под ним
+    Erase,
+}
+
+// Прямой отрезок пути, нарисованный пером
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment<C: Coordinate = i32> {
+    pub from: Point<C>,
+    pub to: Point<C>,
+    pub color: Color,
+    pub width: u32,
+    pub mode: PenMode,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+        Direction::UpLeft,
+        Direction::UpRight,
+        Direction::DownLeft,
+        Direction::DownRight,
+    ];
+
+    pub fn opposite(self) -> Self {
+        self.left().left()
+    }
+
+    // Направление после поворота налево
+    pub fn left(self) -> Self {
+        self.left_45().left_45()
+    }
+
+    // Направление после поворота направо
+    pub fn right(self) -> Self {
+        self.right_45().right_45()
+    }
+
+    // Направление после поворота налево на 45 градусов
+    pub fn left_45(self) -> Self {
+        match self {
+            Direction::Up => Direction::UpLeft,
+            Direction::UpLeft => Direction::Left,
+            Direction::Left => Direction::DownLeft,
+            Direction::DownLeft => Direction::Down,
+            Direction::Down => Direction::DownRight,
+            Direction::DownRight => Direction::Right,
+            Direction::Right => Direction::UpRight,
+            Direction::UpRight => Direction::Up,
+        }
+    }
+
+    // Направление после поворота направо на 45 градусов
+    pub fn right_45(self) -> Self {
+        match self {
+            Direction::Up => Direction::UpRight,
+            Direction::UpRight => Direction::Right,
+            Direction::Right => Direction::DownRight,
+            Direction::DownRight => Direction::Down,
+            Direction::Down => Direction::DownLeft,
+            Direction::DownLeft => Direction::Left,
+            Direction::Left => Direction::UpLeft,
+            Direction::UpLeft => Direction::Up,
+        }
+    }
+
+    // Смещение по осям при шаге в этом направлении, по диагонали меняются обе координаты
+    pub fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Right => (1, 0),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        let (dx, dy) = self.delta();
+        dx != 0 && dy != 0
+    }
 }
 
 impl fmt::Display for Direction {
@@ -186,10 +1098,71 @@ impl fmt::Display for Direction {
             Direction::Down => write!(f, "down"),
             Direction::Left => write!(f, "left"),
             Direction::Right => write!(f, "right"),
+            Direction::UpLeft => write!(f, "up_left"),
+            Direction::UpRight => write!(f, "up_right"),
+            Direction::DownLeft => write!(f, "down_left"),
+            Direction::DownRight => write!(f, "down_right"),
+        }
+    }
+}
+
+// Цвет пера
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Color {
+    #[default]
+    Black,
+    White,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Cyan,
+    Magenta,
+}
+
+impl Color {
+    pub const ALL: [Color; 8] = [
+        Color::Black,
+        Color::White,
+        Color::Red,
+        Color::Green,
+        Color::Blue,
+        Color::Yellow,
+        Color::Cyan,
+        Color::Magenta,
+    ];
+
+    // Красная, зеленая и синяя составляющие цвета
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Black => (0, 0, 0),
+            Color::White => (255, 255, 255),
+            Color::Red => (255, 0, 0),
+            Color::Green => (0, 255, 0),
+            Color::Blue => (0, 0, 255),
+            Color::Yellow => (255, 255, 0),
+            Color::Cyan => (0, 255, 255),
+            Color::Magenta => (255, 0, 255),
         }
     }
 }
 
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Color::Black => "black",
+            Color::White => "white",
+            Color::Red => "red",
+            Color::Green => "green",
+            Color::Blue => "blue",
+            Color::Yellow => "yellow",
+            Color::Cyan => "cyan",
+            Color::Magenta => "magenta",
+        };
+        write!(f, "{name}")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,42 +1170,42 @@ mod tests {
     #[test]
     fn test_robot_new() {
         let robot = Robot::new(1, 2, Direction::Left, true);
-        assert_eq!(robot.x, 1);
-        assert_eq!(robot.y, 2);
+        assert_eq!(robot.x(), 1);
+        assert_eq!(robot.y(), 2);
         assert_eq!(robot.direction, Direction::Left);
-        assert!(robot.drawing);
+        assert!(robot.pen.down);
     }
 
     #[test]
     fn test_robot_move_forward_up() {
         let mut robot = Robot::new(0, 0, Direction::Up, false);
         robot.move_forward().unwrap();
-        assert_eq!(robot.x, 0);
-        assert_eq!(robot.y, 1);
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.y(), 1);
     }
 
     #[test]
     fn test_robot_move_forward_right() {
         let mut robot = Robot::new(0, 0, Direction::Right, false);
         robot.move_forward().unwrap();
-        assert_eq!(robot.x, 1);
-        assert_eq!(robot.y, 0);
+        assert_eq!(robot.x(), 1);
+        assert_eq!(robot.y(), 0);
     }
 
     #[test]
     fn test_robot_move_forward_down() {
         let mut robot = Robot::new(0, 0, Direction::Down, false);
         robot.move_forward().unwrap();
-        assert_eq!(robot.x, 0);
-        assert_eq!(robot.y, -1);
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.y(), -1);
     }
 
     #[test]
     fn test_robot_move_forward_left() {
         let mut robot = Robot::new(0, 0, Direction::Left, false);
         robot.move_forward().unwrap();
-        assert_eq!(robot.x, -1);
-        assert_eq!(robot.y, 0);
+        assert_eq!(robot.x(), -1);
+        assert_eq!(robot.y(), 0);
     }
 
     #[test]
@@ -264,20 +1237,20 @@ mod tests {
     #[test]
     fn test_robot_pen_down_and_up() {
         let mut robot = Robot::default();
-        assert!(!robot.drawing);
+        assert!(!robot.pen.down);
         robot.down_pen();
-        assert!(robot.drawing);
+        assert!(robot.pen.down);
         robot.up_pen();
-        assert!(!robot.drawing);
+        assert!(!robot.pen.down);
     }
 
     #[test]
     fn test_robot_builder_defaults() {
         let robot = RobotBuilder::default().build();
-        assert_eq!(robot.x, 0);
-        assert_eq!(robot.y, 0);
+        assert_eq!(robot.x(), 0);
+        assert_eq!(robot.y(), 0);
         assert_eq!(robot.direction, Direction::Up);
-        assert!(!robot.drawing);
+        assert!(!robot.pen.down);
     }
 
     #[test]
@@ -288,9 +1261,471 @@ mod tests {
             .direction(Direction::Down)
             .drawing(true)
             .build();
-        assert_eq!(robot.x, 5);
-        assert_eq!(robot.y, -3);
+        assert_eq!(robot.x(), 5);
+        assert_eq!(robot.y(), -3);
         assert_eq!(robot.direction, Direction::Down);
-        assert!(robot.drawing);
+        assert!(robot.pen.down);
+    }
+
+    #[test]
+    fn test_robot_is_blocked_at_grid_edge() {
+        let mut robot = Robot::new(i32::MAX - 1, 0, Direction::Right, false);
+        assert!(!robot.is_blocked());
+        robot.move_forward().unwrap();
+        assert!(robot.is_blocked());
+        assert!(matches!(robot.move_forward(), Err(Error::OutOfBounds)));
+        assert_eq!(robot.x(), i32::MAX);
+        robot.turn_left();
+        assert!(!robot.is_blocked());
+    }
+
+    #[test]
+    fn test_robot_move_backward() {
+        let mut robot = Robot::new(0, 0, Direction::Right, false);
+        robot.move_backward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (-1, 0));
+        assert_eq!(robot.direction, Direction::Right);
+
+        let mut robot = Robot::new(0, i32::MAX, Direction::Down, false);
+        assert!(matches!(robot.move_backward(), Err(Error::OutOfBounds)));
+        assert_eq!(robot.y(), i32::MAX);
+    }
+
+    #[test]
+    fn test_robot_pen_color() {
+        let mut robot = RobotBuilder::new().color(Color::Red).build();
+        assert_eq!(robot.color(), Color::Red);
+        robot.set_color(Color::Blue);
+        assert_eq!(robot.color(), Color::Blue);
+        assert_eq!(Robot::default().color(), Color::Black);
+    }
+
+    #[test]
+    fn test_robot_pen_width() {
+        let mut robot = RobotBuilder::new().pen_width(3).build();
+        assert_eq!(robot.pen_width(), 3);
+        robot.set_pen_width(5);
+        assert_eq!(robot.pen_width(), 5);
+        assert_eq!(Robot::default().pen_width(), 1);
+    }
+
+    #[test]
+    fn test_robot_path() {
+        let mut robot = Robot::default();
+        robot.move_forward().unwrap();
+        assert!(robot.path().is_empty());
+
+        robot.down_pen();
+        robot.set_color(Color::Red);
+        robot.move_forward().unwrap();
+        robot.turn_right();
+        robot.move_backward().unwrap();
+        robot.up_pen();
+        robot.move_forward().unwrap();
+        assert_eq!(
+            robot.path(),
+            [
+                Segment {
+                    from: Point::new(0, 1),
+                    to: Point::new(0, 2),
+                    color: Color::Red,
+                    width: 1,
+                    mode: PenMode::Draw,
+                },
+                Segment {
+                    from: Point::new(0, 2),
+                    to: Point::new(-1, 2),
+                    color: Color::Red,
+                    width: 1,
+                    mode: PenMode::Draw,
+                },
+            ]
+        );
+
+        robot.clear_path();
+        assert!(robot.path().is_empty());
+    }
+
+    #[test]
+    fn test_robot_bounds() {
+        let mut robot = RobotBuilder::new()
+            .bounds(Bounds::new(-1, 1, 0, 2))
+            .direction(Direction::Right)
+            .build();
+        robot.move_forward().unwrap();
+        assert!(robot.is_blocked());
+        assert!(matches!(robot.move_forward(), Err(Error::OutOfBounds)));
+        assert_eq!(robot.x(), 1);
+
+        robot.turn_right();
+        assert!(robot.is_blocked());
+        assert!(robot.move_backward().is_ok());
+        assert!(robot.move_backward().is_ok());
+        assert!(matches!(robot.move_backward(), Err(Error::OutOfBounds)));
+        assert_eq!((robot.x(), robot.y()), (1, 2));
+        assert_eq!(robot.bounds(), Bounds::new(-1, 1, 0, 2));
+    }
+
+    #[test]
+    fn test_robot_world_obstacles() {
+        let world = World::from_ascii_map("#\n.").unwrap();
+        let mut robot = RobotBuilder::new().world(world).build();
+        assert!(robot.is_blocked());
+        assert!(matches!(robot.move_forward(), Err(Error::Blocked(0, 1))));
+        assert_eq!(robot.y(), 0);
+
+        robot.turn_right();
+        assert!(!robot.is_blocked());
+        robot.set_world(World::new().with_obstacle(-1, 0));
+        assert!(matches!(robot.move_backward(), Err(Error::Blocked(-1, 0))));
+
+        // Перенос в точку тоже не входит в занятую клетку и не покидает поле
+        assert!(matches!(
+            robot.set_position(-1, 0),
+            Err(Error::Blocked(-1, 0))
+        ));
+        let mut robot = RobotBuilder::new()
+            .bounds(Bounds::new(-5, 5, -5, 5))
+            .build();
+        assert!(matches!(
+            robot.set_position(100, 100),
+            Err(Error::OutOfBounds)
+        ));
+        robot.set_position(5, -5).unwrap();
+        assert_eq!((robot.x(), robot.y()), (5, -5));
+        robot.set_home(Home {
+            position: Point::new(6, 0),
+            direction: Direction::Up,
+        });
+        assert!(matches!(robot.reset(), Err(Error::OutOfBounds)));
+        assert_eq!((robot.x(), robot.y()), (5, -5));
+    }
+
+    #[test]
+    fn test_robot_torus_wrap() {
+        let mut robot = RobotBuilder::new()
+            .bounds(Bounds::new(0, 2, 0, 2))
+            .wrap(WrapMode::Torus)
+            .x(2)
+            .direction(Direction::Right)
+            .drawing(true)
+            .build();
+        assert!(!robot.is_blocked());
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 0));
+        robot.move_backward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 0));
+        assert!(robot.path().is_empty());
+
+        robot.turn_right();
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 2));
+
+        robot.set_world(World::new().with_obstacle(2, 0));
+        assert!(matches!(robot.move_backward(), Err(Error::Blocked(2, 0))));
+
+        let mut robot = RobotBuilder::new()
+            .wrap(WrapMode::Torus)
+            .y(i32::MAX)
+            .build();
+        robot.move_forward().unwrap();
+        assert_eq!(robot.y(), i32::MIN);
+    }
+
+    #[test]
+    fn test_robot_coordinates() {
+        let mut robot = RobotBuilder::at(i64::from(i32::MAX), 0)
+            .direction(Direction::UpRight)
+            .drawing(true)
+            .build();
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (1 << 31, 1));
+        assert_eq!(robot.path()[0].to, Point::new(1 << 31, 1));
+
+        let world = World::new().with_obstacle(0, 1);
+        let mut robot = RobotBuilder::at(0.5, 0.5).world(world).build();
+        assert!(matches!(robot.move_forward(), Err(Error::Blocked(0, 1))));
+        robot.set_direction(Direction::Left);
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (-0.5, 0.5));
+        assert!(robot.stats().is_visited(-0.9, 0.1));
+
+        let mut robot = Robot::new(0.0, 0.0, Direction::Right, false);
+        robot.set_bounds(Bounds::new(-1.5, 1.5, 0.0, 0.0));
+        robot.set_wrap(WrapMode::Torus);
+        robot.move_forward().unwrap();
+        robot.move_forward().unwrap();
+        assert_eq!(robot.x(), -1.5);
+    }
+
+    #[test]
+    fn test_robot_move_forward_by() {
+        let mut robot = Robot::new(0, 0, Direction::Right, true);
+        robot.move_forward_by(1_000_000).unwrap();
+        assert_eq!(robot.position(), Point::new(1_000_000, 0));
+        assert_eq!(robot.path().len(), 1);
+        assert_eq!(robot.path()[0].from, Point::new(0, 0));
+        assert_eq!(robot.stats().distance(), 1_000_000);
+        assert_eq!(robot.stats().cells_visited(), 1_000_001);
+        assert!(robot.stats().is_visited(500_000, 0));
+
+        let world = World::new().with_obstacle(5, 5).with_obstacle(3, 3);
+        let mut robot = RobotBuilder::new()
+            .direction(Direction::UpRight)
+            .world(world)
+            .build();
+        assert!(matches!(
+            robot.move_forward_by(10),
+            Err(Error::Blocked(3, 3))
+        ));
+        assert_eq!(robot.position(), Point::new(2, 2));
+
+        let mut robot = RobotBuilder::new()
+            .bounds(Bounds::new(-2, 2, -2, 2))
+            .build();
+        assert!(matches!(robot.move_backward_by(5), Err(Error::OutOfBounds)));
+        assert_eq!(robot.position(), Point::new(0, -2));
+
+        robot.set_wrap(WrapMode::Torus);
+        robot.move_forward_by(12).unwrap();
+        assert_eq!(robot.position(), Point::new(0, 0));
+        assert_eq!(robot.stats().cells_visited(), 5);
+    }
+
+    #[test]
+    fn test_robot_display() {
+        let mut robot = Robot::new(3, -1, Direction::Left, true);
+        assert_eq!(robot.to_string(), "Robot at (3, -1) facing left, pen down");
+        robot.up_pen();
+        robot.turn_left_45();
+        assert_eq!(
+            robot.to_string(),
+            "Robot at (3, -1) facing down_left, pen up"
+        );
+    }
+
+    #[test]
+    fn test_robot_fuel() {
+        let mut robot = RobotBuilder::new().fuel(Fuel::new(10, 2, 1)).build();
+        robot.move_forward().unwrap();
+        robot.turn_right();
+        assert_eq!(robot.fuel().unwrap().level, 7);
+
+        assert!(matches!(robot.move_forward_by(5), Err(Error::OutOfFuel)));
+        assert_eq!(robot.position(), Point::new(3, 1));
+        assert_eq!(robot.fuel().unwrap().level, 1);
+        robot.turn_left();
+        robot.turn_left();
+        assert_eq!(robot.fuel().unwrap().level, 0);
+
+        robot.refuel(4);
+        robot.move_backward().unwrap();
+        robot.move_backward().unwrap();
+        assert!(matches!(robot.move_backward(), Err(Error::OutOfFuel)));
+        assert_eq!(robot.position(), Point::new(5, 1));
+
+        let mut robot = Robot::default();
+        robot.refuel(4);
+        assert_eq!(robot.fuel(), None);
+        robot.move_forward_by(100).unwrap();
+    }
+
+    #[test]
+    fn test_robot_reset() {
+        let mut robot = RobotBuilder::new()
+            .x(2)
+            .home(Home {
+                position: Point::new(-1, -1),
+                direction: Direction::Left,
+            })
+            .drawing(true)
+            .build();
+        robot.move_forward().unwrap();
+        robot.reset().unwrap();
+        assert_eq!(robot.position(), Point::new(-1, -1));
+        assert_eq!(robot.direction(), Direction::Left);
+        assert!(!robot.is_drawing());
+        assert_eq!(robot.path().len(), 1);
+
+        let mut robot = Robot::new(3, 4, Direction::Down, false);
+        robot.move_forward().unwrap();
+        robot.turn_left();
+        robot.reset().unwrap();
+        assert_eq!(
+            (robot.x(), robot.y(), robot.direction()),
+            (3, 4, Direction::Down)
+        );
+    }
+
+    #[test]
+    fn test_robot_stats() {
+        let mut robot = Robot::new(1, 1, Direction::Up, false);
+        robot.move_forward().unwrap();
+        robot.turn_right();
+        robot.set_direction(Direction::Right);
+        robot.move_forward().unwrap();
+        robot.move_backward().unwrap();
+        robot.set_position(5, 5).unwrap();
+
+        let stats = robot.stats();
+        assert_eq!(stats.distance(), 3);
+        assert_eq!(stats.turns(), 1);
+        assert_eq!(stats.cells_visited(), 4);
+        assert!(stats.is_visited(2, 2));
+        assert!(!stats.is_visited(2, 1));
+        assert_eq!(stats.to_string(), "distance: 3, turns: 1, cells visited: 4");
+
+        robot.reset_stats();
+        assert_eq!(robot.stats().distance(), 0);
+        assert_eq!(robot.stats().visited().collect::<Vec<_>>(), [(5, 5)]);
+    }
+
+    #[test]
+    fn test_robot_stats_are_compact() {
+        let mut robot = Robot::default();
+        for _ in 0..1000 {
+            robot.move_forward().unwrap();
+        }
+        robot.set_position(0, 1000).unwrap();
+        robot.turn_right();
+        robot.move_forward_by(10).unwrap();
+        robot.move_forward().unwrap();
+
+        // Одна линия вверх и одна вправо
+        assert_eq!(robot.stats().runs.len(), 2);
+        assert_eq!(robot.stats().cells_visited(), 1001 + 11);
+
+        // Длинные линии считаются без перебора клеток
+        let mut robot = Robot::new(0i64, 0, Direction::Up, false);
+        robot.move_forward_by(u32::MAX).unwrap();
+        robot.turn_right_45();
+        robot.move_backward_by(u32::MAX).unwrap();
+        assert_eq!(robot.stats().cells_visited(), 2 * u32::MAX as usize + 1);
+        assert!(robot.stats().is_visited(-7, i64::from(u32::MAX) - 7));
+    }
+
+    #[test]
+    fn test_robot_pen() {
+        let mut robot = RobotBuilder::new()
+            .pen(Pen::new().with_down(true).with_width(3))
+            .build();
+        robot.set_pen_mode(PenMode::Erase);
+        robot.move_forward().unwrap();
+        assert_eq!(robot.path()[0].mode, PenMode::Erase);
+        assert_eq!(robot.path()[0].width, 3);
+
+        let state = robot.snapshot();
+        robot.set_pen(Pen::new().with_color(Color::Blue));
+        assert!(!robot.is_drawing());
+        robot.restore(&state);
+        assert_eq!(
+            robot.pen(),
+            Pen::new()
+                .with_down(true)
+                .with_width(3)
+                .with_mode(PenMode::Erase)
+        );
+    }
+
+    #[test]
+    fn test_robot_snapshot_restore() {
+        let mut robot = Robot::new(0, 0, Direction::Up, true);
+        robot.move_forward().unwrap();
+        let state = robot.snapshot();
+
+        robot.move_forward().unwrap();
+        robot.turn_left();
+        robot.set_color(Color::Green);
+        robot.up_pen();
+        robot.restore(&state);
+
+        assert_eq!(
+            (robot.x(), robot.y(), robot.direction),
+            (0, 1, Direction::Up)
+        );
+        assert!(robot.is_drawing());
+        assert_eq!(robot.color(), Color::Black);
+        assert_eq!(robot.path().len(), 1);
+        assert_eq!(robot.snapshot(), state);
+    }
+
+    #[test]
+    fn test_robot_observer_events() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut robot = Robot::default();
+        robot.add_observer(Arc::new(sender));
+
+        robot.down_pen();
+        robot.move_forward().unwrap();
+        robot.turn_right();
+        robot.set_color(Color::Red);
+        robot.set_color(Color::Red);
+        robot.set_position(5, 5).unwrap();
+
+        // Копия робота сообщает тем же наблюдателям, пока их не удалят
+        let mut copy = robot.clone();
+        copy.clear_observers();
+        copy.turn_left();
+
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                RobotEvent::PenChanged(Pen::new().with_down(true)),
+                RobotEvent::Moved {
+                    from: Point::new(0, 0),
+                    to: Point::new(0, 1),
+                    drawing: true,
+                },
+                RobotEvent::Drawn(Segment {
+                    from: Point::new(0, 0),
+                    to: Point::new(0, 1),
+                    color: Color::Black,
+                    width: 1,
+                    mode: PenMode::Draw,
+                }),
+                RobotEvent::Turned {
+                    from: Direction::Up,
+                    to: Direction::Right,
+                },
+                RobotEvent::PenChanged(Pen::new().with_down(true).with_color(Color::Red)),
+                RobotEvent::Moved {
+                    from: Point::new(0, 1),
+                    to: Point::new(5, 5),
+                    drawing: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_robot_diagonal_movement() {
+        let mut robot = Robot::new(0, 0, Direction::Up, true);
+        robot.turn_right_45();
+        assert_eq!(robot.direction(), Direction::UpRight);
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (1, 1));
+        robot.turn_left();
+        assert_eq!(robot.direction(), Direction::UpLeft);
+        robot.move_backward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (2, 0));
+        assert_eq!(robot.path().len(), 2);
+
+        for direction in Direction::ALL {
+            assert_eq!(direction.left_45().right_45(), direction);
+            assert_eq!(direction.opposite().opposite(), direction);
+        }
+
+        let mut robot = RobotBuilder::new()
+            .bounds(Bounds::new(0, 2, 0, 2))
+            .wrap(WrapMode::Torus)
+            .x(2)
+            .y(1)
+            .direction(Direction::UpRight)
+            .build();
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (0, 2));
+        robot.move_forward().unwrap();
+        assert_eq!((robot.x(), robot.y()), (1, 0));
     }
 }
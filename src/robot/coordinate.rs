@@ -0,0 +1,133 @@
+// Тип координат робота. По умолчанию робот ходит по сетке i32, для огромных полей
+// подходит i64, а f64 позволяет ставить робота между узлами сетки.
+// Робот шагает по каждой оси на целое число клеток, поэтому от координаты нужны только
+// сложение с проверкой переполнения и сравнение с границами поля.
+// Препятствия мира лежат на сетке i32: координата, не попадающая в нее, свободна.
+
+use std::{fmt, hash::Hash};
+
+pub trait Coordinate:
+    Copy + PartialOrd + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+    // Клетка, в которой находится точка, для учета посещенных клеток
+    type Cell: Copy + Eq + Hash + fmt::Debug + Send + Sync + Into<i128>;
+
+    const MIN: Self;
+    const MAX: Self;
+
+    // Координата после шага на delta или None, если результат не представим
+    fn checked_step(self, delta: i64) -> Option<Self>;
+
+    fn cell(self) -> Self::Cell;
+
+    // Координата из сетки i32, в которой заданы аргументы команд
+    fn from_i32(value: i32) -> Self;
+
+    // Клетка мира, в которой находится точка, если она попадает в сетку i32
+    fn world_cell(self) -> Option<i32>;
+
+    // Для расстояний между точками, у больших i64 теряется точность
+    fn to_f64(self) -> f64;
+}
+
+impl Coordinate for i32 {
+    type Cell = i32;
+
+    const MIN: Self = i32::MIN;
+    const MAX: Self = i32::MAX;
+
+    fn checked_step(self, delta: i64) -> Option<Self> {
+        i32::try_from(i64::from(self).checked_add(delta)?).ok()
+    }
+
+    fn cell(self) -> i32 {
+        self
+    }
+
+    fn from_i32(value: i32) -> Self {
+        value
+    }
+
+    fn world_cell(self) -> Option<i32> {
+        Some(self)
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl Coordinate for i64 {
+    type Cell = i64;
+
+    const MIN: Self = i64::MIN;
+    const MAX: Self = i64::MAX;
+
+    fn checked_step(self, delta: i64) -> Option<Self> {
+        self.checked_add(delta)
+    }
+
+    fn cell(self) -> i64 {
+        self
+    }
+
+    fn from_i32(value: i32) -> Self {
+        i64::from(value)
+    }
+
+    fn world_cell(self) -> Option<i32> {
+        i32::try_from(self).ok()
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Coordinate for f64 {
+    type Cell = i64;
+
+    const MIN: Self = f64::MIN;
+    const MAX: Self = f64::MAX;
+
+    // На больших значениях шаг теряется из-за точности, такой шаг тоже не представим
+    fn checked_step(self, delta: i64) -> Option<Self> {
+        let next = self + delta as f64;
+        (next.is_finite() && (delta == 0 || next != self)).then_some(next)
+    }
+
+    fn cell(self) -> i64 {
+        self.floor() as i64
+    }
+
+    fn from_i32(value: i32) -> Self {
+        f64::from(value)
+    }
+
+    fn world_cell(self) -> Option<i32> {
+        let cell = self.floor();
+        (f64::from(i32::MIN)..=f64::from(i32::MAX))
+            .contains(&cell)
+            .then_some(cell as i32)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coordinate_steps() {
+        assert_eq!(i32::MAX.checked_step(1), None);
+        assert_eq!(i64::from(i32::MAX).checked_step(1), Some(1 << 31));
+        assert_eq!((1i64 << 40).world_cell(), None);
+        assert_eq!(0.5f64.checked_step(-1), Some(-0.5));
+        assert_eq!(1e300f64.checked_step(1), None);
+        assert_eq!((-0.5f64).cell(), -1);
+        assert_eq!((-0.5f64).world_cell(), Some(-1));
+    }
+}
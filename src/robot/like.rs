@@ -0,0 +1,646 @@
+// Исполнитель команд. Команды управляют не конкретным роботом, а любым исполнителем
+// с операциями перемещения, поворота и пера: роботом на сетке, заглушкой в тестах,
+// настоящим устройством или удаленным роботом.
+// Повороты по умолчанию выражены через set_direction, а исполнитель без топлива
+// может не реализовывать методы топлива, штампов и надписей.
+// Аргументы команд заданы в сетке i32, поэтому робот с другим типом координат сообщает
+// клетку этой сетки, в которой стоит, а за ее пределами - ближайшую клетку на краю.
+// Для отката перемещений без пути такой робот сохраняет свои точные координаты
+// (SavedPosition), поэтому откат возвращает его туда, где он стоял, а не в клетку сетки.
+// Исполнитель с ограничением выполнения (StepBudget) считает операции и повторения циклов
+// и прерывает выполнение ошибкой StepLimit, когда они закончатся.
+
+use std::{any::Any, sync::Arc};
+
+use super::{Color, Coordinate, Direction, Fuel, Point, Robot, Stamp};
+use crate::error::Error;
+
+pub trait RobotLike {
+    fn position(&self) -> Point;
+
+    fn x(&self) -> i32 {
+        self.position().x
+    }
+
+    fn y(&self) -> i32 {
+        self.position().y
+    }
+
+    fn direction(&self) -> Direction;
+
+    fn is_drawing(&self) -> bool;
+
+    fn color(&self) -> Color;
+
+    fn pen_width(&self) -> u32;
+
+    // Датчик препятствия: может ли исполнитель сделать шаг вперед
+    fn is_blocked(&self) -> bool;
+
+    fn move_forward(&mut self) -> Result<(), Error> {
+        self.move_forward_by(1)
+    }
+
+    fn move_forward_by(&mut self, steps: u32) -> Result<(), Error>;
+
+    fn move_backward_by(&mut self, steps: u32) -> Result<(), Error>;
+
+    // Переносит исполнителя в указанную клетку без рисования,
+    // ошибка - если в клетку нельзя войти
+    fn set_position(&mut self, x: i32, y: i32) -> Result<(), Error>;
+
+    // Положение для отката перемещения без пути
+    fn saved_position(&self) -> SavedPosition {
+        SavedPosition::new(self.position())
+    }
+
+    // Возвращает исполнителя в сохраненное положение без рисования
+    fn restore_position(&mut self, saved: &SavedPosition) -> Result<(), Error> {
+        self.set_position(saved.cell.x, saved.cell.y)
+    }
+
+    fn set_direction(&mut self, direction: Direction);
+
+    fn turn_left(&mut self) {
+        self.set_direction(self.direction().left());
+    }
+
+    fn turn_right(&mut self) {
+        self.set_direction(self.direction().right());
+    }
+
+    fn turn_left_45(&mut self) {
+        self.set_direction(self.direction().left_45());
+    }
+
+    fn turn_right_45(&mut self) {
+        self.set_direction(self.direction().right_45());
+    }
+
+    fn down_pen(&mut self);
+
+    fn up_pen(&mut self);
+
+    fn set_color(&mut self, color: Color);
+
+    fn set_pen_width(&mut self, width: u32);
+
+    // Возвращает исполнителя домой, разворачивает в домашнем направлении и поднимает перо
+    fn reset(&mut self) -> Result<(), Error>;
+
+    fn fuel(&self) -> Option<Fuel> {
+        None
+    }
+
+    fn set_fuel(&mut self, _fuel: Option<Fuel>) {}
+
+    fn refuel(&mut self, _amount: u64) {}
+
+    // Отпечатывает фигуру вокруг исполнителя, исполнитель без рисунка ее пропускает
+    fn stamp(&mut self, _stamp: Stamp) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Пишет текст печатными буквами, исполнитель без рисунка его пропускает
+    fn write_text(&mut self, _text: &str) {}
+
+    // Вызывается перед каждым повторением цикла, ошибка прерывает цикл
+    fn iterate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Выполняет действие исполнителем с указанным именем, если исполнитель - мир
+    // с несколькими роботами. Отдельный исполнитель возвращает None и выполняет действие сам.
+    fn target(&mut self, _name: &str, _action: &mut Action<'_>) -> Option<Result<(), Error>> {
+        None
+    }
+}
+
+// Действие с исполнителем, которое передается исполнителю с другим именем
+pub type Action<'a> = dyn FnMut(&mut dyn RobotLike) -> Result<(), Error> + 'a;
+
+// Клетка сетки i32 и точные координаты исполнителя, если клетка их не передает.
+// Точные координаты понимает только исполнитель того же типа, другой возвращается в клетку.
+#[derive(Debug, Clone)]
+pub struct SavedPosition {
+    cell: Point,
+    exact: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl SavedPosition {
+    pub fn new(cell: Point) -> Self {
+        Self { cell, exact: None }
+    }
+
+    pub fn cell(&self) -> Point {
+        self.cell
+    }
+}
+
+impl<C: Coordinate> RobotLike for Robot<C> {
+    fn position(&self) -> Point {
+        let position = Robot::position(self);
+        Point::new(grid_cell(position.x), grid_cell(position.y))
+    }
+
+    fn direction(&self) -> Direction {
+        Robot::direction(self)
+    }
+
+    fn is_drawing(&self) -> bool {
+        Robot::is_drawing(self)
+    }
+
+    fn color(&self) -> Color {
+        Robot::color(self)
+    }
+
+    fn pen_width(&self) -> u32 {
+        Robot::pen_width(self)
+    }
+
+    fn is_blocked(&self) -> bool {
+        Robot::is_blocked(self)
+    }
+
+    fn move_forward(&mut self) -> Result<(), Error> {
+        Robot::move_forward(self)
+    }
+
+    fn move_forward_by(&mut self, steps: u32) -> Result<(), Error> {
+        Robot::move_forward_by(self, steps)
+    }
+
+    fn move_backward_by(&mut self, steps: u32) -> Result<(), Error> {
+        Robot::move_backward_by(self, steps)
+    }
+
+    fn set_position(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        Robot::set_position(self, C::from_i32(x), C::from_i32(y))
+    }
+
+    fn saved_position(&self) -> SavedPosition {
+        let cell = RobotLike::position(self);
+        let position = Robot::position(self);
+        let exact = Point::new(C::from_i32(cell.x), C::from_i32(cell.y)) != position;
+        SavedPosition {
+            cell,
+            exact: exact.then(|| Arc::new(position) as Arc<dyn Any + Send + Sync>),
+        }
+    }
+
+    fn restore_position(&mut self, saved: &SavedPosition) -> Result<(), Error> {
+        match saved
+            .exact
+            .as_deref()
+            .and_then(|exact| exact.downcast_ref::<Point<C>>())
+        {
+            Some(position) => Robot::set_position(self, position.x, position.y),
+            None => RobotLike::set_position(self, saved.cell.x, saved.cell.y),
+        }
+    }
+
+    fn set_direction(&mut self, direction: Direction) {
+        Robot::set_direction(self, direction);
+    }
+
+    fn turn_left(&mut self) {
+        Robot::turn_left(self);
+    }
+
+    fn turn_right(&mut self) {
+        Robot::turn_right(self);
+    }
+
+    fn turn_left_45(&mut self) {
+        Robot::turn_left_45(self);
+    }
+
+    fn turn_right_45(&mut self) {
+        Robot::turn_right_45(self);
+    }
+
+    fn down_pen(&mut self) {
+        Robot::down_pen(self);
+    }
+
+    fn up_pen(&mut self) {
+        Robot::up_pen(self);
+    }
+
+    fn set_color(&mut self, color: Color) {
+        Robot::set_color(self, color);
+    }
+
+    fn set_pen_width(&mut self, width: u32) {
+        Robot::set_pen_width(self, width);
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        Robot::reset(self)
+    }
+
+    fn fuel(&self) -> Option<Fuel> {
+        Robot::fuel(self)
+    }
+
+    fn set_fuel(&mut self, fuel: Option<Fuel>) {
+        Robot::set_fuel(self, fuel);
+    }
+
+    fn refuel(&mut self, amount: u64) {
+        Robot::refuel(self, amount);
+    }
+
+    fn stamp(&mut self, stamp: Stamp) -> Result<(), Error> {
+        Robot::stamp(self, stamp)
+    }
+
+    fn write_text(&mut self, text: &str) {
+        Robot::write_text(self, text);
+    }
+}
+
+// Исполнитель, который выполняет не больше max_steps операций и повторений циклов.
+// Операции без результата не могут вернуть ошибку, поэтому после конца запаса ошибку
+// возвращает следующее перемещение или повторение цикла: бесконечное выполнение
+// возможно только в цикле, и оно останавливается.
+#[derive(Debug)]
+pub struct StepBudget<'r, R: RobotLike + ?Sized> {
+    robot: &'r mut R,
+    steps: usize,
+    max_steps: usize,
+}
+
+impl<'r, R: RobotLike + ?Sized> StepBudget<'r, R> {
+    pub fn new(robot: &'r mut R, max_steps: usize) -> Self {
+        Self {
+            robot,
+            steps: 0,
+            max_steps,
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    fn spend(&mut self) {
+        self.steps = self.steps.saturating_add(1);
+    }
+
+    fn check(&mut self) -> Result<(), Error> {
+        self.check_by(1)
+    }
+
+    fn check_by(&mut self, steps: usize) -> Result<(), Error> {
+        self.steps = self.steps.saturating_add(steps);
+        if self.steps > self.max_steps {
+            return Err(Error::StepLimit(self.max_steps));
+        }
+        Ok(())
+    }
+}
+
+impl<R: RobotLike + ?Sized> RobotLike for StepBudget<'_, R> {
+    fn position(&self) -> Point {
+        self.robot.position()
+    }
+
+    fn direction(&self) -> Direction {
+        self.robot.direction()
+    }
+
+    fn is_drawing(&self) -> bool {
+        self.robot.is_drawing()
+    }
+
+    fn color(&self) -> Color {
+        self.robot.color()
+    }
+
+    fn pen_width(&self) -> u32 {
+        self.robot.pen_width()
+    }
+
+    fn is_blocked(&self) -> bool {
+        self.robot.is_blocked()
+    }
+
+    fn move_forward(&mut self) -> Result<(), Error> {
+        self.check()?;
+        self.robot.move_forward()
+    }
+
+    fn move_forward_by(&mut self, steps: u32) -> Result<(), Error> {
+        self.check()?;
+        self.robot.move_forward_by(steps)
+    }
+
+    fn move_backward_by(&mut self, steps: u32) -> Result<(), Error> {
+        self.check()?;
+        self.robot.move_backward_by(steps)
+    }
+
+    fn set_position(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        self.check()?;
+        self.robot.set_position(x, y)
+    }
+
+    fn saved_position(&self) -> SavedPosition {
+        self.robot.saved_position()
+    }
+
+    fn restore_position(&mut self, saved: &SavedPosition) -> Result<(), Error> {
+        self.check()?;
+        self.robot.restore_position(saved)
+    }
+
+    fn set_direction(&mut self, direction: Direction) {
+        self.spend();
+        self.robot.set_direction(direction);
+    }
+
+    fn turn_left(&mut self) {
+        self.spend();
+        self.robot.turn_left();
+    }
+
+    fn turn_right(&mut self) {
+        self.spend();
+        self.robot.turn_right();
+    }
+
+    fn turn_left_45(&mut self) {
+        self.spend();
+        self.robot.turn_left_45();
+    }
+
+    fn turn_right_45(&mut self) {
+        self.spend();
+        self.robot.turn_right_45();
+    }
+
+    fn down_pen(&mut self) {
+        self.spend();
+        self.robot.down_pen();
+    }
+
+    fn up_pen(&mut self) {
+        self.spend();
+        self.robot.up_pen();
+    }
+
+    fn set_color(&mut self, color: Color) {
+        self.spend();
+        self.robot.set_color(color);
+    }
+
+    fn set_pen_width(&mut self, width: u32) {
+        self.spend();
+        self.robot.set_pen_width(width);
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.check()?;
+        self.robot.reset()
+    }
+
+    fn fuel(&self) -> Option<Fuel> {
+        self.robot.fuel()
+    }
+
+    fn set_fuel(&mut self, fuel: Option<Fuel>) {
+        self.spend();
+        self.robot.set_fuel(fuel);
+    }
+
+    fn refuel(&mut self, amount: u64) {
+        self.spend();
+        self.robot.refuel(amount);
+    }
+
+    // Штамп стоит столько шагов, сколько отрезков добавляет в путь
+    fn stamp(&mut self, stamp: Stamp) -> Result<(), Error> {
+        self.check_by(usize::try_from(stamp.segments()).unwrap_or(usize::MAX))?;
+        self.robot.stamp(stamp)
+    }
+
+    fn write_text(&mut self, text: &str) {
+        self.spend();
+        self.robot.write_text(text);
+    }
+
+    fn iterate(&mut self) -> Result<(), Error> {
+        self.check()?;
+        self.robot.iterate()
+    }
+
+    fn target(&mut self, name: &str, action: &mut Action<'_>) -> Option<Result<(), Error>> {
+        self.robot.target(name, action)
+    }
+}
+
+fn grid_cell<C: Coordinate>(value: C) -> i32 {
+    value.world_cell().unwrap_or(if value < C::from_i32(0) {
+        i32::MIN
+    } else {
+        i32::MAX
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{
+        Command, CommandList, DownPenCommand, HomeCommand, MoveCommand, TeleportCommand,
+        TurnLeftCommand, TurnRight45Command,
+    };
+
+    // Заглушка, которая только запоминает вызовы
+    #[derive(Debug)]
+    struct Mock {
+        position: Point,
+        direction: Direction,
+        drawing: bool,
+        calls: Vec<String>,
+    }
+
+    impl RobotLike for Mock {
+        fn position(&self) -> Point {
+            self.position
+        }
+
+        fn direction(&self) -> Direction {
+            self.direction
+        }
+
+        fn is_drawing(&self) -> bool {
+            self.drawing
+        }
+
+        fn color(&self) -> Color {
+            Color::default()
+        }
+
+        fn pen_width(&self) -> u32 {
+            1
+        }
+
+        fn is_blocked(&self) -> bool {
+            false
+        }
+
+        fn move_forward_by(&mut self, steps: u32) -> Result<(), Error> {
+            self.calls.push(format!("forward {steps}"));
+            Ok(())
+        }
+
+        fn move_backward_by(&mut self, steps: u32) -> Result<(), Error> {
+            self.calls.push(format!("backward {steps}"));
+            Ok(())
+        }
+
+        fn set_position(&mut self, x: i32, y: i32) -> Result<(), Error> {
+            self.position = Point::new(x, y);
+            Ok(())
+        }
+
+        fn set_direction(&mut self, direction: Direction) {
+            self.calls.push(format!("face {direction}"));
+            self.direction = direction;
+        }
+
+        fn down_pen(&mut self) {
+            self.calls.push("down".to_string());
+            self.drawing = true;
+        }
+
+        fn up_pen(&mut self) {
+            self.calls.push("up".to_string());
+            self.drawing = false;
+        }
+
+        fn set_color(&mut self, _color: Color) {}
+
+        fn set_pen_width(&mut self, _width: u32) {}
+
+        fn reset(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_commands_on_mock() {
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(DownPenCommand) as Box<dyn Command>);
+        commands.add_command(Box::new(MoveCommand::new(3)));
+        commands.add_command(Box::new(TurnLeftCommand::new(1)));
+        commands.add_command(Box::new(TurnRight45Command::new(1)));
+
+        let mut mock = Mock {
+            position: Point::default(),
+            direction: Direction::Up,
+            drawing: false,
+            calls: Vec::new(),
+        };
+        commands.execute_all(&mut mock).unwrap();
+        assert_eq!(
+            mock.calls,
+            ["down", "forward 3", "face left", "face up_left"]
+        );
+
+        commands.rollback_all(&mut mock).unwrap();
+        assert_eq!(mock.direction, Direction::Up);
+        assert!(!mock.drawing);
+        assert_eq!(mock.calls[6..], ["backward 3", "up"]);
+    }
+
+    #[test]
+    fn test_commands_on_i64_robot() {
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(DownPenCommand) as Box<dyn Command>);
+        commands.add_command(Box::new(MoveCommand::new(3)));
+        commands.add_command(Box::new(TurnLeftCommand::new(1)));
+        commands.add_command(Box::new(MoveCommand::new(2)));
+
+        let far = 1i64 << 40;
+        let mut robot = Robot::new(far, 0, Direction::Up, false);
+        commands.execute_all(&mut robot).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(far - 2, 3));
+        assert_eq!(robot.path().len(), 2);
+        assert_eq!(RobotLike::position(&robot), Point::new(i32::MAX, 3));
+
+        commands.rollback_all(&mut robot).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(far, 0));
+        assert!(!robot.is_drawing());
+
+        let mut teleport = TeleportCommand::new(5, -1);
+        teleport.execute(&mut robot).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(5i64, -1));
+    }
+
+    #[test]
+    fn test_step_budget() {
+        let mut body = CommandList::default();
+        body.add_command(Box::new(TurnLeftCommand::new(1)) as Box<dyn Command>);
+        let mut commands = CommandList::default();
+        commands.add_command(Box::new(MoveCommand::new(2)) as Box<dyn Command>);
+        commands.add_command(Box::new(crate::command::WhileCommand::new(
+            crate::command::Condition::Value(true),
+            body,
+        )));
+
+        let mut robot = Robot::default();
+        let mut budget = StepBudget::new(&mut robot, 10);
+        assert!(matches!(
+            commands.execute_all(&mut budget),
+            Err(Error::StepLimit(10))
+        ));
+        // Шаг и пять повторений с поворотом, шестое повторение выходит за запас
+        assert_eq!(budget.steps(), 12);
+        assert_eq!((robot.y(), robot.direction()), (2, Direction::Left));
+
+        // Штамп оплачивается каждым своим отрезком
+        let mut robot = Robot::default();
+        let mut budget = StepBudget::new(&mut robot, 100);
+        budget.stamp(Stamp::Circle(5)).unwrap();
+        assert_eq!(budget.steps(), 48);
+        assert!(matches!(
+            budget.stamp(Stamp::Circle(100_000)),
+            Err(Error::StepLimit(100))
+        ));
+        assert_eq!(robot.path().len(), 28);
+    }
+
+    #[test]
+    fn test_rollback_restores_exact_position() {
+        let far = 5_000_000_000i64;
+        let mut robot = Robot::new(far, 0, Direction::Up, false);
+        let mut teleport = TeleportCommand::new(1, 2);
+        teleport.execute(&mut robot).unwrap();
+        teleport.rollback(&mut robot).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(far, 0));
+
+        let mut robot = Robot::new(0.4, 0.4, Direction::Up, false);
+        let mut teleport = TeleportCommand::new(3, 3);
+        teleport.execute(&mut robot).unwrap();
+        let mut home = HomeCommand::new();
+        home.execute(&mut robot).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(0.4, 0.4));
+        robot.set_position(7.5, -2.5).unwrap();
+        let mut home = HomeCommand::new();
+        home.execute(&mut StepBudget::new(&mut robot, 10)).unwrap();
+        home.rollback(&mut StepBudget::new(&mut robot, 10)).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(7.5, -2.5));
+        teleport.rollback(&mut robot).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(0.4, 0.4));
+
+        // Положение другого исполнителя возвращает в клетку сетки
+        let saved = RobotLike::saved_position(&Robot::new(0.4, 0.4, Direction::Up, false));
+        assert_eq!(saved.cell(), Point::new(0, 0));
+        let mut robot = Robot::default();
+        robot.restore_position(&saved).unwrap();
+        assert_eq!(Robot::position(&robot), Point::new(0, 0));
+    }
+}
@@ -0,0 +1,72 @@
+// Наблюдатель за роботом узнает о каждом изменении его состояния: перемещении,
+// повороте, смене пера, нарисованном отрезке и возврате сохраненного состояния.
+// Наблюдатели разделяются между копиями робота, поэтому методы принимают &self.
+// Отправитель канала тоже является наблюдателем: события можно читать в другом потоке.
+
+use std::{
+    fmt,
+    sync::{Arc, mpsc::Sender},
+};
+
+use super::{Coordinate, Direction, Pen, Point, Segment};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotEvent<C: Coordinate = i32> {
+    // Шаг или перенос в точку, drawing - было ли опущено перо
+    Moved {
+        from: Point<C>,
+        to: Point<C>,
+        drawing: bool,
+    },
+    Turned {
+        from: Direction,
+        to: Direction,
+    },
+    PenChanged(Pen),
+    // Шаг с опущенным пером нарисовал отрезок пути
+    Drawn(Segment<C>),
+    // Робот вернулся в сохраненное состояние
+    Restored,
+}
+
+pub trait RobotObserver<C: Coordinate = i32>: Send + Sync {
+    fn notify(&self, event: &RobotEvent<C>);
+}
+
+impl<C: Coordinate> RobotObserver<C> for Sender<RobotEvent<C>> {
+    fn notify(&self, event: &RobotEvent<C>) {
+        // Получатель мог быть закрыт, робот продолжает работу и без него
+        let _ = self.send(*event);
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Observers<C: Coordinate>(Vec<Arc<dyn RobotObserver<C>>>);
+
+impl<C: Coordinate> Default for Observers<C> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<C: Coordinate> Observers<C> {
+    pub(super) fn add(&mut self, observer: Arc<dyn RobotObserver<C>>) {
+        self.0.push(observer);
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub(super) fn notify(&self, event: RobotEvent<C>) {
+        for observer in &self.0 {
+            observer.notify(&event);
+        }
+    }
+}
+
+impl<C: Coordinate> fmt::Debug for Observers<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
@@ -0,0 +1,88 @@
+// Точка на поле робота. Прибавление направления дает соседнюю точку,
+// а печатается точка так же, как координаты в сообщениях робота: (x, y).
+
+use std::{fmt, ops::Add};
+
+use super::{Coordinate, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point<C: Coordinate = i32> {
+    pub x: C,
+    pub y: C,
+}
+
+impl<C: Coordinate> Point<C> {
+    pub fn new(x: C, y: C) -> Self {
+        Self { x, y }
+    }
+
+    // Соседняя точка в указанном направлении или None при переполнении координаты
+    pub fn checked_add(self, direction: Direction) -> Option<Self> {
+        self.checked_offset(direction, 1)
+    }
+
+    // Точка через steps шагов в указанном направлении
+    pub fn checked_offset(self, direction: Direction, steps: u32) -> Option<Self> {
+        let (dx, dy) = direction.delta();
+        let (steps, dx, dy) = (i64::from(steps), i64::from(dx), i64::from(dy));
+        Some(Self::new(
+            self.x.checked_step(dx * steps)?,
+            self.y.checked_step(dy * steps)?,
+        ))
+    }
+
+    // Расстояние по прямой между точками
+    pub fn distance(self, other: Self) -> f64 {
+        let dx = self.x.to_f64() - other.x.to_f64();
+        let dy = self.y.to_f64() - other.y.to_f64();
+        dx.hypot(dy)
+    }
+}
+
+// Паникует, если координата выходит за пределы своего типа, как и сложение целых чисел
+impl<C: Coordinate> Add<Direction> for Point<C> {
+    type Output = Self;
+
+    fn add(self, direction: Direction) -> Self {
+        self.checked_add(direction)
+            .expect("point coordinate overflow")
+    }
+}
+
+impl<C: Coordinate> From<(C, C)> for Point<C> {
+    fn from((x, y): (C, C)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl<C: Coordinate> From<Point<C>> for (C, C) {
+    fn from(point: Point<C>) -> Self {
+        (point.x, point.y)
+    }
+}
+
+impl<C: Coordinate> fmt::Display for Point<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point() {
+        let point = Point::new(1, 2) + Direction::DownLeft;
+        assert_eq!(point, Point::new(0, 1));
+        assert_eq!(point.to_string(), "(0, 1)");
+        assert_eq!(Point::new(0, 0).distance(Point::new(3, -4)), 5.0);
+        assert_eq!(Point::new(i32::MAX, 0).checked_add(Direction::Right), None);
+        assert_eq!(
+            Point::new(1, 1).checked_offset(Direction::DownRight, 3),
+            Some(Point::new(4, -2))
+        );
+        assert_eq!(<(i32, i32)>::from(Point::from((3, 4))), (3, 4));
+        assert_eq!(Point::new(0.5, 0.0) + Direction::Up, Point::new(0.5, 1.0));
+    }
+}
@@ -0,0 +1,178 @@
+// Робот с дробными координатами и произвольным курсом, как черепаха в Logo.
+// Курс задается в градусах: 0 - вверх, 90 - вправо, поворот направо увеличивает курс.
+// Робот не привязан к сетке, поэтому может рисовать линии под любым углом.
+// Основным остается робот на целочисленной сетке (Robot), этот робот его не заменяет.
+
+use super::Color;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreciseRobot {
+    x: f64,
+    y: f64,
+    heading: f64,
+    drawing: bool,
+    color: Color,
+    pen_width: u32,
+    path: Vec<PreciseSegment>,
+}
+
+// Отрезок, нарисованный роботом с дробными координатами
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreciseSegment {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+    pub color: Color,
+    pub width: u32,
+}
+
+impl Default for PreciseRobot {
+    fn default() -> Self {
+        Self::new(0.0, 0.0, 0.0)
+    }
+}
+
+impl PreciseRobot {
+    pub fn new(x: f64, y: f64, heading: f64) -> Self {
+        Self {
+            x,
+            y,
+            heading: normalize(heading),
+            drawing: false,
+            color: Color::default(),
+            pen_width: 1,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.y
+    }
+
+    // Курс в градусах от 0 до 360
+    pub fn heading(&self) -> f64 {
+        self.heading
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        self.drawing
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    pub fn pen_width(&self) -> u32 {
+        self.pen_width
+    }
+
+    pub fn set_pen_width(&mut self, width: u32) {
+        self.pen_width = width;
+    }
+
+    pub fn path(&self) -> &[PreciseSegment] {
+        &self.path
+    }
+
+    pub fn clear_path(&mut self) {
+        self.path.clear();
+    }
+
+    // Перемещает робота по курсу, отрицательное расстояние перемещает назад
+    pub fn forward(&mut self, distance: f64) {
+        let from = (self.x, self.y);
+        let (sin, cos) = self.heading.to_radians().sin_cos();
+        self.x += distance * sin;
+        self.y += distance * cos;
+
+        log::info!("Move to ({:.3}, {:.3})", self.x, self.y);
+        if self.drawing && distance != 0.0 {
+            self.path.push(PreciseSegment {
+                from,
+                to: (self.x, self.y),
+                color: self.color,
+                width: self.pen_width,
+            });
+        }
+    }
+
+    pub fn back(&mut self, distance: f64) {
+        self.forward(-distance);
+    }
+
+    pub fn left(&mut self, degrees: f64) {
+        self.set_heading(self.heading - degrees);
+    }
+
+    pub fn right(&mut self, degrees: f64) {
+        self.set_heading(self.heading + degrees);
+    }
+
+    pub fn set_heading(&mut self, heading: f64) {
+        self.heading = normalize(heading);
+        log::info!("Heading {:.3}", self.heading);
+    }
+
+    // Переносит робота в точку без рисования
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.x = x;
+        self.y = y;
+        log::info!("Teleport to ({:.3}, {:.3})", self.x, self.y);
+    }
+
+    pub fn down_pen(&mut self) {
+        self.drawing = true;
+    }
+
+    pub fn up_pen(&mut self) {
+        self.drawing = false;
+    }
+}
+
+fn normalize(heading: f64) -> f64 {
+    heading.rem_euclid(360.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: (f64, f64), expected: (f64, f64)) {
+        let distance = (actual.0 - expected.0).hypot(actual.1 - expected.1);
+        assert!(distance < 1e-9, "{actual:?} != {expected:?}");
+    }
+
+    #[test]
+    fn test_precise_robot_triangle() {
+        let mut robot = PreciseRobot::default();
+        robot.down_pen();
+        for _ in 0..3 {
+            robot.forward(10.0);
+            robot.right(120.0);
+        }
+
+        assert_near((robot.x(), robot.y()), (0.0, 0.0));
+        assert_eq!(robot.heading(), 0.0);
+        assert_eq!(robot.path().len(), 3);
+        assert_near(robot.path()[0].to, (0.0, 10.0));
+        assert_near(robot.path()[1].to, (10.0 * 60f64.to_radians().sin(), 5.0));
+    }
+
+    #[test]
+    fn test_precise_robot_heading() {
+        let mut robot = PreciseRobot::new(1.0, 1.0, -90.0);
+        assert_eq!(robot.heading(), 270.0);
+        robot.left(45.0);
+        assert_eq!(robot.heading(), 225.0);
+        robot.back(2f64.sqrt());
+        assert_near((robot.x(), robot.y()), (2.0, 2.0));
+        assert!(robot.path().is_empty());
+    }
+}
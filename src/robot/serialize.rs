@@ -0,0 +1,270 @@
+// Сериализация направления и цвета пера строками, как они записываются в скриптах.
+// Робот и строитель записываются отображением полей: положение, направление, перо, поле,
+// режим краев, дом и топливо, у робота еще нарисованный путь. Мир, наблюдатели и статистика
+// не сохраняются: после чтения робот находится в пустом мире, а статистика начинается заново.
+
+use std::{fmt, marker::PhantomData, sync::Arc};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor},
+    ser::SerializeStruct,
+};
+
+use super::{
+    Bounds, Color, Coordinate, Direction, Fuel, Home, Pen, PenMode, Point, Robot, RobotBuilder,
+    Segment, Stats, WrapMode,
+};
+
+const DIRECTIONS: &[&str] = &[
+    "up",
+    "down",
+    "left",
+    "right",
+    "up_left",
+    "up_right",
+    "down_left",
+    "down_right",
+];
+const COLORS: &[&str] = &[
+    "black", "white", "red", "green", "blue", "yellow", "cyan", "magenta",
+];
+const PEN_MODES: &[&str] = &["draw", "erase"];
+const WRAP_MODES: &[&str] = &["stop", "torus"];
+
+impl Serialize for Direction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Direction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(NameVisitor {
+            expecting: "a direction",
+            names: DIRECTIONS,
+            values: &Direction::ALL,
+        })
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(NameVisitor {
+            expecting: "a color",
+            names: COLORS,
+            values: &Color::ALL,
+        })
+    }
+}
+
+impl Serialize for PenMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            PenMode::Draw => "draw",
+            PenMode::Erase => "erase",
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for PenMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(NameVisitor {
+            expecting: "a pen mode",
+            names: PEN_MODES,
+            values: &[PenMode::Draw, PenMode::Erase],
+        })
+    }
+}
+
+impl Serialize for WrapMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            WrapMode::Stop => "stop",
+            WrapMode::Torus => "torus",
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for WrapMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(NameVisitor {
+            expecting: "a wrap mode",
+            names: WRAP_MODES,
+            values: &[WrapMode::Stop, WrapMode::Torus],
+        })
+    }
+}
+
+// Записывает структуру отображением перечисленных полей и читает ее обратно.
+// Все поля обязательны, а пропущенные после "skip" поля заполняются выражениями,
+// в которых доступны уже прочитанные поля.
+macro_rules! record {
+    (
+        $name:ident $(<$c:ident>)?, $expecting:literal,
+        { $($field:ident),* $(,)? }
+        $(skip { $($skip:ident: $default:expr),* $(,)? })?
+    ) => {
+        impl$(<$c: Coordinate + Serialize>)? Serialize for $name$(<$c>)? {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let fields = [$(stringify!($field)),*];
+                let mut record = serializer.serialize_struct(stringify!($name), fields.len())?;
+                $(record.serialize_field(stringify!($field), &self.$field)?;)*
+                record.end()
+            }
+        }
+
+        impl<'de $(, $c: Coordinate + Deserialize<'de>)?> Deserialize<'de> for $name$(<$c>)? {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct RecordVisitor$(<$c>)?(PhantomData<($($c,)?)>);
+
+                impl<'de $(, $c: Coordinate + Deserialize<'de>)?> Visitor<'de>
+                    for RecordVisitor$(<$c>)?
+                {
+                    type Value = $name$(<$c>)?;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str($expecting)
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> Result<Self::Value, A::Error> {
+                        const FIELDS: &[&str] = &[$(stringify!($field)),*];
+                        $(let mut $field = None;)*
+
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $(stringify!($field) => {
+                                    if $field.is_some() {
+                                        return Err(de::Error::duplicate_field(stringify!($field)));
+                                    }
+                                    $field = Some(map.next_value()?);
+                                })*
+                                _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                            }
+                        }
+
+                        $(let $field = $field
+                            .ok_or_else(|| de::Error::missing_field(stringify!($field)))?;)*
+                        Ok($name {
+                            $($($skip: $default,)*)?
+                            $($field,)*
+                        })
+                    }
+                }
+
+                deserializer.deserialize_map(RecordVisitor(PhantomData))
+            }
+        }
+    };
+}
+
+record!(Point<C>, "a point", { x, y });
+record!(Bounds<C>, "field bounds", { min_x, max_x, min_y, max_y });
+record!(Home<C>, "a home pose", { position, direction });
+record!(Pen, "a pen", { down, color, width, mode });
+record!(Fuel, "fuel", { level, step_cost, turn_cost });
+record!(Segment<C>, "a path segment", { from, to, color, width, mode });
+record!(
+    RobotBuilder<C>, "a robot builder",
+    { x, y, direction, pen, bounds, wrap, home, fuel }
+    skip { world: Arc::default() }
+);
+record!(
+    Robot<C>, "a robot",
+    { position, direction, pen, bounds, wrap, home, fuel, path }
+    skip {
+        world: Arc::default(),
+        stats: Stats::starting_at(position),
+        observers: Default::default(),
+    }
+);
+
+// Ищет значение по имени, имена и значения перечислены в одном порядке
+struct NameVisitor<T: 'static> {
+    expecting: &'static str,
+    names: &'static [&'static str],
+    values: &'static [T],
+}
+
+impl<T: Copy> Visitor<'_> for NameVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        self.names
+            .iter()
+            .position(|name| *name == value)
+            .map(|index| self.values[index])
+            .ok_or_else(|| E::unknown_variant(value, self.names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_names_match_display() {
+        for color in Color::ALL {
+            let json = json::to_string(&color).unwrap();
+            assert_eq!(json, format!("\"{color}\""));
+            assert_eq!(json::from_str::<Color>(&json).unwrap(), color);
+        }
+        for direction in Direction::ALL {
+            let json = json::to_string(&direction).unwrap();
+            assert_eq!(json, format!("\"{direction}\""));
+            assert_eq!(json::from_str::<Direction>(&json).unwrap(), direction);
+        }
+        assert!(json::from_str::<Direction>("\"north\"").is_err());
+    }
+
+    #[test]
+    fn test_robot_round_trip() {
+        let mut robot = RobotBuilder::new()
+            .x(3)
+            .direction(Direction::Left)
+            .drawing(true)
+            .pen_mode(PenMode::Erase)
+            .bounds(Bounds::new(-5, 5, -5, 5))
+            .wrap(WrapMode::Torus)
+            .build();
+        robot.move_forward().unwrap();
+
+        let json = json::to_string(&robot).unwrap();
+        assert!(json.starts_with(r#"{"position":{"x":2,"y":0},"direction":"left","pen":{"#));
+        let restored: Robot = json::from_str(&json).unwrap();
+        assert_eq!(restored.position(), robot.position());
+        assert_eq!(restored.pen(), robot.pen());
+        assert_eq!(restored.bounds(), robot.bounds());
+        assert_eq!(restored.wrap(), WrapMode::Torus);
+        assert_eq!(restored.home(), robot.home());
+        assert_eq!(restored.path(), robot.path());
+        assert_eq!(restored.stats().cells_visited(), 1);
+        assert_eq!(json::to_string(&restored).unwrap(), json);
+
+        assert!(json::from_str::<Robot>(r#"{"position":{"x":1,"y":2}}"#).is_err());
+    }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let builder = RobotBuilder::at(0.5, -1.0).color(Color::Red);
+        let json = json::to_string(&builder).unwrap();
+        let robot = json::from_str::<RobotBuilder<f64>>(&json).unwrap().build();
+        assert_eq!(robot.position(), Point::new(0.5, -1.0));
+        assert_eq!(robot.color(), Color::Red);
+        assert_eq!(robot.home().position, Point::new(0.5, -1.0));
+    }
+}
@@ -0,0 +1,130 @@
+// Штампы - простые фигуры, которые робот отпечатывает вокруг себя, не перемещаясь:
+// точка, контур квадрата и окружность. Штамп рисуется текущим пером, даже поднятым,
+// и запоминается в пути робота как отрезки, поэтому холст видит его так же, как линии.
+// Препятствия и границы поля штамп не проверяет: он только отмечает клетки.
+// Окружность занимает порядка 6 * radius клеток, поэтому ее радиус ограничен MAX_RADIUS.
+
+use std::fmt;
+
+pub const MAX_RADIUS: u32 = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stamp {
+    // Клетка робота
+    Dot,
+    // Контур квадрата со стороной в size клеток с центром в клетке робота,
+    // при четной стороне центр смещен влево вниз
+    Square(u32),
+    // Окружность радиуса radius с центром в клетке робота
+    Circle(u32),
+}
+
+impl Stamp {
+    // Число отрезков фигуры в пути робота без их построения, для окружности - оценка сверху
+    pub fn segments(self) -> u64 {
+        match self {
+            Stamp::Dot | Stamp::Square(1) => 1,
+            Stamp::Square(0) => 0,
+            Stamp::Square(_) => 4,
+            Stamp::Circle(radius) => 8 * (u64::from(radius) + 1),
+        }
+    }
+
+    // Отрезки фигуры: концы заданы смещениями от клетки робота
+    pub(super) fn offsets(self) -> Vec<((i64, i64), (i64, i64))> {
+        match self {
+            Stamp::Dot | Stamp::Square(1) | Stamp::Circle(0) => vec![((0, 0), (0, 0))],
+            Stamp::Square(0) => Vec::new(),
+            Stamp::Square(size) => {
+                let low = -i64::from((size - 1) / 2);
+                let high = low + i64::from(size) - 1;
+                vec![
+                    ((low, low), (high, low)),
+                    ((high, low), (high, high)),
+                    ((high, high), (low, high)),
+                    ((low, high), (low, low)),
+                ]
+            }
+            Stamp::Circle(radius) => circle(i64::from(radius))
+                .into_iter()
+                .map(|point| (point, point))
+                .collect(),
+        }
+    }
+}
+
+// Клетки окружности по алгоритму средней точки, каждая клетка один раз.
+// Клетки на осях и диагоналях общие для соседних октантов, поэтому для них
+// отражений вдвое меньше.
+fn circle(radius: i64) -> Vec<(i64, i64)> {
+    let mut points = Vec::new();
+    let (mut x, mut y, mut error) = (radius, 0, 1 - radius);
+    while x >= y {
+        if y == 0 {
+            points.extend([(x, 0), (0, x), (-x, 0), (0, -x)]);
+        } else if x == y {
+            points.extend([(x, x), (-x, x), (-x, -x), (x, -x)]);
+        } else {
+            points.extend([
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ]);
+        }
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+    points
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stamp::Dot => write!(f, "dot"),
+            Stamp::Square(size) => write!(f, "square {size}"),
+            Stamp::Circle(radius) => write!(f, "circle {radius}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_offsets() {
+        assert_eq!(Stamp::Dot.offsets(), [((0, 0), (0, 0))]);
+        assert!(Stamp::Square(0).offsets().is_empty());
+        assert_eq!(Stamp::Square(4).offsets()[0], ((-1, -1), (2, -1)));
+        assert_eq!(Stamp::Square(3).offsets()[2], ((1, 1), (-1, 1)));
+
+        let circle = Stamp::Circle(2).offsets();
+        assert_eq!(circle.len(), 12);
+        assert!(circle.contains(&((0, 2), (0, 2))));
+        assert!(circle.contains(&((-1, -2), (-1, -2))));
+        assert!(!circle.contains(&((2, 2), (2, 2))));
+        assert_eq!(Stamp::Circle(1).offsets().len(), 4);
+
+        // Каждая клетка окружности встречается один раз
+        for radius in [3, 7, 100] {
+            let circle = Stamp::Circle(radius).offsets();
+            let cells: std::collections::HashSet<_> = circle.iter().collect();
+            assert_eq!(cells.len(), circle.len());
+            assert!(circle.len() as u64 <= Stamp::Circle(radius).segments());
+        }
+        assert_eq!(
+            Stamp::Circle(MAX_RADIUS).offsets()[0],
+            ((65536, 0), (65536, 0))
+        );
+    }
+}
@@ -0,0 +1,131 @@
+// Надписи печатными буквами. Каждая буква - картинка 3x5 клеток, между буквами остается
+// пустой столбец. Строка идет от клетки робота по его направлению, а верх букв смотрит
+// налево от направления, поэтому робот, смотрящий вправо, пишет текст как обычно.
+// Надпись, как и штамп, рисуется текущим пером и не перемещает робота.
+// Строчные буквы печатаются заглавными, символы без картинки - пробелом.
+
+use super::Direction;
+
+const GLYPH_WIDTH: i64 = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+// Строки картинок сверху вниз, '#' - закрашенная клетка
+const FONT: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', [".#.", "#.#", "###", "#.#", "#.#"]),
+    ('B', ["##.", "#.#", "##.", "#.#", "##."]),
+    ('C', [".##", "#..", "#..", "#..", ".##"]),
+    ('D', ["##.", "#.#", "#.#", "#.#", "##."]),
+    ('E', ["###", "#..", "##.", "#..", "###"]),
+    ('F', ["###", "#..", "##.", "#..", "#.."]),
+    ('G', [".##", "#..", "#.#", "#.#", ".##"]),
+    ('H', ["#.#", "#.#", "###", "#.#", "#.#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..#", "..#", "..#", "#.#", ".#."]),
+    ('K', ["#.#", "#.#", "##.", "#.#", "#.#"]),
+    ('L', ["#..", "#..", "#..", "#..", "###"]),
+    ('M', ["#.#", "###", "###", "#.#", "#.#"]),
+    ('N', ["##.", "#.#", "#.#", "#.#", "#.#"]),
+    ('O', [".#.", "#.#", "#.#", "#.#", ".#."]),
+    ('P', ["##.", "#.#", "##.", "#..", "#.."]),
+    ('Q', [".#.", "#.#", "#.#", "##.", ".##"]),
+    ('R', ["##.", "#.#", "##.", "#.#", "#.#"]),
+    ('S', [".##", "#..", ".#.", "..#", "##."]),
+    ('T', ["###", ".#.", ".#.", ".#.", ".#."]),
+    ('U', ["#.#", "#.#", "#.#", "#.#", "###"]),
+    ('V', ["#.#", "#.#", "#.#", "#.#", ".#."]),
+    ('W', ["#.#", "#.#", "###", "###", "#.#"]),
+    ('X', ["#.#", "#.#", ".#.", "#.#", "#.#"]),
+    ('Y', ["#.#", "#.#", ".#.", ".#.", ".#."]),
+    ('Z', ["###", "..#", ".#.", "#..", "###"]),
+    ('0', ["###", "#.#", "#.#", "#.#", "###"]),
+    ('1', [".#.", "##.", ".#.", ".#.", "###"]),
+    ('2', ["##.", "..#", ".#.", "#..", "###"]),
+    ('3', ["##.", "..#", ".#.", "..#", "##."]),
+    ('4', ["#.#", "#.#", "###", "..#", "..#"]),
+    ('5', ["###", "#..", "##.", "..#", "##."]),
+    ('6', [".##", "#..", "###", "#.#", "###"]),
+    ('7', ["###", "..#", ".#.", ".#.", ".#."]),
+    ('8', ["###", "#.#", "###", "#.#", "###"]),
+    ('9', ["###", "#.#", "###", "..#", "##."]),
+    ('.', ["...", "...", "...", "...", ".#."]),
+    (',', ["...", "...", "...", ".#.", "#.."]),
+    ('!', [".#.", ".#.", ".#.", "...", ".#."]),
+    ('?', ["##.", "..#", ".#.", "...", ".#."]),
+    ('-', ["...", "...", "###", "...", "..."]),
+    (':', ["...", ".#.", "...", ".#.", "..."]),
+];
+
+fn glyph(ch: char) -> Option<&'static [&'static str; GLYPH_HEIGHT]> {
+    let ch = ch.to_ascii_uppercase();
+    FONT.iter()
+        .find(|(glyph, _)| *glyph == ch)
+        .map(|(_, rows)| rows)
+}
+
+// Отрезки надписи: концы заданы смещениями от клетки робота.
+// Соседние закрашенные клетки строки собираются в один отрезок.
+pub(super) fn offsets(text: &str, direction: Direction) -> Vec<((i64, i64), (i64, i64))> {
+    let (dx, dy) = direction.delta();
+    let (dx, dy) = (i64::from(dx), i64::from(dy));
+    // Верх букв - налево от направления
+    let (ux, uy) = (-dy, dx);
+    let cell = |column: i64, row: i64| (column * dx + row * ux, column * dy + row * uy);
+
+    let mut segments = Vec::new();
+    for (index, ch) in text.chars().enumerate() {
+        let Some(rows) = glyph(ch) else {
+            continue;
+        };
+        let left = index as i64 * (GLYPH_WIDTH + 1);
+        for (line, pixels) in rows.iter().enumerate() {
+            let row = (GLYPH_HEIGHT - 1 - line) as i64;
+            let mut start = None;
+            for (column, pixel) in pixels.chars().chain(['.']).enumerate() {
+                let column = left + column as i64;
+                match (pixel, start) {
+                    ('#', None) => start = Some(column),
+                    ('#', Some(_)) => {}
+                    (_, Some(first)) => {
+                        segments.push((cell(first, row), cell(column - 1, row)));
+                        start = None;
+                    }
+                    (_, None) => {}
+                }
+            }
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font() {
+        for (ch, rows) in FONT {
+            assert!(
+                rows.iter().all(|row| row.len() == GLYPH_WIDTH as usize
+                    && row.chars().all(|pixel| pixel == '#' || pixel == '.')),
+                "glyph {ch}"
+            );
+        }
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph(' '), None);
+    }
+
+    #[test]
+    fn test_offsets() {
+        // Верхняя строка "T" - один отрезок, ножка - одиночные клетки
+        let segments = offsets("T", Direction::Right);
+        assert_eq!(segments.len(), 5);
+        assert_eq!(segments[0], ((0, 4), (2, 4)));
+        assert_eq!(segments[4], ((1, 0), (1, 0)));
+
+        // Вторая буква начинается через пустой столбец, неизвестный символ пропускается
+        assert_eq!(offsets("~T", Direction::Right)[0], ((4, 4), (6, 4)));
+
+        // Робот, смотрящий вверх, пишет снизу вверх, верх букв - слева
+        assert_eq!(offsets("T", Direction::Up)[0], ((-4, 0), (-4, 2)));
+    }
+}
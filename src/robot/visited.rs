@@ -0,0 +1,231 @@
+// Подсчет посещенных клеток по участкам пути без перечисления клеток.
+// Каждый участок лежит на прямой одного из четырех видов: горизонтальной, вертикальной,
+// диагонали или побочной диагонали. Участки на одной прямой объединяются в отрезки,
+// а клетки, где пересекаются прямые разных видов, вычитаются, чтобы считаться один раз.
+// Пересечения ищутся только среди прямых, которые проходят через отрезок, поэтому
+// время зависит от числа участков, а не от длины пути.
+
+use std::collections::{BTreeMap, HashMap};
+
+// Участок в клетках: начальная клетка, шаг по каждой оси (-1, 0 или 1) и число шагов
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Line {
+    pub start: (i128, i128),
+    pub step: (i128, i128),
+    pub steps: i128,
+}
+
+impl Line {
+    pub fn contains(self, (x, y): (i128, i128)) -> bool {
+        let (dx, dy) = (x - self.start.0, y - self.start.1);
+        let step = if self.step.0 != 0 {
+            dx * self.step.0
+        } else {
+            dy * self.step.1
+        };
+        (0..=self.steps).contains(&step) && (dx, dy) == (step * self.step.0, step * self.step.1)
+    }
+}
+
+// Вид прямой: ключ - номер прямой среди прямых этого вида, параметр - положение клетки на ней
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
+
+const KINDS: [Kind; 4] = [
+    Kind::Horizontal,
+    Kind::Vertical,
+    Kind::Diagonal,
+    Kind::AntiDiagonal,
+];
+
+impl Kind {
+    fn of(step: (i128, i128)) -> Self {
+        match step {
+            (_, 0) => Kind::Horizontal,
+            (0, _) => Kind::Vertical,
+            (dx, dy) if dx == dy => Kind::Diagonal,
+            _ => Kind::AntiDiagonal,
+        }
+    }
+
+    fn key(self, (x, y): (i128, i128)) -> i128 {
+        match self {
+            Kind::Horizontal => y,
+            Kind::Vertical => x,
+            Kind::Diagonal => x - y,
+            Kind::AntiDiagonal => x + y,
+        }
+    }
+
+    fn param(self, (x, y): (i128, i128)) -> i128 {
+        match self {
+            Kind::Vertical => y,
+            _ => x,
+        }
+    }
+
+    fn cell(self, key: i128, param: i128) -> (i128, i128) {
+        match self {
+            Kind::Horizontal => (param, key),
+            Kind::Vertical => (key, param),
+            Kind::Diagonal => (param, param - key),
+            Kind::AntiDiagonal => (param, key - param),
+        }
+    }
+}
+
+// Объединенные отрезки [начало, конец] параметра на каждой прямой одного вида
+type Lines = BTreeMap<i128, Vec<(i128, i128)>>;
+
+pub(super) fn count(lines: impl IntoIterator<Item = Line>) -> usize {
+    let mut kinds: [Lines; 4] = Default::default();
+    for line in lines {
+        let kind = Kind::of(line.step);
+        let end = (
+            line.start.0 + line.step.0 * line.steps,
+            line.start.1 + line.step.1 * line.steps,
+        );
+        let (from, to) = (kind.param(line.start), kind.param(end));
+        kinds[kind as usize]
+            .entry(kind.key(line.start))
+            .or_default()
+            .push((from.min(to), from.max(to)));
+    }
+    for intervals in kinds.iter_mut().flat_map(|lines| lines.values_mut()) {
+        *intervals = merge(std::mem::take(intervals));
+    }
+
+    let total: i128 = kinds
+        .iter()
+        .flat_map(|lines| lines.values().flatten())
+        .map(|(from, to)| to - from + 1)
+        .sum();
+
+    // Виды прямых, проходящих через клетки пересечений
+    let mut crossings: HashMap<(i128, i128), u8> = HashMap::new();
+    for (index, kind) in KINDS.into_iter().enumerate() {
+        for (other_index, other) in KINDS.into_iter().enumerate().skip(index + 1) {
+            for (&key, intervals) in &kinds[other_index] {
+                for &(from, to) in intervals {
+                    for cell in crossings_with(kind, &kinds[index], other, key, (from, to)) {
+                        *crossings.entry(cell).or_default() |= 1 << index | 1 << other_index;
+                    }
+                }
+            }
+        }
+    }
+    let repeated: i128 = crossings
+        .values()
+        .map(|kinds| i128::from(kinds.count_ones()) - 1)
+        .sum();
+
+    usize::try_from(total - repeated).unwrap_or(usize::MAX)
+}
+
+// Сортирует отрезки и объединяет пересекающиеся и соседние
+fn merge(mut intervals: Vec<(i128, i128)>) -> Vec<(i128, i128)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(i128, i128)> = Vec::with_capacity(intervals.len());
+    for (from, to) in intervals {
+        match merged.last_mut() {
+            Some(last) if from <= last.1 + 1 => last.1 = last.1.max(to),
+            _ => merged.push((from, to)),
+        }
+    }
+    merged
+}
+
+// Клетки отрезка прямой other, через которые проходят отрезки прямых вида kind
+fn crossings_with<'a>(
+    kind: Kind,
+    lines: &'a Lines,
+    other: Kind,
+    key: i128,
+    (from, to): (i128, i128),
+) -> impl Iterator<Item = (i128, i128)> + 'a {
+    // Ключ прямой вида kind меняется вдоль отрезка линейно: key(t) = base + slope * t
+    let base = kind.key(other.cell(key, 0));
+    let slope = kind.key(other.cell(key, 1)) - base;
+    let (low, high) = (base + slope * from, base + slope * to);
+
+    lines
+        .range(low.min(high)..=low.max(high))
+        .filter_map(move |(&line, intervals)| {
+            if (line - base) % slope != 0 {
+                return None;
+            }
+            let cell = other.cell(key, (line - base) / slope);
+            let param = kind.param(cell);
+            let index = intervals.partition_point(|&(_, to)| to < param);
+            let &(start, _) = intervals.get(index)?;
+            (start <= param).then_some(cell)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn line(start: (i128, i128), step: (i128, i128), steps: i128) -> Line {
+        Line { start, step, steps }
+    }
+
+    // Клетки участков по одной, для сравнения с подсчетом
+    fn cells(lines: &[Line]) -> usize {
+        let mut cells = HashSet::new();
+        for line in lines {
+            for step in 0..=line.steps {
+                cells.insert((
+                    line.start.0 + line.step.0 * step,
+                    line.start.1 + line.step.1 * step,
+                ));
+            }
+        }
+        cells.len()
+    }
+
+    #[test]
+    fn test_count_matches_cells() {
+        let lines = [
+            line((0, 0), (1, 0), 10),
+            line((10, 0), (0, 1), 10),
+            line((10, 10), (-1, -1), 10),
+            line((0, 10), (1, -1), 10),
+            line((3, 0), (-1, 0), 5),
+            line((5, 5), (0, 1), 0),
+            line((4, -3), (0, 1), 8),
+            line((-2, 3), (1, 1), 4),
+            line((7, 1), (-1, 1), 0),
+        ];
+        assert_eq!(count(lines), cells(&lines));
+        assert_eq!(count([]), 0);
+
+        // Ломаная с поворотами на 45 и 90 градусов
+        let mut lines = Vec::new();
+        let mut start = (0, 0);
+        for index in 0..200 {
+            let step = [(1, 0), (1, 1), (0, -1), (-1, 1), (1, -1)][index % 5];
+            let steps = (index * 7 % 13) as i128;
+            lines.push(line(start, step, steps));
+            start = (start.0 + step.0 * steps, start.1 + step.1 * steps);
+        }
+        assert_eq!(count(lines.iter().copied()), cells(&lines));
+    }
+
+    #[test]
+    fn test_line_contains() {
+        let diagonal = line((1, 1), (-1, 1), 3);
+        assert!(diagonal.contains((-2, 4)));
+        assert!(diagonal.contains((1, 1)));
+        assert!(!diagonal.contains((2, 0)));
+        assert!(!diagonal.contains((-1, 2)));
+        assert!(line((5, 5), (0, 1), 0).contains((5, 5)));
+    }
+}
@@ -0,0 +1,154 @@
+// Мост между роботом и встраиваемым языком сценариев для тех, кому мало языка команд:
+// в таком языке есть циклы, функции и арифметика, а робот доступен через функции
+// move(n), back(n), left(), right(), pen_down(), pen_up(), goto(x, y), x() и y().
+// Движок (например Rhai) регистрирует каждую функцию из FUNCTIONS как вызов
+// ScriptApi::call с ее именем, а движок и мост делят одного робота (Rc<RefCell<Robot>>),
+// поэтому после сценария робот доступен так же, как после списка команд.
+// Функции выполняются теми же командами, что и язык команд, с теми же ошибками.
+// Сам движок пока не подключен: Rhai нет среди зависимостей крейта, поэтому функция
+// "scripting" дает только этот мост, с которым вызовы можно проверить без движка.
+
+use std::{cell::RefCell, rc::Rc};
+
+use super::{
+    command::{
+        Command, DownPenCommand, MoveBackwardCommand, MoveCommand, TeleportCommand,
+        TurnLeftCommand, TurnRightCommand, UpPenCommand,
+    },
+    error::Error,
+    robot::Robot,
+};
+
+// Имена функций для движка и количество их аргументов
+pub const FUNCTIONS: &[(&str, usize)] = &[
+    ("move", 1),
+    ("back", 1),
+    ("left", 0),
+    ("right", 0),
+    ("pen_down", 0),
+    ("pen_up", 0),
+    ("goto", 2),
+    ("x", 0),
+    ("y", 0),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct ScriptApi {
+    robot: Rc<RefCell<Robot>>,
+}
+
+impl ScriptApi {
+    pub fn new(robot: Robot) -> Self {
+        Self {
+            robot: Rc::new(RefCell::new(robot)),
+        }
+    }
+
+    // Робот, которым управляет сценарий, движок хранит копию этого указателя
+    pub fn robot(&self) -> Rc<RefCell<Robot>> {
+        self.robot.clone()
+    }
+
+    // Вызывает функцию робота с целыми аргументами, как их передает движок.
+    // Функции-запросы возвращают значение, остальные - 0.
+    pub fn call(&self, name: &str, args: &[i64]) -> Result<i64, Error> {
+        let arity = FUNCTIONS
+            .iter()
+            .find(|(function, _)| *function == name)
+            .map(|(_, arity)| *arity)
+            .ok_or_else(|| Error::InvalidScriptCall(format!("unknown function {name}")))?;
+        if args.len() != arity {
+            return Err(Error::InvalidScriptCall(format!(
+                "{name} takes {arity} arguments, got {}",
+                args.len()
+            )));
+        }
+
+        let mut robot = self.robot.borrow_mut();
+        let mut command: Box<dyn Command> = match (name, args) {
+            ("move", &[distance]) if distance < 0 => {
+                let steps = u32::try_from(distance.unsigned_abs())
+                    .map_err(|_| invalid_distance(name, distance))?;
+                Box::new(MoveBackwardCommand::new(steps))
+            }
+            ("move", &[distance]) => Box::new(MoveCommand::new(steps(name, distance)?)),
+            ("back", &[distance]) => Box::new(MoveBackwardCommand::new(steps(name, distance)?)),
+            ("left", _) => Box::new(TurnLeftCommand::new(1)),
+            ("right", _) => Box::new(TurnRightCommand::new(1)),
+            ("pen_down", _) => Box::new(DownPenCommand),
+            ("pen_up", _) => Box::new(UpPenCommand),
+            ("goto", &[x, y]) => Box::new(TeleportCommand::new(
+                coordinate(name, x)?,
+                coordinate(name, y)?,
+            )),
+            ("x", _) => return Ok(robot.x().into()),
+            _ => return Ok(robot.y().into()),
+        };
+        command.execute(&mut *robot)?;
+        Ok(0)
+    }
+}
+
+fn steps(name: &str, value: i64) -> Result<u32, Error> {
+    u32::try_from(value).map_err(|_| invalid_distance(name, value))
+}
+
+fn invalid_distance(name: &str, value: i64) -> Error {
+    Error::InvalidScriptCall(format!("invalid distance {value} for {name}"))
+}
+
+fn coordinate(name: &str, value: i64) -> Result<i32, Error> {
+    i32::try_from(value)
+        .map_err(|_| Error::InvalidScriptCall(format!("invalid coordinate {value} for {name}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::Direction;
+
+    #[test]
+    fn test_script_api() {
+        let api = ScriptApi::default();
+        // Квадрат, как его нарисовал бы цикл сценария
+        api.call("pen_down", &[]).unwrap();
+        for _ in 0..4 {
+            api.call("move", &[2]).unwrap();
+            api.call("right", &[]).unwrap();
+        }
+        let robot = api.robot();
+        assert_eq!((robot.borrow().x(), robot.borrow().y()), (0, 0));
+        assert_eq!(robot.borrow().path().len(), 4);
+
+        api.call("move", &[-3]).unwrap();
+        api.call("left", &[]).unwrap();
+        assert_eq!(api.call("y", &[]).unwrap(), -3);
+        assert_eq!(robot.borrow().direction(), Direction::Left);
+
+        api.call("pen_up", &[]).unwrap();
+        api.call("goto", &[5, -7]).unwrap();
+        assert_eq!(api.call("x", &[]).unwrap(), 5);
+        assert_eq!(api.call("y", &[]).unwrap(), -7);
+        assert!(!robot.borrow().is_drawing());
+    }
+
+    #[test]
+    fn test_script_api_errors() {
+        let api = ScriptApi::default();
+        assert!(matches!(
+            api.call("fly", &[]),
+            Err(Error::InvalidScriptCall(message)) if message == "unknown function fly"
+        ));
+        assert!(matches!(
+            api.call("move", &[]),
+            Err(Error::InvalidScriptCall(message)) if message == "move takes 1 arguments, got 0"
+        ));
+        assert!(api.call("back", &[-1]).is_err());
+        assert!(matches!(
+            api.call("move", &[i64::MIN]),
+            Err(Error::InvalidScriptCall(message))
+                if message == "invalid distance -9223372036854775808 for move"
+        ));
+        assert!(api.call("goto", &[1 << 40, 0]).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+// Сервер для управления роботом по сети, например для демонстрации с несколькими клиентами.
+// Клиент подключается по TCP и присылает скрипты по одному в строке.
+// Все клиенты управляют одним общим роботом, строка выполняется целиком или не выполняется
+// совсем, поэтому строки разных клиентов не перемешиваются.
+// На каждую строку сервер отвечает одной строкой:
+// "ok Robot at (0, 1) facing up, pen down" с новым состоянием робота или "error <текст>".
+// Пустая строка только запрашивает состояние.
+// Строка длиннее MAX_LINE байт не читается в память целиком: сервер отвечает ошибкой
+// и пропускает ее до конца.
+// Клиенты не должны читать файлы сервера, поэтому директива "include" в их скриптах запрещена.
+// Пока строка выполняется, другие клиенты ждут робота, поэтому строка может сделать не больше
+// max_steps операций и повторений циклов (по умолчанию MAX_STEPS), иначе она отменяется
+// с ошибкой, и бесконечный цикл клиента не останавливает сервер.
+// Для страницы в браузере есть сервер WebSocket (WebSocketServer), он присылает события
+// робота после каждой команды, чтобы страница могла показать выполнение по шагам.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+
+use log::{info, warn};
+
+use super::{
+    interpreter::Interpreter,
+    robot::{Robot, StepBudget},
+};
+
+mod websocket;
+
+pub use websocket::WebSocketServer;
+
+pub const MAX_STEPS: usize = 1_000_000;
+pub const MAX_LINE: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+pub struct Server {
+    robot: Arc<Mutex<Robot>>,
+    max_steps: usize,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new(Robot::default())
+    }
+}
+
+impl Server {
+    pub fn new(robot: Robot) -> Self {
+        Self {
+            robot: Arc::new(Mutex::new(robot)),
+            max_steps: MAX_STEPS,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    // Общий робот, его можно читать и менять между запросами клиентов
+    pub fn robot(&self) -> MutexGuard<'_, Robot> {
+        // После паники потока клиента робот остается в состоянии до его строки
+        self.robot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Выполняет строку со скриптом и возвращает ответ клиенту без перевода строки
+    pub fn execute(&self, line: &str) -> String {
+        let mut commands = match script(line).interpret() {
+            Ok(commands) => commands,
+            Err(err) => return format!("error {err}"),
+        };
+        let mut robot = self.robot();
+        let before = robot.snapshot();
+        match commands.execute_all(&mut StepBudget::new(&mut *robot, self.max_steps)) {
+            Ok(()) => format!("ok {robot}"),
+            Err(err) => {
+                robot.restore(&before);
+                format!("error {err}")
+            }
+        }
+    }
+
+    // Отвечает на строки клиента до конца ввода
+    pub fn handle(&self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let limit = MAX_LINE as u64 + 1;
+            if reader.by_ref().take(limit).read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            let reply = if line.len() > MAX_LINE && line.last() != Some(&b'\n') {
+                reader.skip_until(b'\n')?;
+                "error Line is too long".to_string()
+            } else {
+                let line = str::from_utf8(&line)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+                self.execute(line.trim())
+            };
+            writeln!(writer, "{reply}")?;
+            writer.flush()?;
+        }
+    }
+
+    // Принимает клиентов и обслуживает каждого в своем потоке.
+    // Возвращается только при ошибке приема соединения.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || server.handle_client(stream));
+        }
+        Ok(())
+    }
+
+    fn handle_client(&self, stream: TcpStream) {
+        let peer = stream
+            .peer_addr()
+            .map_or("unknown client".to_string(), |addr| addr.to_string());
+        info!("Client {peer} connected");
+        let result = stream
+            .try_clone()
+            .and_then(|writer| self.handle(BufReader::new(stream), writer));
+        match result {
+            Ok(()) => info!("Client {peer} disconnected"),
+            Err(err) => warn!("Client {peer} failed: {err}"),
+        }
+    }
+}
+
+// Интерпретатор скрипта клиента, которому недоступны файлы сервера
+pub(crate) fn script(source: &str) -> Interpreter<'_> {
+    Interpreter::new(source).with_loader(|_: &Path| Err(io::ErrorKind::PermissionDenied.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Shutdown;
+
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn test_server_execute() {
+        let server = Server::default();
+        assert_eq!(
+            server.execute("down_pen move 2"),
+            "ok Robot at (0, 2) facing up, pen down"
+        );
+        assert_eq!(server.execute(""), "ok Robot at (0, 2) facing up, pen down");
+        assert!(server.execute("fly 1").starts_with("error "));
+
+        // Строка с ошибкой не меняет робота
+        let world = World::from_ascii_map("#\n.\n.\n.\n.").unwrap();
+        server.robot().set_world(world);
+        assert_eq!(server.execute("move 1 move 1"), "error Blocked at (0, 4)");
+        assert_eq!(server.robot().y(), 2);
+    }
+
+    #[test]
+    fn test_server_refuses_includes() {
+        let server = Server::default();
+        let reply = server.execute("include \"../../etc/hostname\" move 1");
+        assert!(
+            reply.starts_with("error Cannot include ../../etc/hostname: permission denied"),
+            "{reply}"
+        );
+        assert_eq!(server.robot().y(), 0);
+    }
+
+    #[test]
+    fn test_server_step_limit() {
+        let server = Server::default().with_max_steps(100);
+        assert_eq!(
+            server.execute("move 1 while 1 [ turn_left 1 ]"),
+            "error Execution exceeded 100 steps"
+        );
+        assert_eq!(server.execute(""), "ok Robot at (0, 0) facing up, pen up");
+    }
+
+    #[test]
+    fn test_server_line_limit() {
+        let server = Server::default();
+        let mut input = "move ".repeat(MAX_LINE);
+        input.push_str("\nmove 1\n");
+        let mut output = Vec::new();
+        server.handle(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "error Line is too long\nok Robot at (0, 1) facing up, pen up\n"
+        );
+
+        let mut output = Vec::new();
+        server.handle(&[0xFF, b'\n'][..], &mut output).unwrap_err();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_server_clients() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::default();
+        let serving = server.clone();
+        thread::spawn(move || serving.serve(listener));
+
+        let mut replies = Vec::new();
+        for script in ["move 1\n", "turn_right 1\nmove 2\n"] {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(script.as_bytes()).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let reader = BufReader::new(stream);
+            replies.extend(reader.lines().map(Result::unwrap));
+        }
+        assert_eq!(
+            replies,
+            [
+                "ok Robot at (0, 1) facing up, pen up",
+                "ok Robot at (0, 1) facing right, pen up",
+                "ok Robot at (2, 1) facing right, pen up",
+            ]
+        );
+        assert_eq!(server.robot().x(), 2);
+    }
+}
@@ -0,0 +1,561 @@
+// Сервер WebSocket для страницы в браузере, которая показывает выполнение скрипта по шагам.
+// Страница присылает скрипт текстовым кадром, а сервер выполняет его общим роботом и затем
+// присылает кадрами JSON события робота по порядку:
+// {"event":"moved","from":{"x":0,"y":0},"to":{"x":0,"y":1},"drawing":true}
+// {"event":"turned","from":"up","to":"right"}
+// {"event":"pen","down":true,"color":"black"}
+// {"event":"drew","from":{"x":0,"y":0},"to":{"x":0,"y":1},"color":"black"}
+// Последний кадр скрипта - {"event":"done","state":{...}} с состоянием робота или
+// {"event":"error","message":"...","state":{...}}: команды до ошибки остаются выполненными.
+// Как и в сервере TCP, директива "include" запрещена, а скрипт ограничен запасом операций.
+// События собираются во время выполнения и отправляются, когда робот уже свободен: страница,
+// которая не читает ответы, не должна задерживать других клиентов.
+// Протокол (RFC 6455) реализован здесь же, чтобы не тянуть зависимостей: рукопожатие,
+// текстовые и управляющие кадры, кадры из нескольких частей.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use log::{info, warn};
+
+use super::Server;
+use crate::robot::{Point, Robot, RobotEvent, RobotObserver, StepBudget};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// Наибольший размер сообщения от страницы
+const MAX_MESSAGE: usize = 1 << 20;
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+// События робота накапливаются, только пока выполняется скрипт страницы:
+// при выполнении по TCP наблюдатель ничего не хранит
+#[derive(Debug, Default)]
+struct Events(Mutex<Option<Vec<RobotEvent>>>);
+
+impl Events {
+    fn set_recording(&self, recording: bool) {
+        let mut events = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *events = recording.then(Vec::new);
+    }
+
+    fn take(&self) -> Vec<RobotEvent> {
+        let mut events = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+impl RobotObserver for Events {
+    fn notify(&self, event: &RobotEvent) {
+        let mut events = self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(events) = events.as_mut() {
+            events.push(*event);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebSocketServer {
+    server: Server,
+    events: Arc<Events>,
+}
+
+impl WebSocketServer {
+    // Сервер для робота другого сервера, так страницы и клиенты TCP управляют одним роботом
+    pub fn new(server: Server) -> Self {
+        let events = Arc::new(Events::default());
+        server.robot().add_observer(events.clone());
+        Self { server, events }
+    }
+
+    pub fn server(&self) -> &Server {
+        &self.server
+    }
+
+    // Выполняет скрипт и записывает кадры событий и итоговый кадр
+    pub fn execute(&self, source: &str, writer: &mut impl Write) -> io::Result<()> {
+        let (events, message) = self.run(source);
+        for message in events.iter().filter_map(event_message) {
+            write_text(writer, &message)?;
+        }
+        write_text(writer, &message)
+    }
+
+    // События скрипта и итоговое сообщение, робот занят только на время выполнения
+    fn run(&self, source: &str) -> (Vec<RobotEvent>, String) {
+        let mut commands = match super::script(source).interpret() {
+            Ok(commands) => commands,
+            Err(err) => {
+                let robot = self.server.robot();
+                return (Vec::new(), error_message(&err.to_string(), &robot));
+            }
+        };
+
+        let mut robot = self.server.robot();
+        self.events.set_recording(true);
+        let result = commands.execute_all(&mut StepBudget::new(&mut *robot, self.server.max_steps));
+        let events = self.events.take();
+        self.events.set_recording(false);
+
+        let message = match result {
+            Ok(()) => format!(r#"{{"event":"done","state":{}}}"#, state(&robot)),
+            Err(err) => error_message(&err.to_string(), &robot),
+        };
+        (events, message)
+    }
+
+    // Выполняет рукопожатие и отвечает на сообщения страницы до закрытия соединения
+    pub fn handle(&self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        handshake(&mut reader, &mut writer)?;
+        let mut message = Vec::new();
+        loop {
+            let Some((fin, opcode, payload)) = read_frame(&mut reader)? else {
+                return Ok(());
+            };
+            match opcode {
+                TEXT | BINARY | CONTINUATION => {
+                    if message.len() + payload.len() > MAX_MESSAGE {
+                        return Err(invalid("Message is too long"));
+                    }
+                    message.extend_from_slice(&payload);
+                    if !fin {
+                        continue;
+                    }
+                    let text = String::from_utf8(std::mem::take(&mut message))
+                        .map_err(|_| invalid("Message is not valid UTF-8"))?;
+                    self.execute(&text, &mut writer)?;
+                }
+                PING => write_frame(&mut writer, PONG, &payload)?,
+                PONG => {}
+                CLOSE => return write_frame(&mut writer, CLOSE, &payload),
+                _ => return Err(invalid("Unknown frame opcode")),
+            }
+        }
+    }
+
+    // Принимает страницы и обслуживает каждую в своем потоке.
+    // Возвращается только при ошибке приема соединения.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || server.handle_client(stream));
+        }
+        Ok(())
+    }
+
+    fn handle_client(&self, stream: TcpStream) {
+        let peer = stream
+            .peer_addr()
+            .map_or("unknown client".to_string(), |addr| addr.to_string());
+        info!("WebSocket client {peer} connected");
+        let result = stream
+            .try_clone()
+            .and_then(|writer| self.handle(BufReader::new(stream), writer));
+        match result {
+            Ok(()) => info!("WebSocket client {peer} disconnected"),
+            Err(err) => warn!("WebSocket client {peer} failed: {err}"),
+        }
+    }
+}
+
+// Пишет странице события каждой выполненной команды, команда с ошибкой тоже могла
+// сдвинуть робота
+fn event_message(event: &RobotEvent) -> Option<String> {
+    let message = match event {
+        RobotEvent::Moved { from, to, drawing } => format!(
+            r#"{{"event":"moved","from":{},"to":{},"drawing":{drawing}}}"#,
+            point(from),
+            point(to)
+        ),
+        RobotEvent::Turned { from, to } => {
+            format!(r#"{{"event":"turned","from":"{from}","to":"{to}"}}"#)
+        }
+        RobotEvent::PenChanged(pen) => format!(
+            r#"{{"event":"pen","down":{},"color":"{}"}}"#,
+            pen.is_down(),
+            pen.color()
+        ),
+        RobotEvent::Drawn(segment) => format!(
+            r#"{{"event":"drew","from":{},"to":{},"color":"{}"}}"#,
+            point(&segment.from),
+            point(&segment.to),
+            segment.color
+        ),
+        RobotEvent::Restored => return None,
+    };
+    Some(message)
+}
+
+fn point(point: &Point) -> String {
+    format!(r#"{{"x":{},"y":{}}}"#, point.x, point.y)
+}
+
+fn state(robot: &Robot) -> String {
+    format!(
+        r#"{{"x":{},"y":{},"direction":"{}","pen":{},"color":"{}"}}"#,
+        robot.x(),
+        robot.y(),
+        robot.direction(),
+        robot.is_drawing(),
+        robot.color()
+    )
+}
+
+fn error_message(message: &str, robot: &Robot) -> String {
+    format!(
+        r#"{{"event":"error","message":{},"state":{}}}"#,
+        quote(message),
+        state(robot)
+    )
+}
+
+// Строка JSON в кавычках
+fn quote(text: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Читает запрос HTTP на переход к WebSocket и отвечает на него
+fn handshake(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+    let mut key = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("Sec-WebSocket-Key")
+        {
+            key = Some(value.trim().to_string());
+        }
+    }
+
+    let Some(key) = key else {
+        writer.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Err(invalid("Not a WebSocket request"));
+    };
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    writer.flush()
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{key}{GUID}").as_bytes()))
+}
+
+// Кадр: признак последней части, код и данные; None, если соединение закрыто
+fn read_frame(reader: &mut impl Read) -> io::Result<Option<(bool, u8, Vec<u8>)>> {
+    let mut header = [0; 2];
+    match reader.read_exact(&mut header) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE as u64 {
+        return Err(invalid("Message is too long"));
+    }
+
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+    Ok(Some((fin, opcode, payload)))
+}
+
+// Кадры сервера не маскируются и не делятся на части
+fn write_frame(writer: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..126 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+fn write_text(writer: &mut impl Write, text: &str) -> io::Result<()> {
+    write_frame(writer, TEXT, text.as_bytes())
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A827999),
+                20..40 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Кадр страницы: страница всегда маскирует данные
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![
+            if fin { 0x80 } else { 0 } | opcode,
+            0x80 | payload.len() as u8,
+        ];
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+        frame
+    }
+
+    fn server_messages(mut output: &[u8]) -> Vec<String> {
+        let mut messages = Vec::new();
+        while let Some((fin, opcode, payload)) = read_frame(&mut output).unwrap() {
+            assert!(fin);
+            if opcode == TEXT {
+                messages.push(String::from_utf8(payload).unwrap());
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // Пример из RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(base64(b"a"), "YQ==");
+    }
+
+    #[test]
+    fn test_websocket_session() {
+        let server = WebSocketServer::new(Server::default());
+        let mut input = b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+            sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+            .to_vec();
+        input.extend(client_frame(false, TEXT, b"down_pen "));
+        input.extend(client_frame(false, PING, b"hi"));
+        input.extend(client_frame(true, CONTINUATION, b"move 1 turn_right 1"));
+        input.extend(client_frame(true, TEXT, b"fly"));
+        input.extend(client_frame(true, CLOSE, b""));
+
+        let mut output = Vec::new();
+        server.handle(input.as_slice(), &mut output).unwrap();
+        let response = String::from_utf8_lossy(&output);
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let body = &output[response.find("\r\n\r\n").unwrap() + 4..];
+        // Ответ на проверку связи приходит раньше окончания сообщения из двух частей
+        assert_eq!(&body[..4], [0x80 | PONG, 2, b'h', b'i']);
+        let messages = server_messages(&body[4..]);
+        assert_eq!(
+            messages[..4],
+            [
+                r#"{"event":"pen","down":true,"color":"black"}"#,
+                r#"{"event":"moved","from":{"x":0,"y":0},"to":{"x":0,"y":1},"drawing":true}"#,
+                r#"{"event":"drew","from":{"x":0,"y":0},"to":{"x":0,"y":1},"color":"black"}"#,
+                r#"{"event":"turned","from":"up","to":"right"}"#,
+            ]
+        );
+        let state = r#""state":{"x":0,"y":1,"direction":"right","pen":true,"color":"black"}}"#;
+        assert_eq!(messages[4], format!(r#"{{"event":"done",{state}"#));
+        assert!(messages[5].starts_with(r#"{"event":"error","message":"Undefined command fly"#));
+        assert!(messages[5].ends_with(state));
+        assert_eq!(messages.len(), 6);
+
+        // Выполнение по TCP не копит события
+        assert!(server.server().execute("move 1").starts_with("ok"));
+        assert!(server.events.take().is_empty());
+    }
+
+    #[test]
+    fn test_websocket_errors() {
+        let server = WebSocketServer::new(Server::default());
+        let mut output = Vec::new();
+        let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert!(server.handle(&request[..], &mut output).is_err());
+        assert!(output.starts_with(b"HTTP/1.1 400"));
+
+        // Скрипт страницы не читает файлы сервера и не занимает робота бесконечно
+        let server = WebSocketServer::new(Server::default().with_max_steps(10));
+        let mut output = Vec::new();
+        server.execute("include \"robot.rs\"", &mut output).unwrap();
+        server
+            .execute("while 1 [ turn_left 1 ]", &mut output)
+            .unwrap();
+        let messages = server_messages(&output);
+        assert!(messages[0].contains("Cannot include robot.rs: permission denied"));
+        assert!(
+            messages
+                .last()
+                .unwrap()
+                .contains("Execution exceeded 10 steps")
+        );
+
+        assert_eq!(quote("line \"1\"\n\\\t"), r#""line \"1\"\n\\\u0009""#);
+    }
+
+    // Запись кадров проверяет, что робот свободен для других соединений
+    struct Checked<'a> {
+        server: &'a WebSocketServer,
+        output: Vec<u8>,
+    }
+
+    impl Write for Checked<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            assert!(self.server.server.robot.try_lock().is_ok());
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_frames_written_without_lock() {
+        let server = WebSocketServer::new(Server::default());
+        let mut writer = Checked {
+            server: &server,
+            output: Vec::new(),
+        };
+        server.execute("down_pen move 1", &mut writer).unwrap();
+        let messages = server_messages(&writer.output);
+        assert!(messages.len() > 1);
+        assert!(messages.last().unwrap().contains("done"));
+    }
+}
@@ -0,0 +1,171 @@
+// Сеанс работы с роботом: робот, история выполненных команд и холст вместе.
+// Команды выполняются через историю, поэтому их можно отменять и повторять,
+// а на слой робота на холсте после каждого изменения дорисовываются новые отрезки пути.
+// Команда с ошибкой не меняет робота: он возвращается в состояние до нее.
+// Остальные слои холста принадлежат пользователю и не меняются.
+// С функцией "serde" сеанс сохраняется в файл и читается из него (модуль serialize),
+// чтобы продолжить работу с того же места вместе с историей отмены.
+
+use super::{
+    canvas::Canvas,
+    command::{Command, History},
+    error::Error,
+    robot::Robot,
+};
+
+#[cfg(feature = "serde")]
+mod serialize;
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    // Робот до первой команды истории, по нему история восстанавливается при чтении
+    start: Robot,
+    robot: Robot,
+    history: History,
+    canvas: Canvas,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new(Robot::default())
+    }
+}
+
+impl Session {
+    pub fn new(robot: Robot) -> Self {
+        Self {
+            start: robot.clone(),
+            canvas: Canvas::from_robot(&robot),
+            robot,
+            history: History::default(),
+        }
+    }
+
+    // Робот до первой команды истории
+    pub fn start(&self) -> &Robot {
+        &self.start
+    }
+
+    pub fn robot(&self) -> &Robot {
+        &self.robot
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn canvas_mut(&mut self) -> &mut Canvas {
+        &mut self.canvas
+    }
+
+    pub fn execute(&mut self, command: Box<dyn Command>) -> Result<(), Error> {
+        self.atomic(|history, robot| history.execute(command, robot))
+    }
+
+    pub fn undo(&mut self) -> Result<bool, Error> {
+        self.atomic(|history, robot| history.undo(robot))
+    }
+
+    pub fn redo(&mut self) -> Result<bool, Error> {
+        self.atomic(|history, robot| history.redo(robot))
+    }
+
+    // Команда с ошибкой могла успеть сдвинуть робота, поэтому его состояние возвращается
+    fn atomic<T>(
+        &mut self,
+        f: impl FnOnce(&mut History, &mut Robot) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let before = self.robot.snapshot();
+        let result = f(&mut self.history, &mut self.robot);
+        if result.is_err() {
+            self.robot.restore(&before);
+        }
+        self.redraw();
+        result
+    }
+
+    // Путь робота только растет, пока возврат состояния не удалит его конец,
+    // поэтому обычно на холст дорисовываются только новые отрезки
+    fn redraw(&mut self) {
+        let layer = self.canvas.layer(Canvas::DRAWING);
+        let path = self.robot.path();
+        if layer.segments().len() > path.len() {
+            layer.clear();
+        }
+        for segment in &path[layer.segments().len()..] {
+            layer.draw(*segment);
+        }
+        self.canvas
+            .set_robot(self.robot.position(), self.robot.direction());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        command::{DownPenCommand, MoveCommand, TurnRightCommand},
+        robot::{Color, Direction, PenMode, Point, Segment},
+    };
+
+    #[test]
+    fn test_session_keeps_canvas_in_sync() {
+        let mut session = Session::default();
+        session.canvas_mut().layer("grid").draw(Segment {
+            from: Point::new(5, 5),
+            to: Point::new(5, 6),
+            color: Color::Blue,
+            width: 1,
+            mode: PenMode::Draw,
+        });
+        session.execute(Box::new(DownPenCommand)).unwrap();
+        session.execute(Box::new(MoveCommand::new(2))).unwrap();
+        session.execute(Box::new(TurnRightCommand::new(1))).unwrap();
+        assert!(session.canvas().is_drawn(0, 2));
+        assert_eq!(
+            session.canvas().robot(),
+            Some((Point::new(0, 2), session.robot().direction()))
+        );
+
+        assert!(session.undo().unwrap());
+        assert!(session.undo().unwrap());
+        // Отмена возвращает робота назад, но не стирает нарисованное, как и отмена в истории
+        assert!(session.canvas().is_drawn(0, 2));
+        assert!(session.canvas().is_drawn(5, 6));
+        assert_eq!(
+            session
+                .canvas()
+                .get_layer(Canvas::DRAWING)
+                .unwrap()
+                .segments(),
+            session.robot().path()
+        );
+        assert_eq!(
+            session.canvas().robot(),
+            Some((Point::new(0, 0), session.robot().direction()))
+        );
+
+        assert!(session.redo().unwrap());
+        assert!(session.canvas().is_drawn(0, 2));
+        assert_eq!(session.history().commands().len(), 2);
+    }
+
+    #[test]
+    fn test_session_failed_command_changes_nothing() {
+        let mut session = Session::new(Robot::new(0, i32::MAX - 2, Direction::Up, true));
+        session.execute(Box::new(MoveCommand::new(1))).unwrap();
+        assert!(matches!(
+            session.execute(Box::new(MoveCommand::new(5))),
+            Err(Error::OutOfBounds)
+        ));
+
+        assert_eq!(session.robot().y(), i32::MAX - 1);
+        assert_eq!(session.robot().path().len(), 1);
+        assert!(!session.canvas().is_drawn(0, i32::MAX));
+        assert_eq!(session.history().commands().len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+// Сохранение сеанса в JSON. Записываются робот до первой команды истории,
+// выполненные и отмененные команды и холст: {"start":...,"done":[...],"undone":[...],"canvas":...}.
+// Состояние для отмены команд не сериализуется, поэтому при чтении выполненные команды
+// выполняются заново на сохраненном роботе и снова запоминают, что отменять.
+// Мир робота не сохраняется, как и при сериализации робота.
+
+use std::{fmt, fs, path::Path};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, MapAccess, Visitor},
+    ser::SerializeStruct,
+};
+
+use super::*;
+use crate::json;
+
+const SESSION_FIELDS: &[&str] = &["start", "done", "undone", "canvas"];
+
+impl Session {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let json = json::to_string(self).map_err(|err| Error::InvalidSession(err.to_string()))?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let json = fs::read_to_string(path)?;
+        json::from_str(&json).map_err(|err| Error::InvalidSession(err.to_string()))
+    }
+}
+
+impl Serialize for Session {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut session = serializer.serialize_struct("Session", SESSION_FIELDS.len())?;
+        session.serialize_field("start", &self.start)?;
+        session.serialize_field("done", self.history.commands())?;
+        session.serialize_field("undone", self.history.undone())?;
+        session.serialize_field("canvas", &self.canvas)?;
+        session.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Session {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(SessionVisitor)
+    }
+}
+
+struct SessionVisitor;
+
+impl<'de> Visitor<'de> for SessionVisitor {
+    type Value = Session;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a session")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Session, A::Error> {
+        let (mut start, mut done, mut undone, mut canvas) = (None, None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "start" => set(&mut start, "start", &mut map)?,
+                "done" => set(&mut done, "done", &mut map)?,
+                "undone" => set(&mut undone, "undone", &mut map)?,
+                "canvas" => set(&mut canvas, "canvas", &mut map)?,
+                _ => return Err(de::Error::unknown_field(&key, SESSION_FIELDS)),
+            }
+        }
+
+        let start: Robot = required(start, "start")?;
+        let mut robot = start.clone();
+        let history = History::restore(
+            required(done, "done")?,
+            required(undone, "undone")?,
+            &mut robot,
+        )
+        .map_err(|err| de::Error::custom(format!("cannot restore history: {err}")))?;
+        Ok(Session {
+            start,
+            robot,
+            history,
+            canvas: required(canvas, "canvas")?,
+        })
+    }
+}
+
+// Читает значение ключа, ключ может встретиться только один раз
+fn set<'de, T: Deserialize<'de>, A: MapAccess<'de>>(
+    slot: &mut Option<T>,
+    name: &'static str,
+    map: &mut A,
+) -> Result<(), A::Error> {
+    if slot.is_some() {
+        return Err(de::Error::duplicate_field(name));
+    }
+    *slot = Some(map.next_value()?);
+    Ok(())
+}
+
+fn required<T, E: de::Error>(value: Option<T>, name: &'static str) -> Result<T, E> {
+    value.ok_or_else(|| E::missing_field(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        command::{DownPenCommand, MoveCommand, TurnRightCommand},
+        robot::Direction,
+    };
+
+    #[test]
+    fn test_session_save_load() {
+        let mut session = Session::new(Robot::new(1, 1, Direction::Right, false));
+        session.execute(Box::new(DownPenCommand)).unwrap();
+        session.execute(Box::new(MoveCommand::new(2))).unwrap();
+        session.execute(Box::new(TurnRightCommand::new(1))).unwrap();
+        session.execute(Box::new(MoveCommand::new(3))).unwrap();
+        session.undo().unwrap();
+
+        let path = env::temp_dir().join(format!("robot-session-{}.json", std::process::id()));
+        session.save(&path).unwrap();
+        let mut restored = Session::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.robot().position(), session.robot().position());
+        assert_eq!(restored.robot().direction(), Direction::Down);
+        assert_eq!(restored.canvas(), session.canvas());
+        assert_eq!(restored.history().commands().len(), 3);
+
+        // Отмена и повтор работают так же, как до сохранения
+        assert!(restored.redo().unwrap());
+        assert_eq!((restored.robot().x(), restored.robot().y()), (3, -2));
+        while restored.undo().unwrap() {}
+        assert_eq!((restored.robot().x(), restored.robot().y()), (1, 1));
+        assert!(!restored.robot().is_drawing());
+        assert_eq!(restored.history().undone().len(), 4);
+    }
+
+    #[test]
+    fn test_session_load_errors() {
+        assert!(json::from_str::<Session>(r#"{"start":null}"#).is_err());
+        let path = env::temp_dir().join("robot-session-missing.json");
+        assert!(matches!(Session::load(&path), Err(Error::Io(_))));
+    }
+}
@@ -0,0 +1,244 @@
+// Привязки для WebAssembly, чтобы интерпретатор и робот работали в браузере без сервера.
+// Песочница (Playground) хранит робота между вызовами: скрипт можно разобрать (interpret)
+// и выполнить (execute), а состояние робота получить в JSON, рисунок - в SVG.
+// Строка с ошибкой выполнения не меняет состояние робота, как в консоли примеров.
+// Функции экспортируются без wasm-bindgen, чтобы не тянуть зависимостей: строки передаются
+// через линейную память модуля. Страница выделяет буфер (playground_alloc), копирует в него
+// текст скрипта в UTF-8 и вызывает функцию, а результат или текст ошибки забирает
+// по адресу и длине выхода (playground_output_ptr, playground_output_len).
+// Песочница одна на модуль: в браузере модуль выполняется в одном потоке.
+// Модуль собирает команда cargo rustc --release --lib --target wasm32-unknown-unknown
+// --crate-type cdylib --features wasm.
+
+use std::{cell::RefCell, slice};
+
+use super::{
+    canvas::{Canvas, SvgOptions},
+    command::CommandList,
+    interpreter::Interpreter,
+    robot::Robot,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Playground {
+    // Робот, к которому песочница возвращается при сбросе
+    start: Robot,
+    robot: Robot,
+}
+
+impl Playground {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Песочница с начальным роботом, например с полем по размеру страницы
+    pub fn with_robot(robot: Robot) -> Self {
+        Self {
+            start: robot.clone(),
+            robot,
+        }
+    }
+
+    pub fn robot(&self) -> &Robot {
+        &self.robot
+    }
+
+    // Команды скрипта текстом, по команде в строке, или ошибка с местом в скрипте
+    pub fn interpret(&self, source: &str) -> Result<String, String> {
+        Self::commands(source).map(|commands| commands.to_string())
+    }
+
+    pub fn execute(&mut self, source: &str) -> Result<(), String> {
+        let mut commands = Self::commands(source)?;
+        commands
+            .execute_atomic(&mut self.robot)
+            .map_err(|err| err.to_string())
+    }
+
+    // Состояние робота: {"x":0,"y":0,"direction":"up","pen":false,"color":"black"}
+    pub fn state(&self) -> String {
+        let robot = &self.robot;
+        format!(
+            r#"{{"x":{},"y":{},"direction":"{}","pen":{},"color":"{}"}}"#,
+            robot.x(),
+            robot.y(),
+            robot.direction(),
+            robot.is_drawing(),
+            robot.color()
+        )
+    }
+
+    pub fn canvas(&self) -> Canvas {
+        Canvas::from_robot(&self.robot)
+    }
+
+    pub fn reset(&mut self) {
+        self.robot = self.start.clone();
+    }
+
+    fn commands(source: &str) -> Result<CommandList, String> {
+        Interpreter::new(source)
+            .interpret()
+            .map_err(|err| err.annotate(source).trim_end().to_string())
+    }
+}
+
+thread_local! {
+    static PLAYGROUND: RefCell<Playground> = RefCell::new(Playground::new());
+    static OUTPUT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn output(text: String) {
+    OUTPUT.with(|output| *output.borrow_mut() = text);
+}
+
+// Записывает в выход результат или ошибку, возвращает true при успехе
+fn finish(result: Result<String, String>) -> bool {
+    let succeeded = result.is_ok();
+    output(result.unwrap_or_else(|err| err));
+    succeeded
+}
+
+// Текст скрипта из буфера страницы
+//
+// # Safety
+// ptr и len должны описывать буфер, выделенный playground_alloc и заполненный страницей
+unsafe fn source(ptr: *const u8, len: usize) -> Result<String, String> {
+    let bytes = unsafe { slice::from_raw_parts(ptr, len) };
+    String::from_utf8(bytes.to_vec()).map_err(|_| "Script is not valid UTF-8".to_string())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// # Safety
+/// ptr и len должны быть получены из одного вызова playground_alloc
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_free(ptr: *mut u8, len: usize) {
+    drop(unsafe { Vec::from_raw_parts(ptr, 0, len) });
+}
+
+/// # Safety
+/// ptr и len должны описывать буфер из playground_alloc длиной не меньше len
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_interpret(ptr: *const u8, len: usize) -> bool {
+    let result = unsafe { source(ptr, len) }
+        .and_then(|source| PLAYGROUND.with(|playground| playground.borrow().interpret(&source)));
+    finish(result)
+}
+
+/// # Safety
+/// ptr и len должны описывать буфер из playground_alloc длиной не меньше len
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_execute(ptr: *const u8, len: usize) -> bool {
+    let result = unsafe { source(ptr, len) }.and_then(|source| {
+        PLAYGROUND.with(|playground| {
+            let mut playground = playground.borrow_mut();
+            playground.execute(&source)?;
+            Ok(playground.state())
+        })
+    });
+    finish(result)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_state() {
+    output(PLAYGROUND.with(|playground| playground.borrow().state()));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_canvas_svg(scale: u32) {
+    let svg = PLAYGROUND.with(|playground| {
+        playground
+            .borrow()
+            .canvas()
+            .to_svg(SvgOptions::new().with_scale(scale))
+    });
+    output(svg);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_reset() {
+    PLAYGROUND.with(|playground| playground.borrow_mut().reset());
+    output(String::new());
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_output_ptr() -> *const u8 {
+    OUTPUT.with(|output| output.borrow().as_ptr())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_output_len() -> usize {
+    OUTPUT.with(|output| output.borrow().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::robot::{Bounds, RobotBuilder};
+
+    fn output_text() -> String {
+        let bytes =
+            unsafe { slice::from_raw_parts(playground_output_ptr(), playground_output_len()) };
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_playground() {
+        let robot = RobotBuilder::new()
+            .bounds(Bounds::new(-5, 5, -5, 5))
+            .build();
+        let mut playground = Playground::with_robot(robot);
+        assert_eq!(
+            playground.interpret("repeat 2 [ move 1 ]").unwrap(),
+            "move 1\nmove 1\n"
+        );
+        assert!(playground.interpret("fly 1").unwrap_err().contains("^^^"));
+
+        playground
+            .execute("down_pen\nmove 2\nturn_right 1")
+            .unwrap();
+        assert_eq!(
+            playground.state(),
+            r#"{"x":0,"y":2,"direction":"right","pen":true,"color":"black"}"#
+        );
+        assert!(playground.canvas().is_drawn(0, 1));
+
+        // Строка с ошибкой выполнения не меняет робота
+        assert!(playground.execute("move 1\nmove 10").is_err());
+        assert_eq!((playground.robot().x(), playground.robot().y()), (0, 2));
+
+        playground.reset();
+        assert!(playground.canvas().is_empty());
+    }
+
+    #[test]
+    fn test_playground_exports() {
+        let script = "move 3";
+        let ptr = playground_alloc(script.len());
+        unsafe {
+            ptr.copy_from_nonoverlapping(script.as_ptr(), script.len());
+            assert!(playground_execute(ptr, script.len()));
+        }
+        assert!(output_text().starts_with(r#"{"x":0,"y":3,"#));
+
+        playground_canvas_svg(10);
+        assert!(output_text().starts_with("<svg"));
+
+        unsafe {
+            assert!(!playground_interpret(ptr, 2));
+            assert!(output_text().contains("error"));
+            playground_free(ptr, script.len());
+        }
+
+        playground_reset();
+        playground_state();
+        assert!(output_text().starts_with(r#"{"x":0,"y":0,"#));
+    }
+}
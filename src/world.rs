@@ -0,0 +1,418 @@
+// Мир, в котором двигается робот: набор занятых клеток (препятствий).
+// Робот не может войти в занятую клетку, датчик препятствия видит ее заранее.
+// Препятствия можно задать текстовой картой: "#" - занятая клетка, "." или пробел - свободная.
+// Нижняя строка карты имеет y = 0, первый символ строки - x = 0, ось y направлена вверх.
+// В мире могут находиться несколько роботов с именами. Команда TargetCommand выполняется
+// роботом с указанным именем, остальные команды выполняет первый добавленный робот.
+// Команды TargetCommand внутри циклов и составных команд тоже передаются своему роботу.
+// Во время выполнения робот видит препятствия мира, а при правиле Collision::Block
+// клетки других роботов тоже заняты. Препятствия общие для мира и всех роботов.
+
+use std::{collections::HashSet, sync::Arc};
+
+use super::{
+    command::{Command, CommandList},
+    error::Error,
+    robot::{Action, Color, Direction, Fuel, Point, Robot, RobotLike, SavedPosition, Stamp},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct World {
+    obstacles: Arc<HashSet<(i32, i32)>>,
+    // Клетки других роботов в мире, который видит выполняющий команду робот
+    occupied: Vec<(i32, i32)>,
+    // Роботы в порядке добавления
+    robots: Vec<(String, Robot)>,
+    collision: Collision,
+}
+
+// Столкновения между роботами мира
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Collision {
+    // Робот не может войти в клетку другого робота
+    #[default]
+    Block,
+    // Роботы проходят сквозь друг друга
+    Pass,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_ascii_map(map: &str) -> Result<Self, Error> {
+        let lines: Vec<&str> = map.lines().collect();
+        let mut world = Self::new();
+
+        for (row, line) in lines.iter().enumerate() {
+            let y = (lines.len() - 1 - row) as i32;
+            for (x, ch) in line.chars().enumerate() {
+                match ch {
+                    '#' => world.add_obstacle(x as i32, y),
+                    '.' | ' ' => {}
+                    _ => return Err(Error::InvalidMapCharacter(ch, row + 1, x + 1)),
+                }
+            }
+        }
+
+        Ok(world)
+    }
+
+    pub fn with_obstacle(mut self, x: i32, y: i32) -> Self {
+        self.add_obstacle(x, y);
+        self
+    }
+
+    pub fn add_obstacle(&mut self, x: i32, y: i32) {
+        Arc::make_mut(&mut self.obstacles).insert((x, y));
+    }
+
+    pub fn remove_obstacle(&mut self, x: i32, y: i32) -> bool {
+        Arc::make_mut(&mut self.obstacles).remove(&(x, y))
+    }
+
+    pub fn is_obstacle(&self, x: i32, y: i32) -> bool {
+        self.obstacles.contains(&(x, y)) || self.occupied.contains(&(x, y))
+    }
+
+    pub fn obstacles(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.obstacles.iter().chain(&self.occupied).copied()
+    }
+
+    pub fn with_collision(mut self, collision: Collision) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    pub fn collision(&self) -> Collision {
+        self.collision
+    }
+
+    pub fn with_robot(mut self, name: &str, robot: Robot) -> Self {
+        self.add_robot(name, robot);
+        self
+    }
+
+    // Добавляет робота, робот с тем же именем заменяется
+    pub fn add_robot(&mut self, name: &str, robot: Robot) {
+        match self.robot_mut(name) {
+            Some(existing) => *existing = robot,
+            None => self.robots.push((name.to_string(), robot)),
+        }
+    }
+
+    pub fn remove_robot(&mut self, name: &str) -> Option<Robot> {
+        let index = self.index(name)?;
+        Some(self.robots.remove(index).1)
+    }
+
+    pub fn robot(&self, name: &str) -> Option<&Robot> {
+        self.robots
+            .iter()
+            .find(|(robot_name, _)| robot_name == name)
+            .map(|(_, robot)| robot)
+    }
+
+    pub fn robot_mut(&mut self, name: &str) -> Option<&mut Robot> {
+        self.robots
+            .iter_mut()
+            .find(|(robot_name, _)| robot_name == name)
+            .map(|(_, robot)| robot)
+    }
+
+    pub fn robots(&self) -> impl Iterator<Item = (&str, &Robot)> + '_ {
+        self.robots
+            .iter()
+            .map(|(name, robot)| (name.as_str(), robot))
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.robots
+            .iter()
+            .position(|(robot_name, _)| robot_name == name)
+    }
+
+    // Выполняет команду роботом с указанным именем
+    pub fn execute(&mut self, name: &str, command: &mut dyn Command) -> Result<(), Error> {
+        let index = self
+            .index(name)
+            .ok_or_else(|| Error::UnknownRobot(name.to_string()))?;
+        WorldRobot::new(self, index).run(|robot| command.execute(robot))
+    }
+
+    // Выполняет список команд первым добавленным роботом,
+    // команды TargetCommand передаются роботу с указанным именем
+    pub fn execute_all<C: Command + 'static>(
+        &mut self,
+        commands: &mut CommandList<C>,
+    ) -> Result<(), Error> {
+        if self.robots.is_empty() {
+            return Err(Error::NoRobots);
+        }
+        WorldRobot::new(self, 0).run(|robot| commands.execute_all(robot))
+    }
+
+    // Мир, который видит робот с номером index: препятствия мира и, если роботы
+    // не проходят друг сквозь друга, клетки остальных роботов
+    fn view(&self, index: usize) -> World {
+        let mut view = World {
+            obstacles: Arc::clone(&self.obstacles),
+            ..World::default()
+        };
+        if self.collision == Collision::Block {
+            view.occupied = self
+                .robots
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, (_, robot))| (robot.x(), robot.y()))
+                .collect();
+        }
+        view
+    }
+}
+
+// Робот мира, который выполняет команды. Команды TargetCommand он передает роботу
+// с указанным именем и после этого обновляет клетки других роботов в своем мире.
+struct WorldRobot<'w> {
+    world: &'w mut World,
+    index: usize,
+}
+
+impl<'w> WorldRobot<'w> {
+    fn new(world: &'w mut World, index: usize) -> Self {
+        Self { world, index }
+    }
+
+    fn robot(&self) -> &Robot {
+        &self.world.robots[self.index].1
+    }
+
+    fn robot_mut(&mut self) -> &mut Robot {
+        &mut self.world.robots[self.index].1
+    }
+
+    fn enter(&mut self) {
+        let view = self.world.view(self.index);
+        self.robot_mut().set_world(view);
+    }
+
+    // Выполняет действие, пока робот видит других роботов, и затем возвращает ему
+    // только препятствия мира
+    fn run(
+        mut self,
+        action: impl FnOnce(&mut dyn RobotLike) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.enter();
+        let result = action(&mut self);
+        let view = World {
+            obstacles: Arc::clone(&self.world.obstacles),
+            ..World::default()
+        };
+        self.robot_mut().set_world(view);
+        result
+    }
+}
+
+impl RobotLike for WorldRobot<'_> {
+    fn position(&self) -> Point {
+        RobotLike::position(self.robot())
+    }
+
+    fn direction(&self) -> Direction {
+        self.robot().direction()
+    }
+
+    fn is_drawing(&self) -> bool {
+        self.robot().is_drawing()
+    }
+
+    fn color(&self) -> Color {
+        self.robot().color()
+    }
+
+    fn pen_width(&self) -> u32 {
+        self.robot().pen_width()
+    }
+
+    fn is_blocked(&self) -> bool {
+        self.robot().is_blocked()
+    }
+
+    fn move_forward(&mut self) -> Result<(), Error> {
+        RobotLike::move_forward(self.robot_mut())
+    }
+
+    fn move_forward_by(&mut self, steps: u32) -> Result<(), Error> {
+        RobotLike::move_forward_by(self.robot_mut(), steps)
+    }
+
+    fn move_backward_by(&mut self, steps: u32) -> Result<(), Error> {
+        RobotLike::move_backward_by(self.robot_mut(), steps)
+    }
+
+    fn set_position(&mut self, x: i32, y: i32) -> Result<(), Error> {
+        RobotLike::set_position(self.robot_mut(), x, y)
+    }
+
+    fn saved_position(&self) -> SavedPosition {
+        RobotLike::saved_position(self.robot())
+    }
+
+    fn restore_position(&mut self, saved: &SavedPosition) -> Result<(), Error> {
+        RobotLike::restore_position(self.robot_mut(), saved)
+    }
+
+    fn set_direction(&mut self, direction: Direction) {
+        RobotLike::set_direction(self.robot_mut(), direction);
+    }
+
+    fn turn_left(&mut self) {
+        RobotLike::turn_left(self.robot_mut());
+    }
+
+    fn turn_right(&mut self) {
+        RobotLike::turn_right(self.robot_mut());
+    }
+
+    fn turn_left_45(&mut self) {
+        RobotLike::turn_left_45(self.robot_mut());
+    }
+
+    fn turn_right_45(&mut self) {
+        RobotLike::turn_right_45(self.robot_mut());
+    }
+
+    fn down_pen(&mut self) {
+        RobotLike::down_pen(self.robot_mut());
+    }
+
+    fn up_pen(&mut self) {
+        RobotLike::up_pen(self.robot_mut());
+    }
+
+    fn set_color(&mut self, color: Color) {
+        RobotLike::set_color(self.robot_mut(), color);
+    }
+
+    fn set_pen_width(&mut self, width: u32) {
+        RobotLike::set_pen_width(self.robot_mut(), width);
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        RobotLike::reset(self.robot_mut())
+    }
+
+    fn fuel(&self) -> Option<Fuel> {
+        self.robot().fuel()
+    }
+
+    fn set_fuel(&mut self, fuel: Option<Fuel>) {
+        RobotLike::set_fuel(self.robot_mut(), fuel);
+    }
+
+    fn refuel(&mut self, amount: u64) {
+        RobotLike::refuel(self.robot_mut(), amount);
+    }
+
+    fn stamp(&mut self, stamp: Stamp) -> Result<(), Error> {
+        RobotLike::stamp(self.robot_mut(), stamp)
+    }
+
+    fn write_text(&mut self, text: &str) {
+        RobotLike::write_text(self.robot_mut(), text);
+    }
+
+    fn target(&mut self, name: &str, action: &mut Action<'_>) -> Option<Result<(), Error>> {
+        let Some(index) = self.world.index(name) else {
+            return Some(Err(Error::UnknownRobot(name.to_string())));
+        };
+        if index == self.index {
+            return Some(action(self));
+        }
+        let result = WorldRobot::new(self.world, index).run(action);
+        // Другой робот мог переместиться
+        self.enter();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{command::MoveCommand, interpreter::Interpreter};
+
+    #[test]
+    fn test_from_ascii_map() {
+        let world = World::from_ascii_map("#..\n.#.\n..#").unwrap();
+        assert!(world.is_obstacle(0, 2));
+        assert!(world.is_obstacle(1, 1));
+        assert!(world.is_obstacle(2, 0));
+        assert!(!world.is_obstacle(0, 0));
+        assert_eq!(world.obstacles().count(), 3);
+
+        assert!(matches!(
+            World::from_ascii_map("..\n.x"),
+            Err(Error::InvalidMapCharacter('x', 2, 2))
+        ));
+    }
+
+    #[test]
+    fn test_robots_collide() {
+        let mut world = World::new()
+            .with_robot("a", Robot::default())
+            .with_robot("b", Robot::new(0, 2, crate::robot::Direction::Down, false));
+
+        let mut commands: CommandList = Interpreter::new("robot b: move 1\nmove 1")
+            .interpret()
+            .unwrap();
+        assert!(matches!(
+            world.execute_all(&mut commands),
+            Err(Error::Blocked(0, 1))
+        ));
+        assert_eq!(world.robot("a").unwrap().y(), 0);
+        assert_eq!(world.robot("b").unwrap().y(), 1);
+
+        let mut world = world.with_collision(Collision::Pass);
+        world.execute("a", &mut MoveCommand::new(2)).unwrap();
+        assert_eq!(world.robot("a").unwrap().y(), 2);
+        assert!(matches!(
+            world.execute("c", &mut MoveCommand::new(1)),
+            Err(Error::UnknownRobot(name)) if name == "c"
+        ));
+    }
+
+    #[test]
+    fn test_nested_targets() {
+        let mut world = World::new()
+            .with_obstacle(5, 4)
+            .with_robot("a", Robot::default())
+            .with_robot("b", Robot::new(5, 0, crate::robot::Direction::Up, false));
+
+        let source = "repeat 2 [ robot b: move 1 move 1 ] robot b: while not blocked [ move 1 ]";
+        let mut commands: CommandList = Interpreter::new(source).interpret().unwrap();
+        world.execute_all(&mut commands).unwrap();
+        assert_eq!(world.robot("a").unwrap().y(), 2);
+        assert_eq!(world.robot("b").unwrap().y(), 3);
+
+        // Команды в списке запомнили, как себя отменить
+        let b = world.robot_mut("b").unwrap();
+        let target = (&mut commands).into_iter().nth(1).unwrap();
+        target.rollback(b).unwrap();
+        assert_eq!(b.y(), 2);
+
+        // Робот b видит робота a, вставшего у него на пути
+        let mut commands: CommandList =
+            Interpreter::new("robot a: goto 5 5 robot b: [ move 1 move 1 move 1 ]")
+                .interpret()
+                .unwrap();
+        world.remove_obstacle(5, 4);
+        assert!(matches!(
+            world.execute_all(&mut commands),
+            Err(Error::Blocked(5, 5))
+        ));
+        assert_eq!(world.robot("b").unwrap().y(), 4);
+        assert!(!world.robot("b").unwrap().world().is_obstacle(5, 5));
+    }
+}